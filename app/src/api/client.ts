@@ -20,6 +20,7 @@ export const api = axios.create({
 
 // Types
 export interface KeyEntry { key: string; size: number; ttl: number | null; tags: string[]; created_ms?: number }
+export interface TagStat { tag: string; keys: number; bytes: number }
 export interface KeyDetail { key: string; value: any; ttl_ms: number | null; tags: string[]; created_ms?: number }
 
 // Convenience helpers (expand as needed)
@@ -45,6 +46,11 @@ export async function flushAll(): Promise<{ success: boolean; count?: number }>
   return r.data as { success: boolean; count?: number };
 }
 
+export async function listTags(params: { sort?: 'name'|'keys'|'bytes'; order?: 'asc'|'desc'; prefix?: string; limit?: number; offset?: number } = {}): Promise<{ tags: TagStat[]; total: number }> {
+  const r = await api.get('/tags', { params });
+  return r.data as { tags: TagStat[]; total: number };
+}
+
 export async function getSystemStats(): Promise<any> {
   const r = await api.get('/system');
   return r.data;
@@ -1,18 +1,16 @@
 import { useQuery } from '@tanstack/react-query';
-import { api } from '../api/client';
+import { listTags } from '../api/client';
 import { useAuthStore } from '../store/auth';
 import { useCacheStore } from '../store/cache';
 import { useState, useMemo, useEffect } from 'react';
 
-interface SearchItem { key:string; ttl_ms?:number; tags:string[]; created_ms?:number }
-
 export function TagDistribution(){
   const token = useAuthStore(s=>s.token);
   const { flushCounter } = useCacheStore();
   const [activeTag,setActiveTag] = useState<string|null>(null);
   const { data, isLoading, error, refetch, isFetching } = useQuery({
     queryKey:['tag-dist', !!token],
-    queryFn: async()=>{ const r = await api.post('/search', { limit: 800, tags: [] }); return r.data.keys as SearchItem[]; },
+    queryFn: async()=> (await listTags({ sort: 'keys', limit: 80 })).tags,
     enabled: !!token,
     refetchInterval: token ? 7000 : false,
     staleTime: 3000,
@@ -23,12 +21,10 @@ export function TagDistribution(){
   });
   // IMPORTANT: Hooks (useMemo) must be declared before any early return branches to keep order stable.
   const { cloud, maxCount, minCount } = useMemo(()=>{
-    const counts: Record<string, number> = {};
-    (data||[]).forEach(it=> it.tags.forEach(t=> { counts[t]=(counts[t]||0)+1; }));
-    const entries = Object.entries(counts);
-    if(entries.length===0) return { cloud: [] as {tag:string; count:number}[], maxCount:1, minCount:0 };
-    entries.sort((a,b)=> b[1]-a[1]);
-    return { cloud: entries.slice(0,80).map(([tag,count])=>({tag,count})), maxCount: Math.max(...entries.map(e=>e[1])), minCount: Math.min(...entries.map(e=>e[1])) };
+    // Server-side counts over every key (already sorted biggest first), not a sample of them.
+    const cloud = (data||[]).map(t=>({ tag: t.tag, count: t.keys }));
+    if(cloud.length===0) return { cloud, maxCount:1, minCount:0 };
+    return { cloud, maxCount: Math.max(...cloud.map(e=>e.count)), minCount: Math.min(...cloud.map(e=>e.count)) };
   },[data]);
 
   // Listen for flush events and refetch data
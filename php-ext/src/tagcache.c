@@ -131,6 +131,9 @@ static void tc_client_dtor(zend_resource *res) {
     
     if (h->cfg.host) efree(h->cfg.host);
     if (h->cfg.http_base) efree(h->cfg.http_base);
+    if (h->cfg.username) efree(h->cfg.username);
+    if (h->cfg.password) efree(h->cfg.password);
+    if (h->cfg.token) efree(h->cfg.token);
     efree(h);
 }
 
@@ -179,6 +182,44 @@ static int tc_tcp_connect_raw(const char *host, int port, int timeout_ms) {
     return saved;
 }
 
+// Authenticate a freshly connected socket. The server refuses every command
+// before AUTH unless tcp_require_auth is off, so this runs before the
+// connection joins the pool. Returns 0 when no credentials are configured.
+static int tc_tcp_auth(int fd, tc_client_config *cfg) {
+    if (!cfg->token && !cfg->username) return 0;
+    char line[1024]; int n;
+    if (cfg->token) n = snprintf(line, sizeof(line), "AUTH\t%s\n", cfg->token);
+    else n = snprintf(line, sizeof(line), "AUTH\t%s\t%s\n", cfg->username, cfg->password ? cfg->password : "");
+    if (n <= 0 || n >= (int)sizeof(line)) return -1;
+    if (strchr(line, '\n') != line + n - 1) return -1; // credentials must not contain newlines
+    for (int off = 0; off < n; ) {
+        ssize_t w = send(fd, line + off, n - off, MSG_NOSIGNAL);
+        if (w <= 0) { if (w < 0 && errno == EINTR) continue; return -1; }
+        off += w;
+    }
+    struct timeval tv; tv.tv_sec = cfg->timeout_ms / 1000; tv.tv_usec = (cfg->timeout_ms % 1000) * 1000;
+    setsockopt(fd, SOL_SOCKET, SO_RCVTIMEO, &tv, sizeof(tv));
+    // Read byte by byte so nothing past the reply is consumed before the pool buffer exists
+    char reply[64]; size_t len = 0;
+    while (len < sizeof(reply) - 1) {
+        ssize_t r = recv(fd, reply + len, 1, 0);
+        if (r <= 0) { if (r < 0 && errno == EINTR) continue; return -1; }
+        if (reply[len] == '\n') break;
+        len++;
+    }
+    reply[len] = '\0';
+    if (len && reply[len-1] == '\r') reply[--len] = '\0';
+    return strcmp(reply, "OK") == 0 ? 0 : -1;
+}
+
+// Connect and authenticate; -1 if either step fails
+static int tc_tcp_connect(tc_client_config *cfg, int timeout_ms) {
+    int fd = tc_tcp_connect_raw(cfg->host, cfg->port, timeout_ms);
+    if (fd < 0) return -1;
+    if (tc_tcp_auth(fd, cfg) != 0) { close(fd); return -1; }
+    return fd;
+}
+
 // Setup keep-alive with configuration
 void tc_setup_keep_alive(int fd, tc_client_config *cfg) {
     if (!cfg->enable_keep_alive) return;
@@ -252,7 +293,7 @@ static tc_tcp_conn *tc_get_conn(tc_client_handle *h) {
         
         // AGGRESSIVE OPTIMIZATION 5: Reduced timeout for fast recovery
         int fast_timeout = h->cfg.connect_timeout_ms / 2; // Half normal timeout
-        int fd = tc_tcp_connect_raw(h->cfg.host, h->cfg.port, fast_timeout);
+        int fd = tc_tcp_connect(&h->cfg, fast_timeout);
         
         if (fd >= 0) {
             // Setup keep-alive if enabled
@@ -1081,6 +1122,9 @@ PHP_FUNCTION(tagcache_create) {
         if ((z = zend_hash_str_find(Z_ARRVAL_P(options), "timeout_ms", sizeof("timeout_ms")-1)) && Z_TYPE_P(z)==IS_LONG) { h->cfg.timeout_ms = Z_LVAL_P(z); }
         if ((z = zend_hash_str_find(Z_ARRVAL_P(options), "connect_timeout_ms", sizeof("connect_timeout_ms")-1)) && Z_TYPE_P(z)==IS_LONG) { h->cfg.connect_timeout_ms = Z_LVAL_P(z); }
         if ((z = zend_hash_str_find(Z_ARRVAL_P(options), "pool_size", sizeof("pool_size")-1)) && Z_TYPE_P(z)==IS_LONG) { h->cfg.pool_size = Z_LVAL_P(z); }
+        if ((z = zend_hash_str_find(Z_ARRVAL_P(options), "username", sizeof("username")-1)) && Z_TYPE_P(z)==IS_STRING) { h->cfg.username = estrdup(Z_STRVAL_P(z)); }
+        if ((z = zend_hash_str_find(Z_ARRVAL_P(options), "password", sizeof("password")-1)) && Z_TYPE_P(z)==IS_STRING) { h->cfg.password = estrdup(Z_STRVAL_P(z)); }
+        if ((z = zend_hash_str_find(Z_ARRVAL_P(options), "token", sizeof("token")-1)) && Z_TYPE_P(z)==IS_STRING) { h->cfg.token = estrdup(Z_STRVAL_P(z)); }
         
         // Parse serializer option
         if ((z = zend_hash_str_find(Z_ARRVAL_P(options), "serializer", sizeof("serializer")-1)) && Z_TYPE_P(z)==IS_STRING) {
@@ -1138,7 +1182,7 @@ PHP_FUNCTION(tagcache_create) {
     for (int i = 0; i < h->pool_len; i++) {
         // Attempt connection with reduced timeout for faster startup
         int fast_timeout = h->cfg.connect_timeout_ms / 2;
-        h->pool[i].fd = tc_tcp_connect_raw(h->cfg.host, h->cfg.port, fast_timeout);
+        h->pool[i].fd = tc_tcp_connect(&h->cfg, fast_timeout);
         
         if (h->pool[i].fd >= 0) {
             // Setup keep-alive if enabled
@@ -1182,7 +1226,7 @@ PHP_FUNCTION(tagcache_create) {
     if (successful_connections < h->pool_len / 2) {
         for (int i = 0; i < h->pool_len; i++) {
             if (h->pool[i].fd < 0) { // Only retry failed connections
-                h->pool[i].fd = tc_tcp_connect_raw(h->cfg.host, h->cfg.port, h->cfg.connect_timeout_ms);
+                h->pool[i].fd = tc_tcp_connect(&h->cfg, h->cfg.connect_timeout_ms);
                 if (h->pool[i].fd >= 0) {
                     // Setup keep-alive if enabled
                     tc_setup_keep_alive(h->pool[i].fd, &h->cfg);
@@ -1625,6 +1669,9 @@ PHP_METHOD(TagCache, create) {
         if ((z=zend_hash_str_find(Z_ARRVAL_P(options), "timeout_ms", sizeof("timeout_ms")-1)) && Z_TYPE_P(z)==IS_LONG) { h->cfg.timeout_ms=Z_LVAL_P(z); }
         if ((z=zend_hash_str_find(Z_ARRVAL_P(options), "connect_timeout_ms", sizeof("connect_timeout_ms")-1)) && Z_TYPE_P(z)==IS_LONG) { h->cfg.connect_timeout_ms=Z_LVAL_P(z); }
         if ((z=zend_hash_str_find(Z_ARRVAL_P(options), "pool_size", sizeof("pool_size")-1)) && Z_TYPE_P(z)==IS_LONG) { h->cfg.pool_size=Z_LVAL_P(z); }
+        if ((z=zend_hash_str_find(Z_ARRVAL_P(options), "username", sizeof("username")-1)) && Z_TYPE_P(z)==IS_STRING) { h->cfg.username=estrdup(Z_STRVAL_P(z)); }
+        if ((z=zend_hash_str_find(Z_ARRVAL_P(options), "password", sizeof("password")-1)) && Z_TYPE_P(z)==IS_STRING) { h->cfg.password=estrdup(Z_STRVAL_P(z)); }
+        if ((z=zend_hash_str_find(Z_ARRVAL_P(options), "token", sizeof("token")-1)) && Z_TYPE_P(z)==IS_STRING) { h->cfg.token=estrdup(Z_STRVAL_P(z)); }
         
         // Parse serializer option  
         if ((z=zend_hash_str_find(Z_ARRVAL_P(options), "serializer", sizeof("serializer")-1)) && Z_TYPE_P(z)==IS_STRING) {
@@ -1641,7 +1688,7 @@ PHP_METHOD(TagCache, create) {
     }
     h->pool_len = h->cfg.pool_size; h->pool = ecalloc(h->pool_len, sizeof(tc_tcp_conn));
     for(int i=0;i<h->pool_len;i++){
-        h->pool[i].fd = tc_tcp_connect_raw(h->cfg.host, h->cfg.port, h->cfg.connect_timeout_ms);
+        h->pool[i].fd = tc_tcp_connect(&h->cfg, h->cfg.connect_timeout_ms);
         if (h->pool[i].fd>=0) { 
             // Setup keep-alive if enabled
             tc_setup_keep_alive(h->pool[i].fd, &h->cfg);
@@ -1870,6 +1917,9 @@ PHP_METHOD(TagCache, close) {
         if (ow->h->pool) { for(int i=0;i<ow->h->pool_len;i++){ if (ow->h->pool[i].fd>=0) close(ow->h->pool[i].fd); } efree(ow->h->pool); }
         if (ow->h->cfg.host) efree(ow->h->cfg.host);
         if (ow->h->cfg.http_base) efree(ow->h->cfg.http_base);
+        if (ow->h->cfg.username) efree(ow->h->cfg.username);
+        if (ow->h->cfg.password) efree(ow->h->cfg.password);
+        if (ow->h->cfg.token) efree(ow->h->cfg.token);
         efree(ow->h); ow->h=NULL;
     }
     RETURN_NULL();
@@ -46,6 +46,10 @@ typedef struct _tc_client_config {
     int keep_alive_idle;        // Keep-alive idle time (seconds)
     int keep_alive_interval;    // Keep-alive probe interval (seconds)
     int keep_alive_count;       // Keep-alive probe count
+    // Credentials sent with AUTH on every new connection
+    char *username;
+    char *password;
+    char *token;
 } tc_client_config;
 
 typedef struct _tc_tcp_conn {
@@ -27,6 +27,8 @@ final class TcpTransport implements TransportInterface
     private array $pool = [];
     private int $rr = 0;
     private Config $config;
+    /** @var array{token?: string, username?: string, password?: string}|null credentials from login(), overriding Config::$auth */
+    private ?array $credentials = null;
     
     // Connection health tracking
     private int $connectionFailures = 0;
@@ -299,8 +301,14 @@ final class TcpTransport implements TransportInterface
                     }
                 }
                 
+                $this->authenticate($sock);
+                
                 return $sock;
                 
+            } catch (AuthenticationException $e) {
+                // Wrong credentials will not get better by retrying
+                $this->closeConnection($sock);
+                throw $e;
             } catch (\Throwable $e) {
                 $lastException = $e;
                 $this->connectionFailures++;
@@ -325,6 +333,44 @@ final class TcpTransport implements TransportInterface
         );
     }
     
+    /**
+     * Sends AUTH on a new connection when credentials are configured. The server
+     * refuses every other command before AUTH unless tcp_require_auth is off.
+     * @param resource $sock
+     * @throws AuthenticationException|ConnectionException
+     */
+    private function authenticate($sock): void
+    {
+        $auth = $this->credentials ?? $this->config->auth;
+        $token = (string)($auth['token'] ?? '');
+        $username = (string)($auth['username'] ?? '');
+        if ($token !== '') {
+            $line = "AUTH\t" . $token;
+        } elseif ($username !== '') {
+            $line = "AUTH\t" . $username . "\t" . (string)($auth['password'] ?? '');
+        } else {
+            return;
+        }
+        if (strpbrk($line, "\r\n") !== false) {
+            throw new AuthenticationException('TCP credentials must not contain newlines');
+        }
+        if (@fwrite($sock, $line . "\n") === false) {
+            throw new ConnectionException('TCP write failed during AUTH');
+        }
+        $resp = @fgets($sock);
+        if ($resp === false) {
+            throw new ConnectionException('TCP read failed during AUTH');
+        }
+        $resp = rtrim($resp, "\r\n");
+        if ($resp === 'OK') {
+            return;
+        }
+        if ($resp === 'ERR unauthorized') {
+            throw $token !== '' ? AuthenticationException::invalidToken() : AuthenticationException::invalidCredentials();
+        }
+        throw new ConnectionException('Unexpected AUTH response: ' . $resp);
+    }
+    
     /**
      * Checks if a connection is healthy and usable
      */
@@ -450,6 +496,8 @@ final class TcpTransport implements TransportInterface
                     $jitter = random_int(0, intval($delay * 0.1));
                     usleep(($delay + $jitter) * 1000);
                 }
+            } catch (AuthenticationException $e) {
+                throw $e;
             } catch (\Throwable $e) {
                 // Convert other exceptions to ApiException
                 throw new ApiException('TCP command failed: ' . $e->getMessage(), 0, $e);
@@ -888,12 +936,16 @@ final class TcpTransport implements TransportInterface
     }
     
     /**
-     * Authentication not supported over TCP
-     * @throws ApiException
+     * Switches the pool to the given account: existing connections are closed and
+     * every new one sends AUTH with these credentials.
+     * @throws AuthenticationException|ConnectionException
      */
     public function login(string $username, string $password): bool
     {
-        throw new ApiException('login not supported over TCP transport; use HTTP');
+        $this->credentials = ['username' => $username, 'password' => $password];
+        $this->resetConnectionPool();
+        $this->conn();
+        return true;
     }
     
     /**
@@ -6,6 +6,7 @@ use PHPUnit\Framework\TestCase;
 use TagCache\Config;
 use TagCache\Transport\TcpTransport;
 use TagCache\Exceptions\ApiException;
+use TagCache\Exceptions\AuthenticationException;
 use TagCache\Exceptions\ConnectionException;
 use TagCache\Exceptions\ConfigurationException;
 use TagCache\Exceptions\NotFoundException;
@@ -325,11 +326,16 @@ class TcpTransportTest extends TestCase
         $this->transport->list();
     }
     
-    public function testAuthenticationNotSupported(): void
+    public function testLoginWithWrongPasswordIsRejected(): void
     {
-        $this->expectException(ApiException::class);
-        $this->expectExceptionMessage('login not supported over TCP transport');
-        $this->transport->login('user', 'pass');
+        try {
+            $this->transport->login('admin', 'definitely-not-the-password');
+            $this->fail('login with a wrong password should be rejected');
+        } catch (AuthenticationException $e) {
+            $this->assertSame(401, $e->getCode());
+        } catch (ConnectionException $e) {
+            $this->markTestSkipped('TagCache server not running on localhost:1984');
+        }
     }
     
     public function testEnhancedStats(): void
@@ -0,0 +1,114 @@
+// =============================
+// BLOCKING FACADE
+// =============================
+// Synchronous wrapper for code without an async runtime: owns a current-thread tokio runtime and
+// blocks on the async client. Do not call it from inside an async context (tokio panics when a
+// runtime is blocked on from one of its own worker threads).
+
+use serde_json::Value;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+use crate::command::{Command, CounterOptions, RateLimitAlgorithm, RateLimitDecision, Reply, Stats, TagMode};
+use crate::config::Config;
+use crate::error::Error;
+
+pub struct Client {
+    inner: crate::Client,
+    rt: Runtime,
+}
+
+impl Client {
+    pub fn new(config: Config) -> Result<Self, Error> {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        Ok(Self { inner: crate::Client::new(config)?, rt })
+    }
+
+    /// The underlying async client (e.g. to hand to async code running elsewhere).
+    pub fn as_async(&self) -> &crate::Client {
+        &self.inner
+    }
+
+    pub fn execute(&self, cmd: &Command) -> Result<Reply, Error> {
+        self.rt.block_on(self.inner.execute(cmd))
+    }
+
+    /// Send `commands` as one pipeline (see [`crate::Pipeline::execute`]).
+    pub fn pipeline(&self, commands: Vec<Command>) -> Result<Vec<Result<Reply, Error>>, Error> {
+        let mut p = self.inner.pipeline();
+        for cmd in commands { p.push(cmd); }
+        self.rt.block_on(p.execute())
+    }
+
+    pub fn put(&self, key: &str, value: &str, tags: &[&str], ttl: Option<Duration>) -> Result<(), Error> {
+        self.rt.block_on(self.inner.put(key, value, tags, ttl))
+    }
+
+    pub fn add(&self, key: &str, value: &str, tags: &[&str], ttl: Option<Duration>) -> Result<bool, Error> {
+        self.rt.block_on(self.inner.add(key, value, tags, ttl))
+    }
+
+    pub fn incr(&self, key: &str, by: i64) -> Result<i64, Error> {
+        self.rt.block_on(self.inner.incr(key, by))
+    }
+
+    pub fn decr(&self, key: &str, by: i64) -> Result<i64, Error> {
+        self.rt.block_on(self.inner.decr(key, by))
+    }
+
+    pub fn incr_with(&self, key: &str, by: f64, options: CounterOptions) -> Result<f64, Error> {
+        self.rt.block_on(self.inner.incr_with(key, by, options))
+    }
+
+    pub fn rate_limit(&self, key: &str, algorithm: RateLimitAlgorithm, limit: u64, window: Duration, tags: &[&str]) -> Result<RateLimitDecision, Error> {
+        self.rt.block_on(self.inner.rate_limit(key, algorithm, limit, window, tags))
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        self.rt.block_on(self.inner.get(key))
+    }
+
+    pub fn del(&self, key: &str) -> Result<bool, Error> {
+        self.rt.block_on(self.inner.del(key))
+    }
+
+    pub fn invalidate_tag(&self, tag: &str) -> Result<usize, Error> {
+        self.rt.block_on(self.inner.invalidate_tag(tag))
+    }
+
+    pub fn invalidate_tags(&self, tags: &[&str], mode: TagMode) -> Result<usize, Error> {
+        self.rt.block_on(self.inner.invalidate_tags(tags, mode))
+    }
+
+    pub fn invalidate_keys(&self, keys: &[&str]) -> Result<usize, Error> {
+        self.rt.block_on(self.inner.invalidate_keys(keys))
+    }
+
+    pub fn add_tags(&self, key: &str, tags: &[&str]) -> Result<Option<bool>, Error> {
+        self.rt.block_on(self.inner.add_tags(key, tags))
+    }
+
+    pub fn remove_tags(&self, key: &str, tags: &[&str]) -> Result<Option<bool>, Error> {
+        self.rt.block_on(self.inner.remove_tags(key, tags))
+    }
+
+    pub fn keys_by_tag(&self, tag: &str) -> Result<Vec<String>, Error> {
+        self.rt.block_on(self.inner.keys_by_tag(tag))
+    }
+
+    pub fn stats(&self) -> Result<Stats, Error> {
+        self.rt.block_on(self.inner.stats())
+    }
+
+    pub fn flush(&self) -> Result<usize, Error> {
+        self.rt.block_on(self.inner.flush())
+    }
+
+    pub fn http_get(&self, path: &str) -> Result<Value, Error> {
+        self.rt.block_on(self.inner.http_get(path))
+    }
+
+    pub fn http_post(&self, path: &str, body: Option<Value>) -> Result<Value, Error> {
+        self.rt.block_on(self.inner.http_post(path, body))
+    }
+}
@@ -0,0 +1,325 @@
+// =============================
+// ASYNC CLIENT
+// =============================
+// `Client` is cheap to clone (all state behind one Arc) and safe to share across tasks. Each call
+// picks a transport from `Config::mode`, retries retryable failures with exponential backoff and
+// maps the reply onto a typed result.
+
+use reqwest::Method;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+use crate::command::{Command, CounterOptions, RateLimitAlgorithm, RateLimitDecision, Reply, Stats, TagMode};
+use crate::config::{Config, Mode};
+use crate::error::Error;
+use crate::http::HttpTransport;
+use crate::tcp::TcpPool;
+use crate::transfer::{ImportMode, ImportReport, TransferFilter};
+
+// Exports / imports of a large keyspace legitimately outlast `Config::timeout`.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Clone)]
+pub struct Client {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: Config,
+    tcp: Option<TcpPool>, // None in Mode::Http
+    http: HttpTransport,  // Always available (fallback + admin endpoints)
+}
+
+impl Client {
+    /// Build a client; connections are opened lazily on first use.
+    pub fn new(config: Config) -> Result<Self, Error> {
+        let tcp = (config.mode != Mode::Http).then(|| {
+            TcpPool::new(config.tcp_addr.clone(), config.pool_size, config.connect_timeout, config.timeout, config.tcp_auth_line())
+        });
+        let http = HttpTransport::new(&config)?;
+        Ok(Self { inner: Arc::new(Inner { config, tcp, http }) })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.inner.config
+    }
+
+    /// Run one command (with retries) and return its typed reply.
+    pub async fn execute(&self, cmd: &Command) -> Result<Reply, Error> {
+        let mut attempt = 0;
+        loop {
+            match self.execute_once(cmd).await {
+                Err(e) if attempt < self.inner.config.max_retries && e.is_retryable(cmd.is_idempotent()) => {
+                    tokio::time::sleep(self.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn execute_once(&self, cmd: &Command) -> Result<Reply, Error> {
+        let Some(tcp) = &self.inner.tcp else { return self.inner.http.execute(cmd).await };
+        let auto = self.inner.config.mode == Mode::Auto;
+        let line = match cmd.to_tcp_line() {
+            Ok(line) => line,
+            Err(_) if auto => return self.inner.http.execute(cmd).await, // Not expressible on TCP
+            Err(e) => return Err(e),
+        };
+        match tcp.round_trip(std::slice::from_ref(&line)).await {
+            Ok(replies) => cmd.parse_tcp_reply(&replies[0]),
+            Err(Error::Connect(_)) if auto => self.inner.http.execute(cmd).await,
+            Err(e) => Err(e),
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.inner.config.retry_delay.saturating_mul(1u32 << attempt.min(16))
+    }
+
+    /// Start a batch of commands sent back-to-back on one connection.
+    pub fn pipeline(&self) -> Pipeline {
+        Pipeline { client: self.clone(), commands: Vec::new() }
+    }
+
+    pub async fn put(&self, key: &str, value: &str, tags: &[&str], ttl: Option<Duration>) -> Result<(), Error> {
+        let cmd = Command::Put { key: key.into(), value: value.into(), tags: strings(tags), ttl };
+        self.execute(&cmd).await.map(|_| ())
+    }
+
+    /// Store only if the key does not exist; returns whether it was added.
+    pub async fn add(&self, key: &str, value: &str, tags: &[&str], ttl: Option<Duration>) -> Result<bool, Error> {
+        match self.execute(&Command::Add { key: key.into(), value: value.into(), tags: strings(tags), ttl }).await? {
+            Reply::Added(added) => Ok(added),
+            other => Err(mismatch(other)),
+        }
+    }
+
+    /// Atomically add `by` (creating the key at `by`); returns the new value.
+    pub async fn incr(&self, key: &str, by: i64) -> Result<i64, Error> {
+        int(self.execute(&Command::Incr { key: key.into(), by, tags: Vec::new(), ttl: None }).await?)
+    }
+
+    pub async fn decr(&self, key: &str, by: i64) -> Result<i64, Error> {
+        int(self.execute(&Command::Decr { key: key.into(), by, tags: Vec::new(), ttl: None }).await?)
+    }
+
+    /// INCR with a float amount and/or bounds / a reset window (negative `by` decrements); returns
+    /// the new value.
+    pub async fn incr_with(&self, key: &str, by: f64, options: CounterOptions) -> Result<f64, Error> {
+        match self.execute(&Command::Counter { key: key.into(), by, options, tags: Vec::new(), ttl: None }).await? {
+            Reply::Number(n) => Ok(n),
+            other => Err(mismatch(other)),
+        }
+    }
+
+    /// Check and record one request against the rate limiter `key` (`limit` requests per `window`).
+    pub async fn rate_limit(&self, key: &str, algorithm: RateLimitAlgorithm, limit: u64, window: Duration, tags: &[&str]) -> Result<RateLimitDecision, Error> {
+        match self.execute(&Command::RateLimit { key: key.into(), algorithm, limit, window, tags: strings(tags) }).await? {
+            Reply::RateLimit(decision) => Ok(decision),
+            other => Err(mismatch(other)),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        match self.execute(&Command::Get { key: key.into() }).await? {
+            Reply::Value(v) => Ok(v),
+            other => Err(mismatch(other)),
+        }
+    }
+
+    /// Remove one key; returns whether it existed.
+    pub async fn del(&self, key: &str) -> Result<bool, Error> {
+        match self.execute(&Command::Del { key: key.into() }).await? {
+            Reply::Deleted(d) => Ok(d),
+            other => Err(mismatch(other)),
+        }
+    }
+
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<usize, Error> {
+        count(self.execute(&Command::InvalidateTag { tag: tag.into() }).await?)
+    }
+
+    pub async fn invalidate_tags(&self, tags: &[&str], mode: TagMode) -> Result<usize, Error> {
+        count(self.execute(&Command::InvalidateTags { tags: strings(tags), mode }).await?)
+    }
+
+    pub async fn invalidate_keys(&self, keys: &[&str]) -> Result<usize, Error> {
+        count(self.execute(&Command::InvalidateKeys { keys: strings(keys) }).await?)
+    }
+
+    /// Add tags to a key without rewriting it; returns whether its tags changed, None when the key
+    /// is missing.
+    pub async fn add_tags(&self, key: &str, tags: &[&str]) -> Result<Option<bool>, Error> {
+        match self.execute(&Command::AddTags { key: key.into(), tags: strings(tags) }).await? {
+            Reply::Tagged(t) => Ok(t),
+            other => Err(mismatch(other)),
+        }
+    }
+
+    /// Take tags off a key without rewriting it; returns whether its tags changed, None when the key
+    /// is missing.
+    pub async fn remove_tags(&self, key: &str, tags: &[&str]) -> Result<Option<bool>, Error> {
+        match self.execute(&Command::RemoveTags { key: key.into(), tags: strings(tags) }).await? {
+            Reply::Tagged(t) => Ok(t),
+            other => Err(mismatch(other)),
+        }
+    }
+
+    pub async fn keys_by_tag(&self, tag: &str) -> Result<Vec<String>, Error> {
+        match self.execute(&Command::KeysByTag { tag: tag.into() }).await? {
+            Reply::Keys(keys) => Ok(keys),
+            other => Err(mismatch(other)),
+        }
+    }
+
+    pub async fn stats(&self) -> Result<Stats, Error> {
+        match self.execute(&Command::Stats).await? {
+            Reply::Stats(s) => Ok(s),
+            other => Err(mismatch(other)),
+        }
+    }
+
+    /// Remove every entry; returns how many were removed.
+    pub async fn flush(&self) -> Result<usize, Error> {
+        count(self.execute(&Command::Flush).await?)
+    }
+
+    /// GET any HTTP endpoint (e.g. `/stats` for the full payload, `/health`) and return its JSON.
+    pub async fn http_get(&self, path: &str) -> Result<Value, Error> {
+        self.inner.http.request(Method::GET, path, None).await
+    }
+
+    /// POST to any HTTP endpoint (admin / auth routes without a typed wrapper).
+    pub async fn http_post(&self, path: &str, body: Option<Value>) -> Result<Value, Error> {
+        self.inner.http.request(Method::POST, path, body).await
+    }
+
+    /// Stream a JSONL dump of the matching entries (GET /admin/export) into `out`; returns the
+    /// number of records written. Always HTTP, and only the connect timeout applies.
+    pub async fn export<W: AsyncWrite + Unpin>(&self, filter: &TransferFilter, out: &mut W) -> Result<u64, Error> {
+        let path = with_query("/admin/export", filter.query_pairs());
+        let req = self.inner.http.builder(Method::GET, &path).timeout(TRANSFER_TIMEOUT);
+        let mut resp = self.inner.http.send(req).await?;
+        let mut records = 0;
+        while let Some(chunk) = resp.chunk().await? {
+            records += chunk.iter().filter(|&&b| b == b'\n').count() as u64;
+            out.write_all(&chunk).await?;
+        }
+        out.flush().await?;
+        Ok(records)
+    }
+
+    /// Upload a JSONL dump (as written by [`Client::export`]) to POST /admin/import, streaming it
+    /// from `input`. Lines the server rejects are listed in the report, not returned as an error.
+    pub async fn import<R: AsyncRead + Send + 'static>(&self, input: R, mode: ImportMode, filter: &TransferFilter) -> Result<ImportReport, Error> {
+        let mut pairs = vec![format!("mode={}", mode.as_str())];
+        pairs.extend(filter.query_pairs());
+        let path = with_query("/admin/import", pairs);
+        let body = reqwest::Body::wrap_stream(ReaderStream::new(input));
+        let req = self.inner.http.builder(Method::POST, &path)
+            .header("Content-Type", "application/x-ndjson")
+            .body(body)
+            .timeout(TRANSFER_TIMEOUT);
+        let resp = self.inner.http.send(req).await?;
+        resp.json().await.map_err(|e| Error::Protocol(e.to_string()))
+    }
+}
+
+/// Commands queued for one round trip. On TCP they are written back-to-back on a single pooled
+/// connection and the replies read in order; over HTTP they run one after another.
+pub struct Pipeline {
+    client: Client,
+    commands: Vec<Command>,
+}
+
+impl Pipeline {
+    pub fn push(&mut self, cmd: Command) -> &mut Self {
+        self.commands.push(cmd);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Send every queued command. The outer error means the batch as a whole failed (connection
+    /// lost, timeout); otherwise each command gets its own result, in order. Only batches that
+    /// failed to connect are retried, since a broken batch may have been partly applied.
+    pub async fn execute(self) -> Result<Vec<Result<Reply, Error>>, Error> {
+        let inner = &self.client.inner;
+        let Some(tcp) = &inner.tcp else { return Ok(self.run_http(0..self.commands.len()).await) };
+        let auto = inner.config.mode == Mode::Auto;
+
+        // Encode up front; commands that cannot go over TCP are answered separately.
+        let mut lines = Vec::with_capacity(self.commands.len());
+        let mut sent = Vec::with_capacity(self.commands.len()); // Index of the command behind each line
+        let mut results: Vec<Option<Result<Reply, Error>>> = Vec::with_capacity(self.commands.len());
+        for (i, cmd) in self.commands.iter().enumerate() {
+            match cmd.to_tcp_line() {
+                Ok(line) => { lines.push(line); sent.push(i); results.push(None); }
+                Err(e) if !auto => results.push(Some(Err(e))),
+                Err(_) => results.push(None), // Sent over HTTP below
+            }
+        }
+
+        let mut attempt = 0;
+        let replies = loop {
+            if lines.is_empty() { break Vec::new(); }
+            match tcp.round_trip(&lines).await {
+                Ok(replies) => break replies,
+                Err(Error::Connect(_)) if auto => {
+                    return Ok(self.run_http(0..self.commands.len()).await);
+                }
+                Err(e @ Error::Connect(_)) => {
+                    if attempt >= inner.config.max_retries { return Err(e); }
+                    tokio::time::sleep(self.client.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        for (reply, &i) in replies.iter().zip(&sent) {
+            results[i] = Some(self.commands[i].parse_tcp_reply(reply));
+        }
+        let pending: Vec<usize> = (0..self.commands.len()).filter(|&i| results[i].is_none()).collect();
+        let via_http = self.run_http(pending.iter().copied()).await;
+        for (i, r) in pending.into_iter().zip(via_http) { results[i] = Some(r); }
+        Ok(results.into_iter().map(|r| r.expect("every command answered")).collect())
+    }
+
+    async fn run_http(&self, indices: impl Iterator<Item = usize>) -> Vec<Result<Reply, Error>> {
+        let mut out = Vec::new();
+        for i in indices {
+            out.push(self.client.inner.http.execute(&self.commands[i]).await);
+        }
+        out
+    }
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+fn mismatch(reply: Reply) -> Error {
+    Error::Protocol(format!("unexpected reply {:?}", reply))
+}
+
+fn int(reply: Reply) -> Result<i64, Error> {
+    match reply { Reply::Int(n) => Ok(n), other => Err(mismatch(other)) }
+}
+
+fn count(reply: Reply) -> Result<usize, Error> {
+    match reply { Reply::Count(n) => Ok(n), other => Err(mismatch(other)) }
+}
+
+fn with_query(path: &str, pairs: Vec<String>) -> String {
+    if pairs.is_empty() { path.to_string() } else { format!("{}?{}", path, pairs.join("&")) }
+}
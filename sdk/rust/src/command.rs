@@ -0,0 +1,509 @@
+// =============================
+// COMMANDS AND REPLIES
+// =============================
+// One `Command` per cache verb of the server's TCP protocol, with its encoding for both transports
+// and the parsing of the matching reply into a typed `Reply`.
+//
+// TCP lines are tab-separated; keys and tags therefore cannot contain tabs or line breaks, tags and
+// the keys of INV_KEYS cannot contain commas, and values cannot contain line breaks. Such commands
+// are rejected with `Error::InvalidInput` (or sent over HTTP in `Mode::Auto`).
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::Method;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::time::Duration;
+
+use crate::error::Error;
+
+/// How a multi-tag invalidation matches keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagMode {
+    Any,     // key has at least one of the tags
+    All,     // key has every tag
+    Subtree, // key has one of the tags or a tag below one (`tenant:42` covers `tenant:42:product:7`)
+}
+
+impl TagMode {
+    pub fn as_str(&self) -> &'static str {
+        match self { TagMode::Any => "any", TagMode::All => "all", TagMode::Subtree => "subtree" }
+    }
+}
+
+/// Algorithm of a [`Command::RateLimit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAlgorithm {
+    FixedWindow,   // `limit` per epoch-aligned window
+    SlidingWindow, // `limit` in any `window`
+    TokenBucket,   // `limit` tokens refilled over `window`
+}
+
+impl RateLimitAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitAlgorithm::FixedWindow => "fixed_window",
+            RateLimitAlgorithm::SlidingWindow => "sliding_window",
+            RateLimitAlgorithm::TokenBucket => "token_bucket",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "fixed_window" => Some(RateLimitAlgorithm::FixedWindow),
+            "sliding_window" => Some(RateLimitAlgorithm::SlidingWindow),
+            "token_bucket" => Some(RateLimitAlgorithm::TokenBucket),
+            _ => None,
+        }
+    }
+}
+
+/// Answer to a [`Command::RateLimit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub remaining: u64,
+    pub reset_after: Duration, // Until a request is allowed again (denied) or the limit is full again (allowed)
+}
+
+/// Float mode, bounds and reset window of a [`Command::Counter`] (the server's INCR options).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CounterOptions {
+    pub float: bool,            // Keep a floating-point counter even for whole amounts
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub reject: bool,           // Refuse results outside min / max instead of clamping them
+    pub window: Option<String>, // Reset at each boundary: "minute", "hour", "day" or e.g. "15m"
+}
+
+impl CounterOptions {
+    fn is_empty(&self) -> bool {
+        *self == CounterOptions::default()
+    }
+
+    // The comma-separated TCP field, e.g. `float,min=0,max=10,bound=reject,window=hour`.
+    fn to_field(&self) -> Result<String, Error> {
+        let mut items = Vec::new();
+        if self.float { items.push("float".to_string()); }
+        if let Some(min) = self.min { items.push(format!("min={}", min)); }
+        if let Some(max) = self.max { items.push(format!("max={}", max)); }
+        if self.reject { items.push("bound=reject".to_string()); }
+        if let Some(window) = &self.window {
+            if window.contains([',', '\t', '\n', '\r']) { return Err(Error::InvalidInput("window contains a separator".into())); }
+            items.push(format!("window={}", window));
+        }
+        Ok(if items.is_empty() { "-".to_string() } else { items.join(",") })
+    }
+
+    fn parse(field: &str) -> Result<Self, String> {
+        let mut options = CounterOptions::default();
+        for item in field.split(',').map(str::trim).filter(|i| !i.is_empty() && *i != "-") {
+            let (name, value) = item.split_once('=').unwrap_or((item, ""));
+            let number = || value.parse::<f64>().map_err(|_| format!("invalid {} '{}'", name, value));
+            match name {
+                "float" => options.float = true,
+                "min" => options.min = Some(number()?),
+                "max" => options.max = Some(number()?),
+                "bound" => options.reject = value == "reject",
+                "window" => options.window = Some(value.to_string()),
+                other => return Err(format!("unknown counter option '{}'", other)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Put { key: String, value: String, tags: Vec<String>, ttl: Option<Duration> },
+    Add { key: String, value: String, tags: Vec<String>, ttl: Option<Duration> },
+    Incr { key: String, by: i64, tags: Vec<String>, ttl: Option<Duration> },
+    Decr { key: String, by: i64, tags: Vec<String>, ttl: Option<Duration> },
+    /// INCR with a float amount and/or options (a negative `by` decrements).
+    Counter { key: String, by: f64, options: CounterOptions, tags: Vec<String>, ttl: Option<Duration> },
+    /// Check and record one request against a server-side rate limiter.
+    RateLimit { key: String, algorithm: RateLimitAlgorithm, limit: u64, window: Duration, tags: Vec<String> },
+    Get { key: String },
+    Del { key: String },
+    InvalidateTag { tag: String },
+    InvalidateTags { tags: Vec<String>, mode: TagMode },
+    InvalidateKeys { keys: Vec<String> },
+    /// Add tags to an existing key, keeping its value, TTL and age.
+    AddTags { key: String, tags: Vec<String> },
+    /// Take tags off an existing key, keeping its value, TTL and age.
+    RemoveTags { key: String, tags: Vec<String> },
+    KeysByTag { tag: String },
+    Stats,
+    Flush,
+}
+
+/// Typed result of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Ok,                     // PUT
+    Added(bool),            // ADD: false when the key already existed
+    Int(i64),               // INCR / DECR: the new value
+    Number(f64),            // Counter: the new value
+    RateLimit(RateLimitDecision), // RATELIMIT
+    Value(Option<String>),  // GET: None when missing or expired
+    Deleted(bool),          // DEL: false when the key did not exist
+    Count(usize),           // INV_TAG / INV_TAGS_* / INV_KEYS / FLUSH: entries removed
+    Tagged(Option<bool>),   // TAG_ADD / TAG_DEL: whether the tags changed; None when the key is missing
+    Keys(Vec<String>),      // KEYS_BY_TAG
+    Stats(Stats),           // STATS
+}
+
+/// Counters returned by STATS (the HTTP `/stats` payload has more; see `Client::http_get`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    pub puts: u64,
+    pub invalidations: u64,
+    pub hit_ratio: f64,
+}
+
+impl Command {
+    /// PUT, DEL, GET and the invalidations can be repeated safely; ADD / INCR / DECR / RATELIMIT cannot.
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(self, Command::Add { .. } | Command::Incr { .. } | Command::Decr { .. } | Command::Counter { .. } | Command::RateLimit { .. })
+    }
+
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Command::Put { .. } => "PUT",
+            Command::Add { .. } => "ADD",
+            Command::Incr { .. } | Command::Counter { .. } => "INCR",
+            Command::Decr { .. } => "DECR",
+            Command::RateLimit { .. } => "RATELIMIT",
+            Command::Get { .. } => "GET",
+            Command::Del { .. } => "DEL",
+            Command::InvalidateTag { .. } => "INV_TAG",
+            Command::InvalidateTags { mode: TagMode::Any, .. } => "INV_TAGS_ANY",
+            Command::InvalidateTags { mode: TagMode::All, .. } => "INV_TAGS_ALL",
+            Command::InvalidateTags { mode: TagMode::Subtree, .. } => "INV_TAGS_TREE",
+            Command::InvalidateKeys { .. } => "INV_KEYS",
+            Command::AddTags { .. } => "TAG_ADD",
+            Command::RemoveTags { .. } => "TAG_DEL",
+            Command::KeysByTag { .. } => "KEYS_BY_TAG",
+            Command::Stats => "STATS",
+            Command::Flush => "FLUSH",
+        }
+    }
+
+    /// Encode as one TCP protocol line (without the trailing newline).
+    pub fn to_tcp_line(&self) -> Result<String, Error> {
+        let v = self.verb();
+        Ok(match self {
+            Command::Put { key, value, tags, ttl } | Command::Add { key, value, tags, ttl } => {
+                check_field("key", key, false)?;
+                if value.contains(['\n', '\r']) { return Err(Error::InvalidInput("value contains a line break".into())); }
+                format!("{v}\t{key}\t{}\t{}\t{value}", ttl_field(ttl), list_field("tag", tags)?)
+            }
+            Command::Incr { key, by, tags, ttl } | Command::Decr { key, by, tags, ttl } => {
+                check_field("key", key, false)?;
+                format!("{v}\t{key}\t{by}\t{}\t{}", ttl_field(ttl), list_field("tag", tags)?)
+            }
+            Command::Counter { key, by, options, tags, ttl } => {
+                check_field("key", key, false)?;
+                format!("{v}\t{key}\t{by}\t{}\t{}\t{}", ttl_field(ttl), list_field("tag", tags)?, options.to_field()?)
+            }
+            Command::RateLimit { key, algorithm, limit, window, tags } => {
+                check_field("key", key, false)?;
+                format!("{v}\t{key}\t{}\t{limit}\t{}\t{}", algorithm.as_str(), window.as_millis(), list_field("tag", tags)?)
+            }
+            Command::Get { key } | Command::Del { key } => { check_field("key", key, false)?; format!("{v}\t{key}") }
+            Command::InvalidateTag { tag } | Command::KeysByTag { tag } => { check_field("tag", tag, false)?; format!("{v}\t{tag}") }
+            Command::InvalidateTags { tags, .. } => format!("{v}\t{}", non_empty_list("tag", tags)?),
+            Command::InvalidateKeys { keys } => format!("{v}\t{}", non_empty_list("key", keys)?),
+            Command::AddTags { key, tags } | Command::RemoveTags { key, tags } => {
+                check_field("key", key, false)?;
+                format!("{v}\t{key}\t{}", non_empty_list("tag", tags)?)
+            }
+            Command::Stats | Command::Flush => v.to_string(),
+        })
+    }
+
+    /// Parse one TCP protocol request line (the inverse of [`Command::to_tcp_line`]), with the same
+    /// leniency as the server: case-insensitive verbs, `-` or empty for "no TTL / no tags", and an
+    /// INCR / DECR amount of 1 when omitted. INCR / DECR with a fractional amount or counter options
+    /// become [`Command::Counter`].
+    pub fn from_tcp_line(line: &str) -> Result<Command, Error> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let mut parts = line.splitn(5, '\t');
+        let verb = parts.next().unwrap_or("").to_ascii_uppercase();
+        let invalid = |msg: &str| Error::InvalidInput(format!("{}: {}", verb, msg));
+        let mut field = |what: &str| match parts.next() {
+            Some(s) if !s.is_empty() => Ok(s.to_string()),
+            _ => Err(invalid(&format!("missing {}", what))),
+        };
+        let ttl = |s: Option<&str>| -> Result<Option<Duration>, Error> {
+            match s {
+                None | Some("") | Some("-") => Ok(None),
+                Some(ms) => ms.parse().map(|ms| Some(Duration::from_millis(ms))).map_err(|_| invalid("ttl_ms is not a number")),
+            }
+        };
+        let list = |s: Option<&str>| -> Vec<String> {
+            match s {
+                None | Some("") | Some("-") => Vec::new(),
+                Some(l) => l.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect(),
+            }
+        };
+        Ok(match verb.as_str() {
+            "PUT" | "ADD" => {
+                let key = field("key")?;
+                let (ttl, tags, value) = (ttl(parts.next())?, list(parts.next()), parts.next().unwrap_or("").to_string());
+                if verb == "PUT" { Command::Put { key, value, tags, ttl } } else { Command::Add { key, value, tags, ttl } }
+            }
+            "INCR" | "DECR" => {
+                let key = field("key")?;
+                let amount = parts.next().filter(|n| !n.is_empty()).unwrap_or("1");
+                let ttl = ttl(parts.next())?;
+                let (tags, options) = parts.next().map_or(("-", "-"), |rest| rest.split_once('\t').unwrap_or((rest, "-")));
+                let (tags, options) = (list(Some(tags)), CounterOptions::parse(options).map_err(|e| invalid(&e))?);
+                match amount.parse::<i64>() {
+                    Ok(by) if options.is_empty() => if verb == "INCR" { Command::Incr { key, by, tags, ttl } } else { Command::Decr { key, by, tags, ttl } },
+                    _ => {
+                        let by: f64 = amount.parse().map_err(|_| invalid("amount is not a number"))?;
+                        Command::Counter { key, by: if verb == "INCR" { by } else { -by }, options, tags, ttl }
+                    }
+                }
+            }
+            "RATELIMIT" => {
+                let key = field("key")?;
+                let algorithm = RateLimitAlgorithm::parse(&field("algorithm")?).ok_or_else(|| invalid("unknown algorithm"))?;
+                let limit = field("limit")?.parse().map_err(|_| invalid("limit is not a number"))?;
+                let rest = field("window_ms")?; // The last field: window_ms, then the optional tags
+                let (window, tags) = rest.split_once('\t').unwrap_or((rest.as_str(), "-"));
+                let window = window.parse().map(Duration::from_millis).map_err(|_| invalid("window_ms is not a number"))?;
+                Command::RateLimit { key, algorithm, limit, window, tags: list(Some(tags)) }
+            }
+            "GET" => Command::Get { key: field("key")? },
+            "DEL" => Command::Del { key: field("key")? },
+            "INV_TAG" => Command::InvalidateTag { tag: field("tag")? },
+            "INV_TAGS_ANY" => Command::InvalidateTags { tags: list(Some(&field("tags")?)), mode: TagMode::Any },
+            "INV_TAGS_ALL" => Command::InvalidateTags { tags: list(Some(&field("tags")?)), mode: TagMode::All },
+            "INV_TAGS_TREE" => Command::InvalidateTags { tags: list(Some(&field("tags")?)), mode: TagMode::Subtree },
+            "INV_KEYS" => Command::InvalidateKeys { keys: list(Some(&field("keys")?)) },
+            "TAG_ADD" => Command::AddTags { key: field("key")?, tags: list(Some(&field("tags")?)) },
+            "TAG_DEL" => Command::RemoveTags { key: field("key")?, tags: list(Some(&field("tags")?)) },
+            "KEYS_BY_TAG" | "KEYS" => Command::KeysByTag { tag: field("tag")? },
+            "STATS" => Command::Stats,
+            "FLUSH" => Command::Flush,
+            "" => return Err(Error::InvalidInput("empty command".into())),
+            _ => return Err(invalid("unknown command")),
+        })
+    }
+
+    /// Parse the TCP reply line for this command.
+    pub fn parse_tcp_reply(&self, line: &str) -> Result<Reply, Error> {
+        if let Some(reason) = line.strip_prefix("ERR ") { return Err(Error::Server(reason.to_string())); }
+        if let Some(rest) = line.strip_prefix("MOVED\t") {
+            let (node, addr) = rest.split_once('\t').unwrap_or((rest, ""));
+            return Err(Error::Moved { node: node.to_string(), addr: addr.to_string() });
+        }
+        let unexpected = || Error::Protocol(line.to_string());
+        let count = |prefix: &str| -> Result<Reply, Error> {
+            line.strip_prefix(prefix).and_then(|n| n.parse().ok()).map(Reply::Count).ok_or_else(unexpected)
+        };
+        match self {
+            Command::Put { .. } => if line == "OK" { Ok(Reply::Ok) } else { Err(unexpected()) },
+            Command::Add { .. } => match line { "ADDED" => Ok(Reply::Added(true)), "EXISTS" => Ok(Reply::Added(false)), _ => Err(unexpected()) },
+            Command::Incr { .. } | Command::Decr { .. } => {
+                line.strip_prefix("VALUE\t").and_then(|n| n.parse().ok()).map(Reply::Int).ok_or_else(unexpected)
+            }
+            Command::Counter { .. } => {
+                line.strip_prefix("VALUE\t").and_then(|n| n.parse().ok()).map(Reply::Number).ok_or_else(unexpected)
+            }
+            Command::RateLimit { .. } => match line.split('\t').collect::<Vec<_>>().as_slice() {
+                [verb @ ("ALLOWED" | "DENIED"), remaining, ms] => Ok(Reply::RateLimit(RateLimitDecision {
+                    allowed: *verb == "ALLOWED",
+                    remaining: remaining.parse().map_err(|_| unexpected())?,
+                    reset_after: Duration::from_millis(ms.parse().map_err(|_| unexpected())?),
+                })),
+                _ => Err(unexpected()),
+            },
+            Command::Get { .. } => match line.strip_prefix("VALUE\t") {
+                Some(v) => Ok(Reply::Value(Some(v.to_string()))),
+                None if line == "NF" => Ok(Reply::Value(None)),
+                None => Err(unexpected()),
+            },
+            Command::Del { .. } => match line { "DEL ok" => Ok(Reply::Deleted(true)), "DEL nf" => Ok(Reply::Deleted(false)), _ => Err(unexpected()) },
+            Command::InvalidateTag { .. } => count("INV_TAG\t"),
+            Command::InvalidateTags { mode: TagMode::Any, .. } => count("INV_TAGS_ANY\t"),
+            Command::InvalidateTags { mode: TagMode::All, .. } => count("INV_TAGS_ALL\t"),
+            Command::InvalidateTags { mode: TagMode::Subtree, .. } => count("INV_TAGS_TREE\t"),
+            Command::InvalidateKeys { .. } => count("INV_KEYS\t"),
+            Command::Flush => count("FLUSH\t"),
+            Command::AddTags { .. } | Command::RemoveTags { .. } => match line.split_once('\t') {
+                Some((_, "1")) => Ok(Reply::Tagged(Some(true))),
+                Some((_, "0")) => Ok(Reply::Tagged(Some(false))),
+                _ if line == "NF" => Ok(Reply::Tagged(None)),
+                _ => Err(unexpected()),
+            },
+            Command::KeysByTag { .. } => match line.strip_prefix("KEYS\t").or(if line == "KEYS" { Some("") } else { None }) {
+                Some(list) => Ok(Reply::Keys(list.split(',').filter(|k| !k.is_empty()).map(str::to_string).collect())),
+                None => Err(unexpected()),
+            },
+            Command::Stats => {
+                let f: Vec<&str> = line.split('\t').collect();
+                match f.as_slice() {
+                    ["STATS", hits, misses, puts, inv, ratio] => Ok(Reply::Stats(Stats {
+                        hits: hits.parse().map_err(|_| unexpected())?,
+                        misses: misses.parse().map_err(|_| unexpected())?,
+                        puts: puts.parse().map_err(|_| unexpected())?,
+                        invalidations: inv.parse().map_err(|_| unexpected())?,
+                        hit_ratio: ratio.parse().map_err(|_| unexpected())?,
+                    })),
+                    _ => Err(unexpected()),
+                }
+            }
+        }
+    }
+
+    /// Method, path (with query) and JSON body of the equivalent HTTP API call.
+    pub fn http_request(&self) -> Result<(Method, String, Option<Value>), Error> {
+        let ttl_ms = |ttl: &Option<Duration>| ttl.map(|d| d.as_millis() as u64);
+        Ok(match self {
+            Command::Put { key, value, tags, ttl } | Command::Add { key, value, tags, ttl } => {
+                check_field("key", key, true)?;
+                let path = if matches!(self, Command::Put { .. }) { "/put" } else { "/add" };
+                (Method::POST, path.into(), Some(json!({"key": key, "value": value, "tags": tags, "ttl_ms": ttl_ms(ttl)})))
+            }
+            Command::Incr { key, by, tags, ttl } | Command::Decr { key, by, tags, ttl } => {
+                check_field("key", key, true)?;
+                let path = if matches!(self, Command::Incr { .. }) { "/incr" } else { "/decr" };
+                (Method::POST, path.into(), Some(json!({"key": key, "by": by, "tags": tags, "ttl_ms": ttl_ms(ttl)})))
+            }
+            Command::Counter { key, by, options, tags, ttl } => {
+                check_field("key", key, true)?;
+                let bound = if options.reject { "reject" } else { "clamp" };
+                (Method::POST, "/incr".into(), Some(json!({
+                    "key": key, "by": by, "tags": tags, "ttl_ms": ttl_ms(ttl),
+                    "float": options.float, "min": options.min, "max": options.max, "bound": bound, "window": options.window,
+                })))
+            }
+            Command::RateLimit { key, algorithm, limit, window, tags } => {
+                check_field("key", key, true)?;
+                let body = json!({"algorithm": algorithm.as_str(), "limit": limit, "window_ms": window.as_millis() as u64, "tags": tags});
+                (Method::POST, format!("/keys/{}/ratelimit", encode(key)), Some(body))
+            }
+            Command::Get { key } => { check_field("key", key, true)?; (Method::GET, format!("/get/{}", encode(key)), None) }
+            Command::Del { key } => (Method::POST, "/invalidate-key".into(), Some(json!({"key": key}))),
+            Command::InvalidateTag { tag } => (Method::POST, "/invalidate-tag".into(), Some(json!({"tag": tag}))),
+            Command::InvalidateTags { tags, mode } => (Method::POST, "/invalidate/tags".into(), Some(json!({"tags": tags, "mode": mode.as_str()}))),
+            Command::InvalidateKeys { keys } => (Method::POST, "/invalidate/keys".into(), Some(json!({"keys": keys}))),
+            Command::AddTags { key, tags } => { check_field("key", key, true)?; (Method::POST, format!("/keys/{}/tags", encode(key)), Some(json!({"add": tags}))) }
+            Command::RemoveTags { key, tags } => { check_field("key", key, true)?; (Method::POST, format!("/keys/{}/tags", encode(key)), Some(json!({"remove": tags}))) }
+            Command::KeysByTag { tag } => (Method::GET, format!("/keys-by-tag?tag={}", encode(tag)), None),
+            Command::Stats => (Method::GET, "/stats".into(), None),
+            Command::Flush => (Method::POST, "/flush".into(), None),
+        })
+    }
+
+    /// Interpret the JSON body of a successful HTTP response for this command.
+    pub fn parse_http_reply(&self, body: &Value) -> Result<Reply, Error> {
+        let unexpected = || Error::Protocol(body.to_string());
+        let count = || body.get("count").and_then(Value::as_u64).map(|n| Reply::Count(n as usize)).ok_or_else(unexpected);
+        match self {
+            Command::Put { .. } => Ok(Reply::Ok),
+            Command::Add { .. } => body.get("added").and_then(Value::as_bool).map(Reply::Added).ok_or_else(unexpected),
+            Command::Incr { .. } | Command::Decr { .. } => match body.get("value").and_then(Value::as_i64) {
+                Some(n) => Ok(Reply::Int(n)),
+                None => Err(body.get("error").and_then(Value::as_str).map(|e| Error::Server(e.to_string())).unwrap_or_else(unexpected)),
+            },
+            Command::Counter { .. } => match body.get("value").and_then(Value::as_f64) {
+                Some(n) => Ok(Reply::Number(n)),
+                None => Err(body.get("error").and_then(Value::as_str).map(|e| Error::Server(e.to_string())).unwrap_or_else(unexpected)),
+            },
+            Command::RateLimit { .. } => {
+                let n = |f: &str| body.get(f).and_then(Value::as_u64).ok_or_else(unexpected);
+                Ok(Reply::RateLimit(RateLimitDecision {
+                    allowed: body.get("allowed").and_then(Value::as_bool).ok_or_else(unexpected)?,
+                    remaining: n("remaining")?,
+                    reset_after: Duration::from_millis(n("reset_after_ms")?),
+                }))
+            }
+            Command::Get { .. } => match body.get("value").and_then(Value::as_str) {
+                Some(v) => Ok(Reply::Value(Some(v.to_string()))),
+                None if body.get("error").and_then(Value::as_str) == Some("not_found") => Ok(Reply::Value(None)),
+                None => Err(unexpected()),
+            },
+            Command::Del { .. } => body.get("success").and_then(Value::as_bool).map(Reply::Deleted).ok_or_else(unexpected),
+            Command::InvalidateTag { .. } | Command::InvalidateTags { .. } | Command::InvalidateKeys { .. } | Command::Flush => count(),
+            Command::AddTags { .. } | Command::RemoveTags { .. } => match body.get("changed").and_then(Value::as_bool) {
+                Some(changed) => Ok(Reply::Tagged(Some(changed))),
+                None if body.get("error").and_then(Value::as_str) == Some("not_found") => Ok(Reply::Tagged(None)),
+                None => Err(unexpected()),
+            },
+            Command::KeysByTag { .. } => body.get("keys").and_then(Value::as_array)
+                .map(|ks| Reply::Keys(ks.iter().filter_map(|k| k.as_str().map(str::to_string)).collect()))
+                .ok_or_else(unexpected),
+            Command::Stats => {
+                let n = |f: &str| body.get(f).and_then(Value::as_u64).unwrap_or(0);
+                Ok(Reply::Stats(Stats {
+                    hits: n("hits"), misses: n("misses"), puts: n("puts"), invalidations: n("invalidations"),
+                    hit_ratio: body.get("hit_ratio").and_then(Value::as_f64).unwrap_or(0.0),
+                }))
+            }
+        }
+    }
+}
+
+// Same text the server's TCP protocol uses, so `pipe` output and logs read like the wire.
+impl fmt::Display for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reply::Ok => write!(f, "OK"),
+            Reply::Added(true) => write!(f, "ADDED"),
+            Reply::Added(false) => write!(f, "EXISTS"),
+            Reply::Int(n) => write!(f, "{}", n),
+            Reply::Number(n) => write!(f, "{}", n),
+            Reply::RateLimit(d) => {
+                let verdict = if d.allowed { "ALLOWED" } else { "DENIED" };
+                write!(f, "{} remaining={} reset_after_ms={}", verdict, d.remaining, d.reset_after.as_millis())
+            }
+            Reply::Value(Some(v)) => write!(f, "{}", v),
+            Reply::Value(None) => write!(f, "NF"),
+            Reply::Deleted(true) => write!(f, "DELETED"),
+            Reply::Deleted(false) => write!(f, "NF"),
+            Reply::Count(n) => write!(f, "{}", n),
+            Reply::Tagged(Some(changed)) => write!(f, "{}", if *changed { "TAGGED" } else { "UNCHANGED" }),
+            Reply::Tagged(None) => write!(f, "NF"),
+            Reply::Keys(keys) => write!(f, "{}", keys.join(",")),
+            Reply::Stats(s) => write!(f, "hits={} misses={} puts={} invalidations={} hit_ratio={:.4}", s.hits, s.misses, s.puts, s.invalidations, s.hit_ratio),
+        }
+    }
+}
+
+fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+// Keys / tags must be non-empty; on the TCP protocol they also cannot hold separators.
+fn check_field(what: &str, s: &str, http: bool) -> Result<(), Error> {
+    if s.is_empty() { return Err(Error::InvalidInput(format!("empty {}", what))); }
+    if !http && s.contains(['\t', '\n', '\r']) {
+        return Err(Error::InvalidInput(format!("{} contains a tab or line break: {:?}", what, s)));
+    }
+    Ok(())
+}
+
+fn ttl_field(ttl: &Option<Duration>) -> String {
+    ttl.map(|d| d.as_millis().to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+// Comma-joined list, "-" when empty.
+fn list_field(what: &str, items: &[String]) -> Result<String, Error> {
+    if items.is_empty() { return Ok("-".to_string()); }
+    non_empty_list(what, items)
+}
+
+fn non_empty_list(what: &str, items: &[String]) -> Result<String, Error> {
+    if items.is_empty() { return Err(Error::InvalidInput(format!("no {}s given", what))); }
+    for item in items {
+        check_field(what, item, false)?;
+        if item.contains(',') { return Err(Error::InvalidInput(format!("{} contains a comma: {:?}", what, item))); }
+    }
+    Ok(items.join(","))
+}
@@ -0,0 +1,118 @@
+// =============================
+// CLIENT CONFIGURATION
+// =============================
+// Mirrors the PHP SDK: a transport mode, the two endpoints, credentials (HTTP auth, and TCP `AUTH` for
+// servers that require it) and pool / timeout / retry knobs. `Config::from_env` reads the same TAGCACHE_*
+// variables.
+
+use std::env;
+use std::time::Duration;
+
+/// Which transport carries cache commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// TCP protocol only.
+    Tcp,
+    /// HTTP JSON API only.
+    Http,
+    /// TCP, falling back to HTTP when the TCP endpoint cannot be reached or a command cannot be
+    /// expressed on the line protocol (e.g. a value containing a newline).
+    #[default]
+    Auto,
+}
+
+impl Mode {
+    pub fn parse(s: &str) -> Option<Mode> {
+        match s.to_ascii_lowercase().as_str() {
+            "tcp" => Some(Mode::Tcp),
+            "http" => Some(Mode::Http),
+            "auto" => Some(Mode::Auto),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub mode: Mode,
+    pub http_url: String,            // Base URL of the HTTP API, e.g. http://localhost:8080
+    pub tcp_addr: String,            // host:port of the TCP protocol, e.g. localhost:1984
+    pub username: Option<String>,    // HTTP Basic / TCP AUTH credentials
+    pub password: Option<String>,
+    pub token: Option<String>,       // Bearer token (takes precedence over Basic)
+    pub pool_size: usize,            // Max concurrent TCP connections (idle ones are reused)
+    pub timeout: Duration,           // Max wait for a reply (per reply line on TCP, per request on HTTP)
+    pub connect_timeout: Duration,
+    pub max_retries: u32,            // Extra attempts after a retryable failure
+    pub retry_delay: Duration,       // Delay before the first retry; doubled on each further attempt
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Auto,
+            http_url: "http://localhost:8080".to_string(),
+            tcp_addr: "localhost:1984".to_string(),
+            username: None,
+            password: None,
+            token: None,
+            pool_size: 8,
+            timeout: Duration::from_millis(5000),
+            connect_timeout: Duration::from_millis(3000),
+            max_retries: 3,
+            retry_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl Config {
+    /// TCP-only configuration for `addr` (host:port).
+    pub fn tcp(addr: impl Into<String>) -> Self {
+        Self { mode: Mode::Tcp, tcp_addr: addr.into(), ..Self::default() }
+    }
+
+    /// HTTP-only configuration for `url` (e.g. `http://localhost:8080`).
+    pub fn http(url: impl Into<String>) -> Self {
+        Self { mode: Mode::Http, http_url: url.into(), ..Self::default() }
+    }
+
+    pub fn with_basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// The TCP `AUTH` line for the configured credentials (token first), if any.
+    pub(crate) fn tcp_auth_line(&self) -> Option<String> {
+        match (&self.token, &self.username, &self.password) {
+            (Some(token), _, _) => Some(format!("AUTH\t{}", token)),
+            (None, Some(user), Some(password)) => Some(format!("AUTH\t{}\t{}", user, password)),
+            _ => None,
+        }
+    }
+
+    /// Defaults overridden by TAGCACHE_MODE, TAGCACHE_HTTP_URL, TAGCACHE_TCP_HOST, TAGCACHE_TCP_PORT,
+    /// TAGCACHE_USERNAME, TAGCACHE_PASSWORD, TAGCACHE_TOKEN, TAGCACHE_TCP_POOL_SIZE,
+    /// TAGCACHE_TIMEOUT_MS and TAGCACHE_MAX_RETRIES.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        let var = |k: &str| env::var(k).ok().filter(|v| !v.is_empty());
+        if let Some(mode) = var("TAGCACHE_MODE").as_deref().and_then(Mode::parse) { config.mode = mode; }
+        if let Some(url) = var("TAGCACHE_HTTP_URL") { config.http_url = url; }
+        let host = var("TAGCACHE_TCP_HOST").unwrap_or_else(|| "localhost".to_string());
+        let port = var("TAGCACHE_TCP_PORT").unwrap_or_else(|| "1984".to_string());
+        config.tcp_addr = format!("{}:{}", host, port);
+        config.username = var("TAGCACHE_USERNAME");
+        config.password = var("TAGCACHE_PASSWORD");
+        config.token = var("TAGCACHE_TOKEN");
+        if let Some(n) = var("TAGCACHE_TCP_POOL_SIZE").and_then(|v| v.parse().ok()) { config.pool_size = n; }
+        if let Some(ms) = var("TAGCACHE_TIMEOUT_MS").and_then(|v| v.parse().ok()) { config.timeout = Duration::from_millis(ms); }
+        if let Some(n) = var("TAGCACHE_MAX_RETRIES").and_then(|v| v.parse().ok()) { config.max_retries = n; }
+        config
+    }
+}
@@ -0,0 +1,58 @@
+// =============================
+// ERRORS
+// =============================
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The server could not be reached; the command was not sent.
+    #[error("connection failed: {0}")]
+    Connect(String),
+    /// The connection broke mid-request; the command may or may not have been applied.
+    #[error("i/o error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("timed out waiting for the server")]
+    Timeout,
+    #[error("http error: {0}")]
+    Http(String),
+    #[error("unauthorized")]
+    Unauthorized,
+    /// In cluster mode the key belongs to another node (TCP `MOVED`, HTTP redirect).
+    #[error("moved to node {node} ({addr})")]
+    Moved { node: String, addr: String },
+    /// The server rejected the command (`ERR <reason>` / `{"error": reason}`), e.g. `not_numeric`
+    /// or `read_only_replica`.
+    #[error("server error: {0}")]
+    Server(String),
+    /// The reply did not have the expected shape.
+    #[error("unexpected reply: {0}")]
+    Protocol(String),
+    /// The command cannot be sent (empty key, separator characters on the TCP protocol, ...).
+    #[error("invalid argument: {0}")]
+    InvalidInput(String),
+}
+
+impl Error {
+    /// Whether retrying may succeed without risking a double apply: connection failures always
+    /// qualify, broken / timed out exchanges only for idempotent commands.
+    pub fn is_retryable(&self, idempotent: bool) -> bool {
+        match self {
+            Error::Connect(_) => true,
+            Error::Io(_) | Error::Timeout | Error::Http(_) => idempotent,
+            _ => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_connect() {
+            Error::Connect(e.to_string())
+        } else if e.is_timeout() {
+            Error::Timeout
+        } else {
+            Error::Http(e.to_string())
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// =============================
+// HTTP TRANSPORT
+// =============================
+// JSON API fallback (and the only way to reach admin endpoints). Redirects are not followed: in
+// cluster redirect mode a 307 becomes `Error::Moved`, since credentials would be dropped on the
+// cross-origin hop anyway.
+
+use base64::Engine;
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+
+use crate::command::{Command, Reply};
+use crate::config::Config;
+use crate::error::Error;
+
+pub(crate) struct HttpTransport {
+    base_url: String,
+    client: reqwest::Client,
+    auth_header: Option<String>,
+}
+
+impl HttpTransport {
+    pub(crate) fn new(config: &Config) -> Result<Self, Error> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.timeout)
+            .pool_max_idle_per_host(config.pool_size)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let auth_header = if let Some(token) = &config.token {
+            Some(format!("Bearer {}", token))
+        } else if let (Some(u), Some(p)) = (&config.username, &config.password) {
+            Some(format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", u, p))))
+        } else {
+            None
+        };
+        Ok(Self { base_url: config.http_url.trim_end_matches('/').to_string(), client, auth_header })
+    }
+
+    pub(crate) async fn execute(&self, cmd: &Command) -> Result<Reply, Error> {
+        let (method, path, body) = cmd.http_request()?;
+        let json = self.request(method, &path, body).await?;
+        cmd.parse_http_reply(&json)
+    }
+
+    /// Call any endpoint and return its JSON body; non-2xx statuses become errors.
+    pub(crate) async fn request(&self, method: Method, path: &str, body: Option<Value>) -> Result<Value, Error> {
+        let mut req = self.builder(method, path);
+        if let Some(body) = body { req = req.json(&body); }
+        let resp = self.send(req).await?;
+        Ok(resp.json().await.unwrap_or(Value::Null))
+    }
+
+    /// A request to `path` carrying the configured credentials (for streaming bodies).
+    pub(crate) fn builder(&self, method: Method, path: &str) -> reqwest::RequestBuilder {
+        let req = self.client.request(method, format!("{}{}", self.base_url, path));
+        match &self.auth_header {
+            Some(auth) => req.header("Authorization", auth),
+            None => req,
+        }
+    }
+
+    /// Send `req`; non-2xx statuses become errors, a success hands back the unread response.
+    pub(crate) async fn send(&self, req: reqwest::RequestBuilder) -> Result<reqwest::Response, Error> {
+        let resp = req.send().await?;
+        let status = resp.status();
+        if status.is_success() { return Ok(resp); }
+        let json: Value = resp.json().await.unwrap_or(Value::Null);
+        let field = |f: &str| json.get(f).and_then(Value::as_str).unwrap_or_default().to_string();
+        Err(match status {
+            StatusCode::UNAUTHORIZED => Error::Unauthorized,
+            s if s.is_redirection() && field("error") == "moved" => Error::Moved { node: field("node"), addr: field("location") },
+            s => match json.get("error").and_then(Value::as_str) {
+                Some(e) => Error::Server(e.to_string()),
+                None => Error::Http(format!("HTTP {}", s)),
+            },
+        })
+    }
+}
@@ -0,0 +1,50 @@
+/*!
+ * TagCache Rust client
+ *
+ * Async client for the TagCache server with a pooled TCP transport, HTTP fallback, typed results
+ * and errors, pipelining, timeouts / retries and a blocking facade ([`blocking::Client`]).
+ *
+ * ```no_run
+ * use std::time::Duration;
+ * use tagcache_client::{Client, Command, Config, TagMode};
+ *
+ * # async fn demo() -> Result<(), tagcache_client::Error> {
+ * // TCP on localhost:1984, falling back to HTTP on localhost:8080 (Mode::Auto).
+ * let client = Client::new(Config::default().with_basic_auth("admin", "password"))?;
+ * client.put("user:1", "alice", &["users"], Some(Duration::from_secs(60))).await?;
+ * assert_eq!(client.get("user:1").await?.as_deref(), Some("alice"));
+ * client.invalidate_tags(&["users", "sessions"], TagMode::Any).await?;
+ *
+ * // Many commands, one round trip.
+ * let mut batch = client.pipeline();
+ * for i in 0..1000 {
+ *     batch.push(Command::Get { key: format!("user:{i}") });
+ * }
+ * let replies = batch.execute().await?;
+ * # Ok(()) }
+ * ```
+ *
+ * Every verb of the TCP protocol is a [`Command`] variant; the typed methods on [`Client`] are
+ * shortcuts for [`Client::execute`]. Credentials apply to both transports: over HTTP they are sent
+ * with every request, over TCP every new pooled connection first sends `AUTH` (token, else user and
+ * password). Servers require TCP `AUTH` by default (`tcp_require_auth`); with it turned off,
+ * connections without credentials may only read. A refused `AUTH` surfaces as
+ * [`Error::Unauthorized`]. The TCP protocol is line based: keys, tags and values containing tabs or
+ * newlines are rejected with [`Error::InvalidInput`] in `Mode::Tcp` and sent over HTTP in
+ * `Mode::Auto`; read such values back with `Mode::Http`.
+ */
+
+pub mod blocking;
+mod client;
+mod command;
+mod config;
+mod error;
+mod http;
+mod tcp;
+mod transfer;
+
+pub use client::{Client, Pipeline};
+pub use command::{Command, CounterOptions, RateLimitAlgorithm, RateLimitDecision, Reply, Stats, TagMode};
+pub use config::{Config, Mode};
+pub use error::Error;
+pub use transfer::{ImportMode, ImportReport, LineError, TransferFilter};
@@ -0,0 +1,112 @@
+// =============================
+// TCP TRANSPORT (CONNECTION POOL)
+// =============================
+// Up to `pool_size` connections; idle ones are kept for reuse. A connection goes back to the pool
+// only after a complete exchange, so a timed out or broken request never leaves unread replies
+// behind for the next caller.
+//
+// Pipelining: all request lines are written while replies are read concurrently, so arbitrarily
+// large batches cannot deadlock on full socket buffers.
+//
+// With credentials configured every new connection first sends `AUTH` (token, else user + password).
+// Servers predating TCP auth answer `ERR unknown_command`, which is ignored.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::time::timeout;
+
+use crate::error::Error;
+
+struct Conn {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+pub(crate) struct TcpPool {
+    addr: String,
+    idle: Mutex<Vec<Conn>>,
+    permits: Arc<Semaphore>, // One permit per connection in use
+    connect_timeout: Duration,
+    timeout: Duration,
+    auth: Option<String>, // AUTH line sent on every new connection
+}
+
+impl TcpPool {
+    pub(crate) fn new(addr: String, size: usize, connect_timeout: Duration, timeout: Duration, auth: Option<String>) -> Self {
+        Self { addr, idle: Mutex::new(Vec::new()), permits: Arc::new(Semaphore::new(size.max(1))), connect_timeout, timeout, auth }
+    }
+
+    async fn connect(&self) -> Result<Conn, Error> {
+        let stream = match timeout(self.connect_timeout, TcpStream::connect(&self.addr)).await {
+            Ok(Ok(s)) => s,
+            Ok(Err(e)) => return Err(Error::Connect(format!("{}: {}", self.addr, e))),
+            Err(_) => return Err(Error::Connect(format!("{}: connect timed out", self.addr))),
+        };
+        let _ = stream.set_nodelay(true); // Request / reply traffic: don't wait to coalesce small writes
+        let (r, w) = stream.into_split();
+        let mut conn = Conn { reader: BufReader::new(r), writer: w };
+        if let Some(auth) = &self.auth {
+            conn.writer.write_all(format!("{}\n", auth).as_bytes()).await.map_err(Error::Io)?;
+            let mut reply = String::new();
+            match timeout(self.timeout, conn.reader.read_line(&mut reply)).await {
+                Err(_) => return Err(Error::Timeout),
+                Ok(Err(e)) => return Err(Error::Io(e)),
+                Ok(Ok(0)) => return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into())),
+                Ok(Ok(_)) => {}
+            }
+            match reply.trim_end() {
+                "OK" | "ERR unknown_command" => {}
+                "ERR unauthorized" => return Err(Error::Unauthorized),
+                other => return Err(Error::Server(other.to_string())),
+            }
+        }
+        Ok(conn)
+    }
+
+    /// Send `lines` (each without its newline) on one connection and return one reply line per
+    /// request, in order. `timeout` bounds the wait for each reply, not the whole batch.
+    pub(crate) async fn round_trip(&self, lines: &[String]) -> Result<Vec<String>, Error> {
+        let _permit = self.permits.acquire().await.map_err(|_| Error::Connect("pool closed".into()))?;
+        let mut reused = None;
+        while let Some(c) = self.idle.lock().unwrap().pop() {
+            // An idle connection must have nothing to read; EOF or stray bytes mean the server closed
+            // it (restart, idle timeout) or it is out of sync, so drop it instead of failing the request.
+            if matches!(c.reader.get_ref().try_read(&mut [0u8; 1]), Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock) {
+                reused = Some(c);
+                break;
+            }
+        }
+        let mut conn = match reused {
+            Some(c) => c,
+            None => self.connect().await?,
+        };
+        let mut payload = String::with_capacity(lines.iter().map(|l| l.len() + 1).sum());
+        for l in lines { payload.push_str(l); payload.push('\n'); }
+
+        let Conn { reader, writer } = &mut conn;
+        let write = async { writer.write_all(payload.as_bytes()).await.map_err(Error::Io) };
+        let read = async {
+            let mut replies = Vec::with_capacity(lines.len());
+            let mut buf = String::new();
+            while replies.len() < lines.len() {
+                buf.clear();
+                match timeout(self.timeout, reader.read_line(&mut buf)).await {
+                    Err(_) => return Err(Error::Timeout),
+                    Ok(Err(e)) => return Err(Error::Io(e)),
+                    Ok(Ok(0)) => return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into())),
+                    Ok(Ok(_)) => {}
+                }
+                while buf.ends_with(['\n', '\r']) { buf.pop(); }
+                replies.push(buf.clone());
+            }
+            Ok(replies)
+        };
+        let (_, replies) = tokio::try_join!(write, read)?;
+        self.idle.lock().unwrap().push(conn); // Clean exchange: safe to reuse
+        Ok(replies)
+    }
+}
@@ -0,0 +1,81 @@
+// =============================
+// EXPORT / IMPORT
+// =============================
+// Types for `Client::export` / `Client::import` (GET /admin/export, POST /admin/import). Dumps are
+// JSON Lines, one `{"key","value","tags","ttl_ms","created_ms"}` record per line.
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+
+/// Restrict an export / import to keys with a prefix and / or tags matching an expression
+/// (`users`, `users&!banned`, `(a|b)&c`; `!` > `&` > `|`). Default: everything.
+#[derive(Debug, Clone, Default)]
+pub struct TransferFilter {
+    pub prefix: Option<String>,
+    pub tags: Option<String>,
+}
+
+impl TransferFilter {
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn tags(mut self, expr: impl Into<String>) -> Self {
+        self.tags = Some(expr.into());
+        self
+    }
+
+    pub(crate) fn query_pairs(&self) -> Vec<String> {
+        let mut pairs = Vec::new();
+        if let Some(p) = &self.prefix { pairs.push(format!("prefix={}", utf8_percent_encode(p, NON_ALPHANUMERIC))); }
+        if let Some(t) = &self.tags { pairs.push(format!("tags={}", utf8_percent_encode(t, NON_ALPHANUMERIC))); }
+        pairs
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportMode {
+    /// Upsert the imported keys and leave the rest alone.
+    #[default]
+    Merge,
+    /// Upsert the imported keys, then delete every other key matching the filter.
+    Replace,
+}
+
+impl ImportMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "merge" => Some(ImportMode::Merge),
+            "replace" => Some(ImportMode::Replace),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportMode::Merge => "merge",
+            ImportMode::Replace => "replace",
+        }
+    }
+}
+
+/// Server-side summary of an import.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Records outside the filter or already expired.
+    pub skipped: usize,
+    pub failed: usize,
+    /// Replace mode: keys deleted because the dump did not contain them.
+    pub removed: usize,
+    /// The first failed lines (1-based), see `errors_truncated`.
+    pub errors: Vec<LineError>,
+    pub errors_truncated: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LineError {
+    pub line: usize,
+    pub error: String,
+}
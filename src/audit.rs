@@ -0,0 +1,150 @@
+// =============================
+// AUDIT LOG
+// =============================
+// Security-relevant events (logins, failed logins, lockouts, credential and account changes,
+// flushes, configuration reloads, scheduled invalidations) appended to a JSONL file, one record per line:
+//
+//   {"ts_ms":1730000000000,"event":"login_failed","principal":"ops","peer":"10.0.0.7:52114","detail":"basic"}
+//
+// The file is only ever appended to. The most recent records are also kept in memory (seeded from
+// the end of the file at startup) for GET /admin/audit.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const TAIL_BYTES_PER_RECORD: u64 = 512; // Estimate used to seed the in-memory buffer from the file
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    pub file: Option<String>, // JSONL file to append to (none = in memory only)
+    pub recent: usize,        // Records kept in memory for /admin/audit
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self { file: None, recent: 1000 } // No file unless configured, so nothing lands in the working directory
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    Login,            // /auth/login or TCP AUTH succeeded
+    LoginFailed,      // Wrong password (detail: basic / login / tcp)
+    Lockout,          // An IP or user was locked out (detail: which, and for how long)
+    Rotate,           // Bootstrap credentials rotated
+    PasswordChange,
+    CredentialsReset, // Bootstrap credentials reset to defaults
+    AccountChange,    // User or API key created, replaced or deleted
+    Flush,
+    ConfigReload,
+    Schedule,         // Scheduled invalidation created or cancelled (detail: id and target)
+    ScheduleRun,      // Scheduled invalidation ran (detail: id, target and keys removed)
+}
+
+impl AuditEvent {
+    pub fn parse(s: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(s.trim().to_ascii_lowercase())).ok()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub ts_ms: u64,
+    pub event: AuditEvent,
+    pub principal: Option<String>, // Who acted (or, for failures, the name they tried)
+    pub peer: Option<String>,      // Client address, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Filters for `AuditLog::recent`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditQuery {
+    pub event: Option<String>,
+    pub principal: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct AuditLog {
+    file: Option<Mutex<File>>,
+    recent: Mutex<VecDeque<AuditRecord>>,
+    capacity: usize,
+}
+
+impl AuditLog {
+    /// Keep records in memory only (embedded use, tests).
+    pub fn memory(capacity: usize) -> Self {
+        Self { file: None, recent: Mutex::new(VecDeque::new()), capacity: capacity.max(1) }
+    }
+
+    /// Open (or create) the configured file for appending and load its last records.
+    pub fn open(config: &AuditConfig) -> std::io::Result<Self> {
+        let mut log = Self::memory(config.recent);
+        let Some(path) = config.file.as_deref().filter(|p| !p.is_empty()) else { return Ok(log) };
+        if let Some(parent) = Path::new(path).parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).read(true).open(path)?;
+        log.seed_from(&file)?;
+        log.file = Some(Mutex::new(file));
+        Ok(log)
+    }
+
+    // Read the tail of an existing log into the in-memory buffer.
+    fn seed_from(&mut self, file: &File) -> std::io::Result<()> {
+        let mut reader = BufReader::new(file);
+        let len = reader.get_ref().metadata()?.len();
+        let start = len.saturating_sub(self.capacity as u64 * TAIL_BYTES_PER_RECORD);
+        reader.seek(SeekFrom::Start(start))?;
+        if start > 0 { reader.read_line(&mut String::new())?; } // Skip the partial first line
+        let recent = self.recent.get_mut();
+        for line in reader.lines() {
+            let Ok(record) = serde_json::from_str::<AuditRecord>(&line?) else { continue };
+            if recent.len() == self.capacity { recent.pop_front(); }
+            recent.push_back(record);
+        }
+        Ok(())
+    }
+
+    pub fn record(&self, event: AuditEvent, principal: Option<&str>, peer: Option<SocketAddr>, detail: Option<String>) {
+        let record = AuditRecord {
+            ts_ms: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+            event,
+            principal: principal.map(str::to_string),
+            peer: peer.map(|p| p.to_string()),
+            detail,
+        };
+        if let Some(file) = &self.file {
+            let line = serde_json::to_string(&record).unwrap_or_default();
+            if let Err(e) = writeln!(file.lock(), "{}", line) { warn!("Failed to write audit record: {}", e); }
+        }
+        let mut recent = self.recent.lock();
+        if recent.len() == self.capacity { recent.pop_front(); }
+        recent.push_back(record);
+    }
+
+    /// Matching records, newest first.
+    pub fn recent(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, String> {
+        let event = match query.event.as_deref().filter(|e| !e.is_empty()) {
+            Some(e) => Some(AuditEvent::parse(e).ok_or_else(|| format!("unknown audit event '{}'", e))?),
+            None => None,
+        };
+        let limit = query.limit.unwrap_or(100);
+        Ok(self.recent.lock().iter().rev()
+            .filter(|r| event.is_none_or(|e| r.event == e))
+            .filter(|r| query.principal.as_deref().is_none_or(|p| r.principal.as_deref() == Some(p)))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,610 @@
+// =============================
+// AUTHENTICATION & AUTHORIZATION
+// =============================
+// Principals come from three places:
+// - the bootstrap account (`[authentication] username / password`), always an admin;
+// - `[[authentication.users]]`: further accounts with a role and an optional scope;
+// - `[[authentication.api_keys]]`: static bearer keys with a role and an optional scope.
+// Bearer tokens issued by /auth/login carry the principal that logged in and expire after
+// `token_lifetime_seconds`; they can be refreshed, listed and revoked. Credential and account
+// changes made over the API are written back to the configuration file.
+//
+// Passwords are stored as argon2id PHC strings (`$argon2id$v=19$...`). Plain-text passwords from
+// older configuration files are still accepted, hashed in memory, and rewritten as hashes when the
+// server starts. Because argon2 is deliberately slow and clients may send Basic auth on every
+// request, a successful check is remembered as an HMAC of the password under a per-process key
+// (never the password itself) until that account's hash changes.
+//
+// Password checks go through `login`, which applies the brute-force lockout (see `lockout`).
+//
+// Roles are ordered, each one including the ones before it:
+//   read_only < invalidator (+ delete keys, invalidate tags) < writer (+ put / add / incr / decr) < admin
+// A scope narrows what a principal may touch: `key_prefixes` limits the keys it can read or
+// modify, `tags` limits the tags it can invalidate, list or attach. Keyspace-wide operations
+// (search, key listing, event streams) need an unscoped principal. `namespace` binds a principal
+// to one namespace (see `namespace`): it always works inside it and cannot select another.
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+
+use crate::config::{AuthConfig, TagCacheConfig};
+use crate::lockout::{Locked, Lockout, LockoutConfig};
+use crate::namespace::Namespace;
+
+#[derive(Clone, Debug)]
+pub struct Credentials { pub username: String, pub password: String }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    ReadOnly,
+    Invalidator,
+    Writer,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::ReadOnly => "read_only",
+            Role::Invalidator => "invalidator",
+            Role::Writer => "writer",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "read_only" | "readonly" | "reader" => Some(Role::ReadOnly),
+            "invalidator" => Some(Role::Invalidator),
+            "writer" => Some(Role::Writer),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Optional restriction of a principal to some keys / tags (empty lists = no restriction) and to
+/// one namespace.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scope {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_prefixes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+impl Scope {
+    /// No key or tag restriction (a namespace binding alone keeps keyspace-wide operations open,
+    /// since they only see the namespace).
+    pub fn is_unrestricted(&self) -> bool {
+        self.key_prefixes.is_empty() && self.tags.is_empty()
+    }
+
+    pub fn allows_key(&self, key: &str) -> bool {
+        self.key_prefixes.is_empty() || self.key_prefixes.iter().any(|p| key.starts_with(p.as_str()))
+    }
+
+    pub fn allows_tag(&self, tag: &str) -> bool {
+        self.tags.is_empty() || self.tags.iter().any(|t| t == tag)
+    }
+}
+
+/// An account from `[[authentication.users]]` (or created through POST /auth/users).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserConfig {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+    #[serde(flatten)]
+    pub scope: Scope,
+}
+
+/// A static bearer key from `[[authentication.api_keys]]` (or created through POST /auth/api-keys).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    pub name: String,
+    pub key: String,
+    pub role: Role,
+    #[serde(flatten)]
+    pub scope: Scope,
+}
+
+/// Who is making a request and what they may do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+    pub scope: Scope,
+    pub namespace: Namespace, // The namespace the request / connection works in
+}
+
+impl Principal {
+    /// Unrestricted admin (the bootstrap account).
+    pub fn admin(name: impl Into<String>) -> Self {
+        Self::new(name.into(), Role::Admin, Scope::default())
+    }
+
+    /// Unscoped reader (TCP clients that did not AUTH, when `tcp_require_auth` is off).
+    pub fn anonymous(name: impl Into<String>) -> Self {
+        Self::new(name.into(), Role::ReadOnly, Scope::default())
+    }
+
+    fn new(name: String, role: Role, scope: Scope) -> Self {
+        let namespace = Namespace::bound(scope.namespace.as_deref());
+        Self { name, role, scope, namespace }
+    }
+
+    /// Switch to `requested` (None = stay in the bound namespace, or the default one). Principals
+    /// bound to a namespace cannot leave it.
+    pub fn enter(&mut self, requested: Option<Namespace>) -> Result<(), String> {
+        let bound = Namespace::bound(self.scope.namespace.as_deref());
+        match requested {
+            Some(ns) if !bound.is_default() && ns != bound => {
+                Err(format!("{} is bound to namespace '{}'", self.name, bound.name().unwrap_or_default()))
+            }
+            Some(ns) => { self.namespace = ns; Ok(()) }
+            None => { self.namespace = bound; Ok(()) }
+        }
+    }
+
+    pub fn has_role(&self, required: Role) -> bool {
+        self.role >= required
+    }
+
+    pub fn check_key(&self, key: &str) -> Result<(), String> {
+        if self.scope.allows_key(key) { Ok(()) } else { Err(format!("key '{}' is outside the scope of {}", key, self.name)) }
+    }
+
+    pub fn check_tag(&self, tag: &str) -> Result<(), String> {
+        if self.scope.allows_tag(tag) { Ok(()) } else { Err(format!("tag '{}' is outside the scope of {}", tag, self.name)) }
+    }
+
+    pub fn check_tags<'a>(&self, tags: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+        tags.into_iter().try_for_each(|t| self.check_tag(t))
+    }
+
+    /// Operations on every member of a tag (list, invalidate, rename, merge, expire, TTL policies)
+    /// reach keys whatever their name, so a principal limited to some key prefixes cannot use them.
+    pub fn check_tag_members(&self, tag: &str) -> Result<(), String> {
+        self.check_tag(tag)?;
+        if self.scope.key_prefixes.is_empty() { Ok(()) }
+        else { Err(format!("{} is limited to key prefixes and cannot act on every key of tag '{}'", self.name, tag)) }
+    }
+
+    pub fn check_tags_members<'a>(&self, tags: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+        tags.into_iter().try_for_each(|t| self.check_tag_members(t))
+    }
+
+    /// Subtree operations reach tags nobody listed (and every key carrying them), so a principal
+    /// limited to some tags or key prefixes cannot use them.
+    pub fn check_tag_tree(&self, tag: &str) -> Result<(), String> {
+        if self.scope.is_unrestricted() { Ok(()) } else { Err(format!("tag subtree '{}' is outside the scope of {}", tag, self.name)) }
+    }
+
+    /// Keyspace-wide operations cannot be narrowed to a scope, so they need an unscoped principal.
+    pub fn check_unscoped(&self) -> Result<(), String> {
+        if self.scope.is_unrestricted() { Ok(()) } else { Err(format!("{} is scoped and cannot use keyspace-wide operations", self.name)) }
+    }
+}
+
+// A namespace binding must name a valid namespace.
+fn check_binding(scope: &Scope) -> Result<(), String> {
+    match scope.namespace.as_deref().map(Namespace::named) {
+        Some(Err(e)) => Err(format!("invalid namespace '{}'", e.detail())),
+        _ => Ok(()),
+    }
+}
+
+/// Turn a peer credential (`user:password`, or a token / API key) into a TCP `AUTH` line.
+pub fn tcp_auth_line(credential: &str) -> String {
+    match credential.split_once(':') {
+        Some((user, password)) => format!("AUTH\t{}\t{}", user, password),
+        None => format!("AUTH\t{}", credential),
+    }
+}
+
+// =============================
+// PASSWORD HASHING
+// =============================
+
+/// Hash a password as an argon2id PHC string with a random salt.
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::encode_b64(&rand::thread_rng().gen::<[u8; 16]>()).expect("16-byte salt");
+    Argon2::default().hash_password(password.as_bytes(), &salt).expect("argon2 hashing").to_string()
+}
+
+/// Whether a stored password is a hash (anything else is a legacy plain-text password).
+pub fn is_password_hash(stored: &str) -> bool {
+    stored.starts_with("$argon2")
+}
+
+/// Check a password against a stored hash, or against a legacy plain-text password in constant time.
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    if !is_password_hash(stored) { return bool::from(password.as_bytes().ct_eq(stored.as_bytes())); }
+    PasswordHash::new(stored).is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+/// Hash every plain-text password in `[authentication]` (bootstrap account and users); returns how
+/// many were converted.
+pub fn hash_plaintext_passwords(config: &mut AuthConfig) -> usize {
+    let passwords = std::iter::once(&mut config.password).chain(config.users.iter_mut().map(|u| &mut u.password));
+    let mut converted = 0;
+    for password in passwords.filter(|p| !is_password_hash(p)) {
+        *password = hash_password(password);
+        converted += 1;
+    }
+    converted
+}
+
+/// A bearer token issued by /auth/login or /auth/refresh.
+#[derive(Clone, Debug)]
+struct Session {
+    id: String, // Public handle for listing / revocation (the token itself is never shown again)
+    principal: Principal,
+    issued: SystemTime,
+    expires: SystemTime,
+}
+
+/// A freshly issued token.
+#[derive(Clone, Debug, Serialize)]
+pub struct IssuedToken {
+    pub token: String,
+    pub id: String,
+    pub expires_in: u64, // Seconds
+    pub role: Role,
+}
+
+fn unix_ms(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng().sample_iter(&Alphanumeric).take(len).map(char::from).collect()
+}
+
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+
+/// Why `AuthState::login` refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginError {
+    /// Wrong username or password; carries the lockout this failure started, if any.
+    Invalid(Option<Locked>),
+    /// Too many recent failures; try again after this long.
+    LockedOut(Duration),
+}
+
+// Whether a password read from the configuration (hash or plain text) matches the stored hash.
+fn same_password(configured: &str, stored: &str) -> bool {
+    configured == stored || (!is_password_hash(configured) && verify_password(configured, stored))
+}
+
+#[derive(Clone, Debug)]
+pub struct AuthState {
+    credentials: Arc<Mutex<Credentials>>,       // Bootstrap account; `password` is a hash
+    users: Arc<DashMap<String, UserConfig>>,    // username -> account (`password` is a hash)
+    api_keys: Arc<DashMap<String, ApiKeyConfig>>, // key -> API key entry
+    tokens: Arc<DashMap<String, Session>>,      // bearer token -> session
+    verified: Arc<DashMap<String, [u8; 32]>>,   // username -> HMAC of the last password that verified
+    verify_key: Arc<[u8; 32]>,                  // Per-process HMAC key for `verified`
+    token_lifetime: Duration,
+    lockout: Arc<Lockout>,                      // Failed password counters per IP / user
+    tcp_require_auth: bool,                     // TCP connections must AUTH before any command
+    config_path: Arc<Mutex<PathBuf>>,  // Path to configuration file for persistence
+}
+
+impl AuthState {
+    /// `creds.password` may be a hash or plain text (hashed here).
+    pub fn new(creds: Credentials, config_path: PathBuf) -> Self {
+        let creds = Credentials { password: Self::hashed(creds.password), ..creds };
+        Self {
+            credentials: Arc::new(Mutex::new(creds)),
+            users: Arc::new(DashMap::new()),
+            api_keys: Arc::new(DashMap::new()),
+            tokens: Arc::new(DashMap::new()),
+            verified: Arc::new(DashMap::new()),
+            verify_key: Arc::new(rand::thread_rng().gen()),
+            token_lifetime: DEFAULT_TOKEN_LIFETIME,
+            lockout: Arc::new(Lockout::new(LockoutConfig::default())),
+            tcp_require_auth: true,
+            config_path: Arc::new(Mutex::new(config_path)),
+        }
+    }
+
+    /// Load the extra users, API keys, token lifetime, lockout and TCP policy from `[authentication]`.
+    pub fn with_accounts(self, config: &AuthConfig) -> Self {
+        for user in &config.users {
+            let user = UserConfig { password: Self::hashed(user.password.clone()), ..user.clone() };
+            self.users.insert(user.username.clone(), user);
+        }
+        for key in &config.api_keys { self.api_keys.insert(key.key.clone(), key.clone()); }
+        let token_lifetime = if config.token_lifetime_seconds == 0 { DEFAULT_TOKEN_LIFETIME } else { Duration::from_secs(config.token_lifetime_seconds) };
+        let lockout = Arc::new(Lockout::new(config.lockout.clone()));
+        Self { token_lifetime, lockout, tcp_require_auth: config.tcp_require_auth, ..self }
+    }
+
+    pub fn tcp_require_auth(&self) -> bool { self.tcp_require_auth }
+
+    pub fn token_lifetime(&self) -> Duration { self.token_lifetime }
+
+    fn hashed(password: String) -> String {
+        if is_password_hash(&password) { password } else { hash_password(&password) }
+    }
+
+    // -------- Passwords --------
+
+    fn password_mac(&self, stored_hash: &str, password: &str) -> [u8; 32] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&*self.verify_key).expect("HMAC accepts any key length");
+        mac.update(stored_hash.as_bytes());
+        mac.update(&[0]);
+        mac.update(password.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    // Verify against the stored hash, skipping argon2 when this exact password verified before.
+    fn check_password(&self, username: &str, password: &str, stored: &str) -> bool {
+        let mac = self.password_mac(stored, password);
+        if self.verified.get(username).is_some_and(|known| bool::from(known.ct_eq(&mac))) { return true; }
+        let ok = verify_password(password, stored);
+        if ok { self.verified.insert(username.to_string(), mac); }
+        ok
+    }
+
+    /// Check a username / password pair under the brute-force lockout (HTTP Basic, /auth/login,
+    /// TCP AUTH).
+    pub fn login(&self, u: &str, p: &str, ip: Option<IpAddr>) -> Result<Principal, LoginError> {
+        self.lockout.check(ip, Some(u)).map_err(LoginError::LockedOut)?;
+        match self.authenticate_basic(u, p) {
+            Some(principal) => {
+                self.lockout.record_success(u);
+                Ok(principal)
+            }
+            None => {
+                // Unknown names are only counted against the IP, so they cannot fill the user table.
+                let known = self.credentials.lock().username == u || self.users.contains_key(u);
+                Err(LoginError::Invalid(self.lockout.record_failure(ip, known.then_some(u))))
+            }
+        }
+    }
+
+    /// Check a username / password pair (bootstrap account first, then configured users), without
+    /// lockout accounting.
+    pub fn authenticate_basic(&self, u: &str, p: &str) -> Option<Principal> {
+        let bootstrap = self.credentials.lock().clone();
+        if bootstrap.username == u { return self.check_password(u, p, &bootstrap.password).then(|| Principal::admin(u)); }
+        let user = self.users.get(u)?.clone();
+        self.check_password(u, p, &user.password).then(|| Principal::new(user.username, user.role, user.scope))
+    }
+
+    pub(crate) fn rotate(&self) -> Credentials {
+        let new = Credentials { username: random_string(16), password: random_string(24) };
+        let stored = Credentials { username: new.username.clone(), password: hash_password(&new.password) };
+        let old = std::mem::replace(&mut *self.credentials.lock(), stored);
+        self.revoke_tokens_of(&old.username);
+        new
+    }
+
+    pub(crate) fn change_password(&self, new_password: String) -> bool {
+        let mut creds = self.credentials.lock();
+        creds.password = hash_password(&new_password);
+
+        // Persist to configuration file
+        if let Err(e) = self.persist_credentials_to_config(Some(creds.username.clone()), Some(creds.password.clone())) {
+            eprintln!("Warning: Failed to persist password change to config file: {}", e);
+            // Don't fail the operation, just warn
+        }
+
+        // Clear the admin's tokens to force re-authentication
+        self.revoke_tokens_of(&creds.username);
+        true
+    }
+
+    pub(crate) fn reset_to_defaults(&self) -> bool {
+        let new = Credentials {
+            username: "admin".to_string(),
+            password: hash_password("password"),
+        };
+        let old = std::mem::replace(&mut *self.credentials.lock(), new.clone());
+
+        // Persist to configuration file
+        if let Err(e) = self.persist_credentials_to_config(Some(new.username), Some(new.password)) {
+            eprintln!("Warning: Failed to persist credential reset to config file: {}", e);
+            // Don't fail the operation, just warn
+        }
+
+        // Clear the admin's tokens to force re-authentication
+        self.revoke_tokens_of(&old.username);
+        true
+    }
+
+    /// Re-read the accounts (bootstrap credentials, users, API keys) from the configuration file.
+    /// Sessions of accounts that were removed or whose password, role or scope changed are revoked.
+    /// Token lifetime, lockout and TCP policy still need a restart.
+    pub fn reload_accounts(&self) -> anyhow::Result<()> {
+        let config = TagCacheConfig::load_from_file(&*self.config_path.lock())?.authentication;
+        let new_users: HashMap<String, UserConfig> = config.users.into_iter().map(|u| (u.username.clone(), u)).collect();
+        let mut stale: Vec<String> = self.users.iter()
+            .filter(|old| new_users.get(old.key()).is_none_or(|new| !same_password(&new.password, &old.password) || new.role != old.role || new.scope != old.scope))
+            .map(|old| old.key().clone())
+            .collect();
+        {
+            let mut creds = self.credentials.lock();
+            if creds.username != config.username || !same_password(&config.password, &creds.password) {
+                stale.push(creds.username.clone());
+                *creds = Credentials { username: config.username, password: Self::hashed(config.password) };
+            }
+        }
+        // Keep unchanged hashes as they are (re-hashing plain text would give a new salt).
+        let previous: HashMap<String, String> = self.users.iter().map(|u| (u.key().clone(), u.password.clone())).collect();
+        self.users.clear();
+        for (name, user) in new_users {
+            let password = match previous.get(&name) {
+                Some(old) if same_password(&user.password, old) => old.clone(),
+                _ => Self::hashed(user.password),
+            };
+            self.users.insert(name, UserConfig { password, ..user });
+        }
+        self.api_keys.clear();
+        for key in config.api_keys { self.api_keys.insert(key.key.clone(), key); }
+        for name in stale { self.revoke_tokens_of(&name); }
+        Ok(())
+    }
+
+    // -------- Bearer tokens --------
+
+    /// Start a session for `principal`; expired sessions are swept at the same time.
+    pub fn issue_token(&self, principal: Principal) -> IssuedToken {
+        let now = SystemTime::now();
+        self.tokens.retain(|_, s| s.expires > now);
+        let token = random_string(48);
+        let session = Session { id: random_string(12), principal, issued: now, expires: now + self.token_lifetime };
+        let issued = IssuedToken { token: token.clone(), id: session.id.clone(), expires_in: self.token_lifetime.as_secs(), role: session.principal.role };
+        self.tokens.insert(token, session);
+        issued
+    }
+
+    /// Check a bearer credential: an unexpired session token or an API key.
+    pub fn authenticate_token(&self, t: &str) -> Option<Principal> {
+        if let Some(session) = self.tokens.get(t).map(|s| s.clone()) {
+            if session.expires > SystemTime::now() { return Some(session.principal); }
+            self.tokens.remove(t);
+            return None;
+        }
+        let key = self.api_keys.get(t)?;
+        Some(Principal::new(format!("api_key:{}", key.name), key.role, key.scope.clone()))
+    }
+
+    /// Replace a valid session token with a new one (fresh lifetime, same principal).
+    pub fn refresh_token(&self, t: &str) -> Option<IssuedToken> {
+        let (_, session) = self.tokens.remove(t)?;
+        (session.expires > SystemTime::now()).then(|| self.issue_token(session.principal))
+    }
+
+    /// End the session of a token (logout).
+    pub fn revoke_token(&self, t: &str) -> bool {
+        self.tokens.remove(t).is_some()
+    }
+
+    /// End a session by its public id (admin revocation).
+    pub fn revoke_token_id(&self, id: &str) -> bool {
+        let before = self.tokens.len();
+        self.tokens.retain(|_, s| s.id != id);
+        self.tokens.len() != before
+    }
+
+    /// Live sessions (without the tokens themselves), oldest first.
+    pub fn list_tokens(&self) -> Vec<serde_json::Value> {
+        let now = SystemTime::now();
+        let mut sessions: Vec<Session> = self.tokens.iter().filter(|s| s.expires > now).map(|s| s.value().clone()).collect();
+        sessions.sort_by_key(|s| s.issued);
+        sessions.into_iter().map(|s| serde_json::json!({
+            "id": s.id,
+            "name": s.principal.name,
+            "role": s.principal.role,
+            "issued_ms": unix_ms(s.issued),
+            "expires_ms": unix_ms(s.expires),
+        })).collect()
+    }
+
+    // -------- Account management (admin endpoints) --------
+
+    /// Users without their passwords, sorted by name.
+    pub fn list_users(&self) -> Vec<serde_json::Value> {
+        let mut users: Vec<UserConfig> = self.users.iter().map(|u| u.value().clone()).collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        users.into_iter().map(|u| serde_json::json!({"username": u.username, "role": u.role, "key_prefixes": u.scope.key_prefixes, "tags": u.scope.tags, "namespace": u.scope.namespace})).collect()
+    }
+
+    /// Create or replace a user (the password may be plain text or a hash); existing sessions of
+    /// that user are revoked.
+    pub fn upsert_user(&self, user: UserConfig) -> Result<(), String> {
+        if user.username.is_empty() || user.username.contains(':') { return Err("username must be non-empty and contain no ':'".into()); }
+        if user.password.is_empty() { return Err("password must not be empty".into()); }
+        if user.username == self.credentials.lock().username { return Err("the bootstrap admin is managed through /auth/change_password".into()); }
+        check_binding(&user.scope)?;
+        let user = UserConfig { password: Self::hashed(user.password), ..user };
+        self.revoke_tokens_of(&user.username);
+        self.users.insert(user.username.clone(), user);
+        self.persist_accounts();
+        Ok(())
+    }
+
+    pub fn remove_user(&self, username: &str) -> bool {
+        let removed = self.users.remove(username).is_some();
+        if removed {
+            self.revoke_tokens_of(username);
+            self.persist_accounts();
+        }
+        removed
+    }
+
+    /// API keys without the secret itself (only its first characters), sorted by name.
+    pub fn list_api_keys(&self) -> Vec<serde_json::Value> {
+        let mut keys: Vec<ApiKeyConfig> = self.api_keys.iter().map(|k| k.value().clone()).collect();
+        keys.sort_by(|a, b| a.name.cmp(&b.name));
+        keys.into_iter().map(|k| serde_json::json!({"name": k.name, "key_prefix": k.key.chars().take(8).collect::<String>(), "role": k.role, "key_prefixes": k.scope.key_prefixes, "tags": k.scope.tags, "namespace": k.scope.namespace})).collect()
+    }
+
+    /// Generate a new API key (replacing any key with the same name) and return it.
+    pub fn create_api_key(&self, name: &str, role: Role, scope: Scope) -> Result<String, String> {
+        if name.is_empty() { return Err("name must not be empty".into()); }
+        check_binding(&scope)?;
+        self.api_keys.retain(|_, k| k.name != name);
+        let key = format!("tc_{}", random_string(40));
+        self.api_keys.insert(key.clone(), ApiKeyConfig { name: name.to_string(), key: key.clone(), role, scope });
+        self.persist_accounts();
+        Ok(key)
+    }
+
+    pub fn remove_api_key(&self, name: &str) -> bool {
+        let before = self.api_keys.len();
+        self.api_keys.retain(|_, k| k.name != name);
+        let removed = self.api_keys.len() != before;
+        if removed { self.persist_accounts(); }
+        removed
+    }
+
+    fn revoke_tokens_of(&self, name: &str) {
+        self.tokens.retain(|_, s| s.principal.name != name);
+        self.verified.remove(name);
+    }
+
+    fn persist_accounts(&self) {
+        let mut users: Vec<UserConfig> = self.users.iter().map(|u| u.value().clone()).collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        let mut api_keys: Vec<ApiKeyConfig> = self.api_keys.iter().map(|k| k.value().clone()).collect();
+        api_keys.sort_by(|a, b| a.name.cmp(&b.name));
+        let result = (|| -> anyhow::Result<()> {
+            let config_path = self.config_path.lock();
+            let mut config = TagCacheConfig::load_from_file(&*config_path)?;
+            config.authentication.users = users;
+            config.authentication.api_keys = api_keys;
+            config.save_to_file(&*config_path)
+        })();
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to persist account change to config file: {}", e);
+        }
+    }
+
+    fn persist_credentials_to_config(&self, username: Option<String>, password: Option<String>) -> anyhow::Result<()> {
+        let config_path = self.config_path.lock();
+        let mut config = TagCacheConfig::load_from_file(&*config_path)?;
+        config.update_auth(username, password, &config_path)?;
+        Ok(())
+    }
+
+}
@@ -5,13 +5,14 @@
  * GitHub: https://github.com/aminshamim/tagcache
  * LinkedIn: https://www.linkedin.com/in/aminshamim/
  * 
- * Simple TCP benchmark for TagCache custom protocol
+ * Simple TCP benchmark for TagCache custom protocol, built on the `tagcache-client` crate
+ * (one pooled connection per worker, no retries).
  * Usage: cargo run --release --bin bench_tcp -- [--host 127.0.0.1] [--port 1984] [--conns 32] [--duration 10] [--keys 100] [--mode get|put] [--ttl 60000]
  * It pre-populates keys (for GET mode) then measures ops/sec and latency stats.
  */
 
 use std::{env, time::{Duration, Instant}, sync::{Arc, atomic::{AtomicU64, Ordering}}};
-use tokio::{net::TcpStream, io::{AsyncWriteExt, AsyncBufReadExt, BufReader}};
+use tagcache_client::{Client, Command, Config};
 
 struct Args {
     host: String,
@@ -64,19 +65,17 @@ async fn main() -> anyhow::Result<()> {
     let args = parse_args();
     println!("Benchmark config: host={} port={} conns={} duration={}s keys={} mode={} ttl_ms={}", args.host, args.port, args.conns, args.duration, args.keys, args.mode, args.ttl);
 
-    // Pre-populate if GET mode
+    let client = Client::new(Config { pool_size: args.conns.max(1), max_retries: 0, ..Config::tcp(format!("{}:{}", args.host, args.port)) })?;
+    let ttl = Some(Duration::from_millis(args.ttl));
+
+    // Pre-populate if GET mode (pipelined in batches)
     if args.mode == "get" {
-        let addr = format!("{}:{}", args.host, args.port);
-        let stream = TcpStream::connect(&addr).await?;
-        let (rhalf, mut whalf) = stream.into_split();
-        let mut reader = BufReader::new(rhalf);
-        let mut resp = String::new();
-        for i in 0..args.keys {
-            let k = format!("k{}", i);
-            let line = format!("PUT\t{}\t{}\tbench\tvalue{}\n", k, args.ttl, i);
-            whalf.write_all(line.as_bytes()).await?;
-            resp.clear();
-            reader.read_line(&mut resp).await?; // expect OK
+        for chunk in (0..args.keys).collect::<Vec<_>>().chunks(1000) {
+            let mut batch = client.pipeline();
+            for i in chunk {
+                batch.push(Command::Put { key: format!("k{}", i), value: format!("value{}", i), tags: vec!["bench".into()], ttl });
+            }
+            for reply in batch.execute().await? { reply?; }
         }
     }
 
@@ -86,37 +85,27 @@ async fn main() -> anyhow::Result<()> {
 
     let mut tasks = Vec::new();
     for id in 0..args.conns {
-        let addr = format!("{}:{}", args.host, args.port);
+        let client = client.clone();
         let total_c = total.clone();
         let lat_c = lat_stats.clone();
         let keys = args.keys;
         let mode = args.mode.clone();
-        let ttl = args.ttl;
         let task = tokio::spawn(async move {
-            if let Ok(stream) = TcpStream::connect(&addr).await {
-                let mut reader = BufReader::new(stream);
-                let mut buf = String::new();
-                let mut key_idx = id % keys;
-                while Instant::now() < stop_at {
-                    buf.clear();
-                    let (line, expect_value) = if mode == "get" {
-                        let k = format!("k{}", key_idx);
-                        key_idx = (key_idx + 1) % keys;
-                        (format!("GET\t{}\n", k), true)
-                    } else { // put
-                        let k = format!("k{}", key_idx);
-                        key_idx = (key_idx + 1) % keys;
-                        (format!("PUT\t{}\t{}\tbench\tvalue\n", k, ttl), false)
-                    };
-                    let start = Instant::now();
-                    if let Err(_) = reader.get_mut().write_all(line.as_bytes()).await { break; }
-                    if reader.read_line(&mut buf).await.is_err() { break; }
-                    let elapsed = start.elapsed().as_nanos() as u64;
-                    lat_c.record(elapsed);
-                    if expect_value && !buf.starts_with("VALUE") && !buf.starts_with("NF") {}
-                    total_c.fetch_add(1, Ordering::Relaxed);
-                }
-            } // else connection failed; just return
+            let mut key_idx = id % keys;
+            while Instant::now() < stop_at {
+                let k = format!("k{}", key_idx);
+                key_idx = (key_idx + 1) % keys;
+                let start = Instant::now();
+                let res = if mode == "get" {
+                    client.get(&k).await.map(|_| ())
+                } else { // put
+                    client.put(&k, "value", &["bench"], ttl).await
+                };
+                if res.is_err() { break; } // Connection failed / server gone; stop this worker
+                let elapsed = start.elapsed().as_nanos() as u64;
+                lat_c.record(elapsed);
+                total_c.fetch_add(1, Ordering::Relaxed);
+            }
         });
         tasks.push(task);
     }
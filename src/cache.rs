@@ -0,0 +1,1288 @@
+// =============================
+// CACHE ENGINE
+// =============================
+// The in-memory store: keys are spread over a fixed number of shards, each holding the entries, a
+// tag -> keys reverse index and an expiration heap. Everything here is synchronous and safe to share
+// behind an Arc; the HTTP / TCP layers and background tasks only ever call into `Cache`.
+
+use dashmap::{DashMap, DashSet}; // Concurrent hash map + set (lock sharded) for high concurrency
+use smallvec::SmallVec; // Optimized small vector that stores small number of elements inline (avoids heap for few tags)
+use ahash::RandomState; // Fast hashing state for consistent shard distribution
+use parking_lot::Mutex; // Faster, simpler mutex vs std::sync::Mutex (not poisonable)
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet}; // BinaryHeap: min-heap (via Reverse) backing the per-shard expiration index
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::counter::{CounterOptions, CounterValue};
+use crate::document::{JsonOp, JsonPath};
+use crate::events::{self, CacheEvent, EventBus, EventKind};
+use crate::lock::{self, Lease, LockError};
+use crate::namespace::{self, Namespace, NamespaceError, Namespaces, NamespacesConfig};
+use crate::ratelimit::{Decision, RateLimit};
+use crate::tag_ttl::{TagTtl, TagTtlConfig};
+use crate::tag_version::{InvalidationConfig, InvalidationMode, Stamps, TagVersions};
+use crate::transfer::TransferFilter;
+use crate::value::{Value, ValueError, ValueType};
+use crate::webhooks::{WebhookDispatcher, WebhookEvent};
+
+/// We wrap raw String keys in a newtype Key for type safety + trait impls.
+/// Ord is needed so keys can sit inside the (deadline, key) tuples of the expiry heap.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key(pub(crate) String); // Simple wrapper; cloning duplicates the underlying String.
+
+/// Same idea for Tag — improves clarity and prevents mixing strings accidentally.
+/// Ord is needed for the ordered tag index that hierarchical lookups walk.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Tag(pub(crate) String);
+
+impl Key {
+    pub fn new<S: Into<String>>(s: S) -> Self { Key(s.into()) }
+    pub fn as_str(&self) -> &str { &self.0 }
+}
+
+impl Tag {
+    pub fn new<S: Into<String>>(s: S) -> Self { Tag(s.into()) }
+    pub fn as_str(&self) -> &str { &self.0 }
+}
+
+/// Represents one cached entry (the stored value + metadata).
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub value: Value,                 // The cached value: a string, hash, list, set or JSON document (see value.rs)
+    pub tags: SmallVec<[Tag; 4]>,     // Tags associated with this key (SmallVec keeps up to 4 inline, no heap alloc)
+    pub created_at: Instant,          // When the entry was inserted (for TTL expiration)
+    pub ttl: Option<Duration>,        // Optional time-to-live; None = never expires (unless invalidated)
+    pub created_system: SystemTime,   // Wall clock creation time
+    pub versions: Stamps,             // Generations of its versioned tags at write time (see tag_version.rs)
+}
+
+impl Entry {
+    /// Helper to check if this entry should be considered expired: its TTL elapsed, or one of its
+    /// versioned tags was invalidated since it was written.
+    pub fn is_expired(&self) -> bool {
+        if self.versions.is_stale() { return true; } // Invalidated, waiting for reclamation
+        if let Some(ttl) = self.ttl {             // If a TTL exists
+            self.created_at.elapsed() > ttl       // Compare elapsed time to TTL
+        } else {
+            false                                  // No TTL => never expires
+        }
+    }
+
+    /// Whether a versioned invalidation of one of its tags killed this entry.
+    pub fn is_stale(&self) -> bool {
+        self.versions.is_stale()
+    }
+
+    /// Instant at which this entry expires (None = no TTL).
+    pub fn deadline(&self) -> Option<Instant> {
+        self.ttl.map(|ttl| self.created_at + ttl)
+    }
+
+    // Bytes counted against the namespace quota for this entry stored under `key`.
+    fn size(&self, key: &Key) -> i64 {
+        (key.0.len() + self.value.size()) as i64
+    }
+}
+
+/// A Shard holds a subset of all keys. Sharding reduces contention: each DashMap already shards internally,
+/// but we add an outer manual shard layer to control scaling and future distribution strategies.
+///
+/// Lock order: an `entries` guard may be held while the tag index is updated (`edit_tags`, collection
+/// writes), never the other way round; readers of the tag index snapshot a tag's keys (`members`)
+/// before they look the entries up.
+#[derive(Debug)]
+pub struct Shard {
+    pub entries: DashMap<Key, Entry>,          // Map key -> entry
+    pub tag_to_keys: DashMap<Tag, DashSet<Key>>, // Reverse index: tag -> set of keys sharing it
+    // Ordered copy of the tag names in `tag_to_keys`, so a tag's descendants are one range scan. It
+    // may briefly hold a tag that has just lost its last key; readers check `tag_to_keys`.
+    pub tag_tree: Mutex<BTreeSet<Tag>>,
+    // Expiration index: min-heap of (deadline, key). Records are never updated in place; when a key is
+    // overwritten or deleted its old record simply becomes stale and is skipped once it reaches the top,
+    // or dropped earlier by `compact_expiry` when stale records outnumber the live ones.
+    pub expiry: Mutex<BinaryHeap<Reverse<(Instant, Key)>>>,
+    // Members of versioned tags invalidated here, still to be removed (see `Cache::reclaim_invalidated`).
+    reclaim: Mutex<Reclaim>,
+}
+
+// Key sets taken out of the tag index by versioned invalidations, swept a batch at a time.
+#[derive(Debug, Default)]
+struct Reclaim {
+    detached: Vec<(Tag, DashSet<Key>)>,
+    sweeping: Option<(Tag, Vec<Key>)>,
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shard {
+    pub fn new() -> Self {
+        Self {
+            entries: DashMap::new(),
+            tag_to_keys: DashMap::new(),
+            tag_tree: Mutex::new(BTreeSet::new()),
+            expiry: Mutex::new(BinaryHeap::new()),
+            reclaim: Mutex::new(Reclaim::default()),
+        }
+    }
+
+    // Move `key` in the tag index from the `old` tags to the `new` ones.
+    fn reindex(&self, key: &Key, old: &[Tag], new: &[Tag]) {
+        for tag in old {
+            self.unlink(tag, key);
+        }
+        for tag in new {
+            self.link(tag, key);
+        }
+    }
+
+    // Add `key` to `tag`'s set; a tag new to this shard also enters the ordered index.
+    fn link(&self, tag: &Tag, key: &Key) {
+        let created = match self.tag_to_keys.entry(tag.clone()) {
+            dashmap::mapref::entry::Entry::Occupied(keys) => { keys.get().insert(key.clone()); false }
+            dashmap::mapref::entry::Entry::Vacant(slot) => { slot.insert(DashSet::from_iter([key.clone()])); true }
+        };
+        if created { self.tag_tree.lock().insert(tag.clone()); }
+    }
+
+    // Remove `key` from `tag`'s set, dropping the tag from both indexes once no key is left.
+    fn unlink(&self, tag: &Tag, key: &Key) {
+        let Some(keys) = self.tag_to_keys.get(tag) else { return };
+        keys.remove(key);
+        let empty = keys.is_empty();
+        drop(keys);
+        if empty {
+            // Under the tree lock, so a concurrent link() re-adding the tag is not lost from the tree.
+            let mut tree = self.tag_tree.lock();
+            if self.tag_to_keys.remove_if(tag, |_, keys| keys.is_empty()).is_some() { tree.remove(tag); }
+        }
+    }
+
+    // Live keys carrying `tag` in this shard and their bytes.
+    fn tag_size(&self, tag: &Tag) -> (usize, usize) {
+        self.members(tag).iter().filter_map(|k| self.entries.get(k).filter(|e| !e.is_expired()).map(|e| e.size(k) as usize))
+            .fold((0, 0), |(keys, bytes), size| (keys + 1, bytes + size))
+    }
+
+    // Snapshot of the keys linked to `tag`, so no index guard is held while entries are read.
+    fn members(&self, tag: &Tag) -> Vec<Key> {
+        self.tag_to_keys.get(tag).map(|keys| keys.iter().map(|k| k.clone()).collect()).unwrap_or_default()
+    }
+
+    // Tags of this shard equal to `root` or below it (`root` followed by `separator`).
+    fn subtree(&self, root: &Tag, separator: &str) -> Vec<Tag> {
+        let tree = self.tag_tree.lock();
+        tree.range(root.clone()..)
+            .take_while(|t| t.0.starts_with(&root.0))
+            .filter(|t| t.0.len() == root.0.len() || t.0[root.0.len()..].starts_with(separator))
+            .cloned()
+            .collect()
+    }
+
+    // Take `tag`'s key set out of both indexes (versioned invalidation: its members are dead).
+    fn detach(&self, tag: &Tag) -> Option<DashSet<Key>> {
+        let mut tree = self.tag_tree.lock();
+        let (_, keys) = self.tag_to_keys.remove(tag)?;
+        tree.remove(tag);
+        Some(keys)
+    }
+
+    // Up to `max` keys of one detached tag to check for reclamation.
+    fn next_reclaim(&self, max: usize) -> Option<(Tag, Vec<Key>)> {
+        let mut reclaim = self.reclaim.lock();
+        loop {
+            if let Some((tag, keys)) = reclaim.sweeping.as_mut() {
+                if !keys.is_empty() {
+                    let batch = keys.split_off(keys.len().saturating_sub(max));
+                    return Some((tag.clone(), batch));
+                }
+            }
+            let (tag, keys) = reclaim.detached.pop()?;
+            reclaim.sweeping = Some((tag, keys.into_iter().collect())); // Moves the keys, no copies
+        }
+    }
+
+    // Tags and keys waiting for reclamation.
+    fn pending_reclaims(&self) -> usize {
+        let reclaim = self.reclaim.lock();
+        reclaim.detached.iter().map(|(_, keys)| keys.len()).sum::<usize>() + reclaim.sweeping.as_ref().map_or(0, |(_, keys)| keys.len())
+    }
+
+    // Register an entry's deadline in the expiration index (no-op for entries without TTL).
+    fn schedule_expiry(&self, key: &Key, entry: &Entry) {
+        if let Some(deadline) = entry.deadline() {
+            self.expiry.lock().push(Reverse((deadline, key.clone())));
+        }
+    }
+
+    // Drop stale records once the heap holds more than twice as many as there are entries (each entry
+    // has at most one live record), so rewrite-heavy keys cannot grow it without bound. Called by the
+    // reaper with no other lock held: the heap is taken out, filtered, and merged back with whatever
+    // was scheduled meanwhile.
+    fn compact_expiry(&self) {
+        let len = self.expiry.lock().len();
+        if len <= EXPIRY_COMPACT_MIN || len <= 2 * self.entries.len() { return; }
+        let records = std::mem::take(&mut *self.expiry.lock());
+        let live: Vec<_> = records.into_iter()
+            .filter(|Reverse((deadline, key))| self.entries.get(key).is_some_and(|e| e.deadline() == Some(*deadline)))
+            .collect();
+        self.expiry.lock().extend(live);
+    }
+}
+
+// Smallest expiration heap worth compacting.
+const EXPIRY_COMPACT_MIN: usize = 1024;
+
+/// Overall cache — contains multiple shards and aggregated statistics.
+#[derive(Debug)]
+pub struct Cache {
+    pub shards: Vec<Shard>,               // Fixed number of shards selected by hashing the key
+    pub stats: Arc<Mutex<CacheStats>>,    // Shared stats protected by a Mutex (updates are small / low contention)
+    hasher: RandomState,                 // Fast hashing state (provides build_hasher())
+    pub events: EventBus,                // Broadcast of keyspace events (no cost when nobody subscribes)
+    webhooks: Option<Arc<WebhookDispatcher>>, // Tag invalidation / flush notifications (None = disabled)
+    pub namespaces: Namespaces,          // Per-namespace usage, counters and quotas
+    pub tag_ttl: TagTtl,                 // Tag-level TTL policies applied on writes (see tag_ttl.rs)
+    pub tag_versions: TagVersions,       // Generation counters of versioned tags (see tag_version.rs)
+    last_fence: AtomicU64,               // Last lock fencing token granted (see lock.rs)
+    tag_separator: String,               // Splits hierarchical tags like tenant:42:product:7 into levels
+}
+
+/// How a multi-tag invalidation matches keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagMatch {
+    Any,     // key has at least one of the tags
+    All,     // key has every tag
+    Subtree, // key has one of the tags or a tag below one of them (see `Cache::tag_subtree`)
+}
+
+/// Size of a tag: how many live keys carry it and their bytes (keys + values).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagStats {
+    pub tag: Tag,
+    pub keys: usize,
+    pub bytes: usize,
+}
+
+/// Separator between the levels of a hierarchical tag unless `cache.tag_separator` says otherwise.
+pub const DEFAULT_TAG_SEPARATOR: &str = ":";
+
+/// Simple counters; Clone so we can snapshot for /stats without locking long.
+#[derive(Debug, Default, Clone)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub puts: u64,
+    pub invalidations: u64,
+    pub expired: u64,          // Entries removed because their TTL elapsed (reaper + lazy removal)
+    pub expiry_lag_ms: u64,    // Worst delay between deadline and removal seen by the last reaper pass
+    pub reclaimed: u64,        // Entries of versioned invalidations removed in the background
+}
+
+impl Cache {
+    /// Create a new cache with N shards.
+    pub fn new(num_shards: usize) -> Self {
+        assert!(num_shards > 0, "num_shards must be > 0");
+        let mut shards = Vec::with_capacity(num_shards);
+        for _ in 0..num_shards { // Allocate and push each shard
+            shards.push(Shard::new());
+        }
+        Self {
+            shards,
+            stats: Arc::new(Mutex::new(CacheStats::default())),
+            hasher: RandomState::new(), // Random seed hashing state for consistent distribution
+            events: EventBus::new(events::DEFAULT_EVENT_BUFFER),
+            webhooks: None,
+            namespaces: Namespaces::default(),
+            tag_ttl: TagTtl::default(),
+            tag_versions: TagVersions::default(),
+            last_fence: AtomicU64::new(0),
+            tag_separator: DEFAULT_TAG_SEPARATOR.to_string(),
+        }
+    }
+
+    /// Apply namespace quotas (see namespace.rs).
+    pub fn with_namespaces(mut self, config: NamespacesConfig) -> Self {
+        self.namespaces = Namespaces::new(config);
+        self
+    }
+
+    /// Apply tag TTL policies (see tag_ttl.rs).
+    pub fn with_tag_ttl(mut self, config: &TagTtlConfig) -> Self {
+        self.tag_ttl = TagTtl::new(config);
+        self
+    }
+
+    /// Invalidate the configured tags by generation instead of eagerly (see tag_version.rs).
+    pub fn with_invalidation(mut self, config: &InvalidationConfig) -> Self {
+        self.tag_versions = TagVersions::new(config);
+        self
+    }
+
+    // Namespace accounting for an entry stored under / removed from `key`.
+    fn count_insert(&self, key: &Key, entry: &Entry) {
+        self.namespaces.track(&key.0, |c| { c.usage(1, entry.size(key)); c.puts.fetch_add(1, Relaxed); });
+    }
+
+    fn count_remove(&self, key: &Key, entry: &Entry) {
+        self.namespaces.track(&key.0, |c| c.usage(-1, -entry.size(key)));
+    }
+
+    /// Check a write of a `value_len`-byte value to `key` against its namespace's key and byte
+    /// quotas (always Ok in the default namespace).
+    pub fn admit_write(&self, key: &Key, value_len: usize) -> Result<(), NamespaceError> {
+        let Some((ns, _)) = namespace::split(&key.0) else { return Ok(()) };
+        let shard = &self.shards[self.hash_key(key)];
+        let existing = shard.entries.get(key).filter(|e| !e.is_expired()).map(|e| e.size(key));
+        let added = (key.0.len() + value_len) as i64 - existing.unwrap_or(0);
+        self.namespaces.admit_write(ns, existing.is_none() as u64, added)
+    }
+
+    /// Same for a collection write adding up to `added_bytes` to whatever `key` already holds.
+    pub fn admit_update(&self, key: &Key, added_bytes: usize) -> Result<(), NamespaceError> {
+        let Some((ns, _)) = namespace::split(&key.0) else { return Ok(()) };
+        let shard = &self.shards[self.hash_key(key)];
+        let exists = shard.entries.get(key).is_some_and(|e| !e.is_expired());
+        let added = added_bytes + if exists { 0 } else { key.0.len() };
+        self.namespaces.admit_write(ns, !exists as u64, added as i64)
+    }
+
+    /// Attach a webhook dispatcher notified on tag invalidations and flushes.
+    pub fn with_webhooks(mut self, webhooks: Option<Arc<WebhookDispatcher>>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    // Publish a tag-level invalidation to subscribers and webhooks.
+    fn notify_invalidation(&self, event: WebhookEvent, tags: &[Tag], count: usize) {
+        let kind = if event == WebhookEvent::FlushAll { EventKind::Flush } else { EventKind::InvalidateTag };
+        self.events.publish(|| {
+            let ev = CacheEvent::new(kind, None, tags.iter().map(|t| t.0.clone()).collect(), Some(count));
+            match event {
+                WebhookEvent::InvalidateTagsAny => ev.with_mode("any"),
+                WebhookEvent::InvalidateTagsAll => ev.with_mode("all"),
+                WebhookEvent::InvalidateTagsSubtree => ev.with_mode("subtree"),
+                _ => ev,
+            }
+        });
+        if let Some(hooks) = &self.webhooks {
+            // One operation's tags share a namespace; receivers get it apart from the tag names.
+            let namespace = tags.first().and_then(|t| namespace::split(&t.0)).map(|(ns, _)| ns.to_string());
+            let names = tags.iter().map(|t| namespace::split(&t.0).map_or(t.as_str(), |(_, name)| name).to_string()).collect();
+            hooks.notify(event, namespace, names, count);
+        }
+    }
+
+    /// Split hierarchical tags on `separator` instead of the default `:` (ignored when empty).
+    pub fn with_tag_separator(mut self, separator: &str) -> Self {
+        if !separator.is_empty() { self.tag_separator = separator.to_string(); }
+        self
+    }
+
+    pub fn tag_separator(&self) -> &str {
+        &self.tag_separator
+    }
+
+    /// Replace the event bus with one holding `capacity` events per subscriber before lagging.
+    pub fn with_event_buffer(mut self, capacity: usize) -> Self {
+        self.events = EventBus::new(capacity);
+        self
+    }
+
+    // Publish a key-level event (built only if someone is subscribed).
+    fn emit(&self, kind: EventKind, key: &Key, tags: &[Tag]) {
+        self.events.publish(|| CacheEvent::new(kind, Some(key.0.clone()), tags.iter().map(|t| t.0.clone()).collect(), None));
+    }
+
+    // Decide which shard a key belongs to using hashing.
+    pub(crate) fn hash_key(&self, key: &Key) -> usize {
+        (self.hasher.hash_one(key) as usize) % self.shards.len() // Hash the key and map to shard index
+    }
+
+    /// Insert or update a key with value + tags + optional TTL. Returns the TTL stored.
+    pub fn put(&self, key: Key, value: String, tags: Vec<Tag>, ttl: Option<Duration>) -> Option<Duration> {
+        self.put_value(key, Value::String(value), tags, ttl)
+    }
+
+    /// `put` for a value of any type (replaces whatever the key held). Tag TTL policies may shorten
+    /// or replace `ttl`; returns the TTL stored.
+    pub fn put_value(&self, key: Key, value: Value, tags: Vec<Tag>, ttl: Option<Duration>) -> Option<Duration> {
+        let ttl = self.tag_ttl.apply(&tags, ttl);
+        self.insert(key, value, tags, ttl);
+        ttl
+    }
+
+    // `put_value` keeping `ttl` as given (entries carried over from elsewhere).
+    fn insert(&self, key: Key, value: Value, tags: Vec<Tag>, ttl: Option<Duration>) {
+        let shard_idx = self.hash_key(&key);      // Pick shard
+        let shard = &self.shards[shard_idx];
+
+        // Hold the key's slot while the tag index moves, and stamp only once the key is linked: a
+        // generation bump racing this write then either detaches a set that already holds the key (so
+        // reclamation finds it, waiting on this slot) or comes after a stamp it makes stale.
+        let slot = shard.entries.entry(key.clone());
+        let old_tags = match &slot {
+            dashmap::mapref::entry::Entry::Occupied(old) => old.get().tags.clone(),
+            dashmap::mapref::entry::Entry::Vacant(_) => SmallVec::new(), // No old entry, no tags to clean up
+        };
+        shard.reindex(&key, &old_tags, &tags);
+
+        // Build new entry (Instant::now() captured here).
+        let entry = Entry {
+            value,
+            tags: SmallVec::from_vec(tags.clone()), // Clone tags so we can also iterate original vector for indexing
+            created_at: Instant::now(),
+            ttl,
+            created_system: SystemTime::now(),
+            versions: self.tag_versions.stamp(&tags),
+        };
+
+        shard.schedule_expiry(&key, &entry);      // Track deadline so the reaper can find it without scanning
+        self.count_insert(&key, &entry);
+        match slot {                              // Upsert the actual entry
+            dashmap::mapref::entry::Entry::Occupied(mut old) => { let old = old.insert(entry); self.count_remove(&key, &old); }
+            dashmap::mapref::entry::Entry::Vacant(vacant) => { vacant.insert(entry); }
+        }
+        self.emit(EventKind::Put, &key, &tags);   // After the insert, so a subscriber reading the key sees the new value
+        self.stats.lock().puts += 1;              // Increment PUT counter (lock is short-lived)
+    }
+
+    /// Insert an entry carried over from another server (import, replication): same as `put`, but the
+    /// TTL is kept as given (no tag TTL policies) and the wall clock creation time is kept from the
+    /// source so `created_ms` survives the round trip.
+    pub fn restore(&self, key: Key, value: Value, tags: Vec<Tag>, ttl: Option<Duration>, created: Option<SystemTime>) {
+        self.insert(key.clone(), value, tags, ttl);
+        if let Some(created) = created {
+            let shard = &self.shards[self.hash_key(&key)];
+            if let Some(mut entry) = shard.entries.get_mut(&key) { entry.created_system = created; }
+        }
+    }
+
+    /// Atomically add a key only if it doesn't exist. Returns the TTL stored (after tag TTL policies)
+    /// if added, None if the key already exists.
+    /// This provides atomic protection against race conditions and prevents accidental overwrites.
+    pub fn add(&self, key: Key, value: String, tags: Vec<Tag>, ttl: Option<Duration>) -> Option<Option<Duration>> {
+        let ttl = self.tag_ttl.apply(&tags, ttl);
+        self.add_with(key, || Value::String(value), tags, ttl).then_some(ttl)
+    }
+
+    // `add` of the value built by `make`, which only runs (under the entry's lock) when the key is free.
+    fn add_with(&self, key: Key, make: impl FnOnce() -> Value, tags: Vec<Tag>, ttl: Option<Duration>) -> bool {
+        let shard_idx = self.hash_key(&key);
+        let shard = &self.shards[shard_idx];
+
+        // Use entry API for atomic check-and-insert
+        match shard.entries.entry(key.clone()) {
+            dashmap::mapref::entry::Entry::Occupied(occupied) => {
+                // Key exists - check if it's expired
+                if occupied.get().is_expired() {
+                    // Remove old tag associations for expired entry
+                    let old_tags = occupied.get().tags.clone();
+                    // Move tag associations from the expired entry first, so the stamp below is never
+                    // older than the index (see `insert`)
+                    shard.reindex(&key, &old_tags, &tags);
+                    
+                    // Build new entry
+                    let entry = Entry {
+                        value: make(),
+                        tags: SmallVec::from_vec(tags.clone()),
+                        created_at: Instant::now(),
+                        ttl,
+                        created_system: SystemTime::now(),
+                        versions: self.tag_versions.stamp(&tags),
+                    };
+                    
+                    // Replace expired entry with new one
+                    shard.schedule_expiry(&key, &entry);
+                    self.count_insert(&key, &entry);
+                    let (_, old) = occupied.replace_entry(entry);
+                    self.count_remove(&key, &old);
+                    
+                    self.stats.lock().puts += 1;
+                    self.emit(EventKind::Add, &key, &tags);
+                    true
+                } else {
+                    // Key exists and is not expired - fail the add
+                    false
+                }
+            }
+            dashmap::mapref::entry::Entry::Vacant(vacant) => {
+                // Key doesn't exist - safe to insert; link it before stamping (see `insert`)
+                shard.reindex(&key, &[], &tags);
+                let entry = Entry {
+                    value: make(),
+                    tags: SmallVec::from_vec(tags.clone()),
+                    created_at: Instant::now(),
+                    ttl,
+                    created_system: SystemTime::now(),
+                    versions: self.tag_versions.stamp(&tags),
+                };
+                
+                shard.schedule_expiry(&key, &entry);
+                self.count_insert(&key, &entry);
+                vacant.insert(entry);
+                
+                self.stats.lock().puts += 1;
+                self.emit(EventKind::Add, &key, &tags);
+                true
+            }
+        }
+    }
+
+    /// Atomically increment a numeric value stored at key. Creates key with increment if it doesn't exist.
+    /// Returns Ok(new_value) on success, Err(reason) if value is not numeric or other error.
+    /// Similar to Redis INCR/INCRBY commands with atomic guarantees.
+    pub fn increment(&self, key: Key, by: i64, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<i64, String> {
+        match self.increment_with(key, by.into(), &CounterOptions::default(), tags, ttl)?.0 {
+            CounterValue::Int(n) => Ok(n),
+            CounterValue::Float(_) => Err(ValueError::NotInteger.to_string()),
+        }
+    }
+
+    /// Atomically decrement a numeric value stored at key. Creates key with -decrement if it doesn't exist.
+    pub fn decrement(&self, key: Key, by: i64, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<i64, String> {
+        // Decrement is just increment with negative value
+        self.increment(key, by.checked_neg().ok_or_else(|| ValueError::Overflow.to_string())?, tags, ttl)
+    }
+
+    /// INCR with float mode, bounds and a reset window (see counter.rs). Like `increment`, the write
+    /// restarts the TTL and replaces TTL / tags when given; a window sets the TTL to its end. Returns
+    /// the new value and the TTL stored (after tag TTL policies).
+    pub fn increment_with(&self, key: Key, by: CounterValue, options: &CounterOptions, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<(CounterValue, Option<Duration>), String> {
+        let ttl = options.window.map(|w| w.remaining(SystemTime::now())).or(ttl);
+        let updated = self.write(EventKind::Incr, key, ValueType::String, Some((tags, ttl)), |v| {
+            let Value::String(text) = v else { return Err(ValueError::WrongType(v.kind())) };
+            let new_value = options.apply((!text.is_empty()).then_some(text.as_str()), by)?;
+            *text = new_value.to_string();
+            Ok(new_value)
+        });
+        updated.map(|r| r.expect("writes with `store` always run")).map_err(|e| e.to_string())
+    }
+
+    /// `increment_with` subtracting `by`.
+    pub fn decrement_with(&self, key: Key, by: CounterValue, options: &CounterOptions, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<(CounterValue, Option<Duration>), String> {
+        self.increment_with(key, by.negate().map_err(|e| e.to_string())?, options, tags, ttl)
+    }
+
+    /// Check and record one request against the rate limiter at `key` (see ratelimit.rs). The
+    /// limiter's entry takes `tags` and expires once its state no longer matters.
+    pub fn rate_limit(&self, key: Key, limit: &RateLimit, tags: Vec<Tag>) -> Result<Decision, ValueError> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        let store = Some((tags, Some(limit.ttl(now))));
+        self.update_as(EventKind::Incr, key, limit.algorithm.kind(), store, |v| limit.hit(v, now))
+            .map(|d| d.expect("writes with `store` always run"))
+    }
+
+    // -------- Locks --------
+
+    /// Take the lock `key` for `ttl` (see lock.rs). Fails with `LockError::Held` while another
+    /// lease is live.
+    pub fn lock_acquire(&self, key: Key, ttl: Duration, tags: Vec<Tag>) -> Result<Lease, LockError> {
+        let mut lease = None;
+        let make = || {
+            let granted = Lease::new(lock::next_fence(&self.last_fence), ttl); // Under the entry lock, so grants of a key get increasing tokens
+            let value = granted.to_value();
+            lease = Some(granted);
+            value
+        };
+        if self.add_with(key.clone(), make, tags, Some(ttl)) { return Ok(lease.expect("set when added")); }
+        let shard = &self.shards[self.hash_key(&key)];
+        match shard.entries.get(&key).filter(|e| !e.is_expired()) {
+            Some(e) if lock::holder(&e.value).is_some() => Err(LockError::Held(e.deadline().map_or(Duration::ZERO, |d| d.saturating_duration_since(Instant::now())))),
+            Some(e) => Err(LockError::WrongType(e.value.kind())),
+            None => Err(LockError::Held(Duration::ZERO)), // Released since; worth another try
+        }
+    }
+
+    /// Extend the lease of the lock `key` held by `owner` to `ttl` from now.
+    pub fn lock_renew(&self, key: &Key, owner: &str, ttl: Duration) -> Result<Lease, LockError> {
+        let shard = &self.shards[self.hash_key(key)];
+        let mut entry = shard.entries.get_mut(key).filter(|e| !e.is_expired()).ok_or(LockError::NotHeld)?;
+        let fence = match lock::holder(&entry.value) {
+            Some((holder, fence)) if holder == owner => fence,
+            Some(_) => return Err(LockError::NotOwner),
+            None => return Err(LockError::WrongType(entry.value.kind())),
+        };
+        entry.created_at = Instant::now();
+        entry.ttl = Some(ttl);
+        shard.schedule_expiry(key, &entry);
+        self.emit(EventKind::Put, key, &entry.tags); // Followers pick up the new lease
+        Ok(Lease { owner: owner.to_string(), fence, ttl })
+    }
+
+    /// Release the lock `key`, only for its `owner`.
+    pub fn lock_release(&self, key: &Key, owner: &str) -> Result<(), LockError> {
+        let shard = &self.shards[self.hash_key(key)];
+        let dashmap::mapref::entry::Entry::Occupied(occupied) = shard.entries.entry(key.clone()) else { return Err(LockError::NotHeld) };
+        if occupied.get().is_expired() { return Err(LockError::NotHeld); }
+        match lock::holder(&occupied.get().value) {
+            Some((holder, _)) if holder == owner => {}
+            Some(_) => return Err(LockError::NotOwner),
+            None => return Err(LockError::WrongType(occupied.get().value.kind())),
+        }
+        let (key, old) = occupied.remove_entry();
+        shard.reindex(&key, &old.tags, &[]);
+        self.count_remove(&key, &old);
+        self.emit(EventKind::Delete, &key, &old.tags);
+        Ok(())
+    }
+
+    // -------- Hashes, lists and sets --------
+
+    // Apply `f` to the live value of `key`. Writes that store data pass `store` (tags, TTL): a missing
+    // key then starts as an empty `kind`, and like INCR the write restarts the TTL and replaces the
+    // TTL / tags when given. Removals pass None and return None for a missing key. A collection left
+    // empty is deleted. `f` must leave the value untouched when it fails.
+    fn update<T>(&self, key: Key, kind: ValueType, store: Option<(Vec<Tag>, Option<Duration>)>, f: impl FnOnce(&mut Value) -> Result<T, ValueError>) -> Result<Option<T>, ValueError> {
+        self.update_as(EventKind::Put, key, kind, store, f)
+    }
+
+    // `update` reporting writes as `event` (INCR / DECR are `incr` events).
+    fn update_as<T>(&self, event: EventKind, key: Key, kind: ValueType, store: Option<(Vec<Tag>, Option<Duration>)>, f: impl FnOnce(&mut Value) -> Result<T, ValueError>) -> Result<Option<T>, ValueError> {
+        self.write(event, key, kind, store, f).map(|r| r.map(|(result, _)| result))
+    }
+
+    // `update_as` also returning the entry's TTL afterwards (None once the key is gone).
+    fn write<T>(&self, event: EventKind, key: Key, kind: ValueType, store: Option<(Vec<Tag>, Option<Duration>)>, f: impl FnOnce(&mut Value) -> Result<T, ValueError>) -> Result<Option<(T, Option<Duration>)>, ValueError> {
+        let shard = &self.shards[self.hash_key(&key)];
+        match shard.entries.entry(key.clone()) {
+            dashmap::mapref::entry::Entry::Occupied(mut occupied) if !occupied.get().is_expired() => {
+                let entry = occupied.get_mut();
+                let before = entry.size(&key);
+                let result = f(&mut entry.value)?;
+                if entry.value.is_empty_collection() {
+                    let (key, old) = occupied.remove_entry();
+                    shard.reindex(&key, &old.tags, &[]);
+                    self.namespaces.track(&key.0, |c| c.usage(-1, -before));
+                    self.emit(EventKind::Delete, &key, &old.tags);
+                    return Ok(Some((result, None)));
+                }
+                let grown = entry.size(&key) - before;
+                self.namespaces.track(&key.0, |c| { c.usage(0, grown); c.puts.fetch_add(1, Relaxed); });
+                if let Some((tags, ttl)) = store {
+                    entry.created_at = Instant::now();
+                    entry.created_system = SystemTime::now();
+                    if !tags.is_empty() {
+                        shard.reindex(&key, &entry.tags, &tags);
+                        entry.versions = self.tag_versions.restamp(&entry.versions, &entry.tags, &tags);
+                        entry.tags = SmallVec::from_vec(tags);
+                    }
+                    entry.ttl = self.tag_ttl.apply(&entry.tags, ttl.or(entry.ttl));
+                    shard.schedule_expiry(&key, entry);
+                }
+                self.stats.lock().puts += 1;
+                self.emit(event, &key, &entry.tags);
+                Ok(Some((result, entry.ttl)))
+            }
+            slot => {
+                let Some((tags, ttl)) = store else { return Ok(None) };
+                let mut value = Value::empty(kind);
+                let result = f(&mut value)?;
+                if value.is_empty_collection() { return Ok(Some((result, None))); }
+                let ttl = self.tag_ttl.apply(&tags, ttl);
+                // Linked before stamping, as in `insert`
+                let old_tags = match &slot {
+                    dashmap::mapref::entry::Entry::Occupied(expired) => expired.get().tags.clone(),
+                    dashmap::mapref::entry::Entry::Vacant(_) => SmallVec::new(),
+                };
+                shard.reindex(&key, &old_tags, &tags);
+                let versions = self.tag_versions.stamp(&tags);
+                let entry = Entry { value, tags: SmallVec::from_vec(tags.clone()), created_at: Instant::now(), ttl, created_system: SystemTime::now(), versions };
+                shard.schedule_expiry(&key, &entry);
+                self.count_insert(&key, &entry);
+                match slot {
+                    dashmap::mapref::entry::Entry::Occupied(expired) => {
+                        let (_, old) = expired.replace_entry(entry);
+                        self.count_remove(&key, &old);
+                    }
+                    dashmap::mapref::entry::Entry::Vacant(vacant) => { vacant.insert(entry); }
+                }
+                self.stats.lock().puts += 1;
+                self.emit(event, &key, &tags);
+                Ok(Some((result, ttl)))
+            }
+        }
+    }
+
+    /// Set a hash field. Returns true if the field is new.
+    pub fn hset(&self, key: Key, field: String, value: String, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<bool, ValueError> {
+        self.update(key, ValueType::Hash, Some((tags, ttl)), |v| Ok(v.hash_mut()?.insert(field, value).is_none())).map(|r| r.unwrap_or(false))
+    }
+
+    pub fn hget(&self, key: &Key, field: &str) -> Result<Option<String>, ValueError> {
+        self.read(key, |v| v.hash().map(|h| h.get(field).cloned())).unwrap_or(Ok(None))
+    }
+
+    /// Every field of a hash (empty for a missing key).
+    pub fn hgetall(&self, key: &Key) -> Result<BTreeMap<String, String>, ValueError> {
+        self.read(key, |v| v.hash().cloned()).unwrap_or_else(|| Ok(BTreeMap::new()))
+    }
+
+    /// Remove a hash field. Returns true if it existed.
+    pub fn hdel(&self, key: Key, field: &str) -> Result<bool, ValueError> {
+        self.update(key, ValueType::Hash, None, |v| Ok(v.hash_mut()?.remove(field).is_some())).map(|r| r.unwrap_or(false))
+    }
+
+    /// Add `by` to an integer hash field (a missing field counts as 0). Returns the new value.
+    pub fn hincrby(&self, key: Key, field: String, by: i64, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<i64, ValueError> {
+        let updated = self.update(key, ValueType::Hash, Some((tags, ttl)), |v| {
+            let hash = v.hash_mut()?;
+            let current = match hash.get(&field) {
+                Some(text) => text.trim().parse::<i64>().map_err(|_| ValueError::NotInteger)?,
+                None => 0,
+            };
+            let new_value = current.checked_add(by).ok_or(ValueError::Overflow)?;
+            hash.insert(field, new_value.to_string());
+            Ok(new_value)
+        })?;
+        Ok(updated.unwrap_or(by))
+    }
+
+    /// Push items onto the head of a list, one after the other. Returns the new length.
+    pub fn lpush(&self, key: Key, items: Vec<String>, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<usize, ValueError> {
+        self.push(key, true, items, tags, ttl)
+    }
+
+    /// Append items to the tail of a list. Returns the new length.
+    pub fn rpush(&self, key: Key, items: Vec<String>, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<usize, ValueError> {
+        self.push(key, false, items, tags, ttl)
+    }
+
+    fn push(&self, key: Key, front: bool, items: Vec<String>, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<usize, ValueError> {
+        self.update(key, ValueType::List, Some((tags, ttl)), |v| {
+            let list = v.list_mut()?;
+            for item in items { if front { list.push_front(item) } else { list.push_back(item) } }
+            Ok(list.len())
+        }).map(|r| r.unwrap_or(0))
+    }
+
+    /// Remove and return the first item of a list.
+    pub fn lpop(&self, key: Key) -> Result<Option<String>, ValueError> {
+        self.update(key, ValueType::List, None, |v| Ok(v.list_mut()?.pop_front())).map(Option::flatten)
+    }
+
+    /// Remove and return the last item of a list.
+    pub fn rpop(&self, key: Key) -> Result<Option<String>, ValueError> {
+        self.update(key, ValueType::List, None, |v| Ok(v.list_mut()?.pop_back())).map(Option::flatten)
+    }
+
+    /// Items `start..=stop` of a list; negative indexes count from the end (-1 = last item).
+    pub fn lrange(&self, key: &Key, start: i64, stop: i64) -> Result<Vec<String>, ValueError> {
+        self.read(key, |v| {
+            let list = v.list()?;
+            let len = list.len() as i64;
+            let index = |i: i64| if i < 0 { (len + i).max(0) } else { i };
+            let (start, stop) = (index(start), index(stop).min(len - 1));
+            Ok(if start > stop { Vec::new() } else { list.range(start as usize..=stop as usize).cloned().collect() })
+        }).unwrap_or(Ok(Vec::new()))
+    }
+
+    /// Add members to a set. Returns how many were not already in it.
+    pub fn sadd(&self, key: Key, members: Vec<String>, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<usize, ValueError> {
+        self.update(key, ValueType::Set, Some((tags, ttl)), |v| {
+            let set = v.set_mut()?;
+            Ok(members.into_iter().map(|m| set.insert(m)).filter(|&added| added).count())
+        }).map(|r| r.unwrap_or(0))
+    }
+
+    /// Remove members from a set. Returns how many were in it.
+    pub fn srem(&self, key: Key, members: &[String]) -> Result<usize, ValueError> {
+        self.update(key, ValueType::Set, None, |v| {
+            let set = v.set_mut()?;
+            Ok(members.iter().filter(|m| set.remove(m.as_str())).count())
+        }).map(|r| r.unwrap_or(0))
+    }
+
+    /// Members of a set, sorted (empty for a missing key).
+    pub fn smembers(&self, key: &Key) -> Result<Vec<String>, ValueError> {
+        self.read(key, |v| v.set().map(|s| s.iter().cloned().collect())).unwrap_or(Ok(Vec::new()))
+    }
+
+    // -------- JSON documents --------
+
+    /// The part of a value at `path` (None if the key or the path is missing). Any kind can be read
+    /// this way; strings holding JSON are parsed first.
+    pub fn json_get(&self, key: &Key, path: &JsonPath) -> Option<serde_json::Value> {
+        self.read(key, |v| v.json_at(path)).flatten()
+    }
+
+    /// Apply `op` at `path` of a JSON document as one atomic step; a missing key starts as `null`.
+    /// Returns the value now at `path`.
+    pub fn json_update(&self, key: Key, path: &JsonPath, op: JsonOp, tags: Vec<Tag>, ttl: Option<Duration>) -> Result<serde_json::Value, ValueError> {
+        self.update(key, ValueType::Json, Some((tags, ttl)), |v| v.json_apply(path, op))
+            .map(|r| r.unwrap_or(serde_json::Value::Null))
+    }
+
+    /// Retrieve a string value if present and not expired (None for hashes, lists and sets; see
+    /// `get_value`).
+    pub fn get(&self, key: &Key) -> Option<String> {
+        match self.get_value(key)? {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Retrieve a value of any type if present and not expired.
+    pub fn get_value(&self, key: &Key) -> Option<Value> {
+        self.read(key, Value::clone)
+    }
+
+    // Run `f` on the live value of `key`, counting a hit or a miss (and removing an expired entry).
+    fn read<T>(&self, key: &Key, f: impl FnOnce(&Value) -> T) -> Option<T> {
+        let shard_idx = self.hash_key(key);
+        let shard = &self.shards[shard_idx];
+        
+        // First, check if entry exists and get its expiration status
+        let (value, is_expired) = if let Some(entry) = shard.entries.get(key) {
+            if entry.is_expired() {
+                (None, true)  // Entry exists but is expired
+            } else {
+                (Some(f(&entry.value)), false)  // Entry exists and valid
+            }
+        } else {
+            (None, false)  // Entry doesn't exist
+        };
+        // The read lock is automatically dropped here when `entry` goes out of scope
+        
+        // Now handle expired entry removal without holding read lock
+        if is_expired {
+            // Safe to remove now - no lock conflict
+            let removed = shard.entries.remove_if(key, |_, e| e.is_expired());
+            let stale = removed.as_ref().is_some_and(|(_, e)| e.is_stale());
+            if let Some((_, old_entry)) = removed {
+                self.count_remove(key, &old_entry);
+                // Versioned invalidations were announced when the tag was invalidated
+                if !stale { self.emit(EventKind::Expire, key, &old_entry.tags); }
+                // Clean up tag associations for expired entry
+                shard.reindex(key, &old_entry.tags, &[]);
+            }
+            let mut stats = self.stats.lock();
+            stats.misses += 1;
+            if stale { stats.reclaimed += 1; } else { stats.expired += 1; }
+            self.namespaces.track(&key.0, |c| { c.misses.fetch_add(1, Relaxed); });
+            return None;
+        }
+        
+        // Return value if we have one
+        if let Some(val) = value {
+            self.stats.lock().hits += 1;
+            self.namespaces.track(&key.0, |c| { c.hits.fetch_add(1, Relaxed); });
+            Some(val)
+        } else {
+            self.stats.lock().misses += 1;
+            self.namespaces.track(&key.0, |c| { c.misses.fetch_add(1, Relaxed); });
+            None
+        }
+    }
+
+    /// Return all keys that have a given tag (filtering expired ones).
+    pub fn get_keys_by_tag(&self, tag: &Tag) -> Vec<Key> {
+        let mut result = Vec::new();
+        for shard in &self.shards {                     // Scan every shard (O(shards + keys_for_tag))
+            for key in shard.members(tag) {             // Snapshot first: no index guard while entries are read
+                if let Some(entry) = shard.entries.get(&key) {
+                    if !entry.is_expired() {             // Avoid returning expired keys
+                        result.push(key.clone());
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// `root` and every tag below it: `tenant:42` covers `tenant:42:product:7` but not `tenant:420`.
+    pub fn tag_subtree(&self, root: &Tag) -> Vec<Tag> {
+        let mut tags: Vec<Tag> = self.shards.iter().flat_map(|s| s.subtree(root, &self.tag_separator)).collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// The tags one level below `parent` with the number of live keys in each one's subtree, in
+    /// tag order. An empty `parent` (or a namespace prefix ending in its separator) lists the top level.
+    pub fn tag_children(&self, parent: &Tag) -> Vec<(Tag, usize)> {
+        let sep = self.tag_separator.as_str();
+        let prefix = if parent.0.is_empty() || parent.0.ends_with(namespace::SEPARATOR) { parent.0.clone() } else { format!("{}{}", parent.0, sep) };
+        let mut children: BTreeMap<Tag, usize> = BTreeMap::new();
+        for shard in &self.shards {
+            let tags: Vec<Tag> = {
+                let tree = shard.tag_tree.lock();
+                tree.range(Tag(prefix.clone())..).take_while(|t| t.0.starts_with(&prefix)).cloned().collect()
+            };
+            // A key tagged with several descendants of one child counts once for it.
+            let mut keys: HashMap<Tag, HashSet<Key>> = HashMap::new();
+            for tag in tags {
+                let rest = &tag.0[prefix.len()..];
+                if rest.is_empty() { continue; }
+                let child = Tag(format!("{}{}", prefix, rest.split(sep).next().unwrap_or(rest)));
+                let live = shard.members(&tag).into_iter().filter(|k| shard.entries.get(k).is_some_and(|e| !e.is_expired()));
+                keys.entry(child).or_default().extend(live);
+            }
+            for (child, keys) in keys {
+                *children.entry(child).or_default() += keys.len();
+            }
+        }
+        children.into_iter().filter(|(_, n)| *n > 0).collect()
+    }
+
+    /// Key count and bytes of every tag, in tag order.
+    pub fn tag_stats(&self) -> Vec<TagStats> {
+        let mut sizes: BTreeMap<Tag, (usize, usize)> = BTreeMap::new();
+        for shard in &self.shards {
+            let tags: Vec<Tag> = shard.tag_tree.lock().iter().cloned().collect();
+            for tag in tags {
+                let (keys, bytes) = shard.tag_size(&tag);
+                if keys == 0 { continue; }
+                let size = sizes.entry(tag).or_default();
+                size.0 += keys;
+                size.1 += bytes;
+            }
+        }
+        sizes.into_iter().map(|(tag, (keys, bytes))| TagStats { tag, keys, bytes }).collect()
+    }
+
+    /// Key count and bytes of one tag; None when no live key carries it.
+    pub fn tag_info(&self, tag: &Tag) -> Option<TagStats> {
+        let (keys, bytes) = self.shards.iter().map(|s| s.tag_size(tag)).fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+        (keys > 0).then(|| TagStats { tag: tag.clone(), keys, bytes })
+    }
+
+    /// Replace `from` with `to` on every key carrying it; returns the number of keys changed.
+    pub fn rename_tag(&self, from: &Tag, to: &Tag) -> usize {
+        self.merge_tags(std::slice::from_ref(from), to)
+    }
+
+    /// Replace each of `sources` with `into` on every key carrying it (a key ends up with `into`
+    /// once); returns the number of keys changed.
+    pub fn merge_tags(&self, sources: &[Tag], into: &Tag) -> usize {
+        let mut keys: Vec<Key> = sources.iter().flat_map(|t| self.get_keys_by_tag(t)).collect();
+        keys.sort();
+        keys.dedup();
+        keys.iter().filter(|k| self.retag(k, |tags| {
+            for t in tags.iter_mut() { if sources.contains(t) { *t = into.clone(); } }
+        })).count()
+    }
+
+    /// Add `add` to and take `remove` off every live key matching `filter`, within the namespace of
+    /// those tags; returns the number of keys changed.
+    pub fn retag_matching(&self, filter: &TransferFilter, add: &[Tag], remove: &[Tag]) -> usize {
+        let ns = add.iter().chain(remove).next().and_then(|t| namespace::split(&t.0)).map(|(ns, _)| ns);
+        let mut keys = Vec::new();
+        for shard in &self.shards {
+            for item in shard.entries.iter() {
+                let key = item.key();
+                if item.value().is_expired() || namespace::split(&key.0).map(|(ns, _)| ns) != ns { continue; }
+                if filter.matches(&key.0, &item.value().tags) { keys.push(key.clone()); }
+            }
+        }
+        keys.iter().filter(|k| self.retag(k, |tags| {
+            tags.retain(|t| !remove.contains(t));
+            tags.extend(add.iter().cloned());
+        })).count()
+    }
+
+    // Whether `edit_tags` changed the tags of `key`.
+    fn retag(&self, key: &Key, edit: impl FnOnce(&mut Vec<Tag>)) -> bool {
+        self.edit_tags(key, edit).is_some_and(|(_, changed)| changed)
+    }
+
+    /// Rewrite the tags of a live key with `edit` (duplicates are dropped), leaving its value, TTL
+    /// and creation time alone. The tag index is updated while the entry is locked, so readers never
+    /// see the two disagree. Returns the tags afterwards and whether they changed; None when the key
+    /// is missing or expired.
+    pub fn edit_tags(&self, key: &Key, edit: impl FnOnce(&mut Vec<Tag>)) -> Option<(Vec<Tag>, bool)> {
+        let shard = &self.shards[self.hash_key(key)];
+        let mut entry = shard.entries.get_mut(key)?;
+        if entry.is_expired() { return None; }
+        let mut tags = entry.tags.to_vec();
+        edit(&mut tags);
+        let mut seen = HashSet::new();
+        tags.retain(|t| seen.insert(t.clone()));
+        if tags[..] == entry.tags[..] { return Some((tags, false)); }
+        // Only the tags that really come or go, so a kept tag never leaves the index meanwhile.
+        let gone: Vec<Tag> = entry.tags.iter().filter(|t| !tags.contains(t)).cloned().collect();
+        let new: Vec<Tag> = tags.iter().filter(|t| !entry.tags.contains(t)).cloned().collect();
+        shard.reindex(key, &gone, &new);
+        entry.versions = self.tag_versions.restamp(&entry.versions, &entry.tags, &tags);
+        entry.tags = SmallVec::from_vec(tags.clone());
+        drop(entry);
+        self.emit(EventKind::Put, key, &tags); // Followers pick up the new tags
+        Some((tags, true))
+    }
+
+    /// Make every live key carrying `tag` expire by `at` (wall clock); keys already due earlier keep
+    /// their deadline, and a time in the past expires them at once. Values and tags stay as they
+    /// are. Returns the number of keys whose deadline moved.
+    pub fn expire_tag_at(&self, tag: &Tag, at: SystemTime) -> usize {
+        let deadline = Instant::now() + at.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO);
+        let mut count = 0;
+        for shard in &self.shards {
+            for key in shard.members(tag) {
+                let Some(mut entry) = shard.entries.get_mut(&key) else { continue };
+                if entry.is_expired() || entry.deadline().is_some_and(|d| d <= deadline) { continue; }
+                entry.ttl = Some(deadline.saturating_duration_since(entry.created_at));
+                shard.schedule_expiry(&key, &entry);
+                let tags = entry.tags.clone();
+                drop(entry);
+                self.emit(EventKind::Put, &key, &tags); // Followers pick up the new TTL
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Invalidate every live key of namespace `ns` matching `filter` (stored names, see
+    /// `Namespace::qualify_filter`); returns the number removed.
+    pub fn invalidate_matching(&self, ns: &Namespace, filter: &TransferFilter) -> usize {
+        let mut keys = Vec::new();
+        for shard in &self.shards {
+            for item in shard.entries.iter() {
+                if item.value().is_expired() || ns.strip(&item.key().0).is_none() { continue; }
+                if filter.matches(&item.key().0, &item.value().tags) { keys.push(item.key().clone()); }
+            }
+        }
+        keys.iter().filter(|k| self.invalidate_key(k)).count()
+    }
+
+    /// Invalidate (remove) a single key (and detach all its tags).
+    pub fn invalidate_key(&self, key: &Key) -> bool {
+        let shard_idx = self.hash_key(key);
+        let shard = &self.shards[shard_idx];
+        if let Some((_, entry)) = shard.entries.remove(key) { // Remove returns (key, value)
+            self.count_remove(key, &entry);
+            self.namespaces.track(&key.0, |c| { c.invalidations.fetch_add(1, Relaxed); });
+            self.emit(EventKind::Delete, key, &entry.tags);
+            shard.reindex(key, &entry.tags, &[]);             // Clean reverse index
+            self.stats.lock().invalidations += 1;             // Increment invalidations counter
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Invalidate all keys for a tag; returns number of removed entries.
+    pub fn invalidate_tag(&self, tag: &Tag) -> usize {
+        let count = self.invalidate_members(tag);
+        self.notify_invalidation(WebhookEvent::InvalidateTag, std::slice::from_ref(tag), count);
+        count
+    }
+
+    /// Invalidate keys carrying any / all of `tags` as one operation (one event, one webhook).
+    pub fn invalidate_tags(&self, tags: &[Tag], mode: TagMatch) -> usize {
+        let mut count = 0usize;
+        match mode {
+            TagMatch::Any => { for t in tags { count += self.invalidate_members(t); } }
+            TagMatch::Subtree => {
+                let mut below: Vec<Tag> = tags.iter().flat_map(|t| self.tag_subtree(t)).collect();
+                below.sort();
+                below.dedup(); // Nested roots (tenant:42 and tenant:42:product:7) share tags
+                for t in &below { count += self.invalidate_members(t); }
+            }
+            TagMatch::All => {
+                if let Some(first) = tags.first() {
+                    let mut keys_to_invalidate = Vec::new();
+                    // First pass: collect keys that have all tags (avoid holding read locks during invalidation)
+                    for k in self.get_keys_by_tag(first) {
+                        let shard = &self.shards[self.hash_key(&k)];
+                        if let Some(entry) = shard.entries.get(&k) {
+                            if tags.iter().all(|t| entry.tags.contains(t)) { keys_to_invalidate.push(k.clone()); }
+                        }
+                        // Read lock is automatically released here
+                    }
+                    // Second pass: invalidate collected keys
+                    for k in keys_to_invalidate { if self.invalidate_key(&k) { count += 1; } }
+                }
+            }
+        }
+        let event = match mode {
+            TagMatch::Any => WebhookEvent::InvalidateTagsAny,
+            TagMatch::All => WebhookEvent::InvalidateTagsAll,
+            TagMatch::Subtree => WebhookEvent::InvalidateTagsSubtree,
+        };
+        self.notify_invalidation(event, tags, count);
+        count
+    }
+
+    // Invalidate the keys of `tag` the way its mode says, without publishing anything.
+    fn invalidate_members(&self, tag: &Tag) -> usize {
+        match self.tag_versions.mode_of(tag) {
+            InvalidationMode::Eager => self.remove_tag_members(tag),
+            InvalidationMode::Versioned => self.bump_tag(tag),
+        }
+    }
+
+    // Versioned invalidation: one counter bump kills every member, and each shard hands the tag's key
+    // set over to reclamation as a whole. Returns the number of keys the tag had.
+    fn bump_tag(&self, tag: &Tag) -> usize {
+        self.tag_versions.bump(tag);
+        let mut count = 0;
+        for shard in &self.shards {
+            let Some(keys) = shard.detach(tag) else { continue };
+            count += keys.len();
+            shard.reclaim.lock().detached.push((tag.clone(), keys));
+        }
+        self.stats.lock().invalidations += count as u64;
+        count
+    }
+
+    /// Background half of versioned invalidation: remove entries killed by a generation bump, checking
+    /// at most `max_per_shard` keys per shard so a pass has a bounded pause. Returns the number removed.
+    pub fn reclaim_invalidated(&self, max_per_shard: usize) -> usize {
+        let mut count = 0;
+        let mut swept = false;
+        for shard in &self.shards {
+            let mut budget = max_per_shard;
+            while budget > 0 {
+                let Some((tag, keys)) = shard.next_reclaim(budget) else { break };
+                budget -= keys.len();
+                swept = true;
+                for key in keys {
+                    if let Some((_, entry)) = shard.entries.remove_if(&key, |_, e| e.is_stale()) {
+                        self.count_remove(&key, &entry);
+                        self.namespaces.track(&key.0, |c| { c.invalidations.fetch_add(1, Relaxed); });
+                        shard.reindex(&key, &entry.tags, &[]);
+                        count += 1;
+                    } else if let Some(entry) = shard.entries.get(&key) {
+                        // Written with the new generation while the set was detached: put it back.
+                        if !entry.is_stale() && entry.tags.contains(&tag) { shard.link(&tag, &key); }
+                    }
+                }
+            }
+        }
+        if swept && self.pending_reclaims() == 0 { self.tag_versions.prune(); }
+        self.stats.lock().reclaimed += count as u64;
+        count
+    }
+
+    /// Keys waiting for `reclaim_invalidated`.
+    pub fn pending_reclaims(&self) -> usize {
+        self.shards.iter().map(|s| s.pending_reclaims()).sum()
+    }
+
+    // Remove every key linked to `tag` without publishing anything; callers notify once per operation.
+    fn remove_tag_members(&self, tag: &Tag) -> usize {
+        let mut count = 0;
+        for shard in &self.shards {                          // Scan all shards
+            // Snapshot so the set isn't borrowed while the index is updated
+            for key in shard.members(tag) {                       // Remove each key
+                match shard.entries.remove(&key) {
+                    Some((_, entry)) => {
+                        self.count_remove(&key, &entry);
+                        self.namespaces.track(&key.0, |c| { c.invalidations.fetch_add(1, Relaxed); });
+                        shard.reindex(&key, &entry.tags, &[]); // Also unlinks the key from its other tags
+                        count += 1;
+                    }
+                    None => shard.unlink(tag, &key),
+                }
+            }
+        }
+        self.stats.lock().invalidations += count as u64;      // Record count
+        count
+    }
+
+    /// Incremental expiration: pop due records from each shard's expiry heap and remove those entries.
+    /// At most `max_per_shard` records are examined per shard, so a single pass has a bounded pause
+    /// regardless of how many keys the cache holds. Returns the number of entries removed.
+    pub fn expire_due(&self, max_per_shard: usize) -> usize {
+        let now = Instant::now();
+        let mut count = 0;
+        let mut max_lag = Duration::ZERO;
+        for shard in &self.shards {
+            shard.compact_expiry();
+            for _ in 0..max_per_shard {
+                // Pop one due record; the heap lock is released before touching the entries map.
+                let (deadline, key) = {
+                    let mut heap = shard.expiry.lock();
+                    match heap.peek() {
+                        Some(Reverse((deadline, _))) if *deadline <= now => heap.pop().unwrap().0,
+                        _ => break,
+                    }
+                };
+                // Only remove if the live entry is still expired (it may have been re-put with a new TTL).
+                let removed = shard.entries.remove_if(&key, |_, e| e.deadline().is_some_and(|d| d <= now));
+                if let Some((_, entry)) = removed {
+                    self.count_remove(&key, &entry);
+                    if !entry.is_stale() { self.emit(EventKind::Expire, &key, &entry.tags); }
+                    shard.reindex(&key, &entry.tags, &[]);
+                    max_lag = max_lag.max(now.saturating_duration_since(deadline));
+                    count += 1;
+                }
+            }
+        }
+        let mut stats = self.stats.lock();
+        stats.expired += count as u64;
+        stats.expiry_lag_ms = max_lag.as_millis() as u64;
+        count
+    }
+
+    /// Number of records waiting in the expiration index (includes stale records not yet popped).
+    pub fn pending_expirations(&self) -> usize {
+        self.shards.iter().map(|s| s.expiry.lock().len()).sum()
+    }
+
+    /// Full sweep: remove all expired entries. Walks every entry, so prefer expire_due() on hot paths;
+    /// kept for tests and manual maintenance.
+    pub fn cleanup_expired(&self) -> usize {
+        let mut count = 0;
+        for shard in &self.shards {                 // Visit each shard
+            let mut to_remove = Vec::new();         // Collect keys to remove (avoid holding ref across mutation)
+            for entry in shard.entries.iter() {     // Iterate all entries in shard (read guards)
+                if entry.value().is_expired() {     // Check expiration
+                    to_remove.push(entry.key().clone());
+                }
+            }
+            for key in to_remove {                  // Remove expired ones
+                if let Some((_, entry)) = shard.entries.remove(&key) {
+                    self.count_remove(&key, &entry);
+                    if !entry.is_stale() { self.emit(EventKind::Expire, &key, &entry.tags); }
+                    shard.reindex(&key, &entry.tags, &[]); // Clean reverse mappings
+                    count += 1;
+                }
+            }
+        }
+        self.stats.lock().expired += count as u64;
+        count
+    }
+
+    /// Snapshot statistics (cheap clone of small struct).
+    pub fn get_stats(&self) -> CacheStats {
+        self.stats.lock().clone()
+    }
+
+    pub fn flush_all(&self) -> usize { // Remove ALL entries and tag indexes; return number removed
+        let mut total = 0;
+        for shard in &self.shards {
+            total += shard.entries.len();
+            shard.entries.clear();
+            shard.tag_to_keys.clear();
+            shard.tag_tree.lock().clear();
+            shard.expiry.lock().clear();
+            *shard.reclaim.lock() = Reclaim::default();
+        }
+        self.namespaces.clear_usage();
+        self.stats.lock().invalidations += total as u64;
+        self.notify_invalidation(WebhookEvent::FlushAll, &[], total);
+        total
+    }
+
+    /// Remove every key of a namespace (all keys for the default namespace, like `flush_all`).
+    pub fn flush_namespace(&self, ns: &Namespace) -> usize {
+        if ns.is_default() { return self.flush_all(); }
+        let mut keys = Vec::new();
+        for shard in &self.shards {
+            keys.extend(shard.entries.iter().filter(|e| ns.strip(&e.key().0).is_some()).map(|e| e.key().clone()));
+        }
+        let count = keys.iter().filter(|k| self.invalidate_key(k)).count();
+        // Subscribers already saw a delete per key; webhook receivers get one notice for the namespace.
+        if let Some(hooks) = &self.webhooks {
+            hooks.notify(WebhookEvent::FlushNamespace, ns.name().map(str::to_string), Vec::new(), count);
+        }
+        count
+    }
+}
@@ -0,0 +1,1179 @@
+// =============================
+// COMMAND LINE INTERFACE
+// =============================
+// `tagcache` with no arguments (or `tagcache server`) runs the server; every other subcommand is a
+// small client (built on the `tagcache-client` crate) talking to a running instance.
+
+use anyhow::Context;
+use clap::{Parser, Subcommand}; // Command line argument parsing
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use tokio::task::JoinHandle;
+use tagcache_client::{Client, Command, Config as ClientConfig, CounterOptions, Error as ClientError, ImportMode, Mode, Reply, TagMode, TransferFilter};
+
+use crate::config::{set_config_value, TagCacheConfig};
+use crate::{auth, server, shell};
+
+// =============================
+// CLI COMMAND DEFINITIONS
+// =============================
+#[derive(Parser)]
+#[command(name = "tagcache")]
+#[command(about = "TagCache - Lightweight, sharded, tag-aware in-memory cache server")]
+#[command(version = env!("CARGO_PKG_VERSION"))]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Server host (default: localhost)
+    #[arg(long, default_value = "localhost")]
+    host: String,
+
+    /// Server port (default: 8080)
+    #[arg(long, short = 'p', default_value = "8080")]
+    port: u16,
+
+    /// Authentication username
+    #[arg(long, short = 'u')]
+    username: Option<String>,
+
+    /// Authentication password  
+    #[arg(long)]
+    password: Option<String>,
+
+    /// Authentication token
+    #[arg(long, short = 't')]
+    token: Option<String>,
+
+    /// Protocol for cache commands: http, tcp, or auto (TCP with HTTP fallback). Admin commands
+    /// (health, passwords, promote) always use HTTP.
+    #[arg(long, default_value = "http", value_parser = parse_protocol)]
+    protocol: Mode,
+
+    /// TCP protocol port (default: 1984)
+    #[arg(long, default_value = "1984")]
+    tcp_port: u16,
+}
+
+fn parse_import_mode(s: &str) -> Result<ImportMode, String> {
+    ImportMode::parse(s).ok_or_else(|| format!("unknown mode '{}' (expected merge or replace)", s))
+}
+
+fn parse_protocol(s: &str) -> Result<Mode, String> {
+    Mode::parse(s).ok_or_else(|| format!("unknown protocol '{}' (expected http, tcp or auto)", s))
+}
+
+/// Float mode, bounds and reset window of `increment` / `decrement`.
+#[derive(clap::Args, Default)]
+struct CounterArgs {
+    /// Keep a floating-point counter even for whole amounts
+    #[arg(long)]
+    float: bool,
+    /// Lower bound (results below are clamped, or refused with --reject)
+    #[arg(long, allow_hyphen_values = true)]
+    min: Option<f64>,
+    /// Upper bound (results above are clamped, or refused with --reject)
+    #[arg(long, allow_hyphen_values = true)]
+    max: Option<f64>,
+    /// Refuse results outside --min / --max instead of clamping them
+    #[arg(long)]
+    reject: bool,
+    /// Reset the counter at each boundary: minute, hour, day or a length like 15m
+    #[arg(long)]
+    window: Option<String>,
+}
+
+impl CounterArgs {
+    fn options(&self) -> CounterOptions {
+        CounterOptions { float: self.float, min: self.min, max: self.max, reject: self.reject, window: self.window.clone() }
+    }
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Start the TagCache server
+    Server,
+    
+    /// Store a key-value pair with optional tags
+    Put {
+        /// The cache key
+        key: String,
+        /// The value to store
+        value: String,
+        /// Comma-separated list of tags
+        #[arg(long, short)]
+        tags: Option<String>,
+        /// TTL in milliseconds
+        #[arg(long)]
+        ttl_ms: Option<u64>,
+    },
+    
+    /// Atomically add a key-value pair (fails if key exists)
+    Add {
+        /// The cache key
+        key: String,
+        /// The value to store
+        value: String,
+        /// Comma-separated list of tags
+        #[arg(long, short)]
+        tags: Option<String>,
+        /// TTL in milliseconds
+        #[arg(long)]
+        ttl_ms: Option<u64>,
+    },
+    
+    /// Atomically increment a numeric value (creates if not exists)
+    Increment {
+        /// The cache key
+        key: String,
+        /// Amount to increment by (default: 1; a fraction makes it a float counter)
+        #[arg(long, short, default_value = "1", allow_hyphen_values = true)]
+        by: f64,
+        /// Comma-separated list of tags
+        #[arg(long, short)]
+        tags: Option<String>,
+        /// TTL in milliseconds
+        #[arg(long)]
+        ttl_ms: Option<u64>,
+        #[command(flatten)]
+        counter: CounterArgs,
+    },
+    
+    /// Atomically decrement a numeric value (creates if not exists)
+    Decrement {
+        /// The cache key
+        key: String,
+        /// Amount to decrement by (default: 1; a fraction makes it a float counter)
+        #[arg(long, short, default_value = "1", allow_hyphen_values = true)]
+        by: f64,
+        /// Comma-separated list of tags
+        #[arg(long, short)]
+        tags: Option<String>,
+        /// TTL in milliseconds
+        #[arg(long)]
+        ttl_ms: Option<u64>,
+        #[command(flatten)]
+        counter: CounterArgs,
+    },
+    
+    /// Get operations
+    Get {
+        #[command(subcommand)]
+        get_command: GetCommands,
+    },
+    
+    /// Flush operations  
+    Flush {
+        #[command(subcommand)]
+        flush_command: FlushCommands,
+    },
+    
+    /// Tag administration: list, inspect, rename, merge and bulk-tag
+    Tags {
+        #[command(subcommand)]
+        tags_command: TagsCommands,
+    },
+
+    /// Show server statistics
+    Stats,
+    
+    /// Show server status/health
+    Status,
+    
+    /// Show server health check
+    Health,
+    
+    /// Restart server (if running as service)
+    Restart,
+    
+    /// Change password
+    ChangePassword {
+        /// New password
+        new_password: String,
+    },
+    
+    /// Reset to default credentials (admin/password)
+    ResetCredentials,
+
+    /// Print an argon2 hash of a password, for `password` fields in tagcache.conf
+    HashPassword {
+        /// The password (default: read one line from stdin, keeping it out of the shell history)
+        password: Option<String>,
+    },
+
+    /// Promote a follower to leader (stops replicating, starts accepting writes)
+    Promote,
+
+    /// Interactive shell (history, tab completion, multi-line JSON values, timing)
+    Shell,
+
+    /// Stream commands from a file or stdin, pipelined, printing one result per command and a summary
+    ///
+    /// Each line is either a TCP protocol command (tab-separated, e.g. "PUT\tkey\t-\ttag1,tag2\tvalue")
+    /// or a JSON object (e.g. {"op":"put","key":"k","value":"v","tags":["t"],"ttl_ms":1000}).
+    /// Blank lines and lines starting with '#' are skipped.
+    Pipe {
+        /// Input file (default: stdin)
+        file: Option<PathBuf>,
+        /// Commands per pipeline round trip
+        #[arg(long, default_value = "1000")]
+        batch: usize,
+        /// Pipelines in flight at once. Above 1, commands in different batches may be applied out of
+        /// input order (e.g. a trailing INV_TAG overtaking earlier PUTs)
+        #[arg(long, default_value = "1")]
+        depth: usize,
+        /// Only print failed commands and the summary
+        #[arg(long, short)]
+        quiet: bool,
+    },
+
+    /// Dump entries as JSON Lines (key, value, tags, remaining TTL, creation time)
+    Export {
+        /// Output file (default: stdout)
+        #[arg(long, short)]
+        file: Option<PathBuf>,
+        /// Only keys starting with this prefix
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Only keys whose tags match this expression, e.g. "users&!banned" or "(a|b)&c"
+        #[arg(long, short)]
+        tags: Option<String>,
+    },
+
+    /// Load a JSON Lines dump (as written by `export`) and report lines that failed
+    Import {
+        /// Input file (default: stdin)
+        file: Option<PathBuf>,
+        /// merge: upsert the imported keys; replace: also delete matching keys missing from the dump
+        #[arg(long, default_value = "merge", value_parser = parse_import_mode)]
+        mode: ImportMode,
+        /// Only import keys starting with this prefix (and, with --mode replace, only replace those)
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Only import keys whose tags match this expression
+        #[arg(long, short)]
+        tags: Option<String>,
+    },
+    
+    /// Configuration management
+    Config {
+        #[command(subcommand)]
+        config_command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Show current configuration
+    Show {
+        /// Configuration file path (default: auto-detect)
+        #[arg(long, short)]
+        config: Option<String>,
+    },
+    /// Set a configuration value
+    Set {
+        /// Configuration key (e.g., "authentication.password")
+        key: String,
+        /// Configuration value
+        value: String,
+        /// Configuration file path (default: auto-detect)
+        #[arg(long, short)]
+        config: Option<String>,
+    },
+    /// Store a hashed password for the bootstrap account or an [[authentication.users]] entry
+    SetPassword {
+        /// Account name
+        username: String,
+        /// The new password (default: read one line from stdin)
+        password: Option<String>,
+        /// Configuration file path (default: auto-detect)
+        #[arg(long, short)]
+        config: Option<String>,
+    },
+    /// Reset configuration to defaults
+    Reset {
+        /// Configuration file path (default: auto-detect)
+        #[arg(long, short)]
+        config: Option<String>,
+    },
+    /// Show configuration file path
+    Path,
+}
+
+#[derive(Subcommand)]
+enum GetCommands {
+    /// Get value by key
+    Key { key: String },
+    /// Get keys by tags
+    Tag { tags: String },
+}
+
+#[derive(Subcommand)]
+enum TagsCommands {
+    /// List tags with their key count and memory footprint
+    List {
+        /// Sort by name, keys or bytes
+        #[arg(long, default_value = "name")]
+        sort: String,
+        /// asc or desc (default: desc when sorting by keys or bytes)
+        #[arg(long)]
+        order: Option<String>,
+        /// Only tags starting with this prefix
+        #[arg(long)]
+        prefix: Option<String>,
+        #[arg(long, default_value = "100")]
+        limit: usize,
+        #[arg(long, default_value = "0")]
+        offset: usize,
+    },
+    /// Show one tag's key count and memory footprint
+    Show { tag: String },
+    /// Add comma-separated tags to one key (value, TTL and age are kept)
+    Attach { key: String, tags: String },
+    /// Remove comma-separated tags from one key (value, TTL and age are kept)
+    Detach { key: String, tags: String },
+    /// Rename a tag on every key carrying it
+    Rename { from: String, to: String },
+    /// Fold tags into another one (comma-separated sources)
+    Merge { into: String, from: String },
+    /// Add a tag to every key matching a prefix and/or tag expression
+    Add {
+        tag: String,
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Tag expression, e.g. "users&!banned"
+        #[arg(long, short)]
+        tags: Option<String>,
+    },
+    /// Remove a tag from every key matching a prefix and/or tag expression
+    Remove {
+        tag: String,
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Tag expression, e.g. "users&!banned"
+        #[arg(long, short)]
+        tags: Option<String>,
+    },
+    /// Make every key carrying a tag expire by a deadline (keys due earlier are left alone)
+    Expire {
+        tag: String,
+        /// Milliseconds from now
+        #[arg(long = "in-ms", conflicts_with = "at_ms", required_unless_present = "at_ms")]
+        in_ms: Option<u64>,
+        /// UNIX time in milliseconds
+        #[arg(long = "at-ms")]
+        at_ms: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum FlushCommands {
+    /// Flush specific key
+    Key { key: String },
+    /// Flush by tags
+    Tag { tags: String },
+    /// Flush all entries
+    All,
+}
+
+// Client settings from the global flags; the HTTP and TCP endpoints share `--host`.
+fn client_config(cli: &Cli) -> ClientConfig {
+    ClientConfig {
+        mode: cli.protocol,
+        http_url: format!("http://{}:{}", cli.host, cli.port),
+        tcp_addr: format!("{}:{}", cli.host, cli.tcp_port),
+        username: cli.username.clone(),
+        password: cli.password.clone(),
+        token: cli.token.clone(),
+        max_retries: 0, // Interactive use: report failures immediately
+        ..ClientConfig::default()
+    }
+}
+
+// =============================
+// CLI CLIENT IMPLEMENTATION
+// =============================
+// Thin presentation layer over the `tagcache-client` crate: typed cache commands go through
+// `Client::execute`, admin endpoints through its raw HTTP helpers.
+struct TagCacheClient {
+    client: Client,
+}
+
+impl TagCacheClient {
+    fn new(config: ClientConfig) -> anyhow::Result<Self> {
+        Ok(Self { client: Client::new(config)? })
+    }
+
+    async fn put(&self, key: &str, value: &str, tags: Option<&str>, ttl_ms: Option<u64>) -> anyhow::Result<()> {
+        let cmd = Command::Put { key: key.into(), value: value.into(), tags: split_list(tags), ttl: ttl_ms.map(Duration::from_millis) };
+        if let Err(e) = self.client.execute(&cmd).await {
+            anyhow::bail!("Failed to store key: {}", e);
+        }
+        println!("✓ Successfully stored key '{}' with value '{}'", key, value);
+        print_tags_ttl(tags, ttl_ms);
+        Ok(())
+    }
+
+    async fn add(&self, key: &str, value: &str, tags: Option<&str>, ttl_ms: Option<u64>) -> anyhow::Result<()> {
+        let cmd = Command::Add { key: key.into(), value: value.into(), tags: split_list(tags), ttl: ttl_ms.map(Duration::from_millis) };
+        match self.client.execute(&cmd).await {
+            Ok(Reply::Added(true)) => {
+                println!("✓ Successfully added key '{}' with value '{}'", key, value);
+                print_tags_ttl(tags, ttl_ms);
+            }
+            Ok(_) => println!("✗ Key '{}' already exists - add operation failed", key),
+            Err(e) => anyhow::bail!("Failed to add key: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn increment(&self, key: &str, by: f64, counter: &CounterArgs, tags: Option<&str>, ttl_ms: Option<u64>) -> anyhow::Result<()> {
+        let cmd = counter_command(key, by, counter, tags, ttl_ms, false);
+        match self.client.execute(&cmd).await {
+            Ok(value) => {
+                println!("✓ Successfully incremented key '{}' by {} to {}", key, by, value);
+                print_tags_ttl(tags, ttl_ms);
+            }
+            Err(ClientError::Server(error)) => println!("✗ Increment failed: {}", error),
+            Err(e) => anyhow::bail!("Failed to increment key: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn decrement(&self, key: &str, by: f64, counter: &CounterArgs, tags: Option<&str>, ttl_ms: Option<u64>) -> anyhow::Result<()> {
+        let cmd = counter_command(key, by, counter, tags, ttl_ms, true);
+        match self.client.execute(&cmd).await {
+            Ok(value) => {
+                println!("✓ Successfully decremented key '{}' by {} to {}", key, by, value);
+                print_tags_ttl(tags, ttl_ms);
+            }
+            Err(ClientError::Server(error)) => println!("✗ Decrement failed: {}", error),
+            Err(e) => anyhow::bail!("Failed to decrement key: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn get_key(&self, key: &str) -> anyhow::Result<()> {
+        match self.client.get(key).await {
+            Ok(Some(value)) => {
+                println!("Key: {}", key);
+                println!("Value: {}", value);
+            }
+            Ok(None) => println!("Key '{}' not found", key),
+            Err(e) => anyhow::bail!("Failed to get key: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn get_keys_by_tag(&self, tags: &str) -> anyhow::Result<()> {
+        for tag in split_list(Some(tags)) {
+            match self.client.keys_by_tag(&tag).await {
+                Ok(keys) if keys.is_empty() => println!("Tag '{}' has no keys", tag),
+                Ok(keys) => {
+                    println!("Tag '{}' contains {} keys:", tag, keys.len());
+                    for key in keys {
+                        println!("  - {}", key);
+                    }
+                }
+                Err(e) => println!("Failed to get keys for tag '{}': {}", tag, e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush_key(&self, key: &str) -> anyhow::Result<()> {
+        match self.client.del(key).await {
+            Ok(true) => println!("✓ Successfully flushed key '{}'", key),
+            Ok(false) => println!("Key '{}' was not found", key),
+            Err(e) => anyhow::bail!("Failed to flush key: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn flush_tags(&self, tags: &str) -> anyhow::Result<()> {
+        let cmd = Command::InvalidateTags { tags: split_list(Some(tags)), mode: TagMode::Any };
+        match self.client.execute(&cmd).await {
+            Ok(count) => println!("✓ Successfully flushed {} entries with tags: {}", count, tags),
+            Err(e) => anyhow::bail!("Failed to flush tags: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn flush_all(&self) -> anyhow::Result<()> {
+        match self.client.flush().await {
+            Ok(count) => println!("✓ Successfully flushed all {} entries from cache", count),
+            Err(e) => anyhow::bail!("Failed to flush cache: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn list_tags(&self, sort: &str, order: Option<&str>, prefix: Option<&str>, limit: usize, offset: usize) -> anyhow::Result<()> {
+        let mut path = format!("/tags?sort={}&limit={}&offset={}", encode(sort), limit, offset);
+        if let Some(order) = order { path.push_str(&format!("&order={}", encode(order))); }
+        if let Some(prefix) = prefix { path.push_str(&format!("&prefix={}", encode(prefix))); }
+        let json = self.client.http_get(&path).await.map_err(|e| anyhow::anyhow!("Failed to list tags: {}", e))?;
+        let tags = json.get("tags").and_then(|t| t.as_array()).cloned().unwrap_or_default();
+        println!("{:<40} {:>10} {:>12}", "TAG", "KEYS", "BYTES");
+        for t in &tags {
+            println!("{:<40} {:>10} {:>12}", t["tag"].as_str().unwrap_or(""), t["keys"], t["bytes"]);
+        }
+        println!("{} of {} tag(s)", tags.len(), json.get("total").and_then(|t| t.as_u64()).unwrap_or(0));
+        Ok(())
+    }
+
+    async fn show_tag(&self, tag: &str) -> anyhow::Result<()> {
+        match self.client.http_get(&format!("/tags/{}", encode(tag))).await {
+            Ok(json) if json.get("error").is_some() => println!("Tag '{}' not found", tag),
+            Ok(json) => println!("Tag '{}': {} keys, {} bytes", tag, json["keys"], json["bytes"]),
+            Err(e) => anyhow::bail!("Failed to get tag: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn edit_key_tags(&self, key: &str, tags: &str, add: bool) -> anyhow::Result<()> {
+        let (key, tags) = (key.to_string(), split_list(Some(tags)));
+        let cmd = if add { Command::AddTags { key: key.clone(), tags } } else { Command::RemoveTags { key: key.clone(), tags } };
+        match self.client.execute(&cmd).await {
+            Ok(Reply::Tagged(Some(true))) => println!("✓ Updated the tags of key '{}'", key),
+            Ok(Reply::Tagged(Some(false))) => println!("Tags of key '{}' were already up to date", key),
+            Ok(_) => println!("Key '{}' not found", key),
+            Err(e) => anyhow::bail!("Failed to update tags: {}", e),
+        }
+        Ok(())
+    }
+
+    // POSTs a tag admin action and reports how many keys it changed.
+    async fn tag_action(&self, path: String, body: serde_json::Value, done: &str) -> anyhow::Result<()> {
+        match self.client.http_post(&path, Some(body)).await {
+            Ok(json) => println!("✓ {} ({} keys changed)", done, json.get("count").and_then(|c| c.as_u64()).unwrap_or(0)),
+            Err(e) => anyhow::bail!("Failed: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn stats(&self) -> anyhow::Result<()> {
+        if self.client.config().mode == Mode::Tcp {
+            // TCP-only setups (HTTP port firewalled) get the STATS counters.
+            let stats = match self.client.stats().await {
+                Ok(stats) => stats,
+                Err(e) => anyhow::bail!("Failed to get stats: {}", e),
+            };
+            println!("TagCache Statistics:");
+            println!("==================");
+            println!("Hits: {}", stats.hits);
+            println!("Misses: {}", stats.misses);
+            println!("Puts: {}", stats.puts);
+            println!("Invalidations: {}", stats.invalidations);
+            println!("Hit Ratio: {:.2}%", stats.hit_ratio * 100.0);
+            return Ok(());
+        }
+
+        // The full /stats payload (items, bytes, replication, ...) rather than the TCP STATS counters.
+        let json = match self.client.http_get("/stats").await {
+            Ok(json) => json,
+            Err(e) => anyhow::bail!("Failed to get stats: {}", e),
+        };
+
+        println!("TagCache Statistics:");
+        println!("==================");
+        if let Some(hits) = json.get("hits") {
+            println!("Hits: {}", hits);
+        }
+        if let Some(misses) = json.get("misses") {
+            println!("Misses: {}", misses);
+        }
+        if let Some(puts) = json.get("puts") {
+            println!("Puts: {}", puts);
+        }
+        if let Some(invalidations) = json.get("invalidations") {
+            println!("Invalidations: {}", invalidations);
+        }
+        if let Some(expired) = json.get("expired") {
+            println!("Expired: {}", expired);
+        }
+        if let Some(hit_ratio) = json.get("hit_ratio") {
+            println!("Hit Ratio: {:.2}%", hit_ratio.as_f64().unwrap_or(0.0) * 100.0);
+        }
+        if let Some(items) = json.get("items") {
+            println!("Total Items: {}", items);
+        }
+        if let Some(bytes) = json.get("bytes") {
+            println!("Total Bytes: {}", bytes);
+        }
+        if let Some(tags) = json.get("tags") {
+            println!("Total Tags: {}", tags);
+        }
+        if let Some(shards) = json.get("shard_count") {
+            println!("Shards: {}", shards);
+        }
+        if let Some(repl) = json.get("replication") {
+            let role = repl.get("role").and_then(|r| r.as_str()).unwrap_or("leader");
+            println!("Role: {}", role);
+            if role == "follower" {
+                println!("Leader: {}", repl.get("leader").and_then(|l| l.as_str()).unwrap_or("-"));
+                println!("Connected: {}", repl.get("connected").and_then(|c| c.as_bool()).unwrap_or(false));
+                match repl.get("lag_ms").and_then(|l| l.as_u64()) {
+                    Some(lag) => println!("Replication Lag: {}ms", lag),
+                    None => println!("Replication Lag: unknown"),
+                }
+            } else if let Some(followers) = repl.get("followers") {
+                println!("Followers: {}", followers);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn health(&self) -> anyhow::Result<()> {
+        match self.client.http_get("/health").await {
+            Ok(json) => {
+                println!("Health Check: ✓ OK");
+                if let Some(time) = json.get("time") {
+                    println!("Server Time: {}", time);
+                }
+            }
+            Err(e) => anyhow::bail!("Health check failed: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn status(&self) -> anyhow::Result<()> {
+        let base_url = &self.client.config().http_url;
+        println!("TagCache Server Status:");
+        println!("=====================");
+        
+        // Check HTTP endpoint
+        match self.client.http_get("/health").await {
+            Ok(_) => println!("HTTP Server: ✓ Running on {}", base_url),
+            Err(_) => println!("HTTP Server: ✗ Not responding on {}", base_url),
+        }
+
+        // Check TCP endpoint when it carries the cache commands
+        if self.client.config().mode != Mode::Http {
+            let tcp_addr = &self.client.config().tcp_addr;
+            // TCP only, so Auto mode's HTTP fallback cannot mask a dead TCP port
+            let tcp = Client::new(ClientConfig { mode: Mode::Tcp, ..self.client.config().clone() })?;
+            match tcp.execute(&Command::Stats).await {
+                Ok(_) => println!("TCP Server: ✓ Running on {}", tcp_addr),
+                Err(_) => println!("TCP Server: ✗ Not responding on {}", tcp_addr),
+            }
+        }
+        
+        // Try to get stats for more detailed status
+        match self.stats().await {
+            Ok(_) => {},
+            Err(_) => {
+                println!("Note: Could not retrieve detailed statistics (authentication may be required)");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn restart(&self) -> anyhow::Result<()> {
+        println!("Restart functionality depends on your deployment method:");
+        println!("  • Homebrew service: brew services restart tagcache");
+        println!("  • Systemd: sudo systemctl restart tagcache");
+        println!("  • Docker: docker restart <container_name>");
+        println!("  • Process manager: depends on your setup");
+        Ok(())
+    }
+
+    async fn change_password(&self, new_password: &str) -> anyhow::Result<()> {
+        let body = serde_json::json!({ "new_password": new_password });
+        match self.client.http_post("/auth/change_password", Some(body)).await {
+            Ok(json) if json.get("success").and_then(|s| s.as_bool()).unwrap_or(false) => {
+                println!("✓ Password changed successfully");
+                println!("The new password is: {}", new_password);
+            }
+            Ok(_) => anyhow::bail!("Failed to change password"),
+            Err(e) => anyhow::bail!("Failed to change password: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn export(&self, file: Option<&Path>, filter: TransferFilter) -> anyhow::Result<()> {
+        let records = match file {
+            Some(path) => {
+                let mut out = tokio::fs::File::create(path).await.with_context(|| format!("Failed to create {}", path.display()))?;
+                self.client.export(&filter, &mut out).await
+            }
+            None => self.client.export(&filter, &mut tokio::io::stdout()).await,
+        }.map_err(|e| anyhow::anyhow!("Failed to export: {}", e))?;
+        eprintln!("✓ Exported {} record(s)", records);
+        Ok(())
+    }
+
+    async fn import(&self, file: Option<&Path>, mode: ImportMode, filter: TransferFilter) -> anyhow::Result<()> {
+        let report = match file {
+            Some(path) => {
+                let input = tokio::fs::File::open(path).await.with_context(|| format!("Failed to open {}", path.display()))?;
+                self.client.import(input, mode, &filter).await
+            }
+            None => self.client.import(tokio::io::stdin(), mode, &filter).await,
+        }.map_err(|e| anyhow::anyhow!("Failed to import: {}", e))?;
+        for e in &report.errors { eprintln!("line {}: {}", e.line, e.error); }
+        if report.errors_truncated { eprintln!("... more errors not shown"); }
+        println!("Imported: {}  Skipped: {}  Failed: {}  Removed: {}", report.imported, report.skipped, report.failed, report.removed);
+        if report.failed > 0 { anyhow::bail!("{} line(s) failed to import", report.failed); }
+        println!("✓ Import complete ({})", mode.as_str());
+        Ok(())
+    }
+
+    async fn promote(&self) -> anyhow::Result<()> {
+        match self.client.http_post("/replication/promote", None).await {
+            Ok(json) => match json.get("previous_role").and_then(|r| r.as_str()) {
+                Some("follower") => println!("✓ Promoted to leader; replication stopped and writes are accepted"),
+                _ => println!("Server is already a leader"),
+            },
+            Err(e) => anyhow::bail!("Failed to promote: {}", e),
+        }
+        Ok(())
+    }
+
+    async fn reset_credentials(&self) -> anyhow::Result<()> {
+        match self.client.http_post("/auth/reset", None).await {
+            Ok(json) if json.get("success").and_then(|s| s.as_bool()).unwrap_or(false) => {
+                println!("✓ Credentials reset to defaults");
+                println!("Username: admin");
+                println!("Password: password");
+            }
+            Ok(_) => anyhow::bail!("Failed to reset credentials"),
+            Err(e) => anyhow::bail!("Failed to reset credentials: {}", e),
+        }
+        Ok(())
+    }
+
+    // Read commands line by line and send them in pipelines of `batch` commands, keeping up to
+    // `depth` pipelines in flight (with 1, the server applies commands strictly in input order).
+    // Results are printed in input order as "<line>\t<VERB>\t<result>".
+    async fn pipe(&self, file: Option<&Path>, batch: usize, depth: usize, quiet: bool) -> anyhow::Result<()> {
+        let input: Box<dyn AsyncBufRead + Unpin> = match file {
+            Some(path) => Box::new(BufReader::new(
+                tokio::fs::File::open(path).await.with_context(|| format!("Failed to open {}", path.display()))?,
+            )),
+            None => Box::new(BufReader::new(tokio::io::stdin())),
+        };
+        let (batch, depth) = (batch.max(1), depth.max(1));
+        let started = Instant::now();
+        let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+        let mut summary = PipeSummary::default();
+        let mut in_flight: VecDeque<SentBatch> = VecDeque::new();
+        let mut current = PipeBatch::default();
+        let mut aborted = None;
+
+        let mut lines = input.lines();
+        let mut line_no = 0;
+        while let Some(line) = lines.next_line().await? {
+            line_no += 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') { continue; }
+            match parse_pipe_line(&line) {
+                Ok(cmd) => { current.items.push((line_no, cmd.verb(), None)); current.commands.push(cmd); }
+                Err(e) => current.items.push((line_no, "-", Some(e))),
+            }
+            if current.items.len() >= batch {
+                in_flight.push_back(std::mem::take(&mut current).send(&self.client));
+                if in_flight.len() >= depth {
+                    let done = in_flight.pop_front().expect("non-empty");
+                    if let Err(e) = done.report(&mut out, &mut summary, quiet).await {
+                        aborted = Some(e);
+                        break;
+                    }
+                }
+            }
+        }
+        if aborted.is_none() && !current.items.is_empty() {
+            in_flight.push_back(current.send(&self.client));
+        }
+        // Drain what was already sent (even after a failure: those commands may have been applied).
+        while let Some(done) = in_flight.pop_front() {
+            if let Err(e) = done.report(&mut out, &mut summary, quiet).await {
+                aborted.get_or_insert(e);
+            }
+        }
+        out.flush()?;
+
+        let elapsed = started.elapsed().as_secs_f64();
+        let sent = summary.ok + summary.failed;
+        eprintln!(
+            "Pipe summary: {} commands in {:.2}s ({:.0} ops/sec): {} ok, {} failed, {} invalid lines",
+            sent, elapsed, sent as f64 / elapsed.max(1e-9), summary.ok, summary.failed, summary.invalid
+        );
+        if let Some(e) = aborted {
+            anyhow::bail!("Pipe aborted: {}", e);
+        }
+        if summary.failed + summary.invalid > 0 {
+            anyhow::bail!("{} commands failed, {} lines invalid", summary.failed, summary.invalid);
+        }
+        Ok(())
+    }
+}
+
+// "a, b,c" -> ["a", "b", "c"] (None -> no items)
+fn split_list(list: Option<&str>) -> Vec<String> {
+    list.map(|l| l.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()).unwrap_or_default()
+}
+
+// Percent-encodes a tag (or query value) for a URL.
+fn encode(s: &str) -> String {
+    percent_encoding::utf8_percent_encode(s, percent_encoding::NON_ALPHANUMERIC).to_string()
+}
+
+// Plain INCR / DECR for whole amounts without options, a counter command otherwise.
+fn counter_command(key: &str, by: f64, counter: &CounterArgs, tags: Option<&str>, ttl_ms: Option<u64>, decrement: bool) -> Command {
+    let (key, tags, ttl, options) = (key.to_string(), split_list(tags), ttl_ms.map(Duration::from_millis), counter.options());
+    match by {
+        by if by.fract() == 0.0 && options == CounterOptions::default() => {
+            let by = by as i64;
+            if decrement { Command::Decr { key, by, tags, ttl } } else { Command::Incr { key, by, tags, ttl } }
+        }
+        by => Command::Counter { key, by: if decrement { -by } else { by }, options, tags, ttl },
+    }
+}
+
+fn print_tags_ttl(tags: Option<&str>, ttl_ms: Option<u64>) {
+    if let Some(tags) = tags {
+        println!("  Tags: {}", tags);
+    }
+    if let Some(ttl) = ttl_ms {
+        println!("  TTL: {}ms", ttl);
+    }
+}
+
+// =============================
+// PIPE INPUT AND BATCHES
+// =============================
+// `tagcache pipe` accepts two line formats, detected per line: the TCP protocol itself, or one JSON
+// object per line ({"op": "put", ...}) for scripts that would rather not deal with tab escaping.
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+enum PipeOp {
+    Put { key: String, value: String, #[serde(default)] tags: Vec<String>, ttl_ms: Option<u64> },
+    Add { key: String, value: String, #[serde(default)] tags: Vec<String>, ttl_ms: Option<u64> },
+    Incr { key: String, #[serde(default = "one")] by: i64, #[serde(default)] tags: Vec<String>, ttl_ms: Option<u64> },
+    Decr { key: String, #[serde(default = "one")] by: i64, #[serde(default)] tags: Vec<String>, ttl_ms: Option<u64> },
+    Get { key: String },
+    Del { key: String },
+    InvalidateTag { tag: String },
+    InvalidateTags { tags: Vec<String>, #[serde(default)] mode: PipeTagMode },
+    InvalidateKeys { keys: Vec<String> },
+    AddTags { key: String, tags: Vec<String> },
+    RemoveTags { key: String, tags: Vec<String> },
+    KeysByTag { tag: String },
+    Stats,
+    Flush,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum PipeTagMode {
+    #[default]
+    Any,
+    All,
+}
+
+fn one() -> i64 { 1 }
+
+fn parse_pipe_line(line: &str) -> Result<Command, String> {
+    if !line.trim_start().starts_with('{') {
+        return Command::from_tcp_line(line).map_err(|e| e.to_string());
+    }
+    let ttl = |ms: Option<u64>| ms.map(Duration::from_millis);
+    Ok(match serde_json::from_str::<PipeOp>(line).map_err(|e| format!("invalid JSON command: {}", e))? {
+        PipeOp::Put { key, value, tags, ttl_ms } => Command::Put { key, value, tags, ttl: ttl(ttl_ms) },
+        PipeOp::Add { key, value, tags, ttl_ms } => Command::Add { key, value, tags, ttl: ttl(ttl_ms) },
+        PipeOp::Incr { key, by, tags, ttl_ms } => Command::Incr { key, by, tags, ttl: ttl(ttl_ms) },
+        PipeOp::Decr { key, by, tags, ttl_ms } => Command::Decr { key, by, tags, ttl: ttl(ttl_ms) },
+        PipeOp::Get { key } => Command::Get { key },
+        PipeOp::Del { key } => Command::Del { key },
+        PipeOp::InvalidateTag { tag } => Command::InvalidateTag { tag },
+        PipeOp::InvalidateTags { tags, mode: PipeTagMode::Any } => Command::InvalidateTags { tags, mode: TagMode::Any },
+        PipeOp::InvalidateTags { tags, mode: PipeTagMode::All } => Command::InvalidateTags { tags, mode: TagMode::All },
+        PipeOp::InvalidateKeys { keys } => Command::InvalidateKeys { keys },
+        PipeOp::AddTags { key, tags } => Command::AddTags { key, tags },
+        PipeOp::RemoveTags { key, tags } => Command::RemoveTags { key, tags },
+        PipeOp::KeysByTag { tag } => Command::KeysByTag { tag },
+        PipeOp::Stats => Command::Stats,
+        PipeOp::Flush => Command::Flush,
+    })
+}
+
+#[derive(Default)]
+struct PipeSummary {
+    ok: usize,
+    failed: usize,
+    invalid: usize,
+}
+
+// One pipeline worth of input: every non-blank line in order (line number, verb, parse error), and
+// the commands that parsed.
+#[derive(Default)]
+struct PipeBatch {
+    items: Vec<(usize, &'static str, Option<String>)>,
+    commands: Vec<Command>,
+}
+
+struct SentBatch {
+    items: Vec<(usize, &'static str, Option<String>)>,
+    replies: JoinHandle<Result<Vec<Result<Reply, ClientError>>, ClientError>>,
+}
+
+impl PipeBatch {
+    fn send(self, client: &Client) -> SentBatch {
+        let mut pipeline = client.pipeline();
+        for cmd in self.commands { pipeline.push(cmd); }
+        SentBatch { items: self.items, replies: tokio::spawn(pipeline.execute()) }
+    }
+}
+
+impl SentBatch {
+    // Print this batch's results; Err when the pipeline as a whole failed (connection lost).
+    async fn report(self, out: &mut impl Write, summary: &mut PipeSummary, quiet: bool) -> anyhow::Result<()> {
+        let replies = match self.replies.await? {
+            Ok(replies) => replies,
+            Err(e) => {
+                summary.failed += self.items.iter().filter(|(_, _, invalid)| invalid.is_none()).count();
+                summary.invalid += self.items.iter().filter(|(_, _, invalid)| invalid.is_some()).count();
+                return Err(e.into());
+            }
+        };
+        let mut replies = replies.into_iter();
+        for (line_no, verb, invalid) in self.items {
+            match invalid {
+                Some(e) => { summary.invalid += 1; writeln!(out, "{}\t-\tERR {}", line_no, e)?; }
+                None => match replies.next().expect("one reply per command") {
+                    Ok(reply) => { summary.ok += 1; if !quiet { writeln!(out, "{}\t{}\t{}", line_no, verb, reply)?; } }
+                    Err(e) => { summary.failed += 1; writeln!(out, "{}\t{}\tERR {}", line_no, verb, e)?; }
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+// =============================
+// CONFIG COMMAND HANDLERS
+// =============================
+async fn handle_config_command(config_command: ConfigCommands) -> anyhow::Result<()> {
+    match config_command {
+        ConfigCommands::Show { config } => {
+            let config_path = config.map(PathBuf::from)
+                .unwrap_or_else(TagCacheConfig::default_config_path);
+            
+            match TagCacheConfig::load_from_file(&config_path) {
+                Ok(cfg) => {
+                    println!("Configuration from: {}", config_path.display());
+                    println!("{}", toml::to_string_pretty(&cfg)?);
+                }
+                Err(e) => {
+                    println!("Error loading configuration: {}", e);
+                    return Err(e);
+                }
+            }
+            Ok(())
+        }
+        
+        ConfigCommands::Set { key, value, config } => {
+            let config_path = config.map(PathBuf::from)
+                .unwrap_or_else(TagCacheConfig::default_config_path);
+            
+            let mut cfg = TagCacheConfig::load_from_file(&config_path)?;
+            
+            // Parse the key and update the config
+            match set_config_value(&mut cfg, &key, &value) {
+                Ok(_) => {
+                    cfg.save_to_file(&config_path)?;
+                    println!("✓ Configuration updated: {} = {}", key, value);
+                    println!("Configuration saved to: {}", config_path.display());
+                    println!("Restart the server for changes to take effect.");
+                }
+                Err(e) => {
+                    println!("Error setting configuration: {}", e);
+                    return Err(e);
+                }
+            }
+            Ok(())
+        }
+        
+        ConfigCommands::SetPassword { username, password, config } => {
+            let config_path = config.map(PathBuf::from)
+                .unwrap_or_else(TagCacheConfig::default_config_path);
+            let hash = auth::hash_password(&read_password(password)?);
+
+            let mut cfg = TagCacheConfig::load_from_file(&config_path)?;
+            if cfg.authentication.username == username {
+                cfg.authentication.password = hash;
+            } else if let Some(user) = cfg.authentication.users.iter_mut().find(|u| u.username == username) {
+                user.password = hash;
+            } else {
+                anyhow::bail!("No account named '{}' in {}", username, config_path.display());
+            }
+            cfg.save_to_file(&config_path)?;
+            println!("✓ Password hash stored for '{}'", username);
+            println!("Restart the server for changes to take effect.");
+            Ok(())
+        }
+
+        ConfigCommands::Reset { config } => {
+            let config_path = config.map(PathBuf::from)
+                .unwrap_or_else(TagCacheConfig::default_config_path);
+            
+            let default_cfg = TagCacheConfig::default();
+            default_cfg.save_to_file(&config_path)?;
+            println!("✓ Configuration reset to defaults");
+            println!("Configuration saved to: {}", config_path.display());
+            println!("Restart the server for changes to take effect.");
+            Ok(())
+        }
+        
+        ConfigCommands::Path => {
+            let config_path = TagCacheConfig::default_config_path();
+            println!("Configuration file path: {}", config_path.display());
+            if config_path.exists() {
+                println!("Status: EXISTS");
+            } else {
+                println!("Status: NOT FOUND (will be created with defaults on first use)");
+            }
+            Ok(())
+        }
+    }
+}
+
+// =============================
+// ENTRY POINT
+// =============================
+// Parse the process arguments and run the selected command (called from the `tagcache` binary).
+pub async fn run() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let client_config = client_config(&cli);
+    
+    match cli.command {
+        Some(Commands::Server) | None => {
+            // Start server mode (default behavior)
+            start_server().await
+        }
+        Some(Commands::Shell) => {
+            // The shell blocks on terminal input, so it runs off the async worker threads.
+            tokio::task::spawn_blocking(move || shell::run(client_config)).await?
+        }
+        Some(cmd) => {
+            // Handle CLI commands
+            let client = TagCacheClient::new(client_config)?;
+            
+            match cmd {
+                Commands::Put { key, value, tags, ttl_ms } => {
+                    client.put(&key, &value, tags.as_deref(), ttl_ms).await
+                }
+                Commands::Add { key, value, tags, ttl_ms } => {
+                    client.add(&key, &value, tags.as_deref(), ttl_ms).await
+                }
+                Commands::Increment { key, by, tags, ttl_ms, counter } => {
+                    client.increment(&key, by, &counter, tags.as_deref(), ttl_ms).await
+                }
+                Commands::Decrement { key, by, tags, ttl_ms, counter } => {
+                    client.decrement(&key, by, &counter, tags.as_deref(), ttl_ms).await
+                }
+                Commands::Get { get_command } => {
+                    match get_command {
+                        GetCommands::Key { key } => client.get_key(&key).await,
+                        GetCommands::Tag { tags } => client.get_keys_by_tag(&tags).await,
+                    }
+                }
+                Commands::Flush { flush_command } => {
+                    match flush_command {
+                        FlushCommands::Key { key } => client.flush_key(&key).await,
+                        FlushCommands::Tag { tags } => client.flush_tags(&tags).await,
+                        FlushCommands::All => client.flush_all().await,
+                    }
+                }
+                Commands::Tags { tags_command } => match tags_command {
+                    TagsCommands::List { sort, order, prefix, limit, offset } => {
+                        client.list_tags(&sort, order.as_deref(), prefix.as_deref(), limit, offset).await
+                    }
+                    TagsCommands::Show { tag } => client.show_tag(&tag).await,
+                    TagsCommands::Attach { key, tags } => client.edit_key_tags(&key, &tags, true).await,
+                    TagsCommands::Detach { key, tags } => client.edit_key_tags(&key, &tags, false).await,
+                    TagsCommands::Rename { from, to } => {
+                        let done = format!("Renamed tag '{}' to '{}'", from, to);
+                        client.tag_action(format!("/tags/{}/rename", encode(&from)), serde_json::json!({ "to": to }), &done).await
+                    }
+                    TagsCommands::Merge { into, from } => {
+                        let done = format!("Merged {} into '{}'", from, into);
+                        client.tag_action(format!("/tags/{}/merge", encode(&into)), serde_json::json!({ "from": split_list(Some(&from)) }), &done).await
+                    }
+                    TagsCommands::Add { tag, prefix, tags } => {
+                        let done = format!("Added tag '{}'", tag);
+                        client.tag_action(format!("/tags/{}/add", encode(&tag)), serde_json::json!({ "prefix": prefix, "tags": tags }), &done).await
+                    }
+                    TagsCommands::Remove { tag, prefix, tags } => {
+                        let done = format!("Removed tag '{}'", tag);
+                        client.tag_action(format!("/tags/{}/remove", encode(&tag)), serde_json::json!({ "prefix": prefix, "tags": tags }), &done).await
+                    }
+                    TagsCommands::Expire { tag, in_ms, at_ms } => {
+                        let done = format!("Set a deadline on tag '{}'", tag);
+                        client.tag_action(format!("/tags/{}/expire", encode(&tag)), serde_json::json!({ "in_ms": in_ms, "at_ms": at_ms }), &done).await
+                    }
+                },
+                Commands::Stats => client.stats().await,
+                Commands::Status => client.status().await,
+                Commands::Health => client.health().await,
+                Commands::Restart => client.restart().await,
+                Commands::ChangePassword { new_password } => client.change_password(&new_password).await,
+                Commands::ResetCredentials => client.reset_credentials().await,
+                Commands::HashPassword { password } => {
+                    println!("{}", auth::hash_password(&read_password(password)?));
+                    Ok(())
+                }
+                Commands::Promote => client.promote().await,
+                Commands::Pipe { file, batch, depth, quiet } => {
+                    client.pipe(file.as_deref(), batch, depth, quiet).await
+                }
+                Commands::Export { file, prefix, tags } => {
+                    client.export(file.as_deref(), TransferFilter { prefix, tags }).await
+                }
+                Commands::Import { file, mode, prefix, tags } => {
+                    client.import(file.as_deref(), mode, TransferFilter { prefix, tags }).await
+                }
+                Commands::Config { config_command } => {
+                    handle_config_command(config_command).await
+                }
+                Commands::Server | Commands::Shell => unreachable!(), // Already handled above
+            }
+        }
+    }
+}
+
+// A password from the command line, or one line of stdin when omitted.
+fn read_password(arg: Option<String>) -> anyhow::Result<String> {
+    let password = match arg {
+        Some(p) => p,
+        None => {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if password.is_empty() { anyhow::bail!("Password must not be empty"); }
+    Ok(password)
+}
+
+async fn start_server() -> anyhow::Result<()> {
+    // Initialize tracing (logging). Reads RUST_LOG or default filter.
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    // Load configuration from file
+    let config_path = TagCacheConfig::default_config_path();
+    let config = TagCacheConfig::load_from_file(&config_path)?;
+    
+    println!("TagCache Server starting...");
+    println!("Configuration loaded from: {}", config_path.display());
+
+    server::serve(config, config_path).await
+}
@@ -0,0 +1,466 @@
+// =============================
+// CLUSTER MODE (CONSISTENT HASHING)
+// =============================
+// Several TagCache nodes can share one keyspace. Every node is configured with the same member list
+// and places each member on a hash ring at `virtual_nodes` points; a key belongs to the first point
+// clockwise from its hash. The ring hash is a fixed function (FNV-1a + a 64-bit finalizer), unlike the
+// random-seeded in-process shard hasher, so every node computes the same owner for a key.
+//
+// Routing (only for requests that did not come from another node):
+// * single-key requests for a key owned elsewhere are forwarded to the owner (mode = "forward") or
+//   answered with a redirect to it (mode = "redirect"; TCP always answers `MOVED`);
+// * tag operations, flushes and multi-key requests are run on every node and the results merged.
+// Everything else (stats, search, events, replication) is node-local.
+
+use axum::body::{to_bytes, Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json as ResponseJson, Response};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::warn;
+
+use crate::auth;
+use crate::http::AppState;
+use crate::namespace::{self, NAMESPACE_HEADER};
+
+// Marks a request as already routed by a peer; the receiver serves it locally.
+pub const FORWARDED_HEADER: &str = "x-tagcache-forwarded";
+// TCP equivalent: `LOCAL\t<command line>` runs the command on this node only.
+pub const TCP_LOCAL_PREFIX: &str = "LOCAL\t";
+// Same limit axum applies to JSON bodies.
+const BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ClusterNode {
+    pub id: String,
+    pub http: String, // host:port of the HTTP API
+    pub tcp: String,  // host:port of the TCP protocol
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutingMode {
+    #[default]
+    Forward,  // Proxy requests for remote keys to their owner
+    Redirect, // Reply 307 with the owner's URL and let the client retry there
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClusterConfig {
+    pub enabled: bool,
+    pub node_id: String,         // Which entry of `nodes` this server is
+    pub mode: RoutingMode,
+    pub virtual_nodes: usize,    // Ring points per node (more = smoother distribution)
+    pub timeout_ms: u64,         // Per peer request
+    pub nodes: Vec<ClusterNode>,
+    pub auth: Option<String>,    // `user:password` or API key sent as TCP AUTH to peers that require it
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            node_id: String::new(),
+            mode: RoutingMode::Forward,
+            virtual_nodes: 128,
+            timeout_ms: 2_000,
+            nodes: Vec::new(),
+            auth: None,
+        }
+    }
+}
+
+// Deterministic 64-bit hash used for ring placement (identical on every node and platform).
+pub fn ring_hash(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325; // FNV-1a offset basis
+    for b in bytes {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    // splitmix64 finalizer: FNV alone clusters similar inputs like "node-1#7" / "node-1#8"
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    points: Vec<(u64, usize)>, // (position, node index), sorted by position
+}
+
+impl HashRing {
+    pub fn new(node_ids: &[&str], virtual_nodes: usize) -> Self {
+        let mut points = Vec::with_capacity(node_ids.len() * virtual_nodes.max(1));
+        for (idx, id) in node_ids.iter().enumerate() {
+            for v in 0..virtual_nodes.max(1) {
+                points.push((ring_hash(format!("{}#{}", id, v).as_bytes()), idx));
+            }
+        }
+        points.sort_unstable();
+        Self { points }
+    }
+
+    // Index of the node owning `key`; None for an empty ring.
+    pub fn owner(&self, key: &str) -> Option<usize> {
+        if self.points.is_empty() { return None; }
+        let h = ring_hash(key.as_bytes());
+        let i = self.points.partition_point(|(p, _)| *p < h);
+        Some(self.points[i % self.points.len()].1) // Past the last point wraps to the first
+    }
+}
+
+#[derive(Debug)]
+pub struct Cluster {
+    nodes: Vec<ClusterNode>,
+    local: usize,
+    ring: HashRing,
+    mode: RoutingMode,
+    timeout: Duration,
+    client: reqwest::Client,
+    tcp_auth: Option<String>, // AUTH line sent before fanned-out TCP commands
+}
+
+impl Cluster {
+    pub fn new(config: &ClusterConfig) -> Result<Arc<Self>, String> {
+        if config.nodes.is_empty() { return Err("cluster.nodes is empty".into()); }
+        for (i, n) in config.nodes.iter().enumerate() {
+            if config.nodes[..i].iter().any(|m| m.id == n.id) { return Err(format!("duplicate cluster node id {}", n.id)); }
+        }
+        let local = config.nodes.iter().position(|n| n.id == config.node_id)
+            .ok_or_else(|| format!("cluster.node_id {:?} is not listed in cluster.nodes", config.node_id))?;
+        let ids: Vec<&str> = config.nodes.iter().map(|n| n.id.as_str()).collect();
+        let timeout = Duration::from_millis(config.timeout_ms.max(1));
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none()) // Peers never redirect forwarded requests
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Arc::new(Self {
+            nodes: config.nodes.clone(),
+            local,
+            ring: HashRing::new(&ids, config.virtual_nodes),
+            mode: config.mode,
+            timeout,
+            client,
+            tcp_auth: config.auth.as_deref().filter(|a| !a.is_empty()).map(auth::tcp_auth_line),
+        }))
+    }
+
+    pub fn local_node(&self) -> &ClusterNode {
+        &self.nodes[self.local]
+    }
+
+    // Placement ignores the namespace, so stored (namespaced) and client-facing keys agree.
+    pub fn owner(&self, key: &str) -> &ClusterNode {
+        &self.nodes[self.ring.owner(namespace::base_key(key)).unwrap_or(self.local)]
+    }
+
+    pub fn is_local(&self, key: &str) -> bool {
+        self.ring.owner(namespace::base_key(key)).is_none_or(|i| i == self.local)
+    }
+
+    fn peers(&self) -> impl Iterator<Item = &ClusterNode> {
+        self.nodes.iter().enumerate().filter(move |(i, _)| *i != self.local).map(|(_, n)| n)
+    }
+
+    // GET /cluster body; with `key`, also reports which node owns it.
+    pub fn status(&self, key: Option<&str>) -> serde_json::Value {
+        let nodes: Vec<serde_json::Value> = self.nodes.iter().enumerate()
+            .map(|(i, n)| serde_json::json!({"id": n.id, "http": n.http, "tcp": n.tcp, "local": i == self.local}))
+            .collect();
+        let mut out = serde_json::json!({"node_id": self.local_node().id, "mode": self.mode, "nodes": nodes});
+        if let Some(key) = key {
+            out["key"] = serde_json::json!(key);
+            out["owner"] = serde_json::json!(self.owner(key).id);
+        }
+        out
+    }
+
+    // -------- HTTP --------
+
+    async fn forward(&self, node: &ClusterNode, req: &RequestHead, body: Bytes) -> Result<(StatusCode, Option<HeaderValue>, Bytes), String> {
+        let url = format!("http://{}{}", node.http, req.path_and_query);
+        let mut rb = self.client.request(req.method.clone(), url)
+            .header(FORWARDED_HEADER, self.local_node().id.as_str())
+            .body(body);
+        if let Some(auth) = &req.authorization { rb = rb.header(header::AUTHORIZATION, auth); }
+        if let Some(ct) = &req.content_type { rb = rb.header(header::CONTENT_TYPE, ct); }
+        if let Some(ns) = &req.namespace { rb = rb.header(NAMESPACE_HEADER, ns); }
+        let resp = rb.send().await.map_err(|e| e.to_string())?;
+        let status = resp.status();
+        let content_type = resp.headers().get(header::CONTENT_TYPE).cloned();
+        let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+        Ok((status, content_type, bytes))
+    }
+
+    async fn send_to_owner(&self, key: &str, head: RequestHead, body: Bytes) -> Response {
+        let owner = self.owner(key);
+        if self.mode == RoutingMode::Redirect {
+            let location = format!("http://{}{}", owner.http, head.path_and_query);
+            let body = serde_json::json!({"error": "moved", "key": key, "node": owner.id, "location": location});
+            return (StatusCode::TEMPORARY_REDIRECT, [(header::LOCATION, location)], ResponseJson(body)).into_response();
+        }
+        match self.forward(owner, &head, body).await {
+            Ok((status, content_type, bytes)) => {
+                let mut resp = Response::new(Body::from(bytes));
+                *resp.status_mut() = status;
+                if let Some(ct) = content_type { resp.headers_mut().insert(header::CONTENT_TYPE, ct); }
+                resp
+            }
+            Err(e) => {
+                warn!("Cluster: forwarding to {} failed: {}", owner.id, e);
+                let body = serde_json::json!({"ok": false, "error": "node_unreachable", "node": owner.id});
+                (StatusCode::BAD_GATEWAY, ResponseJson(body)).into_response()
+            }
+        }
+    }
+
+    // Run the request locally and on every peer, then merge the JSON bodies.
+    async fn fan_out(&self, head: RequestHead, body: Bytes, local: impl std::future::Future<Output = Response>) -> Response {
+        let peers = futures_util::future::join_all(self.peers().map(|n| {
+            let (head, body) = (&head, body.clone());
+            async move { (n, self.forward(n, head, body).await) }
+        }));
+        let (local, peers) = tokio::join!(local, peers);
+        if !local.status().is_success() { return local; }
+        let mut merged: serde_json::Value = match to_bytes(local.into_body(), usize::MAX).await.ok().and_then(|b| serde_json::from_slice(&b).ok()) {
+            Some(v) => v,
+            None => return (StatusCode::INTERNAL_SERVER_ERROR, ResponseJson(serde_json::json!({"error": "invalid_local_response"}))).into_response(),
+        };
+        let mut errors = Vec::new();
+        for (node, result) in peers {
+            match result {
+                Ok((status, _, bytes)) if status.is_success() => match serde_json::from_slice(&bytes) {
+                    Ok(v) => merge_json(&mut merged, v),
+                    Err(e) => errors.push(serde_json::json!({"node": node.id, "error": e.to_string()})),
+                },
+                Ok((status, _, bytes)) => errors.push(serde_json::json!({"node": node.id, "error": format!("HTTP {}: {}", status, String::from_utf8_lossy(&bytes))})),
+                Err(e) => errors.push(serde_json::json!({"node": node.id, "error": e})),
+            }
+        }
+        // keys-by-tag applies its limit per node; re-apply it to the merged list.
+        if let Some(limit) = head.limit {
+            if let Some(keys) = merged.get_mut("keys").and_then(|k| k.as_array_mut()) { keys.truncate(limit); }
+        }
+        if errors.is_empty() { return ResponseJson(merged).into_response(); }
+        merged["errors"] = serde_json::Value::Array(errors);
+        (StatusCode::BAD_GATEWAY, ResponseJson(merged)).into_response()
+    }
+
+    // -------- TCP --------
+
+    // Decide where a TCP command runs; `key` is the command's first argument.
+    pub fn route_tcp(&self, cmd: &str, key: Option<&str>) -> TcpRoute {
+        match cmd {
+            c if matches!(c, "PUT" | "ADD" | "INCR" | "DECR" | "RATELIMIT" | "LOCK" | "RENEW" | "UNLOCK" | "TAG_ADD" | "TAG_DEL" | "GET" | "DEL") || crate::tcp::COLLECTION_COMMANDS.contains(&c) => match key {
+                Some(k) if !self.is_local(k) => {
+                    let owner = self.owner(k);
+                    TcpRoute::Moved(format!("MOVED\t{}\t{}", owner.id, owner.tcp))
+                }
+                _ => TcpRoute::Local,
+            },
+            "INV_TAG" | "INV_TAGS_ANY" | "INV_TAGS_ALL" | "INV_TAGS_TREE" | "INV_KEYS" | "TAG_RENAME" | "TAG_MERGE" | "TAG_EXPIRE" | "RETAG" | "KEYS_BY_TAG" | "KEYS" | "FLUSH" => TcpRoute::FanOut,
+            _ => TcpRoute::Local,
+        }
+    }
+
+    // Send `line` to every peer with the LOCAL prefix and merge the replies into `local`
+    // (`<VERB>\t<n>` counts are summed, `KEYS\t<list>` lists are joined).
+    pub async fn fan_out_tcp(&self, line: &str, local: String) -> String {
+        if local.starts_with("ERR") { return local; }
+        let replies = futures_util::future::join_all(self.peers().map(|n| async move { (n, self.tcp_call(n, line).await) })).await;
+        let (verb, first) = local.split_once('\t').unwrap_or((local.as_str(), ""));
+        let mut count: Option<u64> = first.parse().ok();
+        let mut keys: Vec<String> = if verb == "KEYS" { first.split(',').filter(|k| !k.is_empty()).map(str::to_string).collect() } else { Vec::new() };
+        let mut failed = Vec::new();
+        for (node, reply) in replies {
+            match reply {
+                Ok(r) if !r.starts_with("ERR") => {
+                    let value = r.split_once('\t').map(|(_, v)| v).unwrap_or("");
+                    if verb == "KEYS" { keys.extend(value.split(',').filter(|k| !k.is_empty()).map(str::to_string)); }
+                    else if let (Some(c), Ok(n)) = (count.as_mut(), value.parse::<u64>()) { *c += n; }
+                }
+                Ok(r) => { warn!("Cluster: {} answered {}", node.id, r); failed.push(node.id.as_str()); }
+                Err(e) => { warn!("Cluster: {} unreachable: {}", node.id, e); failed.push(node.id.as_str()); }
+            }
+        }
+        if !failed.is_empty() { return format!("ERR node_unreachable {}", failed.join(",")); }
+        match (verb, count) {
+            ("KEYS", _) => format!("KEYS\t{}", keys.join(",")),
+            (verb, Some(c)) => format!("{}\t{}", verb, c),
+            _ => local,
+        }
+    }
+
+    async fn tcp_call(&self, node: &ClusterNode, line: &str) -> Result<String, String> {
+        let call = async {
+            let mut stream = TcpStream::connect(&node.tcp).await?;
+            let auth = self.tcp_auth.as_ref().map(|a| format!("{}\n", a)).unwrap_or_default();
+            stream.write_all(format!("{}{}{}\n", auth, TCP_LOCAL_PREFIX, line).as_bytes()).await?;
+            let mut reader = BufReader::new(stream);
+            let mut reply = String::new();
+            if !auth.is_empty() {
+                reader.read_line(&mut reply).await?;
+                if !reply.starts_with("OK") { return Ok(format!("ERR peer auth: {}", reply.trim_end())); }
+                reply.clear();
+            }
+            reader.read_line(&mut reply).await?;
+            Ok::<_, std::io::Error>(reply.trim_end().to_string())
+        };
+        match tokio::time::timeout(self.timeout, call).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err("timed out".into()),
+        }
+    }
+}
+
+pub enum TcpRoute {
+    Local,
+    Moved(String), // Reply to send instead of executing
+    FanOut,        // Execute locally, then merge with every peer
+}
+
+// The parts of a request needed to replay it on a peer.
+struct RequestHead {
+    method: Method,
+    path_and_query: String,
+    authorization: Option<HeaderValue>,
+    content_type: Option<HeaderValue>,
+    namespace: Option<HeaderValue>,
+    limit: Option<usize>, // `limit` query parameter (keys-by-tag)
+}
+
+impl RequestHead {
+    fn of(req: &Request) -> Self {
+        let uri = req.uri();
+        let limit = uri.query().and_then(|q| q.split('&').find_map(|p| p.strip_prefix("limit=")?.parse().ok()));
+        Self {
+            method: req.method().clone(),
+            path_and_query: uri.path_and_query().map(|p| p.as_str().to_string()).unwrap_or_else(|| uri.path().to_string()),
+            authorization: req.headers().get(header::AUTHORIZATION).cloned(),
+            content_type: req.headers().get(header::CONTENT_TYPE).cloned(),
+            namespace: req.headers().get(NAMESPACE_HEADER).cloned(),
+            limit,
+        }
+    }
+}
+
+enum HttpRoute {
+    Local,
+    PathKey(String), // /get/:key, /keys/:key[/...]
+    BodyKey,         // {"key": ...} in the JSON body
+    FanOut,
+}
+
+fn classify(method: &axum::http::Method, path: &str) -> HttpRoute {
+    let decode = |k: &str| percent_encoding::percent_decode_str(k).decode_utf8_lossy().into_owned();
+    if let Some(k) = path.strip_prefix("/get/") { return HttpRoute::PathKey(decode(k)); }
+    if let Some(k) = path.strip_prefix("/keys/") {
+        // /keys/:key and its /hash, /list, /set sub-resources
+        let k = k.split('/').next().unwrap_or("");
+        if !k.is_empty() && !path.starts_with("/keys/bulk/") { return HttpRoute::PathKey(decode(k)); }
+    }
+    match path {
+        "/put" | "/add" | "/incr" | "/decr" | "/invalidate-key" => HttpRoute::BodyKey,
+        // Tag rename / merge / add / remove / expire touch keys on every node
+        p if p.starts_with("/tags/") && ["/rename", "/merge", "/add", "/remove", "/expire"].iter().any(|op| p.ends_with(op)) => HttpRoute::FanOut,
+        // Every node applies tag TTL policies to the keys it owns
+        p if p.starts_with("/admin/tag-ttl/") => HttpRoute::FanOut,
+        // Every node runs a copy of a schedule against the keys it owns (same id, see `pin_schedule`)
+        p if p.starts_with("/schedules") && method != axum::http::Method::GET => HttpRoute::FanOut,
+        "/invalidate-tag" | "/invalidate/tags" | "/invalidate/keys" | "/keys-by-tag" | "/tags/children" | "/flush"
+        | "/keys/bulk/get" | "/keys/bulk/delete" => HttpRoute::FanOut,
+        _ => HttpRoute::Local,
+    }
+}
+
+// Fix a new schedule's id and due time before it is fanned out, so every node's copy shares the id
+// (one DELETE cancels them all) and runs at the same moment. Bodies that are not JSON objects pass
+// through for the handler to reject.
+fn pin_schedule(body: Bytes) -> Bytes {
+    let Ok(serde_json::Value::Object(mut fields)) = serde_json::from_slice(&body) else { return body };
+    if fields.get("id").and_then(|id| id.as_str()).is_none_or(str::is_empty) {
+        fields.insert("id".into(), crate::schedule::new_id().into());
+    }
+    if !fields.contains_key("at_ms") {
+        if let Some(delay) = fields.get("in_ms").and_then(|d| d.as_u64()) {
+            fields.remove("in_ms");
+            fields.insert("at_ms".into(), crate::schedule::now_ms().saturating_add(delay).into());
+        }
+    }
+    serde_json::to_vec(&fields).map(Bytes::from).unwrap_or(body)
+}
+
+// Sum counts, concatenate key / item lists, add up child tag counts, OR success flags.
+fn merge_json(acc: &mut serde_json::Value, other: serde_json::Value) {
+    let (Some(acc), serde_json::Value::Object(other)) = (acc.as_object_mut(), other) else { return };
+    for (field, value) in other {
+        match (field.as_str(), acc.get_mut(&field), value) {
+            ("count", Some(a), serde_json::Value::Number(n)) => {
+                *a = serde_json::json!(a.as_u64().unwrap_or(0) + n.as_u64().unwrap_or(0));
+            }
+            ("keys" | "items", Some(serde_json::Value::Array(a)), serde_json::Value::Array(b)) => a.extend(b),
+            ("children", Some(serde_json::Value::Array(a)), serde_json::Value::Array(b)) => merge_children(a, b),
+            ("success", Some(a), serde_json::Value::Bool(b)) => *a = serde_json::json!(a.as_bool().unwrap_or(false) || b),
+            _ => {}
+        }
+    }
+}
+
+// Tag children from several nodes: one item per tag, counts summed, in tag order.
+fn merge_children(acc: &mut Vec<serde_json::Value>, other: Vec<serde_json::Value>) {
+    let mut counts: std::collections::BTreeMap<String, u64> = std::collections::BTreeMap::new();
+    for child in acc.drain(..).chain(other) {
+        let Some(tag) = child["tag"].as_str() else { continue };
+        *counts.entry(tag.to_string()).or_default() += child["count"].as_u64().unwrap_or(0);
+    }
+    acc.extend(counts.into_iter().map(|(tag, count)| serde_json::json!({"tag": tag, "count": count})));
+}
+
+// Axum middleware applying the routing rules above (no-op when cluster mode is off).
+pub async fn route_request(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let Some(cluster) = state.cluster.clone() else { return next.run(req).await };
+    if req.headers().contains_key(FORWARDED_HEADER) { return next.run(req).await; }
+    match classify(req.method(), req.uri().path()) {
+        HttpRoute::Local => next.run(req).await,
+        HttpRoute::PathKey(key) if cluster.is_local(&key) => next.run(req).await,
+        HttpRoute::PathKey(key) => {
+            let head = RequestHead::of(&req);
+            match to_bytes(req.into_body(), BODY_LIMIT).await {
+                Ok(body) => cluster.send_to_owner(&key, head, body).await,
+                Err(_) => StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+            }
+        }
+        HttpRoute::BodyKey => {
+            let head = RequestHead::of(&req);
+            let (parts, body) = req.into_parts();
+            let Ok(body) = to_bytes(body, BODY_LIMIT).await else { return StatusCode::PAYLOAD_TOO_LARGE.into_response() };
+            let key = serde_json::from_slice::<serde_json::Value>(&body).ok()
+                .and_then(|v| v.get("key").and_then(|k| k.as_str()).map(str::to_string));
+            match key {
+                Some(key) if !cluster.is_local(&key) => cluster.send_to_owner(&key, head, body).await,
+                // Local key (or a malformed body the handler will reject): serve here.
+                _ => next.run(Request::from_parts(parts, Body::from(body))).await,
+            }
+        }
+        HttpRoute::FanOut => {
+            let head = RequestHead::of(&req);
+            let (mut parts, body) = req.into_parts();
+            let Ok(mut body) = to_bytes(body, BODY_LIMIT).await else { return StatusCode::PAYLOAD_TOO_LARGE.into_response() };
+            if head.method == Method::POST && head.path_and_query == "/schedules" {
+                body = pin_schedule(body);
+                parts.headers.remove(header::CONTENT_LENGTH);
+            }
+            let local = next.run(Request::from_parts(parts, Body::from(body.clone())));
+            cluster.fan_out(head, body, local).await
+        }
+    }
+}
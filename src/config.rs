@@ -0,0 +1,387 @@
+// =============================
+// CONFIGURATION MANAGEMENT
+// =============================
+// `tagcache.conf` (TOML) mapped onto plain structs. Every section has defaults so a missing file is
+// created on first start, and a handful of environment variables override the file.
+
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::audit::AuditConfig;
+use crate::auth::{self, ApiKeyConfig, UserConfig};
+use crate::cluster::{self, ClusterConfig};
+use crate::events;
+use crate::lockout::LockoutConfig;
+use crate::namespace::NamespacesConfig;
+use crate::replication::{ReplicationConfig, Role};
+use crate::schedule::SchedulesConfig;
+use crate::tag_ttl::TagTtlConfig;
+use crate::tag_version::InvalidationConfig;
+use crate::webhooks::WebhooksConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub http_port: u16,
+    pub tcp_port: u16,
+    pub num_shards: usize,
+    pub allowed_origin: Option<String>,
+    #[serde(default = "default_expiry_tick_ms")]
+    pub expiry_tick_ms: u64,          // How often the expiry reaper wakes up
+    #[serde(default = "default_expiry_batch_size")]
+    pub expiry_batch_size: usize,     // Max entries expired per shard per tick (bounds pause time)
+    #[serde(default = "default_events_buffer")]
+    pub events_buffer: usize,         // Events buffered per subscriber before it starts lagging
+}
+
+fn default_expiry_tick_ms() -> u64 { 100 }
+fn default_expiry_batch_size() -> usize { 1000 }
+fn default_events_buffer() -> usize { events::DEFAULT_EVENT_BUFFER }
+fn default_tcp_require_auth() -> bool { true }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthConfig {
+    pub username: String,             // Bootstrap account (always admin)
+    pub password: String,
+    pub token_lifetime_seconds: u64,
+    #[serde(default = "default_tcp_require_auth")]
+    pub tcp_require_auth: bool,       // TCP clients must AUTH first (otherwise unauthenticated TCP is read-only)
+    #[serde(default)]
+    pub users: Vec<UserConfig>,       // Further accounts with a role and optional scope
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,  // Static bearer keys with a role and optional scope
+    #[serde(default)]
+    pub lockout: LockoutConfig,       // Brute-force protection for password checks
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    pub default_ttl_seconds: u64,
+    pub max_tags_per_entry: usize,
+    pub max_key_length: usize,
+    pub max_value_length: usize,
+    #[serde(default = "default_tag_separator")]
+    pub tag_separator: String,        // Splits hierarchical tags (tenant:42:product:7) for subtree operations
+}
+
+fn default_tag_separator() -> String { crate::cache::DEFAULT_TAG_SEPARATOR.to_string() }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    pub level: String,
+    pub format: String,
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerformanceConfig {
+    pub tcp_nodelay: bool,
+    pub tcp_keepalive_seconds: u64,
+    pub max_connections: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    pub require_auth: bool,
+    pub rate_limit_per_minute: u64,
+    pub allowed_ips: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagCacheConfig {
+    pub server: ServerConfig,
+    pub authentication: AuthConfig,
+    pub cache: CacheConfig,
+    pub logging: LoggingConfig,
+    pub performance: PerformanceConfig,
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    #[serde(default)]
+    pub replication: ReplicationConfig,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub namespaces: NamespacesConfig,
+    #[serde(default)]
+    pub tag_ttl: TagTtlConfig,
+    #[serde(default)]
+    pub schedules: SchedulesConfig,
+    #[serde(default)]
+    pub invalidation: InvalidationConfig,
+}
+
+impl Default for TagCacheConfig {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig {
+                http_port: 8080,
+                tcp_port: 1984,
+                num_shards: 16,
+                allowed_origin: None,
+                expiry_tick_ms: default_expiry_tick_ms(),
+                expiry_batch_size: default_expiry_batch_size(),
+                events_buffer: default_events_buffer(),
+            },
+            authentication: AuthConfig {
+                username: "admin".to_string(),
+                password: "password".to_string(),
+                token_lifetime_seconds: 3600,
+                tcp_require_auth: default_tcp_require_auth(),
+                users: Vec::new(),
+                api_keys: Vec::new(),
+                lockout: LockoutConfig::default(),
+            },
+            cache: CacheConfig {
+                default_ttl_seconds: 0,
+                max_tags_per_entry: 100,
+                max_key_length: 1024,
+                max_value_length: 1048576,
+                tag_separator: default_tag_separator(),
+            },
+            logging: LoggingConfig {
+                level: "info".to_string(),
+                format: "pretty".to_string(),
+                file: None,
+            },
+            performance: PerformanceConfig {
+                tcp_nodelay: true,
+                tcp_keepalive_seconds: 7200,
+                max_connections: 10000,
+            },
+            security: SecurityConfig {
+                require_auth: true,
+                rate_limit_per_minute: 0,
+                allowed_ips: None,
+            },
+            webhooks: WebhooksConfig::default(),
+            replication: ReplicationConfig::default(),
+            cluster: ClusterConfig::default(),
+            audit: AuditConfig::default(),
+            namespaces: NamespacesConfig::default(),
+            tag_ttl: TagTtlConfig::default(),
+            schedules: SchedulesConfig::default(),
+            invalidation: InvalidationConfig::default(),
+        }
+    }
+}
+
+impl TagCacheConfig {
+    /// Get the default configuration file path
+    pub fn default_config_path() -> PathBuf {
+        // Try current directory first, then user config dir
+        let current_dir_config = PathBuf::from("tagcache.conf");
+        if current_dir_config.exists() {
+            return current_dir_config;
+        }
+        
+        // Check user config directory
+        if let Some(config_dir) = dirs::config_dir() {
+            let user_config = config_dir.join("tagcache").join("tagcache.conf");
+            if user_config.exists() {
+                return user_config;
+            }
+        }
+        
+        // Default to current directory
+        current_dir_config
+    }
+
+    /// Load configuration from file with fallback to defaults
+    pub fn load_from_file<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            println!("Configuration file {} not found, creating with defaults", path.display());
+            let default_config = Self::default();
+            default_config.save_to_file(path)?;
+            return Ok(default_config);
+        }
+
+        let content = fs::read_to_string(path)?;
+        let mut config: TagCacheConfig = toml::from_str(&content)?;
+        
+        // Apply environment variable overrides
+        config.apply_env_overrides();
+        
+        Ok(config)
+    }
+
+    /// Save configuration to file
+    pub fn save_to_file<P: AsRef<std::path::Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        
+        // Create parent directory if it doesn't exist
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let content = toml::to_string_pretty(self)?;
+        fs::write(path, content)?;
+        println!("Configuration saved to {}", path.display());
+        Ok(())
+    }
+
+    /// Apply environment variable overrides
+    fn apply_env_overrides(&mut self) {
+        // Server overrides
+        if let Ok(port) = env::var("PORT").or_else(|_| env::var("TC_HTTP_PORT")) {
+            if let Ok(p) = port.parse() {
+                self.server.http_port = p;
+            }
+        }
+        
+        if let Ok(tcp_port) = env::var("TCP_PORT").or_else(|_| env::var("TC_TCP_PORT")) {
+            if let Ok(p) = tcp_port.parse() {
+                self.server.tcp_port = p;
+            }
+        }
+        
+        if let Ok(shards) = env::var("NUM_SHARDS").or_else(|_| env::var("TC_NUM_SHARDS")) {
+            if let Ok(s) = shards.parse() {
+                self.server.num_shards = s;
+            }
+        }
+        
+        // Auth overrides
+        if let Ok(username) = env::var("TAGCACHE_USERNAME") {
+            self.authentication.username = username;
+        }
+        
+        if let Ok(password) = env::var("TAGCACHE_PASSWORD") {
+            self.authentication.password = password;
+        }
+        
+        // Other overrides
+        if let Ok(origin) = env::var("ALLOWED_ORIGIN") {
+            self.server.allowed_origin = Some(origin);
+        }
+
+        // Replication: TC_REPLICA_OF=host:port starts this instance as a follower of that leader
+        if let Ok(leader) = env::var("TC_REPLICA_OF") {
+            if !leader.is_empty() {
+                self.replication.role = Role::Follower;
+                self.replication.leader = Some(leader);
+            }
+        }
+
+        // Cluster: lets several local instances share one node list and differ only by id
+        if let Ok(node_id) = env::var("TC_NODE_ID") {
+            self.cluster.node_id = node_id;
+        }
+    }
+
+    /// Update authentication credentials and save to file
+    /// Rewrite plain-text passwords in the file at `path` as argon2 hashes (environment overrides
+    /// are not applied, so they never end up in the file). Returns how many were converted.
+    pub fn hash_stored_passwords<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<usize> {
+        let path = path.as_ref();
+        if !path.exists() { return Ok(0); }
+        let mut config: TagCacheConfig = toml::from_str(&fs::read_to_string(path)?)?;
+        let converted = auth::hash_plaintext_passwords(&mut config.authentication);
+        if converted > 0 { config.save_to_file(path)?; }
+        Ok(converted)
+    }
+
+    pub fn update_auth(&mut self, username: Option<String>, password: Option<String>, config_path: &std::path::Path) -> anyhow::Result<()> {
+        if let Some(u) = username {
+            self.authentication.username = u;
+        }
+        if let Some(p) = password {
+            self.authentication.password = p;
+        }
+        self.save_to_file(config_path)?;
+        Ok(())
+    }
+}
+
+// Update one `section.key` setting from its string form (used by `tagcache config set`).
+pub fn set_config_value(config: &mut TagCacheConfig, key: &str, value: &str) -> anyhow::Result<()> {
+    let parts: Vec<&str> = key.split('.').collect();
+    if parts.len() != 2 {
+        anyhow::bail!("Invalid config key format. Use section.key (e.g., authentication.password)");
+    }
+    
+    let section = parts[0];
+    let field = parts[1];
+    
+    match section {
+        "server" => match field {
+            "http_port" => config.server.http_port = value.parse()?,
+            "tcp_port" => config.server.tcp_port = value.parse()?,
+            "num_shards" => config.server.num_shards = value.parse()?,
+            "allowed_origin" => config.server.allowed_origin = if value.is_empty() { None } else { Some(value.to_string()) },
+            "expiry_tick_ms" => config.server.expiry_tick_ms = value.parse()?,
+            "expiry_batch_size" => config.server.expiry_batch_size = value.parse()?,
+            "events_buffer" => config.server.events_buffer = value.parse()?,
+            _ => anyhow::bail!("Unknown server field: {}", field),
+        },
+        "authentication" => match field {
+            "username" => config.authentication.username = value.to_string(),
+            "password" => config.authentication.password = auth::hash_password(value),
+            "token_lifetime_seconds" => config.authentication.token_lifetime_seconds = value.parse()?,
+            "tcp_require_auth" => config.authentication.tcp_require_auth = value.parse()?,
+            _ => anyhow::bail!("Unknown authentication field: {}", field),
+        },
+        "cache" => match field {
+            "default_ttl_seconds" => config.cache.default_ttl_seconds = value.parse()?,
+            "max_tags_per_entry" => config.cache.max_tags_per_entry = value.parse()?,
+            "max_key_length" => config.cache.max_key_length = value.parse()?,
+            "max_value_length" => config.cache.max_value_length = value.parse()?,
+            _ => anyhow::bail!("Unknown cache field: {}", field),
+        },
+        "logging" => match field {
+            "level" => config.logging.level = value.to_string(),
+            "format" => config.logging.format = value.to_string(),
+            "file" => config.logging.file = if value.is_empty() { None } else { Some(value.to_string()) },
+            _ => anyhow::bail!("Unknown logging field: {}", field),
+        },
+        "performance" => match field {
+            "tcp_nodelay" => config.performance.tcp_nodelay = value.parse()?,
+            "tcp_keepalive_seconds" => config.performance.tcp_keepalive_seconds = value.parse()?,
+            "max_connections" => config.performance.max_connections = value.parse()?,
+            _ => anyhow::bail!("Unknown performance field: {}", field),
+        },
+        "security" => match field {
+            "require_auth" => config.security.require_auth = value.parse()?,
+            "rate_limit_per_minute" => config.security.rate_limit_per_minute = value.parse()?,
+            "allowed_ips" => {
+                if value.is_empty() {
+                    config.security.allowed_ips = None;
+                } else {
+                    config.security.allowed_ips = Some(value.split(',').map(|s| s.trim().to_string()).collect());
+                }
+            },
+            _ => anyhow::bail!("Unknown security field: {}", field),
+        },
+        "webhooks" => match field {
+            "max_retries" => config.webhooks.max_retries = value.parse()?,
+            "initial_backoff_ms" => config.webhooks.initial_backoff_ms = value.parse()?,
+            "max_backoff_ms" => config.webhooks.max_backoff_ms = value.parse()?,
+            "timeout_ms" => config.webhooks.timeout_ms = value.parse()?,
+            "dead_letter_file" => config.webhooks.dead_letter_file = if value.is_empty() { None } else { Some(value.to_string()) },
+            _ => anyhow::bail!("Unknown webhooks field: {} (endpoints are edited in the config file)", field),
+        },
+        "replication" => match field {
+            "role" => config.replication.role = match value { "leader" => Role::Leader, "follower" => Role::Follower, _ => anyhow::bail!("role must be leader or follower") },
+            "leader" => config.replication.leader = if value.is_empty() { None } else { Some(value.to_string()) },
+            "allow_writes" => config.replication.allow_writes = value.parse()?,
+            "heartbeat_ms" => config.replication.heartbeat_ms = value.parse()?,
+            "reconnect_delay_ms" => config.replication.reconnect_delay_ms = value.parse()?,
+            _ => anyhow::bail!("Unknown replication field: {}", field),
+        },
+        "cluster" => match field {
+            "enabled" => config.cluster.enabled = value.parse()?,
+            "node_id" => config.cluster.node_id = value.to_string(),
+            "mode" => config.cluster.mode = match value { "forward" => cluster::RoutingMode::Forward, "redirect" => cluster::RoutingMode::Redirect, _ => anyhow::bail!("mode must be forward or redirect") },
+            "virtual_nodes" => config.cluster.virtual_nodes = value.parse()?,
+            "timeout_ms" => config.cluster.timeout_ms = value.parse()?,
+            _ => anyhow::bail!("Unknown cluster field: {} (nodes are edited in the config file)", field),
+        },
+        _ => anyhow::bail!("Unknown config section: {}", section),
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,199 @@
+// =============================
+// COUNTERS
+// =============================
+// Options for INCR / DECR beyond plain i64 arithmetic:
+//
+//   float              INCRBYFLOAT-style: the counter holds a floating-point number
+//   min=0,max=100      bounds; a result outside them is clamped (default) ...
+//   bound=reject       ... or refused with `out_of_range`, leaving the counter unchanged
+//   window=minute      reset at every minute / hour / day boundary (or `30s`, `15m`, `6h`, `2d`)
+//
+// Over TCP the options are one comma-separated field after the tags; over HTTP they are body
+// fields of /incr and /decr. A counter is integer until a float is involved: a fractional `by`
+// or the `float` option makes the result a float, stored as its shortest decimal text.
+//
+// Windows are aligned to the UNIX epoch (UTC) and implemented as a TTL that ends at the next
+// boundary, so a counter whose window has passed is simply gone and the next INCR starts from 0.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::value::ValueError;
+
+/// A counter value or amount.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum CounterValue {
+    Int(i64),
+    Float(f64),
+}
+
+impl CounterValue {
+    /// Parse a stored counter or an amount: integers stay integers, other numbers are floats.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Ok(n) = text.parse::<i64>() { return Some(CounterValue::Int(n)); }
+        text.parse::<f64>().ok().filter(|f| f.is_finite()).map(CounterValue::Float)
+    }
+
+    pub fn as_f64(self) -> f64 {
+        match self {
+            CounterValue::Int(n) => n as f64,
+            CounterValue::Float(f) => f,
+        }
+    }
+
+    pub fn negate(self) -> Result<Self, ValueError> {
+        match self {
+            CounterValue::Int(n) => n.checked_neg().map(CounterValue::Int).ok_or(ValueError::Overflow),
+            CounterValue::Float(f) => Ok(CounterValue::Float(-f)),
+        }
+    }
+}
+
+impl fmt::Display for CounterValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CounterValue::Int(n) => write!(f, "{}", n),
+            CounterValue::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl From<i64> for CounterValue {
+    fn from(n: i64) -> Self {
+        CounterValue::Int(n)
+    }
+}
+
+/// What happens to a result outside `min` / `max`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bound {
+    #[default]
+    Clamp,
+    Reject,
+}
+
+// Longest accepted window (a leap year), so the boundary arithmetic cannot overflow.
+const MAX_WINDOW_SECS: u64 = 366 * 86_400;
+
+/// A reset period, aligned to the UNIX epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Window(Duration);
+
+impl Window {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim().to_ascii_lowercase();
+        let secs = match s.as_str() {
+            "minute" => 60,
+            "hour" => 3600,
+            "day" => 86_400,
+            _ => {
+                let (n, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+                let n: u64 = n.parse().map_err(|_| format!("invalid window '{}'", s))?;
+                let unit = match unit { "s" | "" => 1, "m" => 60, "h" => 3600, "d" => 86_400, _ => return Err(format!("invalid window '{}'", s)) };
+                n.checked_mul(unit).ok_or_else(|| format!("invalid window '{}'", s))?
+            }
+        };
+        if secs == 0 { return Err("window must be at least 1s".to_string()); }
+        if secs > MAX_WINDOW_SECS { return Err(format!("window '{}' is longer than {}d", s, MAX_WINDOW_SECS / 86_400)); }
+        Ok(Window(Duration::from_secs(secs)))
+    }
+
+    /// Time left until the next boundary.
+    pub fn remaining(&self, now: SystemTime) -> Duration {
+        let len = self.0.as_millis().max(1);
+        let now = now.duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        Duration::from_millis((len - now % len) as u64) // Below `len`, which `parse` caps at a year
+    }
+}
+
+impl TryFrom<String> for Window {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, String> {
+        Window::parse(&s)
+    }
+}
+
+impl From<Window> for String {
+    fn from(w: Window) -> String {
+        format!("{}s", w.0.as_secs())
+    }
+}
+
+/// Float mode, bounds and reset window of an INCR / DECR.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CounterOptions {
+    pub float: bool,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub bound: Bound,
+    pub window: Option<Window>,
+}
+
+impl CounterOptions {
+    /// Parse the TCP form, e.g. `float,min=0,max=100,bound=reject,window=hour` (`-` = none).
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut options = CounterOptions::default();
+        for item in s.split(',').map(str::trim).filter(|i| !i.is_empty() && *i != "-") {
+            let (name, value) = item.split_once('=').unwrap_or((item, ""));
+            let number = || value.trim().parse::<f64>().map_err(|_| format!("invalid {} '{}'", name, value));
+            match name.trim().to_ascii_lowercase().as_str() {
+                "float" => options.float = true,
+                "min" => options.min = Some(number()?),
+                "max" => options.max = Some(number()?),
+                "bound" => options.bound = match value.trim() {
+                    "clamp" => Bound::Clamp,
+                    "reject" => Bound::Reject,
+                    other => return Err(format!("invalid bound '{}'", other)),
+                },
+                "window" => options.window = Some(Window::parse(value)?),
+                other => return Err(format!("unknown counter option '{}'", other)),
+            }
+        }
+        if let (Some(min), Some(max)) = (options.min, options.max) {
+            if min > max { return Err("min is greater than max".to_string()); }
+        }
+        Ok(options)
+    }
+
+    /// The new value of a counter holding `current` (None = missing) after adding `by`.
+    pub fn apply(&self, current: Option<&str>, by: CounterValue) -> Result<CounterValue, ValueError> {
+        let current = match current.map(str::trim) {
+            None | Some("") => CounterValue::Int(0),
+            Some(text) => match CounterValue::parse(text) {
+                Some(CounterValue::Float(_)) if !self.float && matches!(by, CounterValue::Int(_)) => return Err(ValueError::NotInteger),
+                Some(n) => n,
+                None => return Err(if self.float { ValueError::NotNumber } else { ValueError::NotInteger }),
+            },
+        };
+        let sum = match (current, by) {
+            (CounterValue::Int(a), CounterValue::Int(b)) if !self.float => CounterValue::Int(a.checked_add(b).ok_or(ValueError::Overflow)?),
+            (a, b) => {
+                let sum = a.as_f64() + b.as_f64();
+                if !sum.is_finite() { return Err(ValueError::Overflow); }
+                CounterValue::Float(sum)
+            }
+        };
+        self.bounded(sum)
+    }
+
+    fn bounded(&self, value: CounterValue) -> Result<CounterValue, ValueError> {
+        let x = value.as_f64();
+        let (limit, below) = match (self.min, self.max) {
+            (Some(min), _) if x < min => (min, true),
+            (_, Some(max)) if x > max => (max, false),
+            _ => return Ok(value),
+        };
+        if self.bound == Bound::Reject { return Err(ValueError::OutOfRange); }
+        Ok(match value {
+            // Integer counters clamp to the nearest integer inside the bounds
+            CounterValue::Int(_) => CounterValue::Int(if below { limit.ceil() } else { limit.floor() } as i64),
+            CounterValue::Float(_) => CounterValue::Float(limit),
+        })
+    }
+}
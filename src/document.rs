@@ -0,0 +1,225 @@
+// =============================
+// JSON DOCUMENTS
+// =============================
+// Paths into JSON-typed entries and the updates applied at them. A path is either a JSON Pointer
+// (`/items/0/price`, `""` for the whole document) or the dotted / bracketed JSONPath subset
+// (`$.items[0].price`, `$['odd key']`, `$` for the whole document); wildcards, slices and filters
+// are not supported since every path must name exactly one location. Array indexes may be negative
+// to count from the end (-1 = last element).
+//
+// Updates create missing object members along the way (a missing or null parent becomes an
+// object) and check everything before changing the document, so a failed update leaves it as it was.
+// They also report how much the document's JSON text grew, measured on the parts they touch, so the
+// cache keeps a document's size without serializing all of it on every update.
+
+use serde_json::{Map, Number, Value as Json};
+
+use crate::value::ValueError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    segments: Vec<String>, // Member names, or array indexes when the parent is an array
+}
+
+/// An update applied at a path.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonOp {
+    Set(Json),         // Replace (or create) the value
+    Merge(Json),       // RFC 7396 merge patch: objects merge recursively, null members are removed
+    Append(Vec<Json>), // Push onto an array (a missing value starts as [])
+    Incr(Number),      // Add to a number (a missing value counts as 0)
+}
+
+impl JsonOp {
+    /// Parse an operation name (`set`, `merge`, `append`, `incr`) with its argument.
+    pub fn parse(name: &str, arg: Json) -> Result<Self, ValueError> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "set" => Ok(JsonOp::Set(arg)),
+            "merge" => Ok(JsonOp::Merge(arg)),
+            "append" => Ok(JsonOp::Append(match arg { Json::Array(items) => items, item => vec![item] })),
+            "incr" => match arg {
+                Json::Number(by) => Ok(JsonOp::Incr(by)),
+                _ => Err(ValueError::NotNumber),
+            },
+            other => Err(ValueError::UnknownOperation(other.to_string())),
+        }
+    }
+}
+
+impl JsonPath {
+    /// The whole document.
+    pub fn root() -> Self {
+        JsonPath { segments: Vec::new() }
+    }
+
+    pub fn parse(path: &str) -> Result<Self, ValueError> {
+        let path = path.trim();
+        if path.is_empty() { return Ok(Self::root()); }
+        if let Some(pointer) = path.strip_prefix('/') {
+            let segments = pointer.split('/').map(|s| s.replace("~1", "/").replace("~0", "~")).collect();
+            return Ok(JsonPath { segments });
+        }
+        let Some(mut rest) = path.strip_prefix('$') else {
+            return Err(invalid(path, "must start with '$' or '/'"));
+        };
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 { return Err(invalid(path, "empty member name")); }
+                segments.push(after[..end].to_string());
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let (segment, tail) = match after.chars().next() {
+                    Some(quote @ ('\'' | '"')) => {
+                        let close = after[1..].find(quote).ok_or_else(|| invalid(path, "unterminated quote"))? + 1;
+                        (after[1..close].to_string(), after[close + 1..].strip_prefix(']'))
+                    }
+                    _ => {
+                        let close = after.find(']').ok_or_else(|| invalid(path, "missing ']'"))?;
+                        let index = after[..close].trim();
+                        if index.parse::<i64>().is_err() { return Err(invalid(path, "brackets need an index or a quoted name")); }
+                        (index.to_string(), Some(&after[close + 1..]))
+                    }
+                };
+                rest = tail.ok_or_else(|| invalid(path, "missing ']'"))?;
+                segments.push(segment);
+            } else {
+                return Err(invalid(path, "expected '.' or '['"));
+            }
+        }
+        Ok(JsonPath { segments })
+    }
+
+    /// The value at this path, if there is one.
+    pub fn get<'a>(&self, doc: &'a Json) -> Option<&'a Json> {
+        self.segments.iter().try_fold(doc, |node, segment| match node {
+            Json::Object(map) => map.get(segment),
+            Json::Array(items) => index(segment, items.len()).and_then(|i| items.get(i)),
+            _ => None,
+        })
+    }
+
+    /// Apply `op` at this path. Returns the value now stored there and the change in length of
+    /// the document's JSON text.
+    pub fn apply(&self, doc: &mut Json, op: JsonOp) -> Result<(Json, isize), ValueError> {
+        self.check(doc, &op)?;
+        let mut grown = 0;
+        let mut node = doc;
+        for segment in &self.segments {
+            if node.is_null() {
+                *node = Json::Object(Map::new());
+                grown -= 2; // `null` -> `{}`
+            }
+            node = match node {
+                Json::Object(map) => {
+                    if !map.contains_key(segment) { grown += member_len(segment, map.is_empty()) + 4; } // `"segment":null`
+                    map.entry(segment.clone()).or_insert(Json::Null)
+                }
+                Json::Array(items) => { let len = items.len(); &mut items[index(segment, len).expect("checked")] }
+                _ => unreachable!("checked"),
+            };
+        }
+        match op {
+            JsonOp::Set(value) => {
+                grown += text_len(&value) as isize - text_len(node) as isize;
+                *node = value;
+            }
+            JsonOp::Merge(patch) => {
+                let before = text_len(node) as isize;
+                merge_patch(node, patch);
+                grown += text_len(node) as isize - before;
+            }
+            JsonOp::Append(items) => {
+                if node.is_null() {
+                    *node = Json::Array(Vec::new());
+                    grown -= 2; // `null` -> `[]`
+                }
+                if let Json::Array(array) = node {
+                    for item in items {
+                        grown += text_len(&item) as isize + !array.is_empty() as isize; // Comma before all but the first
+                        array.push(item);
+                    }
+                }
+            }
+            JsonOp::Incr(by) => {
+                let before = text_len(node) as isize;
+                *node = Json::Number(add(node.as_number(), &by)?);
+                grown += text_len(node) as isize - before;
+            }
+        }
+        Ok((node.clone(), grown))
+    }
+
+    // Everything `apply` could fail on, checked before the document is touched.
+    fn check(&self, doc: &Json, op: &JsonOp) -> Result<(), ValueError> {
+        let mut node = Some(doc);
+        for segment in &self.segments {
+            node = match node {
+                None | Some(Json::Null) => None, // Created as objects by `apply`
+                Some(Json::Object(map)) => map.get(segment),
+                Some(Json::Array(items)) => match index(segment, items.len()) {
+                    Some(i) if i < items.len() => items.get(i),
+                    _ => return Err(ValueError::InvalidPath(format!("no element {} in an array of {}", segment, items.len()))),
+                },
+                Some(_) => return Err(ValueError::InvalidPath(format!("'{}' is below a scalar", segment))),
+            };
+        }
+        match (op, node) {
+            (JsonOp::Append(_), Some(found)) if !found.is_array() && !found.is_null() => Err(ValueError::NotArray),
+            (JsonOp::Incr(_), Some(found)) if !found.is_number() && !found.is_null() => Err(ValueError::NotNumber),
+            (JsonOp::Incr(by), found) => add(found.and_then(Json::as_number), by).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn invalid(path: &str, why: &str) -> ValueError {
+    ValueError::InvalidPath(format!("'{}': {}", path, why))
+}
+
+// Array position of `segment` (negative counts from the end); None if it is not an index.
+fn index(segment: &str, len: usize) -> Option<usize> {
+    let i = segment.parse::<i64>().ok()?;
+    if i < 0 { len.checked_sub(i.unsigned_abs() as usize) } else { Some(i as usize) }
+}
+
+// Integers stay integers while they fit; anything else is added as floating point.
+fn add(current: Option<&Number>, by: &Number) -> Result<Number, ValueError> {
+    let zero = Number::from(0);
+    let current = current.unwrap_or(&zero);
+    if let (Some(a), Some(b)) = (current.as_i64(), by.as_i64()) {
+        return a.checked_add(b).map(Number::from).ok_or(ValueError::Overflow);
+    }
+    let sum = current.as_f64().unwrap_or(0.0) + by.as_f64().unwrap_or(0.0);
+    Number::from_f64(sum).ok_or(ValueError::Overflow)
+}
+
+/// Length of the compact JSON text of `value`, without building it.
+pub fn text_len(value: &(impl serde::Serialize + ?Sized)) -> usize {
+    struct Count(usize);
+    impl std::io::Write for Count {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> { self.0 += buf.len(); Ok(buf.len()) }
+        fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+    }
+    let mut count = Count(0);
+    serde_json::to_writer(&mut count, value).map_or(0, |_| count.0)
+}
+
+// `"name":` plus the comma separating it from the members before it.
+fn member_len(name: &str, first: bool) -> isize {
+    (text_len(name) + 1 + !first as usize) as isize
+}
+
+fn merge_patch(target: &mut Json, patch: Json) {
+    let Json::Object(patch) = patch else { *target = patch; return };
+    if !target.is_object() { *target = Json::Object(Map::new()); }
+    let Json::Object(map) = target else { unreachable!() };
+    for (name, value) in patch {
+        if value.is_null() {
+            map.remove(&name);
+        } else {
+            merge_patch(map.entry(name).or_insert(Json::Null), value);
+        }
+    }
+}
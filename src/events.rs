@@ -0,0 +1,156 @@
+// =============================
+// KEYSPACE EVENTS
+// =============================
+// The cache publishes one CacheEvent per mutation on a tokio broadcast channel. Subscribers (SSE,
+// WebSocket, TCP SUBSCRIBE) each hold a Receiver and apply their own EventFilter.
+//
+// Back-pressure: the channel is bounded and producers never wait. A subscriber that falls more than
+// `capacity` events behind loses the oldest ones and is told how many were dropped (RecvError::Lagged),
+// so a slow consumer can never stall writes to the cache.
+
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+pub const DEFAULT_EVENT_BUFFER: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Put,
+    Add,
+    Incr,
+    Delete,
+    InvalidateTag,
+    Expire,
+    Flush,
+    Schedule, // A scheduled invalidation was created, cancelled or ran (see schedule.rs)
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Put => "put",
+            EventKind::Add => "add",
+            EventKind::Incr => "incr",
+            EventKind::Delete => "delete",
+            EventKind::InvalidateTag => "invalidate_tag",
+            EventKind::Expire => "expire",
+            EventKind::Flush => "flush",
+            EventKind::Schedule => "schedule",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "put" => Some(EventKind::Put),
+            "add" => Some(EventKind::Add),
+            "incr" | "decr" => Some(EventKind::Incr),
+            "delete" | "del" => Some(EventKind::Delete),
+            "invalidate_tag" | "invalidate" => Some(EventKind::InvalidateTag),
+            "expire" | "expired" => Some(EventKind::Expire),
+            "flush" => Some(EventKind::Flush),
+            "schedule" | "scheduled" => Some(EventKind::Schedule),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheEvent {
+    #[serde(rename = "type")]
+    pub kind: EventKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>, // affected keys for tag invalidation / flush
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<&'static str>, // "any" / "all" / "subtree" for multi-tag invalidations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>, // Schedule id of `schedule` events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<&'static str>, // "created" / "cancelled" / "ran" for `schedule` events
+    pub ts: u64,              // unix millis
+}
+
+impl CacheEvent {
+    pub fn new(kind: EventKind, key: Option<String>, tags: Vec<String>, count: Option<usize>) -> Self {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        Self { kind, key, tags, count, mode: None, schedule: None, action: None, ts }
+    }
+
+    pub fn with_mode(mut self, mode: &'static str) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn with_schedule(mut self, id: &str, action: &'static str) -> Self {
+        self.schedule = Some(id.to_string());
+        self.action = Some(action);
+        self
+    }
+}
+
+// Subscriber-side filter. Empty fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub prefix: Option<String>,
+    pub tag: Option<String>,
+    pub kinds: Vec<EventKind>,
+}
+
+impl EventFilter {
+    // Build from the textual forms shared by the HTTP query string and TCP SUBSCRIBE ("-" = unset).
+    pub fn parse(types: Option<&str>, prefix: Option<&str>, tag: Option<&str>) -> Result<Self, String> {
+        let unset = |v: Option<&str>| v.map(str::trim).filter(|v| !v.is_empty() && *v != "-").map(str::to_string);
+        let mut kinds = Vec::new();
+        if let Some(types) = unset(types) {
+            for t in types.split(',').filter(|t| !t.trim().is_empty()) {
+                kinds.push(EventKind::parse(t).ok_or_else(|| format!("unknown_event_type {}", t.trim()))?);
+            }
+        }
+        Ok(Self { prefix: unset(prefix), tag: unset(tag), kinds })
+    }
+
+    pub fn matches(&self, ev: &CacheEvent) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.contains(&ev.kind) { return false; }
+        if let Some(prefix) = &self.prefix {
+            // Events without a key (flush, tag invalidation) are not scoped to a prefix.
+            if let Some(key) = &ev.key { if !key.starts_with(prefix.as_str()) { return false; } }
+        }
+        if let Some(tag) = &self.tag {
+            if !ev.tags.iter().any(|t| t == tag) && ev.kind != EventKind::Flush { return false; }
+        }
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct EventBus {
+    tx: broadcast::Sender<CacheEvent>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self { tx }
+    }
+
+    // Publish lazily: the event is only built when somebody is listening, so an idle bus costs one
+    // atomic load per mutation.
+    pub fn publish<F: FnOnce() -> CacheEvent>(&self, build: F) {
+        if self.tx.receiver_count() > 0 {
+            let _ = self.tx.send(build());
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<CacheEvent> {
+        self.tx.subscribe()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
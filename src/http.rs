@@ -0,0 +1,1761 @@
+// =============================
+// HTTP API
+// =============================
+// Axum router, JSON request / response types, handlers, the auth extractor and the embedded web UI.
+// `build_app` is the single entry point; everything it needs lives in the shared `AppState`.
+
+use axum::{routing::{get, post}, Json, Router, extract::{Path, Query, FromRequestParts, State}, response::Json as ResponseJson, http::{request::Parts, StatusCode}}; // Axum web framework imports for routing & JSON
+use axum::response::{Html, IntoResponse};
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse}; // Server-Sent Events for /events
+use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+use axum::http::{header, Uri};
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use serde::{Deserialize, Serialize}; // Serde for (de)serialization of JSON payloads
+use std::{sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+use sysinfo::System; // System info for CPU monitoring
+use tokio_stream::{wrappers::{BroadcastStream, errors::BroadcastStreamRecvError}, StreamExt};
+use tower_http::cors::CorsLayer; // CORS middleware for HTTP
+
+use crate::audit::{AuditEvent, AuditLog, AuditQuery};
+use crate::auth::{self, AuthState, LoginError, Principal, Scope, UserConfig};
+use crate::lock::{self, Lease, LockError};
+use crate::lockout::Locked;
+use crate::cache::{Cache, Key, Tag, TagMatch};
+use crate::cluster::{self, Cluster};
+use crate::events::{CacheEvent, EventFilter};
+use crate::namespace::{self, Namespace, NamespaceError, NAMESPACE_HEADER};
+use crate::replication::{self, Replication, Role};
+use crate::schedule::{self, Schedule, Scheduler, Target};
+use crate::transfer;
+use crate::counter::{CounterOptions, CounterValue};
+use crate::document::{JsonOp, JsonPath};
+use crate::ratelimit::{Algorithm, RateLimit};
+use crate::tag_ttl::TtlMode;
+use crate::value::{Value, ValueError, ValueType};
+
+// Conditionally embed assets only if the dist folder exists
+#[cfg(feature = "embed-ui")]
+mod embedded_assets {
+    use rust_embed::RustEmbed;
+    
+    #[derive(RustEmbed)]
+    #[folder = "app/dist/"]
+    pub struct Assets;
+}
+
+// Static file handler for the web UI
+async fn static_handler(uri: Uri) -> impl IntoResponse {
+    let path = uri.path().trim_start_matches('/');
+    
+    if path.is_empty() || path == "index.html" {
+        return serve_index_html().into_response();
+    }
+    
+    #[cfg(feature = "embed-ui")]
+    {
+        match embedded_assets::Assets::get(path) {
+            Some(content) => {
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                let headers = [
+                    (header::CONTENT_TYPE, mime.as_ref()),
+                    (header::CACHE_CONTROL, "public, max-age=31536000"),
+                ];
+                (headers, content.data).into_response()
+            }
+            None => serve_index_html().into_response(),
+        }
+    }
+    
+    #[cfg(not(feature = "embed-ui"))]
+    {
+        serve_index_html().into_response()
+    }
+}
+
+fn serve_index_html() -> Html<std::borrow::Cow<'static, [u8]>> {
+    #[cfg(feature = "embed-ui")]
+    {
+        if let Some(content) = embedded_assets::Assets::get("index.html") { return Html(content.data) }
+    }
+    
+    // Fallback UI when assets are not embedded
+    let fallback_html = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>TagCache Server</title>
+    <style>
+        body { font-family: Arial, sans-serif; margin: 40px; background: #f5f5f5; }
+        .container { max-width: 800px; margin: 0 auto; background: white; padding: 30px; border-radius: 8px; box-shadow: 0 2px 4px rgba(0,0,0,0.1); }
+        h1 { color: #333; border-bottom: 2px solid #007acc; padding-bottom: 10px; }
+        .status { background: #e8f5e8; padding: 15px; border-radius: 4px; margin: 20px 0; }
+        .endpoint { background: #f0f0f0; padding: 10px; margin: 10px 0; border-radius: 4px; font-family: monospace; }
+        .method { color: #007acc; font-weight: bold; }
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>🚀 TagCache Server</h1>
+        <div class="status">
+            <strong>✓ Server is running!</strong><br>
+            The TagCache server is operational and ready to handle requests.
+        </div>
+        
+        <h2>API Endpoints</h2>
+        <div class="endpoint"><span class="method">GET</span> /health - Health check</div>
+        <div class="endpoint"><span class="method">GET</span> /stats - Server statistics (requires auth)</div>
+        <div class="endpoint"><span class="method">POST</span> /put - Store key-value data (requires auth)</div>
+        <div class="endpoint"><span class="method">POST</span> /add - Atomically add key-value data (fails if exists, requires auth)</div>
+        <div class="endpoint"><span class="method">POST</span> /incr - Atomically increment numeric value (requires auth)</div>
+        <div class="endpoint"><span class="method">POST</span> /decr - Atomically decrement numeric value (requires auth)</div>
+        <div class="endpoint"><span class="method">GET</span> /get/:key - Retrieve data (requires auth)</div>
+        
+        <h2>Authentication</h2>
+        <p>Use HTTP Basic Auth with username: <code>admin</code> and password: <code>password</code></p>
+        <p>Default credentials should be changed in production!</p>
+        
+        <h2>TCP Protocol</h2>
+        <p>High-performance TCP protocol available on port 1984</p>
+        <p>Commands: PUT, ADD (atomic), INCR, DECR, GET, DEL, INV_TAG, KEYS_BY_TAG, STATS, FLUSH</p>
+        
+        <h2>Documentation</h2>
+        <p>Visit <a href="https://github.com/aminshamim/tagcache">github.com/aminshamim/tagcache</a> for complete documentation.</p>
+    </div>
+</body>
+</html>"#;
+    
+    Html(fallback_html.as_bytes().into())
+}
+
+/// State shared by every HTTP handler and TCP connection.
+#[derive(Clone)]
+pub struct AppState { 
+    pub cache: Arc<Cache>, 
+    pub auth: Arc<AuthState>,
+    pub system: Arc<parking_lot::Mutex<System>>, // System monitor for CPU stats
+    pub replication: Arc<Replication>,           // Role (leader / follower) and replication status
+    pub cluster: Option<Arc<Cluster>>,           // Set in cluster mode (routes keys to their owner node)
+    pub audit: Arc<AuditLog>,                    // Security event log (/admin/audit)
+    pub schedules: Arc<Scheduler>,               // Scheduled invalidations (/schedules)
+}
+
+impl AppState {
+    /// State for a standalone (leader, non-clustered) server around an existing cache.
+    pub fn new(cache: Arc<Cache>, auth: Arc<AuthState>) -> Self {
+        let audit = Arc::new(AuditLog::memory(1000));
+        let schedules = Arc::new(Scheduler::memory(cache.clone(), audit.clone()));
+        Self {
+            cache,
+            auth,
+            system: Arc::new(parking_lot::Mutex::new(System::new())),
+            replication: Replication::new(&replication::ReplicationConfig::default()),
+            cluster: None,
+            audit,
+            schedules,
+        }
+    }
+}
+
+/// The client address, when the server was started with connect info (`tagcache server` is).
+pub struct Peer(pub Option<std::net::SocketAddr>);
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Peer {
+    type Rejection = std::convert::Infallible;
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(Peer(parts.extensions.get::<axum::extract::ConnectInfo<std::net::SocketAddr>>().map(|c| c.0)))
+    }
+}
+
+/// Audit a failed password check and the lockout it may have started.
+pub fn audit_login_failure(audit: &AuditLog, user: &str, peer: Option<std::net::SocketAddr>, via: &str, locked: Option<Locked>) {
+    audit.record(AuditEvent::LoginFailed, Some(user), peer, Some(via.to_string()));
+    match locked {
+        Some(Locked::Ip(d)) => audit.record(AuditEvent::Lockout, Some(user), peer, Some(format!("ip locked for {}s", d.as_secs()))),
+        Some(Locked::User(d)) => audit.record(AuditEvent::Lockout, Some(user), peer, Some(format!("user locked for {}s", d.as_secs()))),
+        None => {}
+    }
+}
+
+fn locked_out(retry_after: Duration) -> Rejection {
+    (StatusCode::TOO_MANY_REQUESTS, ResponseJson(serde_json::json!({"error": "locked_out", "retry_after": retry_after.as_secs().max(1)})))
+}
+
+// Request guard for auth (per-route, simpler + fast): authenticates the caller, checks the role
+// the endpoint needs and the scope for keys named in the path. Handlers check keys / tags that
+// arrive in request bodies against the principal it carries.
+pub struct Authenticated(pub Principal);
+
+pub type Rejection = (StatusCode, ResponseJson<serde_json::Value>);
+
+fn forbidden(reason: String) -> Rejection {
+    (StatusCode::FORBIDDEN, ResponseJson(serde_json::json!({"error": "forbidden", "reason": reason})))
+}
+
+#[axum::async_trait]
+impl FromRequestParts<Arc<AppState>> for Authenticated {
+    type Rejection = Rejection;
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let mut principal = authenticate(parts, state)?;
+        let path = parts.uri.path();
+        let decoded = |s: &str| percent_encoding::percent_decode_str(s).decode_utf8_lossy().into_owned();
+        client_names([decoded(path).as_str(), decoded(parts.uri.query().unwrap_or("")).as_str()])?; // Path keys / tags, query tags and prefixes
+        let required = required_role(&parts.method, path);
+        if !principal.has_role(required) {
+            return Err(forbidden(format!("{} requires the {} role", path, required.as_str())));
+        }
+        principal.enter(requested_namespace(&parts.headers).map_err(namespace_rejection)?).map_err(forbidden)?;
+        if !principal.namespace.is_default() {
+            if server_wide(path) { return Err(forbidden(format!("{} is not available inside a namespace", path))); }
+            state.cache.namespaces.admit(&principal.namespace).map_err(namespace_rejection)?;
+        }
+        if !principal.scope.is_unrestricted() {
+            if let Some(key) = path_key(path) {
+                principal.check_key(&key).map_err(forbidden)?;
+            } else if KEYSPACE_WIDE.contains(&path) {
+                principal.check_unscoped().map_err(forbidden)?;
+            }
+        }
+        Ok(Authenticated(principal))
+    }
+}
+
+// The namespace named by the X-TagCache-Namespace header (set from a /ns/<name>/ prefix too).
+fn requested_namespace(headers: &axum::http::HeaderMap) -> Result<Option<Namespace>, NamespaceError> {
+    let Some(value) = headers.get(NAMESPACE_HEADER) else { return Ok(None) };
+    let name = value.to_str().map_err(|_| NamespaceError::Invalid(String::from_utf8_lossy(value.as_bytes()).into_owned()))?;
+    Namespace::named(name).map(Some)
+}
+
+fn namespace_rejection(e: NamespaceError) -> Rejection {
+    let (status, field) = match &e {
+        NamespaceError::Invalid(_) => (StatusCode::BAD_REQUEST, "namespace"),
+        NamespaceError::Unknown(_) => (StatusCode::NOT_FOUND, "namespace"),
+        NamespaceError::Quota("max_ops_per_sec") => (StatusCode::TOO_MANY_REQUESTS, "quota"),
+        NamespaceError::Quota(_) => (StatusCode::INSUFFICIENT_STORAGE, "quota"),
+        NamespaceError::ReservedChar(_) => (StatusCode::BAD_REQUEST, "name"),
+    };
+    (status, ResponseJson(serde_json::json!({"error": e.code(), field: e.detail()})))
+}
+
+// Keys, tags, prefixes and tag expressions sent by a client must not name another namespace's entries.
+fn client_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<(), Rejection> {
+    namespace::check_names(names).map_err(namespace_rejection)
+}
+
+// Endpoints that act on the whole server rather than a keyspace; refused inside a namespace.
+fn server_wide(path: &str) -> bool {
+    if matches!(path, "/auth/whoami" | "/auth/refresh" | "/auth/logout") { return false; }
+    ["/auth/", "/admin/", "/replication", "/cluster", "/events"].iter().any(|p| path.starts_with(p))
+}
+
+// /ns/<name>/<path> is /<path> with `X-TagCache-Namespace: <name>`.
+async fn namespace_prefix(mut req: axum::extract::Request, next: axum::middleware::Next) -> axum::response::Response {
+    let rewritten = req.uri().path().strip_prefix("/ns/").and_then(|rest| {
+        let (name, path) = rest.split_once('/')?;
+        let path_and_query = match req.uri().query() { Some(q) => format!("/{}?{}", path, q), None => format!("/{}", path) };
+        Some((axum::http::HeaderValue::from_str(name).ok()?, path_and_query.parse::<Uri>().ok()?))
+    });
+    if let Some((name, uri)) = rewritten {
+        req.headers_mut().insert(NAMESPACE_HEADER, name);
+        *req.uri_mut() = uri;
+    }
+    next.run(req).await
+}
+
+// The bearer credential of a request, if it uses one.
+fn bearer(headers: &axum::http::HeaderMap) -> Option<&str> {
+    headers.get(header::AUTHORIZATION)?.to_str().ok()?.strip_prefix("Bearer ")
+}
+
+fn authenticate(parts: &Parts, state: &AppState) -> Result<Principal, Rejection> {
+    let unauthorized = || (StatusCode::UNAUTHORIZED, ResponseJson(serde_json::json!({"error":"unauthorized"})));
+    if let Some(token) = bearer(&parts.headers) { return state.auth.authenticate_token(token).ok_or_else(unauthorized); }
+    let basic = parts.headers.get(header::AUTHORIZATION).and_then(|h| h.to_str().ok()).and_then(|s| s.strip_prefix("Basic "));
+    let decoded = basic.and_then(|b| B64.decode(b).ok()).and_then(|d| String::from_utf8(d).ok()).ok_or_else(unauthorized)?;
+    let (u, p) = decoded.split_once(':').ok_or_else(unauthorized)?;
+    let peer = parts.extensions.get::<axum::extract::ConnectInfo<std::net::SocketAddr>>().map(|c| c.0);
+    state.auth.login(u, p, peer.map(|a| a.ip())).map_err(|e| match e {
+        LoginError::LockedOut(wait) => locked_out(wait),
+        LoginError::Invalid(locked) => {
+            audit_login_failure(&state.audit, u, peer, "basic", locked);
+            unauthorized()
+        }
+    })
+}
+
+// Minimum role per endpoint; anything not listed (auth and account management, /admin/*,
+// /flush, promotion) needs admin.
+fn required_role(method: &axum::http::Method, path: &str) -> auth::Role {
+    use auth::Role;
+    if matches!(path, "/auth/whoami" | "/auth/refresh" | "/auth/logout") { return Role::ReadOnly; }
+    if path.starts_with("/auth/") || path.starts_with("/admin/") { return Role::Admin; }
+    match (method.as_str(), path) {
+        ("GET", _) | ("POST", "/search" | "/keys/bulk/get") => Role::ReadOnly,
+        ("POST", "/invalidate-key" | "/invalidate-tag" | "/invalidate/tags" | "/invalidate/keys" | "/keys/bulk/delete") => Role::Invalidator,
+        ("POST", p) if p.starts_with("/tags/") && p.ends_with("/expire") => Role::Invalidator, // No more than invalidating the tag
+        ("DELETE", p) if p.starts_with("/keys/") => Role::Invalidator,
+        ("POST", "/schedules") => Role::Invalidator, // Invalidations that run later
+        ("DELETE", p) if p.starts_with("/schedules/") => Role::Invalidator,
+        ("POST", "/put" | "/add" | "/incr" | "/decr") => Role::Writer,
+        ("POST", p) if p.starts_with("/keys/") => Role::Writer, // Hash / list / set writes
+        ("PUT" | "PATCH", p) if p.starts_with("/keys/") => Role::Writer,
+        _ => Role::Admin,
+    }
+}
+
+// Endpoints that read or watch the whole keyspace; scoped principals cannot use them.
+const KEYSPACE_WIDE: &[&str] = &["/search", "/keys", "/tags", "/events", "/events/ws", "/flush", "/admin/export", "/admin/import"];
+
+// The key named by /get/:key and /keys/:key[/hash|list|set/...], percent-decoded.
+fn path_key(path: &str) -> Option<String> {
+    let raw = match path.strip_prefix("/keys/") {
+        Some(rest) if !rest.starts_with("bulk/") => rest.split('/').next().unwrap_or(""),
+        _ => path.strip_prefix("/get/")?,
+    };
+    if raw.is_empty() || raw.contains('/') { return None; }
+    Some(percent_encoding::percent_decode_str(raw).decode_utf8_lossy().into_owned())
+}
+
+#[derive(Deserialize)] struct LoginBody { username:String, password:String }
+#[derive(Serialize)] struct RotateResponse { ok:bool, username:String, password:String }
+
+// =============================
+// HTTP REQUEST / RESPONSE TYPES
+// =============================
+// Structures below represent shapes of JSON payloads. serde derives (Deserialize/Serialize) generate code.
+#[derive(Deserialize)]
+pub struct PutRequest { // Input for /put
+    pub key: String,
+    pub value: String,
+    pub tags: Vec<String>,
+    pub ttl_seconds: Option<u64>, // Alternative TTL unit
+    pub ttl_ms: Option<u64>,      // Preferred millisecond TTL
+}
+
+#[derive(Deserialize)]
+pub struct AddRequest { // Input for /add - atomic add operation
+    pub key: String,
+    pub value: String,
+    pub tags: Vec<String>,
+    pub ttl_seconds: Option<u64>, // Alternative TTL unit
+    pub ttl_ms: Option<u64>,      // Preferred millisecond TTL
+}
+
+#[derive(Deserialize)]
+pub struct IncrementRequest { // Input for /incr - atomic increment operation
+    pub key: String,
+    pub by: Option<serde_json::Number>, // Amount to increment by (default: 1; a fraction makes it a float counter)
+    pub tags: Option<Vec<String>>, // Optional tags to set/update
+    pub ttl_seconds: Option<u64>, // Alternative TTL unit
+    pub ttl_ms: Option<u64>,      // Preferred millisecond TTL
+    #[serde(flatten)]
+    pub options: CounterOptions,  // float, min, max, bound ("clamp" | "reject"), window ("minute", "15m", ...)
+}
+
+#[derive(Deserialize)]
+pub struct DecrementRequest { // Input for /decr - atomic decrement operation
+    pub key: String,
+    pub by: Option<serde_json::Number>, // Amount to decrement by (default: 1; a fraction makes it a float counter)
+    pub tags: Option<Vec<String>>, // Optional tags to set/update
+    pub ttl_seconds: Option<u64>, // Alternative TTL unit
+    pub ttl_ms: Option<u64>,      // Preferred millisecond TTL
+    #[serde(flatten)]
+    pub options: CounterOptions,  // float, min, max, bound ("clamp" | "reject"), window ("minute", "15m", ...)
+}
+
+#[derive(Serialize)]
+pub struct PutResponse { // Output of /put
+    pub ok: bool,
+    pub ttl_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct AddResponse { // Output of /add
+    pub ok: bool,
+    pub added: bool,      // true if key was added, false if key already existed
+    pub ttl_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct IncrementResponse { // Output of /incr
+    pub ok: bool,
+    pub value: CounterValue, // New value after increment
+    pub ttl_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct DecrementResponse { // Output of /decr
+    pub ok: bool,
+    pub value: CounterValue, // New value after decrement
+    pub ttl_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct GetResponse { // Could be used if we wanted a typed success response
+    pub value: String,
+}
+
+#[derive(Deserialize)]
+pub struct KeysByTagQuery { // Query parameters (?tag=...&limit=...)
+    pub tag: String,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct KeysByTagResponse { // Response for keys-by-tag
+    pub keys: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct InvalidateKeyRequest { // Body for /invalidate-key
+    pub key: String,
+}
+
+#[derive(Deserialize)]
+pub struct InvalidateTagRequest { // Body for /invalidate-tag
+    pub tag: String,
+}
+
+#[derive(Serialize)]
+pub struct InvalidateResponse { // Shared invalidation response
+    pub success: bool,
+    pub count: Option<usize>, // Present for tag invalidation
+}
+
+#[derive(Serialize, Clone)]
+pub struct StatsResponse { // /stats output (extended)
+    pub hits: u64,
+    pub misses: u64,
+    pub puts: u64,
+    pub invalidations: u64,
+    pub hit_ratio: f64,
+    pub items: usize,
+    pub bytes: usize,
+    pub tags: usize,
+    pub shard_count: usize,
+    pub shard_items: Vec<usize>,   // length = shard_count
+    pub shard_bytes: Vec<usize>,   // length = shard_count
+    pub expired: u64,              // total entries removed by TTL
+    pub expiry_lag_ms: u64,        // worst deadline->removal delay in the last reaper pass
+    pub expiry_pending: usize,     // records queued in the expiration index
+    pub reclaimed: u64,            // entries of versioned tag invalidations removed so far
+    pub reclaim_pending: usize,    // invalidated versioned tags and keys still to sweep
+    pub event_subscribers: usize,  // open /events, /events/ws and SUBSCRIBE streams
+    pub replication: replication::ReplicationStatus, // role, follower lag, connected followers
+}
+
+// RESTful key endpoints types
+#[derive(Deserialize)]
+pub struct KeyUpsertBody {
+    pub value: serde_json::Value,
+    #[serde(default, rename = "type")]
+    pub kind: Option<String>,       // string (default), hash, list, set or json
+    pub ttl_ms: Option<u64>,
+    pub tags: Option<Vec<String>>, // optional to allow updating value only
+}
+
+#[derive(Serialize)]
+pub struct KeyMetadataResponse {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub ttl_ms: Option<u64>, // remaining ttl
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct SearchBody {
+    pub q: Option<String>,
+    pub tag_any: Option<Vec<String>>,
+    pub tag_all: Option<Vec<String>>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResultItem { pub key: String, pub ttl_ms: Option<u64>, pub tags: Vec<String>, pub created_ms: Option<u64> }
+#[derive(Serialize)]
+pub struct SearchResult { pub keys: Vec<SearchResultItem> }
+
+#[derive(Deserialize)]
+pub struct InvalidateTagsBody { pub tags: Vec<String>, pub mode: Option<String> }
+#[derive(Deserialize)]
+pub struct TagChildrenQuery { pub tag: Option<String> } // Absent = top-level tags
+#[derive(Deserialize)]
+pub struct TagListQuery {
+    pub prefix: Option<String>,
+    pub sort: Option<String>,  // name (default), keys or bytes
+    pub order: Option<String>, // asc / desc (default: asc by name, desc by keys / bytes)
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+#[derive(Deserialize)]
+pub struct TagRenameBody { pub to: String }
+#[derive(Deserialize)]
+pub struct TagMergeBody { pub from: Vec<String> }
+#[derive(Deserialize)]
+pub struct TagApplyBody { pub prefix: Option<String>, pub tags: Option<String> } // Key prefix and tag expression
+#[derive(Deserialize)]
+pub struct TagExpireBody { pub at_ms: Option<u64>, pub in_ms: Option<u64> } // UNIX ms deadline or delay from now
+#[derive(Deserialize)]
+pub struct ScheduleBody { // Input for POST /schedules
+    pub id: Option<String>,  // Chosen id (a random one otherwise; in cluster mode the receiving node picks it)
+    pub at_ms: Option<u64>,  // UNIX ms ...
+    pub in_ms: Option<u64>,  // ... or a delay from now
+    #[serde(flatten)]
+    pub target: Target,
+}
+#[derive(Deserialize)]
+pub struct TagTtlBody { pub ttl_ms: Option<u64>, pub ttl_seconds: Option<u64>, pub mode: Option<String> }
+#[derive(Deserialize)]
+pub struct InvalidateKeysBody { pub keys: Vec<String> }
+
+#[derive(Deserialize)]
+pub struct BulkKeysBody { pub keys: Vec<String> }
+
+#[derive(Serialize)]
+pub struct BulkGetItem { pub key: String, pub value: serde_json::Value, pub ttl_ms: Option<u64>, pub tags: Vec<String>, pub created_ms: Option<u64> }
+
+// =============================
+// HTTP HANDLERS
+// Each handler is async and receives shared state via Axum's State extractor.
+// =============================
+async fn put_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(req): Json<PutRequest>) -> Result<ResponseJson<PutResponse>, Rejection> {
+    client_names(std::iter::once(req.key.as_str()).chain(req.tags.iter().map(String::as_str)))?;
+    who.check_key(&req.key).and_then(|_| who.check_tags(req.tags.iter().map(String::as_str))).map_err(forbidden)?;
+    let key = who.namespace.key(req.key);
+    state.cache.admit_write(&key, req.value.len()).map_err(namespace_rejection)?;
+    let tags: Vec<Tag> = req.tags.into_iter().map(|t| who.namespace.tag(t)).collect();
+    let ttl = req.ttl_ms.map(Duration::from_millis).or_else(|| req.ttl_seconds.map(Duration::from_secs));
+    let stored = state.cache.put(key, req.value, tags, ttl); // After tag TTL policies
+    Ok(ResponseJson(PutResponse { ok: true, ttl_ms: stored.map(|d| d.as_millis() as u64) }))
+}
+
+// ADD handler - atomically adds key only if it doesn't exist
+async fn add_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(req): Json<AddRequest>) -> Result<ResponseJson<AddResponse>, Rejection> {
+    client_names(std::iter::once(req.key.as_str()).chain(req.tags.iter().map(String::as_str)))?;
+    who.check_key(&req.key).and_then(|_| who.check_tags(req.tags.iter().map(String::as_str))).map_err(forbidden)?;
+    let key = who.namespace.key(req.key);
+    state.cache.admit_write(&key, req.value.len()).map_err(namespace_rejection)?;
+    let tags: Vec<Tag> = req.tags.into_iter().map(|t| who.namespace.tag(t)).collect();
+    let ttl = req.ttl_ms.map(Duration::from_millis).or_else(|| req.ttl_seconds.map(Duration::from_secs));
+    let stored = state.cache.add(key, req.value, tags, ttl);
+    Ok(ResponseJson(AddResponse { ok: true, added: stored.is_some(), ttl_ms: stored.flatten().map(|d| d.as_millis() as u64) }))
+}
+
+// INCREMENT handler - atomically increment a numeric value
+async fn increment_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(req): Json<IncrementRequest>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    client_names(std::iter::once(req.key.as_str()).chain(req.tags.iter().flatten().map(String::as_str)))?;
+    who.check_key(&req.key).and_then(|_| who.check_tags(req.tags.iter().flatten().map(String::as_str))).map_err(forbidden)?;
+    let key = who.namespace.key(req.key);
+    let by = counter_amount(req.by); // Default increment by 1
+    state.cache.admit_write(&key, by.to_string().len()).map_err(namespace_rejection)?;
+    let tags = req.tags.unwrap_or_default().into_iter().map(|t| who.namespace.tag(t)).collect();
+    let ttl = req.ttl_ms.map(Duration::from_millis).or_else(|| req.ttl_seconds.map(Duration::from_secs));
+    
+    match state.cache.increment_with(key, by, &req.options, tags, ttl) {
+        Ok((new_value, stored)) => Ok(ResponseJson(serde_json::json!({
+            "ok": true,
+            "value": new_value,
+            "ttl_ms": stored.map(|d| d.as_millis() as u64) // A window's end, after tag TTL policies
+        }))),
+        Err(error) => Ok(ResponseJson(serde_json::json!({
+            "ok": false,
+            "error": error
+        })))
+    }
+}
+
+// DECREMENT handler - atomically decrement a numeric value
+async fn decrement_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(req): Json<DecrementRequest>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    client_names(std::iter::once(req.key.as_str()).chain(req.tags.iter().flatten().map(String::as_str)))?;
+    who.check_key(&req.key).and_then(|_| who.check_tags(req.tags.iter().flatten().map(String::as_str))).map_err(forbidden)?;
+    let key = who.namespace.key(req.key);
+    let by = counter_amount(req.by); // Default decrement by 1
+    state.cache.admit_write(&key, by.to_string().len()).map_err(namespace_rejection)?;
+    let tags = req.tags.unwrap_or_default().into_iter().map(|t| who.namespace.tag(t)).collect();
+    let ttl = req.ttl_ms.map(Duration::from_millis).or_else(|| req.ttl_seconds.map(Duration::from_secs));
+    
+    match state.cache.decrement_with(key, by, &req.options, tags, ttl) {
+        Ok((new_value, stored)) => Ok(ResponseJson(serde_json::json!({
+            "ok": true,
+            "value": new_value,
+            "ttl_ms": stored.map(|d| d.as_millis() as u64) // A window's end, after tag TTL policies
+        }))),
+        Err(error) => Ok(ResponseJson(serde_json::json!({
+            "ok": false,
+            "error": error
+        })))
+    }
+}
+
+// `by` of /incr and /decr: integers stay integers, other numbers make a float counter.
+fn counter_amount(by: Option<serde_json::Number>) -> CounterValue {
+    match by {
+        None => CounterValue::Int(1),
+        Some(n) => n.as_i64().map(CounterValue::Int).unwrap_or_else(|| CounterValue::Float(n.as_f64().unwrap_or(1.0))),
+    }
+}
+
+// GET handler returns either {value: ...} or {error: "not_found"}
+async fn get_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>) -> ResponseJson<serde_json::Value> {
+    let key = who.namespace.key(key);
+    match state.cache.get_value(&key) {
+        Some(Value::String(value)) => ResponseJson(serde_json::json!({"value": value})),
+        Some(other) => ResponseJson(serde_json::json!({"error": "wrong_type", "type": other.kind()})),
+        None => ResponseJson(serde_json::json!({"error": "not_found"})),
+    }
+}
+
+// List keys associated with a tag.
+async fn keys_by_tag_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Query(query): Query<KeysByTagQuery>) -> Result<ResponseJson<KeysByTagResponse>, Rejection> {
+    who.check_tag_members(&query.tag).map_err(forbidden)?;
+    let tag = who.namespace.tag(query.tag);
+    let mut keys = state.cache.get_keys_by_tag(&tag).iter().filter_map(|k| who.namespace.strip(&k.0)).map(str::to_string).collect::<Vec<_>>();
+    if let Some(limit) = query.limit { if keys.len() > limit { keys.truncate(limit); } }
+    Ok(ResponseJson(KeysByTagResponse { keys }))
+}
+
+// Invalidate single key.
+async fn invalidate_key_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(req): Json<InvalidateKeyRequest>) -> Result<ResponseJson<InvalidateResponse>, Rejection> {
+    client_names([req.key.as_str()])?;
+    who.check_key(&req.key).map_err(forbidden)?;
+    let key = who.namespace.key(req.key);
+    let success = state.cache.invalidate_key(&key);
+    Ok(ResponseJson(InvalidateResponse { success, count: None }))
+}
+
+// Invalidate all keys with a tag.
+async fn invalidate_tag_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(req): Json<InvalidateTagRequest>) -> Result<ResponseJson<InvalidateResponse>, Rejection> {
+    client_names([req.tag.as_str()])?;
+    who.check_tag_members(&req.tag).map_err(forbidden)?;
+    let tag = who.namespace.tag(req.tag);
+    let count = state.cache.invalidate_tag(&tag);
+    Ok(ResponseJson(InvalidateResponse { success: count > 0, count: Some(count) }))
+}
+
+// Flush all keys of the namespace (every key in the default namespace)
+async fn flush_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer) -> ResponseJson<InvalidateResponse> { let count = state.cache.flush_namespace(&who.namespace);
+    let detail = match who.namespace.name() { Some(ns) => format!("{} keys in namespace {}", count, ns), None => format!("{} keys", count) };
+    state.audit.record(AuditEvent::Flush, Some(&who.name), peer, Some(detail));
+    ResponseJson(InvalidateResponse { success: true, count: Some(count) }) }
+
+// Return stats snapshot (the namespace's own counters inside a namespace).
+async fn stats_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated) -> axum::response::Response {
+    if !who.namespace.is_default() { return ResponseJson(state.cache.namespaces.stats(&who.namespace)).into_response(); }
+    let stats = state.cache.get_stats();
+    let hit_ratio = if stats.hits + stats.misses > 0 {            // Avoid divide by zero
+        stats.hits as f64 / (stats.hits + stats.misses) as f64
+    } else { 0.0 };
+    // aggregate item & byte counts
+    let mut items = 0usize;
+    let mut bytes = 0usize;
+    let mut tag_set: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut shard_items_vec = Vec::with_capacity(state.cache.shards.len());
+    let mut shard_bytes_vec = Vec::with_capacity(state.cache.shards.len());
+    for shard in &state.cache.shards {
+        // Expired-but-not-yet-reaped entries are excluded so they don't inflate item/byte counts.
+        let mut si = 0usize;
+        let mut sb = 0usize;
+        for e in shard.entries.iter() { if e.value().is_expired() { continue; } si += 1; sb += e.value().value.size(); }
+        shard_items_vec.push(si);
+        shard_bytes_vec.push(sb);
+        items += si;
+        bytes += sb;
+        for t in shard.tag_to_keys.iter() { tag_set.insert(t.key().0.clone()); }
+    }
+    ResponseJson(StatsResponse {                                   // Build JSON struct
+        hits: stats.hits,
+        misses: stats.misses,
+        puts: stats.puts,
+        invalidations: stats.invalidations,
+        hit_ratio,
+        items,
+        bytes,
+        tags: tag_set.len(),
+        shard_count: state.cache.shards.len(),
+        shard_items: shard_items_vec,
+        shard_bytes: shard_bytes_vec,
+        expired: stats.expired,
+        expiry_lag_ms: stats.expiry_lag_ms,
+        expiry_pending: state.cache.pending_expirations(),
+        reclaimed: stats.reclaimed,
+        reclaim_pending: state.cache.pending_reclaims(),
+        event_subscribers: state.cache.events.subscriber_count(),
+        replication: state.replication.status(),
+    }).into_response()
+}
+
+// GET /admin/namespaces -> usage, counters and quotas of every namespace (admin only)
+async fn namespaces_handler(State(state): State<Arc<AppState>>, _auth: Authenticated) -> ResponseJson<serde_json::Value> {
+    ResponseJson(serde_json::json!({"namespaces": state.cache.namespaces.list()}))
+}
+
+// GET /admin/tag-ttl -> the tag TTL policies of the namespace
+async fn tag_ttl_list_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated) -> ResponseJson<serde_json::Value> {
+    let policies: Vec<serde_json::Value> = state.cache.tag_ttl.list().into_iter()
+        .filter_map(|(tag, ttl, mode)| Some(serde_json::json!({"tag": who.namespace.strip(&tag.0)?, "ttl_ms": ttl.as_millis() as u64, "mode": mode.as_str()})))
+        .collect();
+    ResponseJson(serde_json::json!({"policies": policies}))
+}
+
+// PUT /admin/tag-ttl/:tag { ttl_ms | ttl_seconds, mode: min|override } -> add or replace a policy
+async fn tag_ttl_set_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(tag): Path<String>, Json(body): Json<TagTtlBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    who.check_tag_members(&tag).map_err(forbidden)?;
+    let ttl = body.ttl_ms.map(Duration::from_millis).or_else(|| body.ttl_seconds.map(Duration::from_secs))
+        .ok_or_else(|| invalid_body("ttl_ms or ttl_seconds is required".to_string()))?;
+    let mode = match body.mode.as_deref() {
+        None => TtlMode::Min,
+        Some(m) => TtlMode::parse(m).ok_or_else(|| invalid_body(format!("unknown mode '{}' (expected min or override)", m)))?,
+    };
+    state.cache.tag_ttl.set(who.namespace.tag(tag.clone()), ttl, mode);
+    Ok(ResponseJson(serde_json::json!({"ok": true, "tag": tag, "ttl_ms": ttl.as_millis() as u64, "mode": mode.as_str()})))
+}
+
+// DELETE /admin/tag-ttl/:tag
+async fn tag_ttl_delete_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(tag): Path<String>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    who.check_tag_members(&tag).map_err(forbidden)?;
+    Ok(ResponseJson(serde_json::json!({"ok": true, "removed": state.cache.tag_ttl.remove(&who.namespace.tag(tag))})))
+}
+
+// =============================
+// REST: GET /keys/:key -> metadata
+// =============================
+#[derive(Deserialize)]
+pub struct KeyPathQuery { pub path: Option<String> }
+
+async fn rest_get_key(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Query(q): Query<KeyPathQuery>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    // `?path=` reads part of the value (JSON Pointer or JSONPath, see document.rs)
+    let path = q.path.as_deref().map(JsonPath::parse).transpose().map_err(value_rejection)?;
+    let key_wrap = who.namespace.key(key.clone());
+    let shard_idx = state.cache.hash_key(&key_wrap);
+    let shard = &state.cache.shards[shard_idx];
+    if let Some(entry) = shard.entries.get(&key_wrap) {
+        if entry.is_expired() { return Ok(ResponseJson(serde_json::json!({"error":"not_found"}))); } // The reaper removes it
+        let remaining = entry.ttl.map(|ttl| {
+            let elapsed = entry.created_at.elapsed();
+            if elapsed >= ttl { 0 } else { (ttl - elapsed).as_millis() as u64 }
+        });
+        // try parse JSON value
+    let parsed = match &path {
+        Some(path) => match entry.value.json_at(path) {
+            Some(part) => part,
+            None => return Ok(ResponseJson(serde_json::json!({"error":"not_found","path": q.path}))),
+        },
+        None => entry.value.to_json(),
+    };
+    let tags = who.namespace.strip_tags(&entry.tags);
+    let created_ms = entry.created_system.duration_since(UNIX_EPOCH).ok().map(|d| d.as_millis() as u64);
+    let mut body = serde_json::json!({"key": key, "type": entry.value.kind(), "value": parsed, "ttl_ms": remaining, "tags": tags, "created_ms": created_ms});
+    if let Some(p) = q.path { body["path"] = serde_json::Value::String(p); }
+    return Ok(ResponseJson(body));
+    }
+    Ok(ResponseJson(serde_json::json!({"error":"not_found"})))
+}
+
+// PUT /keys/:key
+async fn rest_put_key(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<KeyUpsertBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    client_names(body.tags.iter().flatten().map(String::as_str))?;
+    who.check_tags(body.tags.iter().flatten().map(String::as_str)).map_err(forbidden)?; // The key was checked from the path
+    let ttl = body.ttl_ms.map(Duration::from_millis);
+    let tags_vec = body.tags.unwrap_or_default().into_iter().map(|t| who.namespace.tag(t)).collect::<Vec<_>>();
+    // store string representation (or the collection given with `type`)
+    let kind = match body.kind.as_deref() {
+        Some(k) => ValueType::parse(k).ok_or_else(|| invalid_body(format!("unknown type '{}'", k)))?,
+        None => ValueType::String,
+    };
+    let value = Value::from_json(kind, body.value).map_err(invalid_body)?;
+    let key = who.namespace.key(key);
+    state.cache.admit_write(&key, value.size()).map_err(namespace_rejection)?;
+    let ttl_ms = state.cache.put_value(key, value, tags_vec, ttl).map(|d| d.as_millis() as u64);
+    Ok(ResponseJson(serde_json::json!({"ok":true,"ttl_ms": ttl_ms})))
+}
+
+// DELETE /keys/:key
+async fn rest_delete_key(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>) -> ResponseJson<serde_json::Value> {
+    let removed = state.cache.invalidate_key(&who.namespace.key(key));
+    ResponseJson(serde_json::json!({"ok": removed, "deleted": if removed {1} else {0}}))
+}
+
+// =============================
+// REST: hashes, lists and sets under /keys/:key/{hash,list,set}
+// =============================
+#[derive(Deserialize)]
+pub struct HashFieldBody { pub value: String, pub ttl_ms: Option<u64>, pub tags: Option<Vec<String>> }
+#[derive(Deserialize)]
+pub struct HashIncrBody { pub by: Option<i64>, pub ttl_ms: Option<u64>, pub tags: Option<Vec<String>> }
+#[derive(Deserialize)]
+pub struct ListPushBody { pub items: Vec<String>, pub side: Option<String>, pub ttl_ms: Option<u64>, pub tags: Option<Vec<String>> }
+#[derive(Deserialize)]
+pub struct ListSideQuery { pub side: Option<String> }
+#[derive(Deserialize)]
+pub struct ListRangeQuery { pub start: Option<i64>, pub stop: Option<i64> }
+#[derive(Deserialize)]
+pub struct SetMembersBody { pub members: Vec<String>, pub ttl_ms: Option<u64>, pub tags: Option<Vec<String>> }
+#[derive(Deserialize)]
+pub struct KeyTagsBody { pub add: Option<Vec<String>>, pub remove: Option<Vec<String>>, pub replace: Option<Vec<String>> }
+
+fn invalid_body(error: String) -> Rejection {
+    (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": error})))
+}
+
+fn value_rejection(e: ValueError) -> Rejection {
+    match e {
+        ValueError::WrongType(found) => (StatusCode::CONFLICT, ResponseJson(serde_json::json!({"error": e.code(), "type": found}))),
+        ValueError::InvalidPath(_) | ValueError::UnknownOperation(_) => (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": e.code(), "detail": e.to_string()}))),
+        _ => (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": e.code()}))),
+    }
+}
+
+// Namespaced tags of a collection write, after the scope check.
+fn write_tags(who: &Principal, tags: Option<Vec<String>>) -> Result<Vec<Tag>, Rejection> {
+    let tags = tags.unwrap_or_default();
+    client_names(tags.iter().map(String::as_str))?;
+    who.check_tags(tags.iter().map(String::as_str)).map_err(forbidden)?; // The key was checked from the path
+    Ok(tags.into_iter().map(|t| who.namespace.tag(t)).collect())
+}
+
+// `left` (head) or `right` (tail) of a list.
+fn list_side(side: Option<&str>, default_left: bool) -> Result<bool, Rejection> {
+    match side.map(|s| s.to_ascii_lowercase()).as_deref() {
+        None => Ok(default_left),
+        Some("left") => Ok(true),
+        Some("right") => Ok(false),
+        Some(other) => Err(invalid_body(format!("unknown side '{}' (expected left or right)", other))),
+    }
+}
+
+// GET /keys/:key/hash
+async fn hash_getall_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let fields = state.cache.hgetall(&who.namespace.key(key.clone())).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"key": key, "fields": fields})))
+}
+
+// GET /keys/:key/hash/:field
+async fn hash_get_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path((key, field)): Path<(String, String)>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    match state.cache.hget(&who.namespace.key(key), &field).map_err(value_rejection)? {
+        Some(value) => Ok(ResponseJson(serde_json::json!({"value": value}))),
+        None => Ok(ResponseJson(serde_json::json!({"error": "not_found"}))),
+    }
+}
+
+// PUT /keys/:key/hash/:field { value, ttl_ms?, tags? }
+async fn hash_set_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path((key, field)): Path<(String, String)>, Json(body): Json<HashFieldBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let tags = write_tags(&who, body.tags)?;
+    let key = who.namespace.key(key);
+    state.cache.admit_update(&key, field.len() + body.value.len()).map_err(namespace_rejection)?;
+    let created = state.cache.hset(key, field, body.value, tags, body.ttl_ms.map(Duration::from_millis)).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"ok": true, "created": created})))
+}
+
+// DELETE /keys/:key/hash/:field
+async fn hash_del_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path((key, field)): Path<(String, String)>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let removed = state.cache.hdel(who.namespace.key(key), &field).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"ok": removed, "deleted": if removed {1} else {0}})))
+}
+
+// POST /keys/:key/hash/:field/incr { by?, ttl_ms?, tags? }
+async fn hash_incr_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path((key, field)): Path<(String, String)>, Json(body): Json<HashIncrBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let tags = write_tags(&who, body.tags)?;
+    let key = who.namespace.key(key);
+    let by = body.by.unwrap_or(1);
+    state.cache.admit_update(&key, field.len() + by.to_string().len()).map_err(namespace_rejection)?;
+    let value = state.cache.hincrby(key, field, by, tags, body.ttl_ms.map(Duration::from_millis)).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"ok": true, "value": value})))
+}
+
+// GET /keys/:key/list?start=0&stop=-1
+async fn list_range_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Query(q): Query<ListRangeQuery>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let items = state.cache.lrange(&who.namespace.key(key.clone()), q.start.unwrap_or(0), q.stop.unwrap_or(-1)).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"key": key, "items": items})))
+}
+
+// POST /keys/:key/list/push { items, side?: "left"|"right" (default right), ttl_ms?, tags? }
+async fn list_push_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<ListPushBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let left = list_side(body.side.as_deref(), false)?;
+    let tags = write_tags(&who, body.tags)?;
+    let key = who.namespace.key(key);
+    state.cache.admit_update(&key, body.items.iter().map(String::len).sum()).map_err(namespace_rejection)?;
+    let ttl = body.ttl_ms.map(Duration::from_millis);
+    let len = if left { state.cache.lpush(key, body.items, tags, ttl) } else { state.cache.rpush(key, body.items, tags, ttl) }.map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"ok": true, "len": len})))
+}
+
+// POST /keys/:key/list/pop?side=left|right (default left)
+async fn list_pop_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Query(q): Query<ListSideQuery>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let key = who.namespace.key(key);
+    let value = if list_side(q.side.as_deref(), true)? { state.cache.lpop(key) } else { state.cache.rpop(key) }.map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"value": value})))
+}
+
+// GET /keys/:key/set
+async fn set_members_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let members = state.cache.smembers(&who.namespace.key(key.clone())).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"key": key, "members": members})))
+}
+
+// POST /keys/:key/set/add { members, ttl_ms?, tags? }
+async fn set_add_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<SetMembersBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let tags = write_tags(&who, body.tags)?;
+    let key = who.namespace.key(key);
+    state.cache.admit_update(&key, body.members.iter().map(String::len).sum()).map_err(namespace_rejection)?;
+    let added = state.cache.sadd(key, body.members, tags, body.ttl_ms.map(Duration::from_millis)).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"ok": true, "added": added})))
+}
+
+// POST /keys/:key/set/remove { members }
+async fn set_remove_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<SetMembersBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let removed = state.cache.srem(who.namespace.key(key), &body.members).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"ok": true, "removed": removed})))
+}
+
+// POST /keys/:key/tags { add?, remove? } or { replace } - retag without touching value, TTL or age
+async fn key_tags_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<KeyTagsBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    if body.replace.is_some() && (body.add.is_some() || body.remove.is_some()) {
+        return Err(invalid_body("replace cannot be combined with add or remove".to_string()));
+    }
+    if body.replace.is_none() && body.add.is_none() && body.remove.is_none() {
+        return Err(invalid_body("one of add, remove or replace is required".to_string()));
+    }
+    let (add, remove) = (write_tags(&who, body.add)?, write_tags(&who, body.remove)?);
+    let replace = body.replace.map(|tags| write_tags(&who, Some(tags))).transpose()?;
+    let edited = state.cache.edit_tags(&who.namespace.key(key.clone()), |tags| match replace {
+        Some(replace) => *tags = replace,
+        None => {
+            tags.retain(|t| !remove.contains(t));
+            tags.extend(add);
+        }
+    });
+    match edited {
+        Some((tags, changed)) => Ok(ResponseJson(serde_json::json!({"ok": true, "key": key, "tags": who.namespace.strip_tags(&tags), "changed": changed}))),
+        None => Ok(ResponseJson(serde_json::json!({"error": "not_found"}))),
+    }
+}
+
+// =============================
+// REST: JSON documents (PATCH /keys/:key)
+// =============================
+#[derive(Deserialize)]
+pub struct JsonPatchBody {
+    pub op: String,                  // set, merge, append or incr
+    #[serde(default)]
+    pub path: String,                // JSON Pointer or JSONPath; empty / "$" = the whole document
+    #[serde(default)]
+    pub value: serde_json::Value,    // The new value, merge patch, item(s) to append or increment
+    pub ttl_ms: Option<u64>,
+    pub tags: Option<Vec<String>>,
+}
+
+// PATCH /keys/:key { op, path, value, ttl_ms?, tags? } -> the value now at `path`
+async fn json_patch_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<JsonPatchBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let path = JsonPath::parse(&body.path).map_err(value_rejection)?;
+    let op = JsonOp::parse(&body.op, body.value).map_err(value_rejection)?;
+    let tags = write_tags(&who, body.tags)?;
+    let key = who.namespace.key(key);
+    state.cache.admit_update(&key, op_size(&op)).map_err(namespace_rejection)?;
+    let value = state.cache.json_update(key, &path, op, tags, body.ttl_ms.map(Duration::from_millis)).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"ok": true, "path": body.path, "value": value})))
+}
+
+// Bytes an update may add, for the namespace quota check.
+fn op_size(op: &JsonOp) -> usize {
+    match op {
+        JsonOp::Set(v) | JsonOp::Merge(v) => v.to_string().len(),
+        JsonOp::Append(items) => items.iter().map(|v| v.to_string().len()).sum(),
+        JsonOp::Incr(_) => 0,
+    }
+}
+
+// =============================
+// REST: rate limiters (POST /keys/:key/ratelimit)
+// =============================
+#[derive(Deserialize)]
+pub struct RateLimitBody {
+    pub algorithm: String,           // fixed_window, sliding_window or token_bucket
+    pub limit: u64,                  // Requests per window
+    pub window_ms: u64,
+    pub tags: Option<Vec<String>>,
+}
+
+// POST /keys/:key/ratelimit { algorithm, limit, window_ms, tags? } -> { allowed, limit, remaining, reset_after_ms }
+async fn rate_limit_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<RateLimitBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let algorithm = Algorithm::parse(&body.algorithm)
+        .ok_or_else(|| invalid_body(format!("unknown algorithm '{}' (expected fixed_window, sliding_window or token_bucket)", body.algorithm)))?;
+    let limit = RateLimit::new(algorithm, body.limit, Duration::from_millis(body.window_ms)).map_err(invalid_body)?;
+    let tags = write_tags(&who, body.tags)?;
+    let key = who.namespace.key(key);
+    state.cache.admit_update(&key, 13).map_err(namespace_rejection)?; // At most one timestamp per request
+    let d = state.cache.rate_limit(key, &limit, tags).map_err(value_rejection)?;
+    Ok(ResponseJson(serde_json::json!({
+        "ok": true, "allowed": d.allowed, "limit": d.limit, "remaining": d.remaining, "reset_after_ms": d.reset_after.as_millis() as u64,
+    })))
+}
+
+// =============================
+// REST: locks (POST /keys/:key/lock/{acquire,renew,release})
+// =============================
+#[derive(Deserialize)]
+pub struct LockAcquireBody {
+    pub ttl_ms: u64,                 // Lease
+    pub wait_ms: Option<u64>,        // Keep trying this long while the lock is held (default: fail at once)
+    pub tags: Option<Vec<String>>,
+}
+#[derive(Deserialize)]
+pub struct LockRenewBody { pub owner: String, pub ttl_ms: u64 }
+#[derive(Deserialize)]
+pub struct LockReleaseBody { pub owner: String }
+
+fn lock_rejection(e: LockError) -> Rejection {
+    let body = match &e {
+        LockError::Held(left) => serde_json::json!({"error": e.code(), "retry_after_ms": left.as_millis() as u64}),
+        LockError::WrongType(found) => serde_json::json!({"error": e.code(), "type": found}),
+        LockError::NotHeld | LockError::NotOwner => serde_json::json!({"error": e.code()}),
+    };
+    let status = if e == LockError::NotHeld { StatusCode::NOT_FOUND } else { StatusCode::CONFLICT };
+    (status, ResponseJson(body))
+}
+
+fn lease_json(lease: &Lease) -> serde_json::Value {
+    serde_json::json!({"ok": true, "owner": lease.owner, "fence": lease.fence, "ttl_ms": lease.ttl.as_millis() as u64})
+}
+
+// POST /keys/:key/lock/acquire { ttl_ms, wait_ms?, tags? } -> { owner, fence, ttl_ms } | 409 locked
+async fn lock_acquire_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<LockAcquireBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    if body.ttl_ms == 0 { return Err(invalid_body("ttl_ms must be at least 1".to_string())); }
+    let tags = write_tags(&who, body.tags)?;
+    let key = who.namespace.key(key);
+    state.cache.admit_update(&key, 48).map_err(namespace_rejection)?; // Owner and fencing tokens
+    let ttl = Duration::from_millis(body.ttl_ms);
+    let lease = match body.wait_ms {
+        Some(wait) if wait > 0 => lock::acquire_wait(&state.cache, key, ttl, tags, Duration::from_millis(wait)).await,
+        _ => state.cache.lock_acquire(key, ttl, tags),
+    };
+    Ok(ResponseJson(lease_json(&lease.map_err(lock_rejection)?)))
+}
+
+// POST /keys/:key/lock/renew { owner, ttl_ms } -> { owner, fence, ttl_ms }
+async fn lock_renew_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<LockRenewBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    if body.ttl_ms == 0 { return Err(invalid_body("ttl_ms must be at least 1".to_string())); }
+    let lease = state.cache.lock_renew(&who.namespace.key(key), &body.owner, Duration::from_millis(body.ttl_ms)).map_err(lock_rejection)?;
+    Ok(ResponseJson(lease_json(&lease)))
+}
+
+// POST /keys/:key/lock/release { owner }
+async fn lock_release_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(key): Path<String>, Json(body): Json<LockReleaseBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    state.cache.lock_release(&who.namespace.key(key), &body.owner).map_err(lock_rejection)?;
+    Ok(ResponseJson(serde_json::json!({"ok": true})))
+}
+
+// POST /keys/bulk/get { keys: [] }
+async fn bulk_get_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(body): Json<BulkKeysBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    client_names(body.keys.iter().map(String::as_str))?;
+    body.keys.iter().try_for_each(|k| who.check_key(k)).map_err(forbidden)?;
+    let mut items: Vec<BulkGetItem> = Vec::with_capacity(body.keys.len());
+    for k in body.keys {
+        let key_wrap = who.namespace.key(k.clone());
+        let shard_idx = state.cache.hash_key(&key_wrap);
+        let shard = &state.cache.shards[shard_idx];
+        if let Some(entry) = shard.entries.get(&key_wrap) {
+            if entry.is_expired() { continue; } // Left for the reaper
+            let remaining = entry.ttl.map(|ttl| {
+                let elapsed = entry.created_at.elapsed();
+                if elapsed >= ttl { 0 } else { (ttl - elapsed).as_millis() as u64 }
+            });
+            let parsed = entry.value.to_json();
+            let tags = who.namespace.strip_tags(&entry.tags);
+            let created_ms = entry.created_system.duration_since(UNIX_EPOCH).ok().map(|d| d.as_millis() as u64);
+            items.push(BulkGetItem { key: k, value: parsed, ttl_ms: remaining, tags, created_ms });
+        }
+    }
+    Ok(ResponseJson(serde_json::json!({"items": items})))
+}
+
+// POST /keys/bulk/delete { keys: [] }
+async fn bulk_delete_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(body): Json<BulkKeysBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    client_names(body.keys.iter().map(String::as_str))?;
+    body.keys.iter().try_for_each(|k| who.check_key(k)).map_err(forbidden)?;
+    let mut count = 0usize;
+    for k in body.keys { if state.cache.invalidate_key(&who.namespace.key(k)) { count += 1; } }
+    Ok(ResponseJson(serde_json::json!({"success": true, "count": count})))
+}
+
+// POST /search
+async fn search_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(body): Json<SearchBody>) -> Result<ResponseJson<SearchResult>, Rejection> {
+    client_names(body.tag_all.iter().chain(&body.tag_any).flatten().chain(&body.q).map(String::as_str))?;
+    let ns = &who.namespace;
+    let mut results: Vec<SearchResultItem> = Vec::new();
+    let limit = body.limit.unwrap_or(100);
+    // Build tag sets for all/all semantics
+    if body.tag_all.as_ref().map(|v| !v.is_empty()).unwrap_or(false) {
+        // Intersection of keys across all tags
+        let tag_objs: Vec<Tag> = body.tag_all.clone().unwrap().into_iter().map(|t| ns.tag(t)).collect();
+    let key_counts: dashmap::DashMap<String, usize> = dashmap::DashMap::new();
+        for tag in &tag_objs {
+            let keys = state.cache.get_keys_by_tag(tag);
+            for k in keys { key_counts.entry(k.0).and_modify(|c| *c+=1).or_insert(1); }
+        }
+        for kv in key_counts.iter() {
+            if *kv.value() == tag_objs.len() { // present in all
+                if results.len() >= limit { break; }
+                // fetch metadata
+                if let Some(meta) = fetch_meta_simple(&state.cache, ns, kv.key()) { results.push(meta); }
+            }
+        }
+    } else if body.tag_any.as_ref().map(|v| !v.is_empty()).unwrap_or(false) {
+        let mut seen = std::collections::HashSet::new();
+    for t in body.tag_any.clone().unwrap() { if results.len()>=limit { break; } let keys = state.cache.get_keys_by_tag(&ns.tag(t)); for k in keys { if seen.insert(k.0.clone()) { if let Some(meta) = fetch_meta_simple(&state.cache, ns, &k.0) { results.push(meta); if results.len()>=limit { break; } } } } }
+    } else if let Some(q) = body.q.clone() {
+        let qlower = q.to_string();
+    for shard in &state.cache.shards {
+            for entry in shard.entries.iter() {
+                let kref = &entry.key().0;
+                if ns.strip(kref).is_some_and(|k| k.starts_with(&qlower)) {
+            if let Some(meta) = fetch_meta_simple(&state.cache, ns, kref) { results.push(meta); }
+                    if results.len()>=limit { break; }
+                }
+            }
+            if results.len()>=limit { break; }
+        }
+    } else { // enumerate newest first across ALL shards (previously stopped after first shard hit limit)
+        for shard in &state.cache.shards {
+            for entry in shard.entries.iter() {
+                if entry.value().is_expired() { continue; }
+                if let Some(meta) = fetch_meta_simple(&state.cache, ns, &entry.key().0) { results.push(meta); }
+            }
+        }
+        // Sort newest first then enforce limit
+        results.sort_by_key(|r| std::cmp::Reverse(r.created_ms));
+        if results.len() > limit { results.truncate(limit); }
+    }
+    Ok(ResponseJson(SearchResult { keys: results }))
+}
+
+// Metadata of a stored key, if it is live and belongs to `ns` (key and tags as the client names them).
+fn fetch_meta_simple(cache: &Cache, ns: &Namespace, key_str: &str) -> Option<SearchResultItem> {
+    let name = ns.strip(key_str)?;
+    let key = Key(key_str.to_string());
+    let shard_idx = cache.hash_key(&key);
+    let shard = &cache.shards[shard_idx];
+    if let Some(entry) = shard.entries.get(&key) {
+        if entry.is_expired() { return None; }
+        let remaining = entry.ttl.map(|ttl| {
+            let elapsed = entry.created_at.elapsed();
+            if elapsed >= ttl { 0 } else { (ttl - elapsed).as_millis() as u64 }
+        });
+    let tags = ns.strip_tags(&entry.tags);
+    let created_ms = entry.created_system.duration_since(UNIX_EPOCH).ok().map(|d| d.as_millis() as u64);
+    return Some(SearchResultItem { key: name.to_string(), ttl_ms: remaining, tags, created_ms });
+    }
+    None
+}
+
+// POST /invalidate/tags
+async fn invalidate_tags_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(body): Json<InvalidateTagsBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    client_names(body.tags.iter().map(String::as_str))?;
+    who.check_tags_members(body.tags.iter().map(String::as_str)).map_err(forbidden)?;
+    let mode = match body.mode.as_deref() {
+        Some("all") => TagMatch::All,
+        Some("subtree" | "prefix") => TagMatch::Subtree,
+        _ => TagMatch::Any,
+    };
+    if mode == TagMatch::Subtree { body.tags.iter().try_for_each(|t| who.check_tag_tree(t)).map_err(forbidden)?; }
+    let tags: Vec<Tag> = body.tags.into_iter().map(|t| who.namespace.tag(t)).collect();
+    let count = state.cache.invalidate_tags(&tags, mode);
+    Ok(ResponseJson(serde_json::json!({"success": true, "count": count})))
+}
+
+// GET /tags/children?tag=tenant:42 - the tags one level below `tag` with their key counts
+async fn tag_children_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Query(query): Query<TagChildrenQuery>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let parent = query.tag.unwrap_or_default();
+    who.check_tag_tree(&parent).map_err(forbidden)?;
+    let children: Vec<serde_json::Value> = state.cache.tag_children(&who.namespace.tag(parent.clone())).into_iter()
+        .filter_map(|(tag, count)| Some(serde_json::json!({"tag": who.namespace.strip(&tag.0)?, "count": count})))
+        .collect();
+    Ok(ResponseJson(serde_json::json!({"tag": parent, "separator": state.cache.tag_separator(), "children": children})))
+}
+
+// GET /tags?prefix=&sort=keys&order=desc&limit=100&offset=0 - tags with key counts and bytes
+async fn list_tags_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Query(q): Query<TagListQuery>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let prefix = q.prefix.unwrap_or_default();
+    let mut tags: Vec<(String, usize, usize)> = state.cache.tag_stats().into_iter()
+        .filter_map(|s| Some((who.namespace.strip(&s.tag.0)?.to_string(), s.keys, s.bytes)))
+        .filter(|(tag, _, _)| tag.starts_with(&prefix))
+        .collect();
+    let sort = q.sort.as_deref().unwrap_or("name");
+    let descending = match q.order.as_deref() {
+        None => sort != "name",
+        Some("asc") => false,
+        Some("desc") => true,
+        Some(other) => return Err(invalid_body(format!("unknown order '{}' (expected asc or desc)", other))),
+    };
+    // Tags come in name order; the stable sorts keep it among equal counts.
+    match (sort, descending) {
+        ("name", false) => {}
+        ("name", true) => tags.reverse(),
+        ("keys", false) => tags.sort_by_key(|t| t.1),
+        ("keys", true) => tags.sort_by_key(|t| std::cmp::Reverse(t.1)),
+        ("bytes", false) => tags.sort_by_key(|t| t.2),
+        ("bytes", true) => tags.sort_by_key(|t| std::cmp::Reverse(t.2)),
+        (other, _) => return Err(invalid_body(format!("unknown sort '{}' (expected name, keys or bytes)", other))),
+    }
+    let total = tags.len();
+    let (offset, limit) = (q.offset.unwrap_or(0), q.limit.unwrap_or(100));
+    let page: Vec<serde_json::Value> = tags.into_iter().skip(offset).take(limit)
+        .map(|(tag, keys, bytes)| serde_json::json!({"tag": tag, "keys": keys, "bytes": bytes}))
+        .collect();
+    Ok(ResponseJson(serde_json::json!({"tags": page, "total": total, "offset": offset, "limit": limit})))
+}
+
+// GET /tags/:tag
+async fn tag_info_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(tag): Path<String>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    who.check_tag(&tag).map_err(forbidden)?;
+    match state.cache.tag_info(&who.namespace.tag(tag.clone())) {
+        Some(s) => Ok(ResponseJson(serde_json::json!({"tag": tag, "keys": s.keys, "bytes": s.bytes}))),
+        None => Ok(ResponseJson(serde_json::json!({"error": "not_found"}))),
+    }
+}
+
+// POST /tags/:tag/rename { to }
+async fn rename_tag_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(tag): Path<String>, Json(body): Json<TagRenameBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    client_names([body.to.as_str()])?;
+    who.check_tags_members([tag.as_str(), body.to.as_str()]).map_err(forbidden)?;
+    if body.to.is_empty() { return Err(invalid_body("to must not be empty".to_string())); }
+    let count = state.cache.rename_tag(&who.namespace.tag(tag), &who.namespace.tag(body.to));
+    Ok(ResponseJson(serde_json::json!({"success": true, "count": count})))
+}
+
+// POST /tags/:tag/merge { from: [..] } - fold the `from` tags into :tag
+async fn merge_tags_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(tag): Path<String>, Json(body): Json<TagMergeBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    client_names(body.from.iter().map(String::as_str))?;
+    who.check_tags_members(body.from.iter().map(String::as_str).chain([tag.as_str()])).map_err(forbidden)?;
+    let sources: Vec<Tag> = body.from.into_iter().map(|t| who.namespace.tag(t)).collect();
+    let count = state.cache.merge_tags(&sources, &who.namespace.tag(tag));
+    Ok(ResponseJson(serde_json::json!({"success": true, "count": count})))
+}
+
+// POST /tags/:tag/add and /tags/:tag/remove { prefix?, tags? } - on every key matching the query
+fn tag_apply(state: &AppState, who: &Principal, tag: String, body: TagApplyBody, add: bool) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    who.check_unscoped().map_err(forbidden)?;
+    client_names(body.prefix.iter().chain(&body.tags).map(String::as_str))?;
+    let filter = transfer::TransferFilter::parse(body.prefix, body.tags.as_deref()).map_err(invalid_body)?;
+    if filter.is_empty() { return Err(invalid_body("prefix or tags is required".to_string())); }
+    let tag = [who.namespace.tag(tag)];
+    let filter = who.namespace.qualify_filter(filter);
+    let count = if add { state.cache.retag_matching(&filter, &tag, &[]) } else { state.cache.retag_matching(&filter, &[], &tag) };
+    Ok(ResponseJson(serde_json::json!({"success": true, "count": count})))
+}
+
+async fn tag_add_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(tag): Path<String>, Json(body): Json<TagApplyBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    tag_apply(&state, &who, tag, body, true)
+}
+
+async fn tag_remove_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(tag): Path<String>, Json(body): Json<TagApplyBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    tag_apply(&state, &who, tag, body, false)
+}
+
+// POST /tags/:tag/expire { at_ms } or { in_ms } - every key carrying :tag expires by then
+async fn tag_expire_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(tag): Path<String>, Json(body): Json<TagExpireBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    who.check_tag_members(&tag).map_err(forbidden)?;
+    let at = match (body.at_ms, body.in_ms) {
+        (Some(at), None) => UNIX_EPOCH + Duration::from_millis(at),
+        (None, Some(delay)) => SystemTime::now() + Duration::from_millis(delay),
+        _ => return Err(invalid_body("exactly one of at_ms or in_ms is required".to_string())),
+    };
+    let count = state.cache.expire_tag_at(&who.namespace.tag(tag), at);
+    Ok(ResponseJson(serde_json::json!({"success": true, "count": count})))
+}
+
+// A principal may schedule (and see) the invalidations it could run itself.
+fn check_schedule_target(who: &Principal, target: &Target) -> Result<(), String> {
+    match target {
+        Target::Tags { tags, mode } if mode == "subtree" || mode == "prefix" => tags.iter().try_for_each(|t| who.check_tag_tree(t)),
+        Target::Tags { tags, .. } => who.check_tags_members(tags.iter().map(String::as_str)),
+        Target::Keys { keys } => keys.iter().try_for_each(|k| who.check_key(k)),
+        Target::Query { .. } => who.check_unscoped(),
+    }
+}
+
+// GET /schedules -> pending schedules of the namespace, soonest first
+async fn schedules_list_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated) -> ResponseJson<serde_json::Value> {
+    let schedules: Vec<Schedule> = state.schedules.list(&who.namespace).into_iter()
+        .filter(|s| check_schedule_target(&who, &s.target).is_ok()).collect();
+    ResponseJson(serde_json::json!({"schedules": schedules, "count": schedules.len()}))
+}
+
+// POST /schedules {target, ..., at_ms | in_ms, id?}
+async fn schedule_create_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer, Json(body): Json<ScheduleBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    body.target.validate().map_err(invalid_body)?;
+    check_schedule_target(&who, &body.target).map_err(forbidden)?;
+    let now = schedule::now_ms();
+    let at_ms = match (body.at_ms, body.in_ms) {
+        (Some(at), None) => at,
+        (None, Some(delay)) => now.saturating_add(delay),
+        _ => return Err(invalid_body("exactly one of at_ms or in_ms is required".to_string())),
+    };
+    let id = body.id.filter(|id| !id.is_empty()).unwrap_or_else(schedule::new_id);
+    if let Some(existing) = state.schedules.get(&id) {
+        if existing.namespace.as_deref() != who.namespace.name() { return Err(invalid_body(format!("schedule id '{}' is taken", id))); }
+    }
+    let schedule = Schedule {
+        id, at_ms, target: body.target,
+        namespace: who.namespace.name().map(str::to_string),
+        created_by: Some(who.name.clone()),
+        created_ms: now,
+    };
+    state.schedules.add(schedule.clone(), peer);
+    Ok(ResponseJson(serde_json::json!({"ok": true, "schedule": schedule})))
+}
+
+// GET /schedules/:id
+async fn schedule_get_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Path(id): Path<String>) -> ResponseJson<serde_json::Value> {
+    match state.schedules.get(&id).filter(|s| s.namespace.as_deref() == who.namespace.name() && check_schedule_target(&who, &s.target).is_ok()) {
+        Some(schedule) => ResponseJson(serde_json::json!({"schedule": schedule})),
+        None => ResponseJson(serde_json::json!({"error": "not_found"})),
+    }
+}
+
+// DELETE /schedules/:id -> cancel
+async fn schedule_cancel_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer, Path(id): Path<String>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let Some(schedule) = state.schedules.get(&id).filter(|s| s.namespace.as_deref() == who.namespace.name()) else {
+        return Ok(ResponseJson(serde_json::json!({"ok": false, "error": "not_found"})));
+    };
+    check_schedule_target(&who, &schedule.target).map_err(forbidden)?;
+    let cancelled = state.schedules.cancel(&id, Some(&who.name), peer).is_some(); // False if it just ran
+    Ok(ResponseJson(serde_json::json!({"ok": cancelled, "success": cancelled})))
+}
+
+// POST /invalidate/keys
+async fn invalidate_keys_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Json(body): Json<InvalidateKeysBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    client_names(body.keys.iter().map(String::as_str))?;
+    body.keys.iter().try_for_each(|k| who.check_key(k)).map_err(forbidden)?;
+    let mut count = 0usize;
+    for k in body.keys { if state.cache.invalidate_key(&who.namespace.key(k)) { count+=1; } }
+    Ok(ResponseJson(serde_json::json!({"success": true, "count": count})))
+}
+
+// AUTH handlers
+async fn login_handler(State(state): State<Arc<AppState>>, Peer(peer): Peer, Json(body): Json<LoginBody>) -> (StatusCode, ResponseJson<serde_json::Value>) {
+    match state.auth.login(&body.username, &body.password, peer.map(|a| a.ip())) {
+        Ok(principal) => {
+            state.audit.record(AuditEvent::Login, Some(&principal.name), peer, None);
+            let issued = state.auth.issue_token(principal);
+            (StatusCode::OK, ResponseJson(serde_json::json!(issued)))
+        }
+        Err(LoginError::LockedOut(wait)) => locked_out(wait),
+        Err(LoginError::Invalid(locked)) => {
+            audit_login_failure(&state.audit, &body.username, peer, "login", locked);
+            (StatusCode::UNAUTHORIZED, ResponseJson(serde_json::json!({"error":"invalid_credentials"})))
+        }
+    }
+}
+
+// POST /auth/refresh (Bearer session token) -> a new token with a fresh lifetime; the old one is revoked
+async fn refresh_handler(State(state): State<Arc<AppState>>, _auth: Authenticated, headers: axum::http::HeaderMap) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let issued = bearer(&headers).and_then(|t| state.auth.refresh_token(t))
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": "refresh needs a session token from /auth/login (not Basic auth or an API key)"}))))?;
+    Ok(ResponseJson(serde_json::json!(issued)))
+}
+
+// POST /auth/logout (Bearer session token) -> revokes that token
+async fn logout_handler(State(state): State<Arc<AppState>>, _auth: Authenticated, headers: axum::http::HeaderMap) -> ResponseJson<serde_json::Value> {
+    ResponseJson(serde_json::json!({"ok": bearer(&headers).is_some_and(|t| state.auth.revoke_token(t))}))
+}
+
+// Sessions: GET /auth/tokens, DELETE /auth/tokens/:id (admin only)
+async fn list_tokens_handler(State(state): State<Arc<AppState>>, _auth: Authenticated) -> ResponseJson<serde_json::Value> {
+    ResponseJson(serde_json::json!({"tokens": state.auth.list_tokens()}))
+}
+
+async fn revoke_token_handler(State(state): State<Arc<AppState>>, _auth: Authenticated, Path(id): Path<String>) -> ResponseJson<serde_json::Value> {
+    ResponseJson(serde_json::json!({"ok": state.auth.revoke_token_id(&id)}))
+}
+
+// GET /auth/whoami -> the caller's name, role and scope (any role)
+async fn whoami_handler(Authenticated(who): Authenticated) -> ResponseJson<serde_json::Value> {
+    ResponseJson(serde_json::json!({"name": who.name, "role": who.role, "key_prefixes": who.scope.key_prefixes, "tags": who.scope.tags, "namespace": who.namespace.name()}))
+}
+
+// Accounts: GET/POST /auth/users, DELETE /auth/users/:username (admin only)
+#[derive(Deserialize)]
+pub struct UserBody { pub username: String, pub password: String, pub role: String, #[serde(default)] pub key_prefixes: Vec<String>, #[serde(default)] pub tags: Vec<String>, #[serde(default)] pub namespace: Option<String> }
+
+#[derive(Deserialize)]
+pub struct ApiKeyBody { pub name: String, pub role: String, #[serde(default)] pub key_prefixes: Vec<String>, #[serde(default)] pub tags: Vec<String>, #[serde(default)] pub namespace: Option<String> }
+
+fn parse_role(role: &str) -> Result<auth::Role, Rejection> {
+    auth::Role::parse(role).ok_or_else(|| (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": format!("unknown role '{}' (expected read_only, invalidator, writer or admin)", role)}))))
+}
+
+async fn list_users_handler(State(state): State<Arc<AppState>>, _auth: Authenticated) -> ResponseJson<serde_json::Value> {
+    ResponseJson(serde_json::json!({"users": state.auth.list_users()}))
+}
+
+async fn upsert_user_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer, Json(body): Json<UserBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let role = parse_role(&body.role)?;
+    let detail = format!("upsert user {} ({})", body.username, role.as_str());
+    let user = UserConfig { username: body.username, password: body.password, role, scope: Scope { key_prefixes: body.key_prefixes, tags: body.tags, namespace: body.namespace } };
+    state.auth.upsert_user(user).map_err(|e| (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": e}))))?;
+    state.audit.record(AuditEvent::AccountChange, Some(&who.name), peer, Some(detail));
+    Ok(ResponseJson(serde_json::json!({"ok": true})))
+}
+
+async fn delete_user_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer, Path(username): Path<String>) -> ResponseJson<serde_json::Value> {
+    let removed = state.auth.remove_user(&username);
+    if removed { state.audit.record(AuditEvent::AccountChange, Some(&who.name), peer, Some(format!("delete user {}", username))); }
+    ResponseJson(serde_json::json!({"ok": removed}))
+}
+
+// API keys: GET/POST /auth/api-keys, DELETE /auth/api-keys/:name (admin only). The key is only
+// returned once, by the POST that creates it.
+async fn list_api_keys_handler(State(state): State<Arc<AppState>>, _auth: Authenticated) -> ResponseJson<serde_json::Value> {
+    ResponseJson(serde_json::json!({"api_keys": state.auth.list_api_keys()}))
+}
+
+async fn create_api_key_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer, Json(body): Json<ApiKeyBody>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let role = parse_role(&body.role)?;
+    let key = state.auth.create_api_key(&body.name, role, Scope { key_prefixes: body.key_prefixes, tags: body.tags, namespace: body.namespace })
+        .map_err(|e| (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": e}))))?;
+    state.audit.record(AuditEvent::AccountChange, Some(&who.name), peer, Some(format!("create api key {} ({})", body.name, role.as_str())));
+    Ok(ResponseJson(serde_json::json!({"ok": true, "name": body.name, "key": key, "role": role})))
+}
+
+async fn delete_api_key_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer, Path(name): Path<String>) -> ResponseJson<serde_json::Value> {
+    let removed = state.auth.remove_api_key(&name);
+    if removed { state.audit.record(AuditEvent::AccountChange, Some(&who.name), peer, Some(format!("delete api key {}", name))); }
+    ResponseJson(serde_json::json!({"ok": removed}))
+}
+
+async fn rotate_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer) -> ResponseJson<RotateResponse> { let new = state.auth.rotate();
+    state.audit.record(AuditEvent::Rotate, Some(&who.name), peer, Some(format!("new username {}", new.username)));
+    ResponseJson(RotateResponse { ok: true, username: new.username, password: new.password }) }
+
+async fn change_password_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer, Json(body): Json<serde_json::Value>) -> ResponseJson<serde_json::Value> {
+    if let Some(new_password) = body.get("new_password").and_then(|p| p.as_str()) {
+        let success = state.auth.change_password(new_password.to_string());
+        state.audit.record(AuditEvent::PasswordChange, Some(&who.name), peer, None);
+        ResponseJson(serde_json::json!({"success": success}))
+    } else {
+        ResponseJson(serde_json::json!({"success": false, "error": "new_password is required"}))
+    }
+}
+
+async fn reset_credentials_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer) -> ResponseJson<serde_json::Value> {
+    let success = state.auth.reset_to_defaults();
+    state.audit.record(AuditEvent::CredentialsReset, Some(&who.name), peer, None);
+    ResponseJson(serde_json::json!({"success": success}))
+}
+
+// GET /admin/audit?event=&principal=&limit= -> recent audit records, newest first (admin only)
+async fn audit_handler(State(state): State<Arc<AppState>>, _auth: Authenticated, Query(query): Query<AuditQuery>) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    let entries = state.audit.recent(&query).map_err(|e| (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": e}))))?;
+    Ok(ResponseJson(serde_json::json!({"entries": entries})))
+}
+
+// POST /admin/reload -> re-read the accounts from the configuration file (admin only)
+async fn reload_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated, Peer(peer): Peer) -> Result<ResponseJson<serde_json::Value>, Rejection> {
+    state.auth.reload_accounts().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, ResponseJson(serde_json::json!({"error": e.to_string()}))))?;
+    state.audit.record(AuditEvent::ConfigReload, Some(&who.name), peer, Some("authentication".to_string()));
+    Ok(ResponseJson(serde_json::json!({"ok": true, "reloaded": ["authentication"]})))
+}
+
+
+
+/// Build the Axum HTTP router (all endpoints, the web UI fallback, cluster routing, the read-only
+/// replica guard and CORS for `allowed_origin`).
+pub fn build_app(app_state: Arc<AppState>, allowed_origin: Option<String>) -> Router {
+    let router = Router::new()
+        // Each route maps path + method to handler. State cloned into each closure.
+        .route("/put", post(put_handler))
+        .route("/add", post(add_handler))
+        .route("/incr", post(increment_handler))
+        .route("/decr", post(decrement_handler))
+        .route("/get/:key", get(get_handler))
+        .route("/keys-by-tag", get(keys_by_tag_handler))
+        .route("/invalidate-key", post(invalidate_key_handler))
+        .route("/invalidate-tag", post(invalidate_tag_handler))
+        .route("/flush", post(flush_handler))
+        .route("/stats", get(stats_handler))
+    // New RESTful routes
+    .route("/keys/:key", get(rest_get_key).put(rest_put_key).patch(json_patch_handler).delete(rest_delete_key))
+    .route("/keys/:key/hash", get(hash_getall_handler))
+    .route("/keys/:key/hash/:field", get(hash_get_handler).put(hash_set_handler).delete(hash_del_handler))
+    .route("/keys/:key/hash/:field/incr", post(hash_incr_handler))
+    .route("/keys/:key/list", get(list_range_handler))
+    .route("/keys/:key/list/push", post(list_push_handler))
+    .route("/keys/:key/list/pop", post(list_pop_handler))
+    .route("/keys/:key/set", get(set_members_handler))
+    .route("/keys/:key/set/add", post(set_add_handler))
+    .route("/keys/:key/set/remove", post(set_remove_handler))
+    .route("/keys/:key/tags", post(key_tags_handler))
+    .route("/keys/:key/ratelimit", post(rate_limit_handler))
+    .route("/keys/:key/lock/acquire", post(lock_acquire_handler))
+    .route("/keys/:key/lock/renew", post(lock_renew_handler))
+    .route("/keys/:key/lock/release", post(lock_release_handler))
+    .route("/search", post(search_handler))
+    .route("/keys", get(list_keys_handler))
+    .route("/invalidate/tags", post(invalidate_tags_handler))
+    .route("/invalidate/keys", post(invalidate_keys_handler))
+    .route("/tags", get(list_tags_handler))
+    .route("/tags/children", get(tag_children_handler))
+    .route("/tags/:tag", get(tag_info_handler))
+    .route("/tags/:tag/rename", post(rename_tag_handler))
+    .route("/tags/:tag/merge", post(merge_tags_handler))
+    .route("/tags/:tag/add", post(tag_add_handler))
+    .route("/tags/:tag/remove", post(tag_remove_handler))
+    .route("/tags/:tag/expire", post(tag_expire_handler))
+    .route("/schedules", get(schedules_list_handler).post(schedule_create_handler))
+    .route("/schedules/:id", get(schedule_get_handler).delete(schedule_cancel_handler))
+    .route("/keys/bulk/get", post(bulk_get_handler))
+    .route("/keys/bulk/delete", post(bulk_delete_handler))
+        // auth endpoints
+        .route("/auth/login", post(login_handler))
+        .route("/auth/rotate", post(rotate_handler))
+        .route("/auth/change_password", post(change_password_handler))
+        .route("/auth/reset", post(reset_credentials_handler))
+        .route("/auth/whoami", get(whoami_handler))
+        .route("/auth/refresh", post(refresh_handler))
+        .route("/auth/logout", post(logout_handler))
+        .route("/auth/tokens", get(list_tokens_handler))
+        .route("/auth/tokens/:id", axum::routing::delete(revoke_token_handler))
+        .route("/auth/users", get(list_users_handler).post(upsert_user_handler))
+        .route("/auth/users/:username", axum::routing::delete(delete_user_handler))
+        .route("/auth/api-keys", get(list_api_keys_handler).post(create_api_key_handler))
+        .route("/auth/api-keys/:name", axum::routing::delete(delete_api_key_handler))
+    .route("/health", get(health_handler))
+    .route("/system", get(system_handler))
+    .route("/events", get(events_sse_handler))
+    .route("/events/ws", get(events_ws_handler))
+    .route("/replication", get(replication_status_handler))
+    .route("/replication/promote", post(promote_handler))
+    .route("/cluster", get(cluster_status_handler))
+    .route("/admin/export", get(export_handler))
+        .route("/admin/audit", get(audit_handler))
+        .route("/admin/reload", post(reload_handler))
+        .route("/admin/namespaces", get(namespaces_handler))
+        .route("/admin/tag-ttl", get(tag_ttl_list_handler))
+        .route("/admin/tag-ttl/:tag", axum::routing::put(tag_ttl_set_handler).delete(tag_ttl_delete_handler))
+    .route("/admin/import", post(import_handler))
+        // Serve the React UI for all other routes (SPA routing)
+        .fallback(static_handler)
+    .layer(axum::middleware::from_fn_with_state(app_state.clone(), cluster::route_request))
+    .layer(axum::middleware::from_fn_with_state(app_state.clone(), replica_write_guard))
+    .with_state(app_state.clone());
+    // The /ns/<name>/ prefix is rewritten before routing, so it works for every endpoint.
+    let router = Router::new().fallback_service(router).layer(axum::middleware::from_fn(namespace_prefix));
+
+    // CORS: allow specified origin or fallback * (dev). Allow auth headers.
+    let cors = if let Some(origin) = allowed_origin { CorsLayer::very_permissive().allow_origin(origin.parse::<axum::http::HeaderValue>().unwrap()) } else { CorsLayer::very_permissive() };
+    router.layer(cors)
+}
+
+// =============================
+// REPLICATION: GET /replication, POST /replication/promote
+// =============================
+async fn replication_status_handler(State(state): State<Arc<AppState>>, _auth: Authenticated) -> ResponseJson<replication::ReplicationStatus> {
+    ResponseJson(state.replication.status())
+}
+
+async fn promote_handler(State(state): State<Arc<AppState>>, _auth: Authenticated) -> ResponseJson<serde_json::Value> {
+    let previous = state.replication.promote();
+    ResponseJson(serde_json::json!({"ok": true, "role": Role::Leader.as_str(), "previous_role": previous.as_str()}))
+}
+
+// GET /cluster[?key=K] -> members, routing mode and (optionally) the owner of K
+#[derive(Deserialize)]
+pub struct ClusterQuery { pub key: Option<String> }
+
+async fn cluster_status_handler(State(state): State<Arc<AppState>>, _auth: Authenticated, Query(q): Query<ClusterQuery>) -> ResponseJson<serde_json::Value> {
+    match &state.cluster {
+        Some(cluster) => ResponseJson(cluster.status(q.key.as_deref())),
+        None => ResponseJson(serde_json::json!({"enabled": false})),
+    }
+}
+
+// =============================
+// EXPORT / IMPORT: GET /admin/export, POST /admin/import (JSON Lines, see transfer.rs)
+// =============================
+#[derive(Deserialize)]
+pub struct TransferQuery {
+    pub prefix: Option<String>,
+    pub tags: Option<String>, // Tag expression, e.g. `users&!banned`
+    pub mode: Option<String>, // Import only: merge (default) | replace
+}
+
+fn bad_request(error: String) -> axum::response::Response {
+    (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"ok": false, "error": error}))).into_response()
+}
+
+// In cluster mode each node exports / imports only the keys it owns.
+async fn export_handler(State(state): State<Arc<AppState>>, _auth: Authenticated, Query(q): Query<TransferQuery>) -> axum::response::Response {
+    let filter = match transfer::TransferFilter::parse(q.prefix, q.tags.as_deref()) {
+        Ok(f) => f,
+        Err(e) => return bad_request(e),
+    };
+    let chunks = tokio_stream::wrappers::ReceiverStream::new(transfer::export(state.cache.clone(), filter));
+    let body = axum::body::Body::from_stream(chunks.map(Ok::<_, std::convert::Infallible>));
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response()
+}
+
+async fn import_handler(State(state): State<Arc<AppState>>, _auth: Authenticated, Query(q): Query<TransferQuery>, body: axum::body::Body) -> axum::response::Response {
+    let mode = match q.mode.as_deref().filter(|m| !m.is_empty()) {
+        None => transfer::ImportMode::default(),
+        Some(m) => match transfer::ImportMode::parse(m) {
+            Some(mode) => mode,
+            None => return bad_request(format!("unknown import mode '{}' (expected merge or replace)", m)),
+        },
+    };
+    let filter = match transfer::TransferFilter::parse(q.prefix, q.tags.as_deref()) {
+        Ok(f) => f,
+        Err(e) => return bad_request(e),
+    };
+    let mut importer = transfer::Importer::new(state.cache.clone(), state.cluster.clone(), mode, filter);
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(bytes) => importer.feed(&bytes),
+            Err(e) => {
+                // Upload broke off: keep what was applied, but never run the replace sweep on a partial dump.
+                let mut body = serde_json::to_value(importer.abort()).unwrap_or_default();
+                body["ok"] = false.into();
+                body["error"] = format!("request body: {}", e).into();
+                return (StatusCode::BAD_REQUEST, ResponseJson(body)).into_response();
+            }
+        }
+    }
+    let report = importer.finish();
+    let ok = report.failed == 0;
+    let mut body = serde_json::to_value(report).unwrap_or_default();
+    body["ok"] = ok.into();
+    ResponseJson(body).into_response()
+}
+
+// POST endpoints that only read (or manage auth / replication) and stay open on a read-only follower.
+const REPLICA_SAFE_POSTS: &[&str] = &["/search", "/keys/bulk/get", "/replication/promote"];
+
+// Reject mutating requests while this server is a read-only follower.
+async fn replica_write_guard(State(state): State<Arc<AppState>>, req: axum::extract::Request, next: axum::middleware::Next) -> axum::response::Response {
+    let method = req.method();
+    let path = req.uri().path();
+    let mutating = !(method == axum::http::Method::GET || method == axum::http::Method::HEAD || method == axum::http::Method::OPTIONS);
+    if mutating && state.replication.rejects_writes() && !path.starts_with("/auth/") && !REPLICA_SAFE_POSTS.contains(&path) {
+        let body = serde_json::json!({"ok": false, "error": "read_only_replica", "leader": state.replication.leader()});
+        return (StatusCode::FORBIDDEN, ResponseJson(body)).into_response();
+    }
+    next.run(req).await
+}
+
+async fn health_handler() -> ResponseJson<serde_json::Value> { ResponseJson(serde_json::json!({"status":"ok","time": chrono::Utc::now().to_rfc3339()})) }
+
+async fn system_handler(State(state): State<Arc<AppState>>) -> ResponseJson<serde_json::Value> {
+    let mut system = state.system.lock();
+    system.refresh_cpu(); // Refresh CPU usage
+    system.refresh_memory(); // Refresh memory usage
+    
+    let cpu_cores: Vec<serde_json::Value> = system.cpus()
+        .iter()
+        .enumerate()
+        .map(|(idx, cpu)| serde_json::json!({
+            "core": idx,
+            "name": cpu.name(),
+            "usage": cpu.cpu_usage(),
+            "frequency": cpu.frequency()
+        }))
+        .collect();
+    
+    // Calculate global CPU usage as average of all cores
+    let global_cpu_usage = if system.cpus().is_empty() {
+        0.0
+    } else {
+        system.cpus().iter().map(|cpu| cpu.cpu_usage()).sum::<f32>() / system.cpus().len() as f32
+    };
+    
+    ResponseJson(serde_json::json!({
+        "cpu_cores": cpu_cores,
+        "core_count": system.cpus().len(),
+        "global_cpu_usage": global_cpu_usage,
+        "total_memory": system.total_memory(),
+        "used_memory": system.used_memory(),
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    }))
+}
+
+
+
+// =============================
+// EVENT STREAM: GET /events (SSE) and GET /events/ws (WebSocket)
+// Query: ?types=put,delete&prefix=user:&tag=session  (all optional)
+// =============================
+#[derive(Deserialize, Default)]
+pub struct EventsQuery {
+    pub types: Option<String>,
+    pub prefix: Option<String>,
+    pub tag: Option<String>,
+}
+
+async fn events_sse_handler(State(state): State<Arc<AppState>>, _auth: Authenticated, Query(q): Query<EventsQuery>) -> Result<Sse<impl futures_util::Stream<Item = Result<SseEvent, std::convert::Infallible>>>, (StatusCode, ResponseJson<serde_json::Value>)> {
+    let filter = EventFilter::parse(q.types.as_deref(), q.prefix.as_deref(), q.tag.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": e}))))?;
+    let stream = BroadcastStream::new(state.cache.events.subscribe()).filter_map(move |msg| match msg {
+        Ok(ev) if filter.matches(&ev) => Some(Ok(SseEvent::default().event(ev.kind.as_str()).json_data(&ev).unwrap_or_default())),
+        Ok(_) => None,
+        // Slow consumer: oldest events were dropped; tell the client how many and keep going.
+        Err(BroadcastStreamRecvError::Lagged(n)) => Some(Ok(SseEvent::default().event("lagged").data(n.to_string()))),
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+async fn events_ws_handler(State(state): State<Arc<AppState>>, _auth: Authenticated, Query(q): Query<EventsQuery>, ws: WebSocketUpgrade) -> axum::response::Response {
+    let filter = match EventFilter::parse(q.types.as_deref(), q.prefix.as_deref(), q.tag.as_deref()) {
+        Ok(f) => f,
+        Err(e) => return (StatusCode::BAD_REQUEST, ResponseJson(serde_json::json!({"error": e}))).into_response(),
+    };
+    let rx = state.cache.events.subscribe();
+    ws.on_upgrade(move |socket| ws_event_loop(socket, rx, filter))
+}
+
+async fn ws_event_loop(mut socket: WebSocket, mut rx: tokio::sync::broadcast::Receiver<CacheEvent>, filter: EventFilter) {
+    loop {
+        tokio::select! {
+            msg = rx.recv() => {
+                let text = match msg {
+                    Ok(ev) if filter.matches(&ev) => match serde_json::to_string(&ev) { Ok(t) => t, Err(_) => continue },
+                    Ok(_) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => serde_json::json!({"type": "lagged", "dropped": n}).to_string(),
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
+                if socket.send(WsMessage::Text(text)).await.is_err() { break; }
+            }
+            incoming = socket.recv() => {
+                // Clients don't send anything meaningful; stop on close or error.
+                match incoming { Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break, _ => {} }
+            }
+        }
+    }
+}
+
+// Lightweight listing of all keys with metadata (newest first)
+async fn list_keys_handler(State(state): State<Arc<AppState>>, Authenticated(who): Authenticated) -> ResponseJson<serde_json::Value> {
+    let mut out: Vec<serde_json::Value> = Vec::new();
+    for shard in &state.cache.shards {
+        for e in shard.entries.iter() {
+            if e.value().is_expired() { continue; }
+            let Some(key) = who.namespace.strip(&e.key().0) else { continue };
+            let ttl_ms = e.value().ttl.map(|ttl| {
+                let elapsed = e.value().created_at.elapsed();
+                if elapsed >= ttl { 0 } else { (ttl - elapsed).as_millis() as u64 }
+            });
+            let created_ms = e.value().created_system.duration_since(UNIX_EPOCH).ok().map(|d| d.as_millis() as u64);
+            let tags = who.namespace.strip_tags(&e.value().tags);
+            out.push(serde_json::json!({
+                "key": key,
+                "size": e.value().value.size(),
+                "ttl": ttl_ms,
+                "tags": tags,
+                "created_ms": created_ms
+            }));
+        }
+    }
+    out.sort_by(|a,b| b.get("created_ms").and_then(|v| v.as_u64()).cmp(&a.get("created_ms").and_then(|v| v.as_u64())));
+    ResponseJson(serde_json::json!({"keys": out}))
+}
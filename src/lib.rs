@@ -0,0 +1,77 @@
+/*!
+ * TagCache - Lightweight, sharded, tag-aware in-memory cache server
+ *
+ * Author: Md. Aminul Islam Sarker <aminshamim@gmail.com>
+ * GitHub: <https://github.com/aminshamim/tagcache>
+ * LinkedIn: <https://www.linkedin.com/in/aminshamim/>
+ *
+ * The `tagcache` binary is a thin wrapper around this library; the same engine and protocol
+ * handlers can be embedded in-process.
+ *
+ * # Embedding the cache
+ *
+ * [`Cache`] is a plain synchronous structure; share it behind an `Arc`:
+ *
+ * ```
+ * use std::time::Duration;
+ * use tagcache::{Cache, Key, Tag};
+ *
+ * let cache = Cache::new(16);
+ * cache.put(Key::new("user:1"), "alice".into(), vec![Tag::new("users")], Some(Duration::from_secs(60)));
+ * assert_eq!(cache.get(&Key::new("user:1")).as_deref(), Some("alice"));
+ * assert_eq!(cache.invalidate_tag(&Tag::new("users")), 1);
+ * ```
+ *
+ * Expired entries are never returned, but memory is only reclaimed by the reaper; start it with
+ * [`server::spawn_expiry_reaper`] when embedding long-lived caches with TTLs.
+ *
+ * # Serving the protocols
+ *
+ * [`build_app`] returns the HTTP API as an axum `Router` and [`tcp::serve_tcp`] runs the TCP line
+ * protocol on a bound listener; both work on a shared [`AppState`]. [`server::serve`] wires a complete
+ * server together from a [`TagCacheConfig`], exactly as `tagcache server` does.
+ *
+ * # Modules
+ *
+ * * [`cache`] — `Cache`, `Shard`, `Entry`, `Key`, `Tag` (the engine); [`value`] — string, hash, list, set and JSON values
+ *   ([`document`] — JSON paths and path updates; [`counter`] — INCR / DECR options; [`ratelimit`] — rate limiters);
+ *   [`namespace`] — per-tenant keyspaces and quotas; [`lock`] — leases with fencing tokens; [`tag_ttl`] — tag TTL policies;
+ *   [`schedule`] — scheduled invalidations; [`tag_version`] — versioned tag invalidation
+ * * [`config`] — `tagcache.conf` types, defaults and environment overrides
+ * * [`auth`] — accounts, roles and bearer tokens; [`lockout`], [`audit`] — brute-force protection and audit log
+ * * [`http`] / [`tcp`] — protocol handlers
+ * * [`events`], [`webhooks`], [`replication`], [`cluster`] — optional subsystems
+ * * [`server`] — full server assembly; [`cli`] — the command line front-end
+ */
+
+pub mod audit; // Append-only JSONL log of security events
+pub mod auth; // Accounts, roles, password hashes + bearer tokens
+pub mod cache; // Sharded tag-aware store (the engine)
+pub mod cli; // `tagcache` command line (server + client subcommands)
+pub mod cluster; // Multi-node keyspace partitioning (hash ring, forwarding, fan-out)
+pub mod config; // tagcache.conf structures and loading
+pub mod counter; // Float, bounded and windowed INCR / DECR
+pub mod document; // JSON document paths and path-level updates
+pub mod events; // Keyspace event bus (SSE / WebSocket / TCP SUBSCRIBE)
+pub mod http; // Axum router and handlers
+pub mod lock; // Distributed locks: leases, owner tokens, fencing tokens
+pub mod lockout; // Failed login counters with exponential lockout
+pub mod namespace; // Multi-tenant keyspaces, per-namespace counters and quotas
+pub mod ratelimit; // Fixed window, sliding window and token bucket rate limiters
+pub mod replication; // Leader -> follower snapshot + mutation streaming
+pub mod schedule; // Scheduled / delayed invalidations
+pub mod server; // Wires everything together from a config
+pub mod shell; // Interactive REPL (`tagcache shell`)
+pub mod tag_expr; // Boolean tag filters (`a&!b`, `(a|b)&c`)
+pub mod tag_ttl; // Tag-level TTL policies (min / override)
+pub mod tag_version; // Generation-based (versioned) tag invalidation
+pub mod tcp; // Line-based TCP protocol
+pub mod transfer; // JSONL export / import
+pub mod value; // Entry values: strings, hashes, lists, sets and JSON documents
+pub mod webhooks; // Outbound notifications for tag invalidations / flushes
+
+pub use auth::{AuthState, Credentials};
+pub use cache::{Cache, CacheStats, Entry, Key, Shard, Tag, TagMatch};
+pub use config::TagCacheConfig;
+pub use value::{Value, ValueType};
+pub use http::{build_app, AppState};
@@ -0,0 +1,114 @@
+// =============================
+// LOCKS
+// =============================
+// Leases for coordinating workers. A lock is an entry holding the hash {owner, fence} whose TTL is
+// the lease; taking one is the same atomic insert-if-absent as ADD, so a holder that dies simply
+// lets its lease run out and the lock is free again.
+//
+//   owner   random token handed to the acquirer; renewing and releasing the lock require it
+//   fence   fencing token, strictly increasing over every lock this server grants. Pass it to the
+//           protected resource, which refuses work carrying a lower token than one it has seen, so a
+//           paused holder whose lease ran out can no longer do damage.
+//
+// Fencing tokens follow the clock (at least the microseconds since the epoch, and always above the
+// previous one), so they keep increasing across restarts and when a follower is promoted.
+
+use rand::{distributions::Alphanumeric, Rng};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::cache::{Cache, Key, Tag};
+use crate::value::{Value, ValueType};
+
+/// A granted (or renewed) lock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    pub owner: String,
+    pub fence: u64,
+    pub ttl: Duration,
+}
+
+impl Lease {
+    pub(crate) fn new(fence: u64, ttl: Duration) -> Self {
+        let owner = rand::thread_rng().sample_iter(&Alphanumeric).take(24).map(char::from).collect();
+        Lease { owner, fence, ttl }
+    }
+
+    pub(crate) fn to_value(&self) -> Value {
+        Value::Hash(BTreeMap::from([("owner".to_string(), self.owner.clone()), ("fence".to_string(), self.fence.to_string())]))
+    }
+}
+
+// Owner and fencing token of a lock entry; None for any other value.
+pub(crate) fn holder(value: &Value) -> Option<(&str, u64)> {
+    let Value::Hash(h) = value else { return None };
+    Some((h.get("owner")?.as_str(), h.get("fence")?.parse().ok()?))
+}
+
+// Take the next fencing token from `last` (the previous one).
+pub(crate) fn next_fence(last: &AtomicU64) -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as u64);
+    let next = |last: u64| last.saturating_add(1).max(now);
+    next(last.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| Some(next(last))).unwrap_or_default())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockError {
+    Held(Duration),       // Someone else holds it; their lease runs out in this long (unless renewed)
+    NotHeld,              // Renew / release of a lock that is free (or whose lease ran out)
+    NotOwner,             // Renew / release with another holder's token
+    WrongType(ValueType), // The key holds something else than a lock
+}
+
+impl LockError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LockError::Held(_) => "locked",
+            LockError::NotHeld => "not_held",
+            LockError::NotOwner => "not_owner",
+            LockError::WrongType(_) => "wrong_type",
+        }
+    }
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::Held(left) => write!(f, "lock is held for another {}ms", left.as_millis()),
+            LockError::NotHeld => write!(f, "lock is not held"),
+            LockError::NotOwner => write!(f, "lock is held by another owner"),
+            LockError::WrongType(found) => write!(f, "key holds a {} value, not a lock", found.as_str()),
+        }
+    }
+}
+
+/// `Cache::lock_acquire`, retrying for up to `timeout` while the lock is held. Wakes up when the
+/// lock's key is deleted or released, and when the holder's lease runs out.
+pub async fn acquire_wait(cache: &Cache, key: Key, ttl: Duration, tags: Vec<Tag>, timeout: Duration) -> Result<Lease, LockError> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut events = cache.events.subscribe(); // Before the first attempt, so no release is missed
+    loop {
+        let left = match cache.lock_acquire(key.clone(), ttl, tags.clone()) {
+            Err(LockError::Held(left)) => left,
+            granted_or_error => return granted_or_error,
+        };
+        let now = tokio::time::Instant::now();
+        if now >= deadline { return Err(LockError::Held(left)); }
+        let retry = deadline.min(now + left + Duration::from_millis(1));
+        loop {
+            tokio::select! {
+                ev = events.recv() => match ev {
+                    // Keyless events are tag invalidations and flushes, which may have removed it.
+                    Ok(ev) if ev.key.as_ref().is_none_or(|k| *k == key.0) => break,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(_)) => break,
+                    Err(RecvError::Closed) => { tokio::time::sleep_until(retry).await; break }
+                },
+                _ = tokio::time::sleep_until(retry) => break,
+            }
+        }
+    }
+}
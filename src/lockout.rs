@@ -0,0 +1,127 @@
+// =============================
+// BRUTE-FORCE PROTECTION
+// =============================
+// Failed password checks (HTTP Basic, /auth/login, TCP AUTH) are counted per client IP and per
+// username. Once either counter reaches `max_failures`, further attempts from that IP / for that
+// user are refused without checking the password for `base_seconds`, doubling with every further
+// failure up to `max_seconds`. A successful login clears the user's counter; counters are forgotten
+// once they have been quiet for `max_seconds`.
+//
+// Only accounts that exist get a user counter, so guessing usernames cannot grow the table. Quiet
+// counters are swept at most every `SWEEP_INTERVAL`, not on every failure, and past `MAX_TRACKED`
+// addresses new ones are not counted until a sweep makes room (the user counters still apply).
+//
+// Bearer tokens and API keys are not counted: they are too long to guess, and counting them would
+// lock out clients that merely keep using an expired token.
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+const SWEEP_THRESHOLD: usize = 10_000;                  // Smaller tables are never swept
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10); // At most one sweep this often
+const MAX_TRACKED: usize = 100_000;                      // Counters per table
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LockoutConfig {
+    pub enabled: bool,
+    pub max_failures: u32, // Failures before the first lockout
+    pub base_seconds: u64, // First lockout; doubles with every further failure
+    pub max_seconds: u64,  // Longest lockout (also how long a quiet counter is remembered)
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self { enabled: true, max_failures: 5, base_seconds: 1, max_seconds: 900 }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Counter {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// What a failed attempt led to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locked {
+    Ip(Duration),
+    User(Duration),
+}
+
+#[derive(Debug)]
+pub struct Lockout {
+    config: LockoutConfig,
+    by_ip: DashMap<IpAddr, Counter>,
+    by_user: DashMap<String, Counter>,
+    last_sweep: Mutex<Instant>,
+}
+
+impl Lockout {
+    pub fn new(config: LockoutConfig) -> Self {
+        Self { config, by_ip: DashMap::new(), by_user: DashMap::new(), last_sweep: Mutex::new(Instant::now()) }
+    }
+
+    /// `Err(retry_after)` while the IP or the user is locked out.
+    pub fn check(&self, ip: Option<IpAddr>, user: Option<&str>) -> Result<(), Duration> {
+        if !self.config.enabled { return Ok(()); }
+        let now = Instant::now();
+        let remaining = |c: &Counter| c.locked_until.and_then(|t| t.checked_duration_since(now)).filter(|d| !d.is_zero());
+        let ip_wait = ip.and_then(|ip| self.by_ip.get(&ip).and_then(|c| remaining(&c)));
+        let user_wait = user.and_then(|u| self.by_user.get(u).and_then(|c| remaining(&c)));
+        match ip_wait.max(user_wait) {
+            Some(wait) => Err(wait),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a failed password check; returns the lockout it started, if any. Pass `user` only for an
+    /// account that exists.
+    pub fn record_failure(&self, ip: Option<IpAddr>, user: Option<&str>) -> Option<Locked> {
+        if !self.config.enabled { return None; }
+        self.sweep(Instant::now());
+        let ip_lock = ip.and_then(|ip| self.bump(&self.by_ip, ip)).map(Locked::Ip);
+        let user_lock = user.and_then(|u| self.bump(&self.by_user, u.to_string())).map(Locked::User);
+        user_lock.or(ip_lock)
+    }
+
+    /// A successful login clears the user's counter (the IP's decays on its own).
+    pub fn record_success(&self, user: &str) {
+        self.by_user.remove(user);
+    }
+
+    // Forget quiet counters once the tables are big enough to matter, at most every SWEEP_INTERVAL.
+    fn sweep(&self, now: Instant) {
+        if self.by_ip.len() + self.by_user.len() <= SWEEP_THRESHOLD { return; }
+        let Some(mut last) = self.last_sweep.try_lock() else { return }; // Another failure is sweeping
+        if now.duration_since(*last) < SWEEP_INTERVAL { return; }
+        *last = now;
+        let memory = self.memory();
+        self.by_ip.retain(|_, c| now.duration_since(c.last_failure) < memory);
+        self.by_user.retain(|_, c| now.duration_since(c.last_failure) < memory);
+    }
+
+    // How long a quiet counter is remembered.
+    fn memory(&self) -> Duration {
+        Duration::from_secs(self.config.max_seconds.max(1))
+    }
+
+    fn bump<K: Eq + Hash + Clone>(&self, map: &DashMap<K, Counter>, key: K) -> Option<Duration> {
+        let now = Instant::now();
+        let memory = self.memory();
+        if map.len() >= MAX_TRACKED && !map.contains_key(&key) { return None; }
+        let mut counter = map.entry(key).or_insert(Counter { failures: 0, last_failure: now, locked_until: None });
+        if now.duration_since(counter.last_failure) >= memory { counter.failures = 0; }
+        counter.failures += 1;
+        counter.last_failure = now;
+        let over = counter.failures.checked_sub(self.config.max_failures.max(1))?;
+        let lock = Duration::from_secs(self.config.base_seconds.max(1).saturating_mul(1u64 << over.min(32)).min(self.config.max_seconds.max(1)));
+        counter.locked_until = Some(now + lock);
+        Some(lock)
+    }
+}
@@ -0,0 +1,386 @@
+// =============================
+// NAMESPACES
+// =============================
+// Several teams can share one server (or cluster) without their keys and tags colliding. A request
+// or connection works in a namespace: selected with the `X-TagCache-Namespace` header or a
+// `/ns/<name>/...` URL prefix over HTTP, and with `SELECT <name>` over TCP. Clients that never
+// select one use the default namespace, which is the plain keyspace of earlier versions.
+//
+// Keys and tags of namespace `team-a` are stored as `team-a\x1f<key>` / `team-a\x1f<tag>`, so tag
+// invalidation, key listings and flushes inside a namespace only ever reach its own entries, while
+// replication and export / import carry namespaced entries like any others.
+// Cluster placement ignores the namespace: a key lives on the same node in every namespace.
+//
+// The cache counts keys, bytes (key + value), hits, misses, puts and invalidations per namespace as
+// entries come and go. Quotas (keys, bytes, operations per second; 0 = unlimited) are checked by the
+// HTTP / TCP layers before a command runs, so concurrent writers can overshoot them slightly.
+// Accounts can be bound to a namespace (`namespace` in their scope): they always work inside it.
+//
+// Client-supplied keys, tags, prefixes and tag expressions may not contain the separator (HTTP 400 /
+// TCP `ERR invalid_name`); otherwise a client of one namespace could name another's stored entries.
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering::Relaxed};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::cache::{Key, Tag};
+use crate::transfer::TransferFilter;
+
+/// HTTP header selecting the namespace of a request.
+pub const NAMESPACE_HEADER: &str = "x-tagcache-namespace";
+
+/// Separates the namespace from the key / tag in stored names.
+pub const SEPARATOR: char = '\u{1f}';
+
+const MAX_NAME_LEN: usize = 64;
+
+/// Limits for one namespace (0 = unlimited).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quota {
+    pub max_keys: u64,
+    pub max_bytes: u64,        // Keys + values
+    pub max_ops_per_sec: u64,  // Commands / requests of any kind
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamespaceConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub quota: Quota,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NamespacesConfig {
+    pub allow_undeclared: bool,          // Any valid name may be selected (false = only `declared` ones)
+    pub default_quota: Quota,            // Quota of named namespaces not listed in `declared`
+    pub declared: Vec<NamespaceConfig>,
+}
+
+impl Default for NamespacesConfig {
+    fn default() -> Self {
+        Self { allow_undeclared: true, default_quota: Quota::default(), declared: Vec::new() }
+    }
+}
+
+/// The keyspace a request or connection works in (`Namespace::default()` = the plain keyspace).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Namespace(Option<String>);
+
+impl Namespace {
+    /// A named namespace: 1-64 ASCII letters, digits, `-`, `_` or `.` ("default", "-" or "" select
+    /// the default namespace).
+    pub fn named(name: &str) -> Result<Self, NamespaceError> {
+        let name = name.trim();
+        if name.is_empty() || name == "-" || name == "default" { return Ok(Self::default()); }
+        let valid = name.len() <= MAX_NAME_LEN && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if valid { Ok(Self(Some(name.to_string()))) } else { Err(NamespaceError::Invalid(name.to_string())) }
+    }
+
+    // The namespace an account is bound to (names from the config are trusted as they are).
+    pub(crate) fn bound(name: Option<&str>) -> Self {
+        Self(name.filter(|n| !n.is_empty() && *n != "default").map(str::to_string))
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.0.is_none()
+    }
+
+    fn qualify(&self, s: String) -> String {
+        match &self.0 {
+            Some(ns) => format!("{}{}{}", ns, SEPARATOR, s),
+            None => s,
+        }
+    }
+
+    /// The stored key for `key` in this namespace.
+    pub fn key(&self, key: impl Into<String>) -> Key {
+        Key(self.qualify(key.into()))
+    }
+
+    /// The stored tag for `tag` in this namespace.
+    pub fn tag(&self, tag: impl Into<String>) -> Tag {
+        Tag(self.qualify(tag.into()))
+    }
+
+    /// The client-facing name of a stored key or tag, if it belongs to this namespace.
+    pub fn strip<'a>(&self, stored: &'a str) -> Option<&'a str> {
+        match &self.0 {
+            Some(ns) => stored.strip_prefix(ns.as_str())?.strip_prefix(SEPARATOR),
+            None => (!stored.contains(SEPARATOR)).then_some(stored),
+        }
+    }
+
+    /// A key / tag filter over client-facing names, rewritten to match the stored ones.
+    pub fn qualify_filter(&self, filter: TransferFilter) -> TransferFilter {
+        if self.is_default() { return filter; }
+        TransferFilter {
+            prefix: Some(self.qualify(filter.prefix.unwrap_or_default())),
+            tags: filter.tags.map(|e| e.map_tags(&|t| self.qualify(t))),
+        }
+    }
+
+    /// Client-facing names of an entry's tags.
+    pub fn strip_tags(&self, tags: &[Tag]) -> Vec<String> {
+        tags.iter().filter_map(|t| self.strip(&t.0)).map(str::to_string).collect()
+    }
+
+    // Rewrite a TCP command line so its keys and tags name stored keys / tags of this namespace.
+    // FLUSH becomes `FLUSH <namespace>`, so peers that receive it through cluster fan-out flush the
+    // same namespace.
+    pub(crate) fn qualify_tcp(&self, cmd: &str, text: &str) -> String {
+        let Some(ns) = &self.0 else { return text.to_string() };
+        if cmd == "FLUSH" { return format!("FLUSH\t{}", ns); }
+        let Some((name_field, list_fields, limit)) = tcp_name_fields(cmd) else { return text.to_string() };
+        let mut fields: Vec<String> = text.splitn(limit, '\t').map(str::to_string).collect();
+        if let Some(field) = name_field.and_then(|i| fields.get_mut(i)) {
+            *field = self.qualify(std::mem::take(field));
+        }
+        for &i in list_fields {
+            let Some(field) = fields.get_mut(i).filter(|f| !f.is_empty() && f.as_str() != "-") else { continue };
+            *field = field.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).map(|s| self.qualify(s.to_string())).collect::<Vec<_>>().join(",");
+        }
+        fields.join("\t")
+    }
+}
+
+// Where a TCP command names keys / tags: (key or tag field, comma separated tag / key list fields,
+// number of fields).
+fn tcp_name_fields(cmd: &str) -> Option<(Option<usize>, &'static [usize], usize)> {
+    Some(match cmd {
+        "PUT" | "ADD" => (Some(1), &[3], 5),
+        "INCR" | "DECR" => (Some(1), &[4], 6), // Counter options follow the tags
+        "RATELIMIT" => (Some(1), &[5], 6),
+        "LOCK" => (Some(1), &[4], 5),
+        "RENEW" | "UNLOCK" => (Some(1), &[], 2),
+        "TAG_ADD" | "TAG_DEL" => (Some(1), &[2], 3),
+        c if crate::tcp::COLLECTION_STORE_COMMANDS.contains(&c) => (Some(1), &[3], 5),
+        c if crate::tcp::COLLECTION_COMMANDS.contains(&c) => (Some(1), &[], 2),
+        "GET" | "DEL" | "INV_TAG" | "KEYS_BY_TAG" | "KEYS" => (Some(1), &[], 2),
+        "INV_TAGS_ANY" | "INV_TAGS_ALL" | "INV_TAGS_TREE" | "INV_KEYS" => (None, &[1], 2),
+        "TAG_INFO" => (Some(1), &[], 2),
+        "TAG_EXPIRE" => (Some(1), &[], 3),
+        "TAG_RENAME" | "TAG_MERGE" => (None, &[1, 2], 3),
+        "RETAG" => (None, &[2], 5), // Prefix and expression are qualified by the command itself
+        _ => return None,
+    })
+}
+
+/// Reject a client-supplied key, tag, prefix or tag expression containing the separator.
+pub fn check_name(name: &str) -> Result<(), NamespaceError> {
+    if name.contains(SEPARATOR) { Err(NamespaceError::ReservedChar(name.to_string())) } else { Ok(()) }
+}
+
+/// `check_name` for several names.
+pub fn check_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<(), NamespaceError> {
+    names.into_iter().try_for_each(check_name)
+}
+
+// `check_name` for the keys, tags, prefixes and expressions of a TCP command line (values may
+// contain anything).
+pub(crate) fn check_tcp(cmd: &str, text: &str) -> Result<(), NamespaceError> {
+    let (name_field, list_fields, limit): (Option<usize>, &[usize], usize) = match cmd {
+        "RETAG" => (None, &[2, 3, 4], 5),
+        "SUBSCRIBE" => (None, &[2, 3], 4), // Event prefix and tag
+        c => match tcp_name_fields(c) { Some(fields) => fields, None => return Ok(()) },
+    };
+    let fields: Vec<&str> = text.splitn(limit, '\t').collect();
+    check_names(name_field.iter().chain(list_fields).filter_map(|&i| fields.get(i)).flat_map(|f| f.split(',')))
+}
+
+/// Split a stored key or tag into (namespace, name); None for the default namespace.
+pub fn split(stored: &str) -> Option<(&str, &str)> {
+    stored.split_once(SEPARATOR)
+}
+
+/// The key without its namespace (cluster placement hashes this, so namespaces don't move keys).
+pub fn base_key(stored: &str) -> &str {
+    split(stored).map_or(stored, |(_, key)| key)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NamespaceError {
+    Invalid(String),       // Not a valid namespace name
+    Unknown(String),       // Not declared and `allow_undeclared` is off
+    Quota(&'static str),   // max_keys | max_bytes | max_ops_per_sec
+    ReservedChar(String),  // A client key / tag containing the separator
+}
+
+impl NamespaceError {
+    /// Error code used in HTTP bodies and TCP replies.
+    pub fn code(&self) -> &'static str {
+        match self {
+            NamespaceError::Invalid(_) => "invalid_namespace",
+            NamespaceError::Unknown(_) => "unknown_namespace",
+            NamespaceError::Quota(_) => "quota_exceeded",
+            NamespaceError::ReservedChar(_) => "invalid_name",
+        }
+    }
+
+    /// The namespace name, the exceeded quota or the refused key / tag.
+    pub fn detail(&self) -> &str {
+        match self {
+            NamespaceError::Invalid(name) | NamespaceError::Unknown(name) | NamespaceError::ReservedChar(name) => name,
+            NamespaceError::Quota(quota) => quota,
+        }
+    }
+}
+
+/// Usage and activity of one namespace.
+#[derive(Debug, Default)]
+pub struct NamespaceCounters {
+    keys: AtomicI64,
+    bytes: AtomicI64,
+    pub(crate) hits: AtomicU64,
+    pub(crate) misses: AtomicU64,
+    pub(crate) puts: AtomicU64,
+    pub(crate) invalidations: AtomicU64,
+    window: Mutex<Option<(Instant, u64)>>, // Current one-second window for max_ops_per_sec
+}
+
+impl NamespaceCounters {
+    pub(crate) fn usage(&self, keys: i64, bytes: i64) {
+        self.keys.fetch_add(keys, Relaxed);
+        self.bytes.fetch_add(bytes, Relaxed);
+    }
+
+    fn keys(&self) -> u64 { self.keys.load(Relaxed).max(0) as u64 }
+    fn bytes(&self) -> u64 { self.bytes.load(Relaxed).max(0) as u64 }
+}
+
+/// GET /admin/namespaces, /stats and TCP STATS inside a namespace.
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceStats {
+    pub namespace: String, // "default" for the plain keyspace
+    pub keys: u64,         // Includes expired entries not reaped yet
+    pub bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub puts: u64,
+    pub invalidations: u64,
+    pub hit_ratio: f64,
+    pub quota: Quota,
+}
+
+/// Per-namespace counters and quotas, owned by the cache.
+#[derive(Debug)]
+pub struct Namespaces {
+    config: NamespacesConfig,
+    quotas: HashMap<String, Quota>,
+    default_ns: NamespaceCounters,                   // The plain keyspace (kept apart: no map lookup on the hot path)
+    named: DashMap<String, Arc<NamespaceCounters>>,  // Created on first use
+}
+
+impl Default for Namespaces {
+    fn default() -> Self {
+        Self::new(NamespacesConfig::default())
+    }
+}
+
+impl Namespaces {
+    pub fn new(config: NamespacesConfig) -> Self {
+        let quotas = config.declared.iter().map(|n| (n.name.clone(), n.quota)).collect();
+        Self { config, quotas, default_ns: NamespaceCounters::default(), named: DashMap::new() }
+    }
+
+    // Run `f` on the counters of the namespace a stored key or tag belongs to.
+    pub(crate) fn track(&self, stored: &str, f: impl FnOnce(&NamespaceCounters)) {
+        match split(stored) {
+            None => f(&self.default_ns),
+            Some((ns, _)) => match self.named.get(ns) {
+                Some(counters) => f(&counters),
+                None => f(&self.named.entry(ns.to_string()).or_default()),
+            },
+        }
+    }
+
+    // Every entry is gone (flush_all): zero the usage, keep the activity counters.
+    pub(crate) fn clear_usage(&self) {
+        let zero = |c: &NamespaceCounters| { c.keys.store(0, Relaxed); c.bytes.store(0, Relaxed); };
+        zero(&self.default_ns);
+        self.named.iter().for_each(|c| zero(&c));
+    }
+
+    pub fn quota(&self, ns: &Namespace) -> Quota {
+        match ns.name() {
+            None => Quota::default(),
+            Some(name) => self.quotas.get(name).copied().unwrap_or(self.config.default_quota),
+        }
+    }
+
+    /// Check that `ns` may be used and count one operation against its ops/sec quota.
+    pub fn admit(&self, ns: &Namespace) -> Result<(), NamespaceError> {
+        let Some(name) = ns.name() else { return Ok(()) };
+        if !self.config.allow_undeclared && !self.quotas.contains_key(name) { return Err(NamespaceError::Unknown(name.to_string())); }
+        let max = self.quota(ns).max_ops_per_sec;
+        if max == 0 { return Ok(()); }
+        let now = Instant::now();
+        let mut admitted = true;
+        self.track(&ns.qualify(String::new()), |c| {
+            let mut window = c.window.lock();
+            match window.as_mut() {
+                Some((start, count)) if now.duration_since(*start) < Duration::from_secs(1) => {
+                    admitted = *count < max;
+                    if admitted { *count += 1; }
+                }
+                _ => *window = Some((now, 1)),
+            }
+        });
+        if admitted { Ok(()) } else { Err(NamespaceError::Quota("max_ops_per_sec")) }
+    }
+
+    // Check a write adding `new_keys` keys and `added_bytes` bytes against the key and byte quotas.
+    pub(crate) fn admit_write(&self, ns: &str, new_keys: u64, added_bytes: i64) -> Result<(), NamespaceError> {
+        let quota = self.quotas.get(ns).copied().unwrap_or(self.config.default_quota);
+        if quota.max_keys == 0 && quota.max_bytes == 0 { return Ok(()); }
+        let (keys, bytes) = self.named.get(ns).map_or((0, 0), |c| (c.keys(), c.bytes()));
+        if quota.max_keys > 0 && new_keys > 0 && keys + new_keys > quota.max_keys { return Err(NamespaceError::Quota("max_keys")); }
+        if quota.max_bytes > 0 && added_bytes > 0 && bytes + added_bytes as u64 > quota.max_bytes { return Err(NamespaceError::Quota("max_bytes")); }
+        Ok(())
+    }
+
+    fn snapshot(&self, name: &str, c: &NamespaceCounters, quota: Quota) -> NamespaceStats {
+        let (hits, misses) = (c.hits.load(Relaxed), c.misses.load(Relaxed));
+        NamespaceStats {
+            namespace: name.to_string(),
+            keys: c.keys(),
+            bytes: c.bytes(),
+            hits,
+            misses,
+            puts: c.puts.load(Relaxed),
+            invalidations: c.invalidations.load(Relaxed),
+            hit_ratio: if hits + misses > 0 { hits as f64 / (hits + misses) as f64 } else { 0.0 },
+            quota,
+        }
+    }
+
+    pub fn stats(&self, ns: &Namespace) -> NamespaceStats {
+        let quota = self.quota(ns);
+        match ns.name() {
+            None => self.snapshot("default", &self.default_ns, quota),
+            Some(name) => match self.named.get(name) {
+                Some(c) => self.snapshot(name, &c, quota),
+                None => self.snapshot(name, &NamespaceCounters::default(), quota),
+            },
+        }
+    }
+
+    /// Every namespace that is declared or has been used, default first, then by name.
+    pub fn list(&self) -> Vec<NamespaceStats> {
+        let mut names: Vec<String> = self.named.iter().map(|c| c.key().clone()).chain(self.quotas.keys().cloned()).collect();
+        names.sort();
+        names.dedup();
+        std::iter::once(self.stats(&Namespace::default()))
+            .chain(names.iter().map(|n| self.stats(&Namespace::bound(Some(n)))))
+            .collect()
+    }
+}
@@ -0,0 +1,137 @@
+// =============================
+// RATE LIMITERS
+// =============================
+// Server-side rate limits keyed by an arbitrary string. One call checks and records a request and
+// answers allowed / remaining / reset-after, under the key's shard lock, so there is no race at
+// window edges (unlike INCR + TTL done by the client). Three algorithms:
+//
+//   fixed_window     at most `limit` requests per window; windows are aligned to the UNIX epoch and
+//                    the state is a plain counter that expires at the end of its window
+//   sliding_window   at most `limit` requests in any `window`; the state is a list of request times
+//                    (ms since the epoch), so it holds up to `limit` items
+//   token_bucket     `limit` tokens refilled evenly over `window`, one taken per request; the state
+//                    is a hash {credit, at} with the bucket's content in 1/window tokens, so the
+//                    arithmetic stays exact
+//
+// Limiters are ordinary entries: they carry tags (invalidate_tag resets a whole customer's limits),
+// show up in GET /keys/:key and a DEL resets one. `reset_after` is the wait until a request would be
+// allowed again when denied, and until the limiter is back to its full limit when allowed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::value::{Value, ValueError, ValueType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    FixedWindow,
+    SlidingWindow,
+    TokenBucket,
+}
+
+impl Algorithm {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "fixed_window" | "fixed" => Some(Algorithm::FixedWindow),
+            "sliding_window" | "sliding" => Some(Algorithm::SlidingWindow),
+            "token_bucket" | "bucket" => Some(Algorithm::TokenBucket),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::FixedWindow => "fixed_window",
+            Algorithm::SlidingWindow => "sliding_window",
+            Algorithm::TokenBucket => "token_bucket",
+        }
+    }
+
+    // The kind of value holding the limiter's state.
+    pub(crate) fn kind(&self) -> ValueType {
+        match self {
+            Algorithm::FixedWindow => ValueType::String,
+            Algorithm::SlidingWindow => ValueType::List,
+            Algorithm::TokenBucket => ValueType::Hash,
+        }
+    }
+}
+
+/// An algorithm with its limit: `limit` requests per `window`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub algorithm: Algorithm,
+    pub limit: u64,
+    pub window: Duration,
+}
+
+/// The answer to one request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u64,
+    pub remaining: u64,
+    pub reset_after: Duration,
+}
+
+impl RateLimit {
+    pub fn new(algorithm: Algorithm, limit: u64, window: Duration) -> Result<Self, String> {
+        if limit == 0 { return Err("limit must be at least 1".to_string()); }
+        if window.as_millis() == 0 { return Err("window must be at least 1ms".to_string()); }
+        Ok(RateLimit { algorithm, limit, window })
+    }
+
+    fn window_ms(&self) -> u64 {
+        self.window.as_millis() as u64
+    }
+
+    /// How long the state must outlive a request at `now_ms` (the entry's TTL).
+    pub fn ttl(&self, now_ms: u64) -> Duration {
+        match self.algorithm {
+            Algorithm::FixedWindow => Duration::from_millis(self.window_ms() - now_ms % self.window_ms()),
+            Algorithm::SlidingWindow | Algorithm::TokenBucket => self.window,
+        }
+    }
+
+    /// Record a request at `now_ms` against the state in `value` (empty for a new limiter). The
+    /// state is only touched once it has been read successfully.
+    pub fn hit(&self, value: &mut Value, now_ms: u64) -> Result<Decision, ValueError> {
+        let (limit, window) = (self.limit, self.window_ms());
+        let decision = |allowed: bool, remaining: u64, reset_after_ms: u64| Decision { allowed, limit, remaining, reset_after: Duration::from_millis(reset_after_ms) };
+        match (self.algorithm, value) {
+            (Algorithm::FixedWindow, Value::String(count)) => {
+                let used = if count.is_empty() { 0 } else { count.trim().parse::<u64>().map_err(|_| ValueError::NotInteger)? };
+                let allowed = used < limit;
+                if allowed { *count = (used + 1).to_string(); }
+                Ok(decision(allowed, limit.saturating_sub(used + allowed as u64), self.ttl(now_ms).as_millis() as u64))
+            }
+            (Algorithm::SlidingWindow, Value::List(log)) => {
+                let times = log.iter().map(|t| t.parse::<u64>()).collect::<Result<Vec<_>, _>>().map_err(|_| ValueError::NotInteger)?;
+                let mut live: VecDeque<u64> = times.into_iter().filter(|t| t + window > now_ms).collect();
+                let allowed = (live.len() as u64) < limit;
+                // Denied: wait until enough of the oldest requests leave the window.
+                let reset_after = if allowed { live.push_back(now_ms); window } else { live[live.len() - limit as usize] + window - now_ms };
+                let remaining = limit.saturating_sub(live.len() as u64);
+                *log = live.iter().map(u64::to_string).collect();
+                Ok(decision(allowed, remaining, reset_after))
+            }
+            (Algorithm::TokenBucket, Value::Hash(state)) => {
+                // A token is `window` credit and each millisecond adds `limit` credit.
+                let field = |name: &str| state.get(name).map(|v| v.parse::<u128>().map_err(|_| ValueError::NotInteger)).transpose();
+                let (credit, at) = (field("credit")?, field("at")?);
+                let (limit, window, now) = (limit as u128, window as u128, now_ms as u128);
+                let full = limit * window;
+                let mut credit = credit.map_or(full, |c| (c + now.saturating_sub(at.unwrap_or(now)) * limit).min(full));
+                let allowed = credit >= window;
+                if allowed { credit -= window; }
+                let missing = if allowed { full - credit } else { window - credit };
+                state.insert("credit".to_string(), credit.to_string());
+                state.insert("at".to_string(), now_ms.to_string());
+                Ok(decision(allowed, (credit / window) as u64, missing.div_ceil(limit) as u64))
+            }
+            (_, v) => Err(ValueError::WrongType(v.kind())),
+        }
+    }
+}
@@ -0,0 +1,371 @@
+// =============================
+// LEADER–FOLLOWER REPLICATION
+// =============================
+// Asynchronous, single-leader replication over the TCP protocol port. A follower connects to its
+// leader and sends `REPLICATE`; the leader answers with a full snapshot of every shard followed by a
+// stream of mutations, one JSON object per line (JSON so values may contain tabs / newlines).
+//
+// The stream is driven by the keyspace event bus. Key-level events are sent as the key's *current*
+// state (re-read when the event is forwarded), so replaying them in order always converges to the
+// leader's state even if several writes to one key are coalesced. If the leader's event buffer
+// overflows for a slow follower, the leader simply sends a fresh snapshot instead.
+//
+// Followers serve reads and reject writes (unless `allow_writes` is set) until promoted.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast::error::RecvError, Notify};
+use tracing::{info, warn};
+
+use crate::events::{CacheEvent, EventKind};
+use crate::cache::{Cache, Entry, Key, Tag, TagMatch};
+use crate::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Leader,   // Accepts writes; serves REPLICATE to followers (a standalone server is a leader with none)
+    Follower, // Mirrors `leader`
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Leader => "leader",
+            Role::Follower => "follower",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplicationConfig {
+    pub role: Role,
+    pub leader: Option<String>,     // Leader TCP address (host:port), required for followers
+    pub allow_writes: bool,         // Let a follower accept local writes (they are not sent back to the leader)
+    pub heartbeat_ms: u64,          // Leader -> follower ping interval while idle
+    pub reconnect_delay_ms: u64,    // Follower back-off between connection attempts
+    pub auth: Option<String>,       // `user:password` or API key sent as TCP AUTH to the leader
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            role: Role::Leader,
+            leader: None,
+            allow_writes: false,
+            heartbeat_ms: 1_000,
+            reconnect_delay_ms: 1_000,
+            auth: None,
+        }
+    }
+}
+
+// One line of the replication stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ReplOp {
+    SnapshotBegin { items: usize, ts: u64 },
+    SnapshotEnd { ts: u64 },
+    Set { key: String, value: Value, tags: Vec<String>, ttl_ms: Option<u64>, ts: u64 },
+    Del { key: String, ts: u64 },
+    InvalidateTags { tags: Vec<String>, ts: u64 },
+    InvalidateSubtrees { tags: Vec<String>, ts: u64 },
+    Flush { ts: u64 },
+    Ping { ts: u64 },
+}
+
+impl ReplOp {
+    // Leader wall-clock time (unix millis) at which the change happened.
+    pub fn ts(&self) -> u64 {
+        match self {
+            ReplOp::SnapshotBegin { ts, .. } | ReplOp::SnapshotEnd { ts } | ReplOp::Set { ts, .. }
+            | ReplOp::Del { ts, .. } | ReplOp::InvalidateTags { ts, .. } | ReplOp::InvalidateSubtrees { ts, .. } | ReplOp::Flush { ts } | ReplOp::Ping { ts } => *ts,
+        }
+    }
+}
+
+// Shape of the `replication` object in /stats and GET /replication.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicationStatus {
+    pub role: Role,
+    pub leader: Option<String>,
+    pub read_only: bool,
+    pub connected: bool,               // follower: currently streaming from the leader
+    pub lag_ms: Option<u64>,           // follower: leader timestamp -> apply delay of the last message
+    pub last_contact_ms: Option<u64>,  // follower: time since the last message (heartbeats included)
+    pub full_syncs: u64,               // follower: snapshots received
+    pub followers: usize,              // leader: connected followers
+}
+
+#[derive(Debug)]
+pub struct Replication {
+    role: Mutex<Role>,
+    leader: Option<String>,
+    allow_writes: bool,
+    heartbeat: Duration,
+    reconnect_delay: Duration,
+    connected: AtomicBool,
+    lag_ms: AtomicU64,
+    last_contact: Mutex<Option<Instant>>,
+    full_syncs: AtomicU64,
+    followers: AtomicUsize,
+    promoted: Notify, // Wakes the follower task so it drops the leader connection right away
+    auth_line: Option<String>, // TCP AUTH line sent to the leader before REPLICATE
+}
+
+impl Replication {
+    pub fn new(config: &ReplicationConfig) -> Arc<Self> {
+        Arc::new(Self {
+            role: Mutex::new(config.role),
+            leader: config.leader.clone().filter(|l| !l.trim().is_empty()),
+            allow_writes: config.allow_writes,
+            heartbeat: Duration::from_millis(config.heartbeat_ms.max(10)),
+            reconnect_delay: Duration::from_millis(config.reconnect_delay_ms.max(10)),
+            connected: AtomicBool::new(false),
+            lag_ms: AtomicU64::new(0),
+            last_contact: Mutex::new(None),
+            full_syncs: AtomicU64::new(0),
+            followers: AtomicUsize::new(0),
+            promoted: Notify::new(),
+            auth_line: config.auth.as_deref().filter(|a| !a.is_empty()).map(crate::auth::tcp_auth_line),
+        })
+    }
+
+    pub fn role(&self) -> Role {
+        *self.role.lock()
+    }
+
+    pub fn leader(&self) -> Option<&str> {
+        self.leader.as_deref()
+    }
+
+    // True when client writes must be refused (follower without allow_writes).
+    pub fn rejects_writes(&self) -> bool {
+        self.role() == Role::Follower && !self.allow_writes
+    }
+
+    // Stop following and start accepting writes. Returns the previous role.
+    pub fn promote(&self) -> Role {
+        let previous = std::mem::replace(&mut *self.role.lock(), Role::Leader);
+        if previous == Role::Follower {
+            info!("Replication: promoted to leader (was following {})", self.leader.as_deref().unwrap_or("-"));
+            self.connected.store(false, Ordering::Relaxed);
+            self.promoted.notify_one();
+        }
+        previous
+    }
+
+    pub fn status(&self) -> ReplicationStatus {
+        let role = self.role();
+        let follower = role == Role::Follower;
+        let last_contact = *self.last_contact.lock();
+        ReplicationStatus {
+            role,
+            leader: self.leader.clone(),
+            read_only: self.rejects_writes(),
+            connected: self.connected.load(Ordering::Relaxed),
+            lag_ms: (follower && last_contact.is_some()).then(|| self.lag_ms.load(Ordering::Relaxed)),
+            last_contact_ms: last_contact.filter(|_| follower).map(|t| t.elapsed().as_millis() as u64),
+            full_syncs: self.full_syncs.load(Ordering::Relaxed),
+            followers: self.followers.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record_contact(&self, leader_ts: u64) {
+        self.lag_ms.store(now_ms().saturating_sub(leader_ts), Ordering::Relaxed);
+        *self.last_contact.lock() = Some(Instant::now());
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+// Keeps the leader's follower count accurate however serve_replica exits.
+struct FollowerGuard<'a>(&'a AtomicUsize);
+
+impl Drop for FollowerGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// =============================
+// LEADER SIDE
+// =============================
+
+// Serve one follower connection (after its `REPLICATE` line) until it disconnects.
+pub async fn serve_replica<R, W>(cache: &Cache, repl: &Replication, reader: &mut R, w: &mut W)
+where R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin {
+    let mut rx = cache.events.subscribe(); // Subscribe before the snapshot so no later write is missed
+    repl.followers.fetch_add(1, Ordering::Relaxed);
+    let _guard = FollowerGuard(&repl.followers);
+    if send_snapshot(cache, w).await.is_err() { return; }
+    let mut heartbeat = tokio::time::interval(repl.heartbeat);
+    let mut buf = String::new();
+    loop {
+        let op = tokio::select! {
+            msg = rx.recv() => match msg {
+                Ok(ev) => match op_for_event(cache, &ev) { Some(op) => op, None => continue },
+                Err(RecvError::Lagged(n)) => {
+                    warn!("Replication: follower fell {} events behind, sending a fresh snapshot", n);
+                    if send_snapshot(cache, w).await.is_err() { break; }
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = heartbeat.tick() => ReplOp::Ping { ts: now_ms() },
+            // Followers send nothing after REPLICATE; we only watch for them going away.
+            n = reader.read_line(&mut buf) => match n { Ok(0) | Err(_) => break, Ok(_) => { buf.clear(); continue } },
+        };
+        if write_ops(w, std::slice::from_ref(&op)).await.is_err() { break; }
+    }
+}
+
+// Translate a keyspace event into the op that brings a follower up to date.
+fn op_for_event(cache: &Cache, ev: &CacheEvent) -> Option<ReplOp> {
+    match ev.kind {
+        EventKind::Put | EventKind::Add | EventKind::Incr => {
+            // Send the current state; if the key is already gone a Delete/Expire event follows.
+            let key = Key(ev.key.clone()?);
+            let shard = &cache.shards[cache.hash_key(&key)];
+            let entry = shard.entries.get(&key)?;
+            set_op(&key, &entry, Instant::now(), ev.ts)
+        }
+        EventKind::Delete | EventKind::Expire => Some(ReplOp::Del { key: ev.key.clone()?, ts: ev.ts }),
+        // Keys removed by an "all" invalidation were already published as individual deletes.
+        EventKind::InvalidateTag if ev.mode == Some("all") => None,
+        EventKind::InvalidateTag if ev.mode == Some("subtree") => Some(ReplOp::InvalidateSubtrees { tags: ev.tags.clone(), ts: ev.ts }),
+        EventKind::InvalidateTag => Some(ReplOp::InvalidateTags { tags: ev.tags.clone(), ts: ev.ts }),
+        EventKind::Flush => Some(ReplOp::Flush { ts: ev.ts }),
+        EventKind::Schedule => None, // Its invalidations are published on their own when it runs
+    }
+}
+
+fn set_op(key: &Key, entry: &Entry, now: Instant, ts: u64) -> Option<ReplOp> {
+    if entry.is_stale() { return None; } // Invalidated through a versioned tag, not reclaimed yet
+    // Ship the remaining TTL rather than the original so the follower expires the key at the same time.
+    let ttl_ms = match entry.deadline() {
+        Some(deadline) if deadline <= now => return None,
+        Some(deadline) => Some((deadline - now).as_millis().max(1) as u64),
+        None => None,
+    };
+    Some(ReplOp::Set {
+        key: key.0.clone(),
+        value: entry.value.clone(),
+        tags: entry.tags.iter().map(|t| t.0.clone()).collect(),
+        ttl_ms,
+        ts,
+    })
+}
+
+async fn send_snapshot<W: AsyncWrite + Unpin>(cache: &Cache, w: &mut W) -> std::io::Result<()> {
+    let items = cache.shards.iter().map(|s| s.entries.len()).sum();
+    write_ops(w, &[ReplOp::SnapshotBegin { items, ts: now_ms() }]).await?;
+    for shard in &cache.shards {
+        // Copy one shard at a time so no map guard is held across an await.
+        let now = Instant::now();
+        let ts = now_ms();
+        let ops: Vec<ReplOp> = shard.entries.iter().filter_map(|e| set_op(e.key(), e.value(), now, ts)).collect();
+        write_ops(w, &ops).await?;
+    }
+    write_ops(w, &[ReplOp::SnapshotEnd { ts: now_ms() }]).await
+}
+
+async fn write_ops<W: AsyncWrite + Unpin>(w: &mut W, ops: &[ReplOp]) -> std::io::Result<()> {
+    if ops.is_empty() { return Ok(()); }
+    let mut out = String::new();
+    for op in ops {
+        out.push_str(&serde_json::to_string(op).map_err(std::io::Error::other)?);
+        out.push('\n');
+    }
+    w.write_all(out.as_bytes()).await
+}
+
+// =============================
+// FOLLOWER SIDE
+// =============================
+
+// Follow the configured leader until promoted, reconnecting (and re-syncing) after any failure.
+pub async fn run_follower(cache: Arc<Cache>, repl: Arc<Replication>) {
+    let Some(leader) = repl.leader().map(str::to_string) else {
+        warn!("Replication: role is follower but no leader address is configured");
+        return;
+    };
+    while repl.role() == Role::Follower {
+        match TcpStream::connect(&leader).await {
+            Ok(stream) => {
+                info!("Replication: connected to leader {}", leader);
+                if let Err(e) = follow(&cache, &repl, stream).await {
+                    warn!("Replication: lost leader {}: {}", leader, e);
+                }
+            }
+            Err(e) => warn!("Replication: cannot reach leader {}: {}", leader, e),
+        }
+        repl.connected.store(false, Ordering::Relaxed);
+        if repl.role() != Role::Follower { break; }
+        tokio::select! {
+            _ = tokio::time::sleep(repl.reconnect_delay) => {}
+            _ = repl.promoted.notified() => {}
+        }
+    }
+}
+
+async fn follow(cache: &Cache, repl: &Replication, mut stream: TcpStream) -> std::io::Result<()> {
+    let (r, mut w) = stream.split();
+    let mut reader = BufReader::new(r);
+    let mut line = String::new();
+    if let Some(auth) = &repl.auth_line {
+        w.write_all(format!("{}\n", auth).as_bytes()).await?;
+        reader.read_line(&mut line).await?;
+        if !line.starts_with("OK") { return Err(std::io::Error::other(format!("leader refused AUTH: {}", line.trim_end()))); }
+    }
+    w.write_all(b"REPLICATE\n").await?;
+    loop {
+        line.clear();
+        let n = tokio::select! {
+            n = reader.read_line(&mut line) => n?,
+            _ = repl.promoted.notified() => return Ok(()),
+        };
+        if n == 0 { return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "connection closed")); }
+        if repl.role() != Role::Follower { return Ok(()); }
+        let text = line.trim_end();
+        if let Some(err) = text.strip_prefix("ERR") { return Err(std::io::Error::other(format!("leader refused: {}", err.trim()))); }
+        let op: ReplOp = serde_json::from_str(text).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        apply(cache, repl, op);
+    }
+}
+
+// Apply one op to the local cache. Writes bypass the read-only check, which only guards client requests.
+pub fn apply(cache: &Cache, repl: &Replication, op: ReplOp) {
+    repl.record_contact(op.ts());
+    match op {
+        ReplOp::SnapshotBegin { items, .. } => {
+            info!("Replication: receiving snapshot of {} entries", items);
+            cache.flush_all();
+            repl.connected.store(true, Ordering::Relaxed);
+        }
+        ReplOp::SnapshotEnd { .. } => { repl.full_syncs.fetch_add(1, Ordering::Relaxed); }
+        ReplOp::Set { key, value, tags, ttl_ms, .. } => {
+            cache.restore(Key(key), value, tags.into_iter().map(Tag).collect(), ttl_ms.map(Duration::from_millis), None);
+        }
+        ReplOp::Del { key, .. } => { cache.invalidate_key(&Key(key)); }
+        ReplOp::InvalidateTags { tags, .. } => {
+            let tags: Vec<Tag> = tags.into_iter().map(Tag).collect();
+            cache.invalidate_tags(&tags, TagMatch::Any);
+        }
+        ReplOp::InvalidateSubtrees { tags, .. } => {
+            let tags: Vec<Tag> = tags.into_iter().map(Tag).collect();
+            cache.invalidate_tags(&tags, TagMatch::Subtree);
+        }
+        ReplOp::Flush { .. } => { cache.flush_all(); }
+        ReplOp::Ping { .. } => {}
+    }
+}
@@ -0,0 +1,274 @@
+// =============================
+// SCHEDULED INVALIDATIONS
+// =============================
+// Invalidations that run later: "drop `campaign:summer` at midnight" without a cron job calling
+// /invalidate-tag. A schedule names its target and when to run it (an absolute UNIX time in ms, or a
+// delay turned into one when it is created):
+//
+//   {"target":"tags","tags":["campaign:summer"],"mode":"any"}   tags (mode any / all / subtree)
+//   {"target":"keys","keys":["banner:1","banner:2"]}             keys
+//   {"target":"query","prefix":"promo:","tags":"summer&!evergreen"}   keys matching a prefix / tag expression
+//
+// Targets keep their client-facing names together with the namespace they were created in, and are
+// qualified when the schedule runs. One background task sleeps until the next schedule is due; a
+// schedule runs once and is then dropped. Creating, cancelling and running a schedule publish a
+// `schedule` keyspace event and an audit record, and the invalidation it performs publishes its usual
+// events and webhooks.
+//
+// With `[schedules] file` set, the pending schedules are written to that JSON file on every change
+// and loaded at startup; schedules that came due while the server was down run right away.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::audit::{AuditEvent, AuditLog};
+use crate::cache::{Cache, TagMatch};
+use crate::events::{CacheEvent, EventKind};
+use crate::namespace::{self, Namespace};
+use crate::transfer::TransferFilter;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulesConfig {
+    pub file: Option<String>, // JSON file holding pending schedules (none = lost on restart)
+}
+
+/// What a schedule invalidates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "target", rename_all = "snake_case")]
+pub enum Target {
+    Tags { tags: Vec<String>, #[serde(default = "default_mode")] mode: String },
+    Keys { keys: Vec<String> },
+    Query { #[serde(default)] prefix: Option<String>, #[serde(default)] tags: Option<String> }, // Tag expression, as in /admin/export
+}
+
+fn default_mode() -> String { "any".to_string() }
+
+impl Target {
+    /// Reject targets that could never run (empty lists, unknown modes, bad expressions).
+    pub fn validate(&self) -> Result<(), String> {
+        let names: Vec<&str> = match self {
+            Target::Tags { tags, .. } => tags.iter().map(String::as_str).collect(),
+            Target::Keys { keys } => keys.iter().map(String::as_str).collect(),
+            Target::Query { prefix, tags } => prefix.iter().chain(tags).map(String::as_str).collect(),
+        };
+        namespace::check_names(names).map_err(|e| format!("'{}' contains a reserved character", e.detail().escape_debug()))?;
+        match self {
+            Target::Tags { tags, mode } => {
+                if tags.iter().all(|t| t.is_empty()) { return Err("tags must not be empty".to_string()); }
+                tag_match(mode).map(|_| ())
+            }
+            Target::Keys { keys } if keys.iter().all(|k| k.is_empty()) => Err("keys must not be empty".to_string()),
+            Target::Keys { .. } => Ok(()),
+            Target::Query { prefix, tags } => {
+                let filter = TransferFilter::parse(prefix.clone(), tags.as_deref())?;
+                if filter.is_empty() { Err("prefix or tags is required".to_string()) } else { Ok(()) }
+            }
+        }
+    }
+
+    // Short description for audit records and logs.
+    fn describe(&self) -> String {
+        match self {
+            Target::Tags { tags, mode } => format!("tags {} ({})", tags.join(","), mode),
+            Target::Keys { keys } => format!("keys {}", keys.join(",")),
+            Target::Query { prefix, tags } => format!("query prefix={} tags={}", prefix.as_deref().unwrap_or("-"), tags.as_deref().unwrap_or("-")),
+        }
+    }
+}
+
+fn tag_match(mode: &str) -> Result<TagMatch, String> {
+    match mode {
+        "any" => Ok(TagMatch::Any),
+        "all" => Ok(TagMatch::All),
+        "subtree" | "prefix" => Ok(TagMatch::Subtree),
+        other => Err(format!("unknown mode '{}' (expected any, all or subtree)", other)),
+    }
+}
+
+/// A pending invalidation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    pub at_ms: u64, // When it runs (UNIX ms)
+    #[serde(flatten)]
+    pub target: Target,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    pub created_ms: u64,
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+#[derive(Debug)]
+pub struct Scheduler {
+    cache: Arc<Cache>,
+    audit: Arc<AuditLog>,
+    pending: Mutex<BTreeMap<String, Schedule>>,
+    file: Option<PathBuf>,
+    saving: Mutex<()>,    // Held from snapshot to rename, so saves reach the file in order
+    wake: Notify,         // Signalled when a schedule is added, so the runner recomputes its sleep
+    running: AtomicBool,
+}
+
+impl Scheduler {
+    /// A scheduler without persistence (embedded use, tests).
+    pub fn memory(cache: Arc<Cache>, audit: Arc<AuditLog>) -> Self {
+        Self { cache, audit, pending: Mutex::new(BTreeMap::new()), file: None, saving: Mutex::new(()), wake: Notify::new(), running: AtomicBool::new(false) }
+    }
+
+    /// Load the schedules left in the configured file (if any). A file that cannot be read or parsed
+    /// is an error, so it is never overwritten by an empty list.
+    pub fn open(config: &SchedulesConfig, cache: Arc<Cache>, audit: Arc<AuditLog>) -> anyhow::Result<Self> {
+        let mut scheduler = Self::memory(cache, audit);
+        let Some(path) = config.file.as_deref().filter(|p| !p.is_empty()) else { return Ok(scheduler) };
+        let path = PathBuf::from(path);
+        if path.exists() {
+            let loaded: Vec<Schedule> = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+            scheduler.pending.get_mut().extend(loaded.into_iter().map(|s| (s.id.clone(), s)));
+        }
+        scheduler.file = Some(path);
+        Ok(scheduler)
+    }
+
+    /// Start the background runner (once; later calls do nothing). Needs a tokio runtime.
+    pub fn start(self: &Arc<Self>) {
+        if self.running.swap(true, Ordering::SeqCst) { return; }
+        let this = self.clone();
+        tokio::spawn(async move {
+            loop {
+                this.run_due(now_ms());
+                let wait = this.next_at().map_or(Duration::from_secs(3600), |at| Duration::from_millis(at.saturating_sub(now_ms())));
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = this.wake.notified() => {}
+                }
+            }
+        });
+    }
+
+    /// Register a schedule (replacing one with the same id) and make sure the runner is going.
+    pub fn add(self: &Arc<Self>, schedule: Schedule, peer: Option<std::net::SocketAddr>) {
+        let detail = format!("create {} at {}: {}", schedule.id, schedule.at_ms, schedule.target.describe());
+        self.audit.record(AuditEvent::Schedule, schedule.created_by.as_deref(), peer, Some(detail));
+        self.publish(&schedule, "created", None);
+        self.pending.lock().insert(schedule.id.clone(), schedule);
+        self.save();
+        self.start();
+        self.wake.notify_one();
+    }
+
+    /// Drop a pending schedule; None when there is no such schedule.
+    pub fn cancel(&self, id: &str, by: Option<&str>, peer: Option<std::net::SocketAddr>) -> Option<Schedule> {
+        let schedule = self.pending.lock().remove(id)?;
+        self.save();
+        self.audit.record(AuditEvent::Schedule, by, peer, Some(format!("cancel {}: {}", id, schedule.target.describe())));
+        self.publish(&schedule, "cancelled", None);
+        Some(schedule)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Schedule> {
+        self.pending.lock().get(id).cloned()
+    }
+
+    /// Pending schedules of a namespace, soonest first.
+    pub fn list(&self, ns: &Namespace) -> Vec<Schedule> {
+        let mut all: Vec<Schedule> = self.pending.lock().values().filter(|s| s.namespace.as_deref() == ns.name()).cloned().collect();
+        all.sort_by(|a, b| (a.at_ms, &a.id).cmp(&(b.at_ms, &b.id)));
+        all
+    }
+
+    fn next_at(&self) -> Option<u64> {
+        self.pending.lock().values().map(|s| s.at_ms).min()
+    }
+
+    /// Run (and drop) every schedule due at `now`; returns how many ran.
+    pub fn run_due(&self, now: u64) -> usize {
+        let due: Vec<Schedule> = {
+            let mut pending = self.pending.lock();
+            let ids: Vec<String> = pending.values().filter(|s| s.at_ms <= now).map(|s| s.id.clone()).collect();
+            ids.iter().filter_map(|id| pending.remove(id)).collect()
+        };
+        if due.is_empty() { return 0; }
+        self.save();
+        for schedule in &due {
+            let count = self.execute(schedule);
+            info!("Schedule {} ran: {} ({} keys removed)", schedule.id, schedule.target.describe(), count);
+            let detail = format!("run {}: {} ({} keys removed)", schedule.id, schedule.target.describe(), count);
+            self.audit.record(AuditEvent::ScheduleRun, schedule.created_by.as_deref(), None, Some(detail));
+            self.publish(schedule, "ran", Some(count));
+        }
+        due.len()
+    }
+
+    // Perform the invalidation; returns the number of keys removed.
+    fn execute(&self, schedule: &Schedule) -> usize {
+        let ns = Namespace::bound(schedule.namespace.as_deref());
+        match &schedule.target {
+            Target::Tags { tags, mode } => {
+                let tags: Vec<_> = tags.iter().filter(|t| !t.is_empty()).map(|t| ns.tag(t.clone())).collect();
+                match (tags.as_slice(), tag_match(mode)) {
+                    ([tag], Ok(TagMatch::Any)) => self.cache.invalidate_tag(tag),
+                    (_, Ok(mode)) => self.cache.invalidate_tags(&tags, mode),
+                    (_, Err(_)) => 0,
+                }
+            }
+            Target::Keys { keys } => keys.iter().filter(|k| self.cache.invalidate_key(&ns.key(k.to_string()))).count(),
+            Target::Query { prefix, tags } => match TransferFilter::parse(prefix.clone(), tags.as_deref()) {
+                Ok(filter) if !filter.is_empty() => self.cache.invalidate_matching(&ns, &ns.qualify_filter(filter)),
+                _ => 0,
+            },
+        }
+    }
+
+    fn publish(&self, schedule: &Schedule, action: &'static str, count: Option<usize>) {
+        self.cache.events.publish(|| {
+            let (key, tags) = match &schedule.target {
+                Target::Tags { tags, .. } => (None, tags.clone()),
+                Target::Keys { keys } if keys.len() == 1 => (Some(keys[0].clone()), Vec::new()),
+                _ => (None, Vec::new()),
+            };
+            let ns = Namespace::bound(schedule.namespace.as_deref());
+            let key = key.map(|k| ns.key(k).0);
+            let tags = tags.into_iter().map(|t| ns.tag(t).0).collect();
+            CacheEvent::new(EventKind::Schedule, key, tags, count).with_schedule(&schedule.id, action)
+        });
+    }
+
+    // Write the pending schedules to the file (no-op without persistence). The snapshot is taken and
+    // written under `saving`, so a slower save can never replace a newer snapshot with an older one.
+    fn save(&self) {
+        let Some(path) = &self.file else { return };
+        let _saving = self.saving.lock();
+        let snapshot: Vec<Schedule> = self.pending.lock().values().cloned().collect();
+        if let Err(e) = write_atomically(path, &serde_json::to_vec_pretty(&snapshot).unwrap_or_default()) {
+            warn!("Could not save the schedules to {}: {}", path.display(), e);
+        }
+    }
+}
+
+// Replace `path` through a temporary file, so a crash never leaves half a file behind.
+fn write_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// A new schedule id.
+pub fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
@@ -0,0 +1,147 @@
+// =============================
+// SERVER IMPLEMENTATION
+// =============================
+// Wires a full server together from a loaded configuration: cache, auth, replication, cluster
+// routing, the expiry reaper and both listeners.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use sysinfo::System;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::audit::AuditLog;
+use crate::auth::{AuthState, Credentials};
+use crate::cache::Cache;
+use crate::cluster::Cluster;
+use crate::config::TagCacheConfig;
+use crate::http::{build_app, AppState};
+use crate::replication::{self, Replication, Role};
+use crate::schedule::Scheduler;
+use crate::tcp::run_tcp_server;
+use crate::webhooks::WebhookDispatcher;
+
+/// Run a TagCache server (HTTP + TCP) until the HTTP listener fails.
+///
+/// `config_path` is where credential changes made over the API are persisted. Logging is left to
+/// the caller (the `tagcache` binary installs a `tracing_subscriber`).
+pub async fn serve(config: TagCacheConfig, config_path: PathBuf) -> anyhow::Result<()> {
+    // Build the cache (Arc so it can be shared across tasks / threads).
+    let webhooks = WebhookDispatcher::start(config.webhooks.clone());
+    if webhooks.is_some() {
+        info!("Webhooks enabled for {} endpoint(s)", config.webhooks.endpoints.len());
+    }
+    let cache = Arc::new(Cache::new(config.server.num_shards)
+        .with_event_buffer(config.server.events_buffer)
+        .with_tag_separator(&config.cache.tag_separator)
+        .with_webhooks(webhooks)
+        .with_namespaces(config.namespaces.clone())
+        .with_tag_ttl(&config.tag_ttl)
+        .with_invalidation(&config.invalidation));
+    
+    // One-time migration: store any plain-text passwords left in the file as hashes.
+    match TagCacheConfig::hash_stored_passwords(&config_path) {
+        Ok(0) => {}
+        Ok(n) => info!("Hashed {} plain-text password(s) in {}", n, config_path.display()),
+        Err(e) => warn!("Could not hash the passwords in {}: {}", config_path.display(), e),
+    }
+
+    // Use credentials from configuration file
+    let auth_creds = Credentials {
+        username: config.authentication.username.clone(),
+        password: config.authentication.password.clone(),
+    };
+    let auth_state = Arc::new(AuthState::new(auth_creds, config_path.clone()).with_accounts(&config.authentication));
+    
+    // Initialize system monitor for CPU stats
+    let mut system = System::new_all();
+    system.refresh_all(); // Initial refresh
+    let system_monitor = Arc::new(parking_lot::Mutex::new(system));
+    
+    let replication = Replication::new(&config.replication);
+    if replication.role() == Role::Follower {
+        info!("Replication: following leader {} (writes {})", config.replication.leader.as_deref().unwrap_or("-"),
+              if config.replication.allow_writes { "allowed" } else { "rejected" });
+        tokio::spawn(replication::run_follower(cache.clone(), replication.clone()));
+    }
+
+    let cluster = if config.cluster.enabled {
+        let cluster = Cluster::new(&config.cluster).map_err(|e| anyhow::anyhow!("invalid cluster config: {}", e))?;
+        info!("Cluster mode: node {} of {} ({:?} routing)", config.cluster.node_id, config.cluster.nodes.len(), config.cluster.mode);
+        Some(cluster)
+    } else { None };
+
+    let audit = match AuditLog::open(&config.audit) {
+        Ok(log) => Arc::new(log),
+        Err(e) => {
+            warn!("Could not open the audit log {:?}: {} (keeping records in memory only)", config.audit.file, e);
+            Arc::new(AuditLog::memory(config.audit.recent))
+        }
+    };
+
+    // Refuse to start rather than run without the pending schedules and overwrite them on the next change.
+    let schedules = Scheduler::open(&config.schedules, cache.clone(), audit.clone())
+        .map_err(|e| anyhow::anyhow!("could not load the schedules from {:?}: {}", config.schedules.file, e))?;
+    let schedules = Arc::new(schedules);
+
+    let app_state = Arc::new(AppState { 
+        cache: cache.clone(), 
+        auth: auth_state.clone(),
+        system: system_monitor,
+        replication,
+        cluster,
+        schedules,
+        audit,
+    });
+    app_state.schedules.start(); // Runs schedules restored from the file
+
+    // Background task: expire due entries in small batches from the per-shard expiry heaps.
+    spawn_expiry_reaper(cache.clone(), Duration::from_millis(config.server.expiry_tick_ms), config.server.expiry_batch_size);
+
+    // Launch TCP server early (independent of HTTP lifecycle). Errors logged to stderr.
+    let tcp_state = app_state.clone();
+    let perf_config = config.performance.clone();
+    let tcp_port = config.server.tcp_port;
+    tokio::spawn(async move {
+        if let Err(e) = run_tcp_server(tcp_state, tcp_port, perf_config).await { eprintln!("TCP server error: {e}"); }
+    });
+
+    // Build Axum router with all endpoints.
+    let app = build_app(app_state.clone(), config.server.allowed_origin.clone());
+
+    // Bind TCP listener for HTTP (await returns listener only when bind succeeds).
+    let listener = tokio::net::TcpListener::bind(&format!("0.0.0.0:{}", config.server.http_port)).await?;
+    info!("TagCache HTTP port={} TCP port={} shards={} expiry_tick={}ms", 
+          config.server.http_port, config.server.tcp_port, config.server.num_shards, config.server.expiry_tick_ms);
+
+    // Serve HTTP forever (await until server stops via error / shutdown signal).
+    // Connect info gives handlers the client address (lockout and audit records).
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
+
+    Ok(()) // Return Result success
+}
+
+/// Spawn the background task that removes expired entries every `tick`, at most `batch` per shard
+/// per tick. Entries past their TTL are hidden from reads either way; the reaper reclaims memory and
+/// emits `expire` events for keys nobody reads again. It also removes the members of versioned tag
+/// invalidations (same batch size).
+pub fn spawn_expiry_reaper(cache: Arc<Cache>, tick: Duration, batch: usize) -> JoinHandle<()> {
+    let (tick, batch) = (tick.max(Duration::from_millis(1)), batch.max(1));
+    tokio::spawn(async move { // Detached; the handle lets embedders abort it
+        let mut interval = time::interval(tick);
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;                       // Wait for next tick
+            let expired_count = cache.expire_due(batch);
+            if expired_count > 0 {                       // Only log if we did work
+                tracing::debug!("Expired {} entries", expired_count);
+            }
+            let reclaimed = cache.reclaim_invalidated(batch); // Members of versioned tag invalidations
+            if reclaimed > 0 {
+                tracing::debug!("Reclaimed {} invalidated entries", reclaimed);
+            }
+        }
+    })
+}
@@ -0,0 +1,827 @@
+// =============================
+// INTERACTIVE SHELL (`tagcache shell`)
+// =============================
+// A REPL over the blocking `tagcache-client`: line editing with history and tab completion of
+// commands, keys and tags (fetched from the server), multi-line quoted / JSON values, per-command
+// timing, and search / tag inspection commands built on the HTTP API.
+//
+// With credentials the shell logs in once via `/auth/login` and sends the session token from then
+// on; if the token is rejected later (rotated, server restarted) it logs in again and retries.
+//
+// When stdin is not a terminal the same commands are read line by line without prompts, so the
+// shell can also be scripted (`tagcache shell < commands.txt`).
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tagcache_client::blocking::Client;
+use tagcache_client::{Command, Config, Error as ClientError, Mode, Reply, TagMode};
+
+const HISTORY_LIMIT: usize = 1000;
+const TAG_CACHE_TTL: Duration = Duration::from_secs(30); // How long completion reuses fetched tags
+
+// (name, arguments, description) - drives `help` and command completion.
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("get", "<key>", "Show a value (JSON values are pretty-printed)"),
+    ("put", "<key> <value> [--tags a,b] [--ttl ms]", "Store a value"),
+    ("add", "<key> <value> [--tags a,b] [--ttl ms]", "Store a value only if the key does not exist"),
+    ("incr", "<key> [by]", "Increment a counter"),
+    ("decr", "<key> [by]", "Decrement a counter"),
+    ("del", "<key>", "Delete a key"),
+    ("info", "<key>", "Show a key's tags, remaining TTL, creation time and size"),
+    ("keys", "<tag>", "List the keys carrying a tag"),
+    ("tags", "[prefix]", "List tags of recently written keys, with key counts"),
+    ("search", "[prefix] [--any a,b] [--all a,b] [--limit n]", "Find keys by prefix or tags"),
+    ("inv-tags", "<tag>... [--all]", "Invalidate keys with any (or all) of the tags"),
+    ("inv-keys", "<key>...", "Invalidate keys"),
+    ("flush", "", "Remove every entry"),
+    ("stats", "", "Show server statistics"),
+    ("health", "", "Check server health"),
+    ("login", "<username> [password]", "Log in and use a session token"),
+    ("timing", "[on|off]", "Show how long each command takes"),
+    ("history", "", "Show command history"),
+    ("help", "[command]", "Show this help"),
+    ("exit", "", "Leave the shell (also: quit, Ctrl-D)"),
+];
+
+// =============================
+// ENTRY POINTS
+// =============================
+/// Run the shell on the terminal (or on piped stdin). Blocking: call it from a plain thread or
+/// `spawn_blocking`, not from an async task.
+pub fn run(config: Config) -> anyhow::Result<()> {
+    let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+    if !interactive {
+        return run_script(config, io::stdin().lock(), &mut io::stdout());
+    }
+    let mut session = Session::connect(config)?;
+    session.timing = true;
+    let mut editor = LineEditor::new(history_path());
+    let prompt = format!("tagcache {}> ", session.endpoint());
+    println!("TagCache shell - type 'help' for commands, Tab to complete, Ctrl-D to exit");
+    let mut out = io::stdout();
+    while let Some(words) = read_command(&mut editor, &prompt, &mut session)? {
+        if words.is_empty() { continue; }
+        if words[0] == "login" && words.len() == 2 {
+            // Ask for the password without echoing it.
+            let Some(password) = editor.read_secret("Password: ")? else { continue };
+            let mut words = words;
+            words.push(password);
+            session.run_timed(&words, &editor.history, &mut out)?;
+            continue;
+        }
+        if let Flow::Exit = session.run_timed(&words, &editor.history, &mut out)? { break; }
+    }
+    Ok(())
+}
+
+/// Run commands read from `input` (one per line, continuation lines for open quotes / JSON) and
+/// write their output to `out`. Used for piped stdin and by tests.
+pub fn run_script(config: Config, input: impl BufRead, out: &mut impl Write) -> anyhow::Result<()> {
+    let mut session = Session::connect(config)?;
+    let mut pending = String::new();
+    for line in input.lines() {
+        let line = line?;
+        if pending.is_empty() && line.trim_start().starts_with('#') { continue; }
+        if !pending.is_empty() { pending.push('\n'); }
+        pending.push_str(&line);
+        let words = match split_words(&pending) {
+            Ok(Parsed::NeedMore) => continue,
+            Ok(Parsed::Words(words)) => words,
+            Err(e) => { writeln!(out, "ERR {}", e)?; pending.clear(); continue; }
+        };
+        pending.clear();
+        if words.is_empty() { continue; }
+        if let Flow::Exit = session.run_timed(&words, &[], out)? { return Ok(()); }
+    }
+    if !pending.is_empty() { writeln!(out, "ERR unterminated input")?; }
+    Ok(())
+}
+
+// Read one command, prompting for continuation lines while a quote or JSON value is open.
+fn read_command(editor: &mut LineEditor, prompt: &str, session: &mut Session) -> io::Result<Option<Vec<String>>> {
+    let mut text = String::new();
+    loop {
+        let p = if text.is_empty() { prompt } else { "...> " };
+        let line = match editor.read_line(p, &mut |before| session.complete(before))? {
+            Some(line) => line,
+            None if text.is_empty() => return Ok(None), // Ctrl-D on an empty prompt
+            None => return Ok(Some(Vec::new())),        // Ctrl-D / Ctrl-C inside a continuation: drop it
+        };
+        if !text.is_empty() { text.push('\n'); }
+        text.push_str(&line);
+        match split_words(&text) {
+            Ok(Parsed::NeedMore) => continue,
+            Ok(Parsed::Words(words)) => {
+                editor.add_history(&text);
+                return Ok(Some(words));
+            }
+            Err(e) => {
+                println!("ERR {}", e);
+                return Ok(Some(Vec::new()));
+            }
+        }
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".tagcache_history"))
+}
+
+// =============================
+// WORD SPLITTING
+// =============================
+/// Result of splitting shell input into words.
+#[derive(Debug, PartialEq)]
+pub enum Parsed {
+    Words(Vec<String>),
+    /// A quote or JSON value is still open; read another line and split the joined text.
+    NeedMore,
+}
+
+/// Split a command line into words: whitespace separated, `"double"` (with `\"`, `\\`, `\n`, `\t`
+/// escapes) or `'single'` quoted, and JSON objects / arrays (which may span lines) taken as one
+/// word and compacted onto a single line.
+pub fn split_words(input: &str) -> Result<Parsed, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() { i += 1; continue; }
+        let mut word = String::new();
+        if chars[i] == '{' || chars[i] == '[' {
+            let start = i;
+            let mut depth = 0usize;
+            let mut in_string = false;
+            while i < chars.len() {
+                let c = chars[i];
+                if in_string {
+                    if c == '\\' { i += 1; } else if c == '"' { in_string = false; }
+                } else {
+                    match c {
+                        '"' => in_string = true,
+                        '{' | '[' => depth += 1,
+                        '}' | ']' => { depth -= 1; if depth == 0 { i += 1; break; } }
+                        _ => {}
+                    }
+                }
+                i += 1;
+            }
+            if depth > 0 || in_string { return Ok(Parsed::NeedMore); }
+            let raw: String = chars[start..i].iter().collect();
+            let value: Value = serde_json::from_str(&raw).map_err(|e| format!("invalid JSON value: {}", e))?;
+            words.push(value.to_string());
+            continue;
+        }
+        while i < chars.len() && !chars[i].is_whitespace() {
+            match chars[i] {
+                '"' => {
+                    i += 1;
+                    loop {
+                        match chars.get(i) {
+                            None => return Ok(Parsed::NeedMore),
+                            Some('"') => break,
+                            Some('\\') => {
+                                i += 1;
+                                match chars.get(i) {
+                                    None => return Ok(Parsed::NeedMore),
+                                    Some('n') => word.push('\n'),
+                                    Some('t') => word.push('\t'),
+                                    Some(&c) => word.push(c),
+                                }
+                            }
+                            Some(&c) => word.push(c),
+                        }
+                        i += 1;
+                    }
+                }
+                '\'' => {
+                    i += 1;
+                    loop {
+                        match chars.get(i) {
+                            None => return Ok(Parsed::NeedMore),
+                            Some('\'') => break,
+                            Some(&c) => word.push(c),
+                        }
+                        i += 1;
+                    }
+                }
+                c => word.push(c),
+            }
+            i += 1;
+        }
+        words.push(word);
+    }
+    Ok(Parsed::Words(words))
+}
+
+// =============================
+// SESSION (COMMAND EXECUTION)
+// =============================
+enum Flow {
+    Continue,
+    Exit,
+}
+
+struct Session {
+    config: Config,                       // Without credentials once logged in (the token is added per client)
+    client: Client,
+    login: Option<(String, String)>,      // Kept to renew the token when the server rejects it
+    timing: bool,
+    tags: Option<(Instant, Vec<String>)>, // Completion cache
+}
+
+impl Session {
+    fn connect(config: Config) -> anyhow::Result<Self> {
+        let login = match (&config.token, &config.username, &config.password) {
+            (None, Some(u), Some(p)) => Some((u.clone(), p.clone())),
+            _ => None,
+        };
+        let client = Client::new(config.clone())?;
+        let mut session = Session { config, client, login: None, timing: false, tags: None };
+        if let Some((user, pass)) = login {
+            session.login(&user, &pass)?;
+        }
+        Ok(session)
+    }
+
+    fn endpoint(&self) -> String {
+        match self.config.mode {
+            Mode::Tcp => self.config.tcp_addr.clone(),
+            _ => self.config.http_url.trim_start_matches("http://").to_string(),
+        }
+    }
+
+    // Exchange username / password for a session token and use it for every later request.
+    fn login(&mut self, username: &str, password: &str) -> anyhow::Result<()> {
+        let anonymous = Client::new(Config { mode: Mode::Http, username: None, password: None, token: None, ..self.config.clone() })?;
+        let reply = match anonymous.http_post("/auth/login", Some(json!({"username": username, "password": password}))) {
+            Ok(reply) => reply,
+            Err(ClientError::Unauthorized) => anyhow::bail!("Login failed: invalid credentials"),
+            Err(e) => anyhow::bail!("Login failed: {}", e),
+        };
+        let token = reply.get("token").and_then(Value::as_str).ok_or_else(|| anyhow::anyhow!("Login failed: no token in reply"))?;
+        self.config = Config { username: None, password: None, token: None, ..self.config.clone() };
+        self.client = Client::new(Config { token: Some(token.to_string()), ..self.config.clone() })?;
+        self.login = Some((username.to_string(), password.to_string()));
+        Ok(())
+    }
+
+    // Run a request, logging in again once if the session token was rejected.
+    fn call<T>(&mut self, f: impl Fn(&Client) -> Result<T, ClientError>) -> Result<T, ClientError> {
+        match f(&self.client) {
+            Err(ClientError::Unauthorized) if self.login.is_some() => {
+                let (user, pass) = self.login.clone().expect("checked");
+                self.login(&user, &pass).map_err(|_| ClientError::Unauthorized)?;
+                f(&self.client)
+            }
+            result => result,
+        }
+    }
+
+    fn run_timed(&mut self, words: &[String], history: &[String], out: &mut impl Write) -> anyhow::Result<Flow> {
+        let started = Instant::now();
+        let flow = match self.run(words, history, out) {
+            Ok(flow) => flow,
+            Err(e) => { writeln!(out, "ERR {}", e)?; Flow::Continue }
+        };
+        if self.timing && !matches!(flow, Flow::Exit) {
+            writeln!(out, "({:.2} ms)", started.elapsed().as_secs_f64() * 1000.0)?;
+        }
+        Ok(flow)
+    }
+
+    fn run(&mut self, words: &[String], history: &[String], out: &mut impl Write) -> anyhow::Result<Flow> {
+        let mut args: Vec<String> = words[1..].to_vec();
+        match words[0].to_ascii_lowercase().as_str() {
+            "get" => {
+                let [key] = positional::<1>(&args, "get <key>")?;
+                match self.call(|c| c.get(&key))? {
+                    Some(value) => writeln!(out, "{}", pretty(&value))?,
+                    None => writeln!(out, "(not found)")?,
+                }
+            }
+            verb @ ("put" | "add") => {
+                let tags = take_flag(&mut args, "--tags").map(|t| split_list(&t)).unwrap_or_default();
+                let ttl = take_flag(&mut args, "--ttl").map(|t| t.parse::<u64>().map(Duration::from_millis)).transpose()
+                    .map_err(|_| anyhow::anyhow!("--ttl expects milliseconds"))?;
+                let [key, value] = positional::<2>(&args, "put <key> <value> [--tags a,b] [--ttl ms]")?;
+                let cmd = if verb == "put" {
+                    Command::Put { key, value, tags, ttl }
+                } else {
+                    Command::Add { key, value, tags, ttl }
+                };
+                match self.call(|c| c.execute(&cmd))? {
+                    Reply::Added(false) => writeln!(out, "EXISTS")?,
+                    _ => writeln!(out, "OK")?,
+                }
+            }
+            verb @ ("incr" | "decr") => {
+                let key = args.first().cloned().ok_or_else(|| anyhow::anyhow!("usage: {} <key> [by]", verb))?;
+                let by = args.get(1).map(|b| b.parse::<i64>()).transpose().map_err(|_| anyhow::anyhow!("amount must be an integer"))?.unwrap_or(1);
+                let value = if verb == "incr" { self.call(|c| c.incr(&key, by))? } else { self.call(|c| c.decr(&key, by))? };
+                writeln!(out, "{}", value)?;
+            }
+            "del" => {
+                let [key] = positional::<1>(&args, "del <key>")?;
+                writeln!(out, "{}", if self.call(|c| c.del(&key))? { "DELETED" } else { "(not found)" })?;
+            }
+            "info" => {
+                let [key] = positional::<1>(&args, "info <key>")?;
+                let path = format!("/keys/{}", percent_encoding::utf8_percent_encode(&key, percent_encoding::NON_ALPHANUMERIC));
+                let info = self.call(|c| c.http_get(&path))?;
+                if info.get("error").and_then(Value::as_str) == Some("not_found") {
+                    writeln!(out, "(not found)")?;
+                    return Ok(Flow::Continue);
+                }
+                let value = info.get("value").cloned().unwrap_or(Value::Null);
+                let size = match &value { Value::String(s) => s.len(), v => v.to_string().len() };
+                writeln!(out, "key:     {}", key)?;
+                writeln!(out, "tags:    {}", join_values(info.get("tags")))?;
+                match info.get("ttl_ms").and_then(Value::as_u64) {
+                    Some(ms) => writeln!(out, "ttl:     {} ms remaining", ms)?,
+                    None => writeln!(out, "ttl:     none")?,
+                }
+                if let Some(created) = info.get("created_ms").and_then(Value::as_i64).and_then(chrono::DateTime::from_timestamp_millis) {
+                    writeln!(out, "created: {}", created.to_rfc3339())?;
+                }
+                writeln!(out, "size:    {} bytes", size)?;
+            }
+            "keys" => {
+                let [tag] = positional::<1>(&args, "keys <tag>")?;
+                let mut keys = self.call(|c| c.keys_by_tag(&tag))?;
+                keys.sort();
+                for key in &keys { writeln!(out, "{}", key)?; }
+                writeln!(out, "({} keys)", keys.len())?;
+            }
+            "tags" => {
+                let prefix = args.first().cloned().unwrap_or_default();
+                let counts = self.tag_counts()?;
+                let mut shown = 0;
+                for (tag, count) in counts.iter().filter(|(t, _)| t.starts_with(&prefix)) {
+                    writeln!(out, "{:<40} {}", tag, count)?;
+                    shown += 1;
+                }
+                writeln!(out, "({} tags, from the 500 most recent keys)", shown)?;
+            }
+            "search" => {
+                let any = take_flag(&mut args, "--any").map(|t| split_list(&t));
+                let all = take_flag(&mut args, "--all").map(|t| split_list(&t));
+                let limit = take_flag(&mut args, "--limit").map(|l| l.parse::<usize>()).transpose()
+                    .map_err(|_| anyhow::anyhow!("--limit expects a number"))?.unwrap_or(50);
+                let body = json!({"q": args.first(), "tag_any": any, "tag_all": all, "limit": limit});
+                let found = self.call(|c| c.http_post("/search", Some(body.clone())))?;
+                let items = found.get("keys").and_then(Value::as_array).cloned().unwrap_or_default();
+                for item in &items {
+                    let ttl = item.get("ttl_ms").and_then(Value::as_u64).map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".into());
+                    writeln!(out, "{:<40} ttl={:<10} tags={}", item.get("key").and_then(Value::as_str).unwrap_or(""), ttl, join_values(item.get("tags")))?;
+                }
+                writeln!(out, "({} keys)", items.len())?;
+            }
+            "inv-tags" => {
+                let mode = if take_switch(&mut args, "--all") { TagMode::All } else { TagMode::Any };
+                if args.is_empty() { anyhow::bail!("usage: inv-tags <tag>... [--all]"); }
+                let tags: Vec<&str> = args.iter().map(String::as_str).collect();
+                writeln!(out, "{} invalidated", self.call(|c| c.invalidate_tags(&tags, mode))?)?;
+                self.tags = None;
+            }
+            "inv-keys" => {
+                if args.is_empty() { anyhow::bail!("usage: inv-keys <key>..."); }
+                let keys: Vec<&str> = args.iter().map(String::as_str).collect();
+                writeln!(out, "{} invalidated", self.call(|c| c.invalidate_keys(&keys))?)?;
+            }
+            "flush" => {
+                writeln!(out, "{} removed", self.call(|c| c.flush())?)?;
+                self.tags = None;
+            }
+            "stats" => {
+                let stats = self.call(|c| c.stats())?;
+                writeln!(out, "hits {}  misses {}  puts {}  invalidations {}  hit ratio {:.2}%",
+                    stats.hits, stats.misses, stats.puts, stats.invalidations, stats.hit_ratio * 100.0)?;
+            }
+            "health" => {
+                let health = self.call(|c| c.http_get("/health"))?;
+                writeln!(out, "{}", health.get("status").and_then(Value::as_str).unwrap_or("unknown"))?;
+            }
+            "login" => {
+                let [user, pass] = positional::<2>(&args, "login <username> [password]")?;
+                self.login(&user, &pass)?;
+                writeln!(out, "Logged in as {}", user)?;
+            }
+            "timing" => {
+                self.timing = match args.first().map(String::as_str) {
+                    Some("on") => true,
+                    Some("off") => false,
+                    None => !self.timing,
+                    Some(_) => anyhow::bail!("usage: timing [on|off]"),
+                };
+                writeln!(out, "timing {}", if self.timing { "on" } else { "off" })?;
+            }
+            "history" => {
+                for (i, line) in history.iter().enumerate() { writeln!(out, "{:>5}  {}", i + 1, line)?; }
+            }
+            "help" => match args.first() {
+                Some(name) => match COMMANDS.iter().find(|(n, _, _)| n == name) {
+                    Some((n, usage, desc)) => writeln!(out, "{} {}\n    {}", n, usage, desc)?,
+                    None => anyhow::bail!("unknown command '{}'", name),
+                },
+                None => {
+                    for (n, usage, desc) in COMMANDS { writeln!(out, "  {:<10} {:<46} {}", n, usage, desc)?; }
+                }
+            },
+            "exit" | "quit" => return Ok(Flow::Exit),
+            other => anyhow::bail!("unknown command '{}' (type 'help')", other),
+        }
+        Ok(Flow::Continue)
+    }
+
+    // Tags (with key counts) of the newest keys, for `tags` and completion. There is no tag listing
+    // endpoint, so they are derived from a `/search` sample like the web UI does.
+    fn tag_counts(&mut self) -> anyhow::Result<Vec<(String, usize)>> {
+        let found = self.call(|c| c.http_post("/search", Some(json!({"limit": 500}))))?;
+        let mut counts: std::collections::BTreeMap<String, usize> = std::collections::BTreeMap::new();
+        for item in found.get("keys").and_then(Value::as_array).into_iter().flatten() {
+            for tag in item.get("tags").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+                *counts.entry(tag.to_string()).or_default() += 1;
+            }
+        }
+        self.tags = Some((Instant::now(), counts.keys().cloned().collect()));
+        Ok(counts.into_iter().collect())
+    }
+
+    // =============================
+    // COMPLETION
+    // =============================
+    // Candidates for the word (or comma-separated tag) under the cursor: returns the char offset
+    // where the replaced segment starts and the full replacements.
+    fn complete(&mut self, before: &str) -> (usize, Vec<String>) {
+        let words: Vec<&str> = before.split_whitespace().collect();
+        let at_new_word = before.is_empty() || before.ends_with(char::is_whitespace);
+        let index = if at_new_word { words.len() } else { words.len() - 1 };
+        let current = if at_new_word { "" } else { words[index] };
+        let start = before.chars().count() - current.chars().count();
+        if index == 0 {
+            let names = COMMANDS.iter().map(|(n, _, _)| n.to_string()).chain(["quit".to_string()]);
+            return (start, names.filter(|n| n.starts_with(current)).collect());
+        }
+        let command = words[0].to_ascii_lowercase();
+        let previous = if index >= 1 { words[index - 1] } else { "" };
+        let tag_context = matches!(previous, "--tags" | "--any" | "--all") || matches!(command.as_str(), "keys" | "inv-tags");
+        if tag_context && !current.starts_with("--") {
+            // Complete the last comma-separated tag.
+            let (head, segment) = current.rsplit_once(',').map(|(h, s)| (h.chars().count() + 1, s)).unwrap_or((0, current));
+            return (start + head, self.complete_tags(segment));
+        }
+        match command.as_str() {
+            "get" | "put" | "add" | "incr" | "decr" | "del" | "info" if index == 1 => (start, self.complete_keys(current)),
+            "inv-keys" | "search" if !current.starts_with("--") => (start, self.complete_keys(current)),
+            "help" if index == 1 => (start, COMMANDS.iter().map(|(n, _, _)| n.to_string()).filter(|n| n.starts_with(current)).collect()),
+            "timing" if index == 1 => (start, ["on", "off"].iter().map(|s| s.to_string()).filter(|s| s.starts_with(current)).collect()),
+            _ => (start, Vec::new()),
+        }
+    }
+
+    fn complete_keys(&mut self, prefix: &str) -> Vec<String> {
+        let body = json!({"q": prefix, "limit": 50});
+        let found = self.call(|c| c.http_post("/search", Some(body.clone()))).unwrap_or(Value::Null);
+        let mut keys: Vec<String> = found.get("keys").and_then(Value::as_array).into_iter().flatten()
+            .filter_map(|item| item.get("key").and_then(Value::as_str).map(str::to_string))
+            .filter(|k| !k.contains(char::is_whitespace)) // Would need quoting
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    fn complete_tags(&mut self, prefix: &str) -> Vec<String> {
+        let fresh = matches!(&self.tags, Some((at, _)) if at.elapsed() < TAG_CACHE_TTL);
+        if !fresh && self.tag_counts().is_err() { return Vec::new(); }
+        self.tags.as_ref().map(|(_, tags)| tags.iter().filter(|t| t.starts_with(prefix)).cloned().collect()).unwrap_or_default()
+    }
+}
+
+// Exactly N positional arguments.
+fn positional<const N: usize>(args: &[String], usage: &str) -> anyhow::Result<[String; N]> {
+    <[String; N]>::try_from(args.to_vec()).map_err(|_| anyhow::anyhow!("usage: {}", usage))
+}
+
+// Remove `--name value` from args and return the value.
+fn take_flag(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    args.remove(i);
+    (i < args.len()).then(|| args.remove(i))
+}
+
+fn take_switch(args: &mut Vec<String>, name: &str) -> bool {
+    let Some(i) = args.iter().position(|a| a == name) else { return false };
+    args.remove(i);
+    true
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+}
+
+fn join_values(list: Option<&Value>) -> String {
+    let items: Vec<&str> = list.and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str).collect();
+    if items.is_empty() { "-".to_string() } else { items.join(",") }
+}
+
+// JSON objects / arrays are shown indented; anything else as stored.
+fn pretty(value: &str) -> String {
+    match serde_json::from_str::<Value>(value) {
+        Ok(v @ (Value::Object(_) | Value::Array(_))) => serde_json::to_string_pretty(&v).unwrap_or_else(|_| value.to_string()),
+        _ => value.to_string(),
+    }
+}
+
+// =============================
+// LINE EDITOR
+// =============================
+// Minimal readline: raw terminal mode (unix), cursor movement, history, Ctrl shortcuts and tab
+// completion. Without a raw-capable terminal it falls back to plain buffered line reads.
+struct LineEditor {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Ctrl(u8), // Letter of the combination, e.g. b'c'
+    Other,
+}
+
+impl LineEditor {
+    fn new(history_path: Option<PathBuf>) -> Self {
+        let mut history: Vec<String> = history_path.as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .map(|s| s.lines().map(|l| l.replace("\\n", "\n")).collect())
+            .unwrap_or_default();
+        let excess = history.len().saturating_sub(HISTORY_LIMIT);
+        history.drain(..excess);
+        Self { history, history_path }
+    }
+
+    fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(String::as_str) == Some(line) { return; }
+        self.history.push(line.to_string());
+        if let Some(path) = &self.history_path {
+            // Appended as it happens so history survives crashes; multi-line entries are escaped.
+            if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(f, "{}", line.replace('\n', "\\n"));
+            }
+        }
+    }
+
+    /// Read one line; None on end of input (Ctrl-D on an empty line) and an empty line on Ctrl-C.
+    fn read_line(&mut self, prompt: &str, complete: &mut dyn FnMut(&str) -> (usize, Vec<String>)) -> io::Result<Option<String>> {
+        let Ok(_raw) = RawMode::enable() else { return plain_read_line(prompt) };
+        let mut buf: Vec<char> = Vec::new();
+        let mut pos = 0;
+        let mut browsing = self.history.len(); // Index into history while pressing Up / Down
+        let mut draft: Vec<char> = Vec::new(); // Line being typed before browsing started
+        redraw(prompt, &buf, pos)?;
+        loop {
+            match read_key()? {
+                None => return Ok(None),
+                Some(Key::Enter) => { print!("\r\n"); io::stdout().flush()?; return Ok(Some(buf.into_iter().collect())); }
+                Some(Key::Ctrl(b'c')) => { print!("^C\r\n"); io::stdout().flush()?; return Ok(Some(String::new())); }
+                Some(Key::Ctrl(b'd')) if buf.is_empty() => { print!("\r\n"); io::stdout().flush()?; return Ok(None); }
+                Some(Key::Ctrl(b'd')) | Some(Key::Delete) => { if pos < buf.len() { buf.remove(pos); } }
+                Some(Key::Char(c)) => { buf.insert(pos, c); pos += 1; }
+                Some(Key::Backspace) => { if pos > 0 { pos -= 1; buf.remove(pos); } }
+                Some(Key::Left) | Some(Key::Ctrl(b'b')) => pos = pos.saturating_sub(1),
+                Some(Key::Right) | Some(Key::Ctrl(b'f')) => pos = (pos + 1).min(buf.len()),
+                Some(Key::Home) | Some(Key::Ctrl(b'a')) => pos = 0,
+                Some(Key::End) | Some(Key::Ctrl(b'e')) => pos = buf.len(),
+                Some(Key::Ctrl(b'u')) => { buf.drain(..pos); pos = 0; }
+                Some(Key::Ctrl(b'k')) => buf.truncate(pos),
+                Some(Key::Ctrl(b'w')) => {
+                    let mut start = pos;
+                    while start > 0 && buf[start - 1].is_whitespace() { start -= 1; }
+                    while start > 0 && !buf[start - 1].is_whitespace() { start -= 1; }
+                    buf.drain(start..pos);
+                    pos = start;
+                }
+                Some(Key::Ctrl(b'l')) => { print!("\x1b[2J\x1b[H"); }
+                Some(Key::Up) | Some(Key::Ctrl(b'p')) => {
+                    if browsing > 0 {
+                        if browsing == self.history.len() { draft = buf.clone(); }
+                        browsing -= 1;
+                        buf = self.history[browsing].chars().collect();
+                        pos = buf.len();
+                    }
+                }
+                Some(Key::Down) | Some(Key::Ctrl(b'n')) => {
+                    if browsing < self.history.len() {
+                        browsing += 1;
+                        buf = if browsing == self.history.len() { draft.clone() } else { self.history[browsing].chars().collect() };
+                        pos = buf.len();
+                    }
+                }
+                Some(Key::Tab) => {
+                    let before: String = buf[..pos].iter().collect();
+                    let (start, candidates) = complete(&before);
+                    let current: String = buf[start..pos].iter().collect();
+                    match candidates.as_slice() {
+                        [] => print!("\x07"),
+                        [only] => {
+                            let mut insert: Vec<char> = only.chars().skip(current.chars().count()).collect();
+                            if !only.ends_with(',') { insert.push(' '); }
+                            let n = insert.len();
+                            buf.splice(pos..pos, insert);
+                            pos += n;
+                        }
+                        many => {
+                            let common = common_prefix(many);
+                            if common.chars().count() > current.chars().count() {
+                                let insert: Vec<char> = common.chars().skip(current.chars().count()).collect();
+                                let n = insert.len();
+                                buf.splice(pos..pos, insert);
+                                pos += n;
+                            } else {
+                                print!("\r\n{}\r\n", many.iter().take(100).cloned().collect::<Vec<_>>().join("  "));
+                            }
+                        }
+                    }
+                }
+                Some(_) => {}
+            }
+            redraw(prompt, &buf, pos)?;
+        }
+    }
+
+    /// Read a line without echoing it (passwords).
+    fn read_secret(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        let Ok(_raw) = RawMode::enable() else { return plain_read_line(prompt) };
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut secret = String::new();
+        loop {
+            match read_key()? {
+                None | Some(Key::Ctrl(b'c')) => { print!("\r\n"); return Ok(None); }
+                Some(Key::Enter) => { print!("\r\n"); io::stdout().flush()?; return Ok(Some(secret)); }
+                Some(Key::Backspace) => { secret.pop(); }
+                Some(Key::Char(c)) => secret.push(c),
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+fn plain_read_line(prompt: &str) -> io::Result<Option<String>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 { return Ok(None); }
+    while line.ends_with(['\n', '\r']) { line.pop(); }
+    Ok(Some(line))
+}
+
+fn redraw(prompt: &str, buf: &[char], pos: usize) -> io::Result<()> {
+    let line: String = buf.iter().map(|&c| if c == '\n' { '↵' } else { c }).collect();
+    let mut out = io::stdout().lock();
+    write!(out, "\r{}{}\x1b[K", prompt, line)?;
+    if pos < buf.len() { write!(out, "\x1b[{}D", buf.len() - pos)?; }
+    out.flush()
+}
+
+fn common_prefix(items: &[String]) -> String {
+    let mut prefix: Vec<char> = items[0].chars().collect();
+    for item in &items[1..] {
+        let n = prefix.iter().zip(item.chars()).take_while(|(a, b)| **a == *b).count();
+        prefix.truncate(n);
+    }
+    prefix.into_iter().collect()
+}
+
+fn read_byte() -> io::Result<Option<u8>> {
+    let mut b = [0u8; 1];
+    match io::stdin().lock().read(&mut b)? {
+        0 => Ok(None),
+        _ => Ok(Some(b[0])),
+    }
+}
+
+// Decode one key press (UTF-8 characters and the common VT100 escape sequences).
+fn read_key() -> io::Result<Option<Key>> {
+    let Some(b) = read_byte()? else { return Ok(None) };
+    Ok(Some(match b {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        127 | 8 => Key::Backspace,
+        1..=26 => Key::Ctrl(b'a' + b - 1),
+        27 => {
+            let Some(next) = read_byte()? else { return Ok(None) };
+            if next != b'[' && next != b'O' { return Ok(Some(Key::Other)); }
+            let mut param = Vec::new();
+            loop {
+                match read_byte()? {
+                    None => return Ok(None),
+                    Some(d @ b'0'..=b'9') => param.push(d),
+                    Some(b'A') => break Key::Up,
+                    Some(b'B') => break Key::Down,
+                    Some(b'C') => break Key::Right,
+                    Some(b'D') => break Key::Left,
+                    Some(b'H') => break Key::Home,
+                    Some(b'F') => break Key::End,
+                    Some(b'~') => break match param.as_slice() {
+                        b"3" => Key::Delete,
+                        b"1" | b"7" => Key::Home,
+                        b"4" | b"8" => Key::End,
+                        _ => Key::Other,
+                    },
+                    Some(_) => break Key::Other,
+                }
+            }
+        }
+        0x20..=0x7e => Key::Char(b as char),
+        0xc0..=0xf7 => {
+            let len = if b >= 0xf0 { 4 } else if b >= 0xe0 { 3 } else { 2 };
+            let mut bytes = vec![b];
+            for _ in 1..len {
+                match read_byte()? { Some(c) => bytes.push(c), None => return Ok(None) }
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Other,
+            }
+        }
+        _ => Key::Other,
+    }))
+}
+
+// Terminal raw mode for the lifetime of the guard: no line buffering, echo or signal keys (Ctrl-C
+// is handled by the editor). Output processing stays on, so "\n" still moves to a new line.
+//
+// The settings to put back live in `ORIGINAL` rather than in the guard, so the panic hook installed
+// by `enable` can restore them before the panic message is printed, whether the panic then unwinds
+// through the guard or aborts the process.
+#[cfg(unix)]
+struct RawMode;
+
+#[cfg(unix)]
+static ORIGINAL: parking_lot::Mutex<Option<libc::termios>> = parking_lot::Mutex::new(None);
+
+#[cfg(unix)]
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        static PANIC_HOOK: std::sync::Once = std::sync::Once::new();
+        PANIC_HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| { restore_terminal(); previous(info); }));
+        });
+        // SAFETY: tcgetattr / tcsetattr only read and write the termios struct we pass for stdin.
+        unsafe {
+            let mut t: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut t) != 0 { return Err(io::Error::last_os_error()); }
+            let original = t;
+            t.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+            t.c_iflag &= !(libc::ICRNL | libc::IXON);
+            t.c_cc[libc::VMIN] = 1;
+            t.c_cc[libc::VTIME] = 0;
+            *ORIGINAL.lock() = Some(original); // Before switching, so a failure midway is still undone
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &t) != 0 {
+                let err = io::Error::last_os_error();
+                restore_terminal();
+                return Err(err);
+            }
+            Ok(RawMode)
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+// Put back the settings saved by `RawMode::enable` (once; later calls do nothing).
+#[cfg(unix)]
+fn restore_terminal() {
+    let Some(original) = ORIGINAL.lock().take() else { return };
+    // SAFETY: restores the settings read in `enable`.
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &original); }
+}
+
+#[cfg(not(unix))]
+struct RawMode;
+
+#[cfg(not(unix))]
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "raw terminal mode is only implemented on unix"))
+    }
+}
@@ -0,0 +1,142 @@
+// =============================
+// TAG EXPRESSIONS
+// =============================
+// Boolean filters over an entry's tags, used to select keys for export / import:
+//
+//   users                  key has tag `users`
+//   users&tenant:42        both tags
+//   users|admins           either tag (`users,admins` is the same)
+//   users&!banned          `users` but not `banned`
+//   (a|b)&!c               parentheses group
+//
+// Precedence: `!` binds tightest, then `&`, then `|` / `,`. Whitespace around operators is ignored.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cache::Tag;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+impl TagExpr {
+    pub fn parse(s: &str) -> Result<TagExpr, String> {
+        let mut p = Parser { chars: s.chars().filter(|c| !c.is_whitespace()).collect(), pos: 0 };
+        let expr = p.or()?;
+        match p.peek() {
+            None => Ok(expr),
+            Some(c) => Err(format!("unexpected '{}' at position {} in tag expression", c, p.pos + 1)),
+        }
+    }
+
+    /// Whether an entry carrying `tags` satisfies the expression.
+    pub fn matches(&self, tags: &[Tag]) -> bool {
+        match self {
+            TagExpr::Tag(t) => tags.iter().any(|tag| tag.0 == *t),
+            TagExpr::Not(e) => !e.matches(tags),
+            TagExpr::And(a, b) => a.matches(tags) && b.matches(tags),
+            TagExpr::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+
+    /// The same expression over renamed tags (e.g. namespace-qualified ones).
+    pub fn map_tags(self, f: &impl Fn(String) -> String) -> TagExpr {
+        match self {
+            TagExpr::Tag(t) => TagExpr::Tag(f(t)),
+            TagExpr::Not(e) => TagExpr::Not(Box::new(e.map_tags(f))),
+            TagExpr::And(a, b) => TagExpr::And(Box::new(a.map_tags(f)), Box::new(b.map_tags(f))),
+            TagExpr::Or(a, b) => TagExpr::Or(Box::new(a.map_tags(f)), Box::new(b.map_tags(f))),
+        }
+    }
+}
+
+impl FromStr for TagExpr {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TagExpr::parse(s)
+    }
+}
+
+impl fmt::Display for TagExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagExpr::Tag(t) => write!(f, "{}", t),
+            TagExpr::Not(e) => match **e {
+                TagExpr::Tag(_) | TagExpr::Not(_) => write!(f, "!{}", e),
+                _ => write!(f, "!({})", e),
+            },
+            TagExpr::And(a, b) => {
+                let side = |e: &TagExpr, f: &mut fmt::Formatter<'_>| match e {
+                    TagExpr::Or(..) => write!(f, "({})", e),
+                    _ => write!(f, "{}", e),
+                };
+                side(a, f)?;
+                write!(f, "&")?;
+                side(b, f)
+            }
+            TagExpr::Or(a, b) => write!(f, "{}|{}", a, b),
+        }
+    }
+}
+
+// Recursive descent over the expression with whitespace already removed.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn or(&mut self) -> Result<TagExpr, String> {
+        let mut left = self.and()?;
+        while matches!(self.peek(), Some('|') | Some(',')) {
+            self.pos += 1;
+            left = TagExpr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<TagExpr, String> {
+        let mut left = self.unary()?;
+        while self.peek() == Some('&') {
+            self.pos += 1;
+            left = TagExpr::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<TagExpr, String> {
+        match self.peek() {
+            Some('!') => {
+                self.pos += 1;
+                Ok(TagExpr::Not(Box::new(self.unary()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.or()?;
+                if self.peek() != Some(')') { return Err("missing ')' in tag expression".to_string()); }
+                self.pos += 1;
+                Ok(inner)
+            }
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if !"&|,!()".contains(c)) { self.pos += 1; }
+                if self.pos == start {
+                    return Err(match self.peek() {
+                        Some(c) => format!("expected a tag before '{}' in tag expression", c),
+                        None => "expected a tag at the end of the tag expression".to_string(),
+                    });
+                }
+                Ok(TagExpr::Tag(self.chars[start..self.pos].iter().collect()))
+            }
+        }
+    }
+}
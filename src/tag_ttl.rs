@@ -0,0 +1,116 @@
+// =============================
+// TAG TTL POLICIES
+// =============================
+// Expiry rules attached to tags, so "everything tagged `session` expires after 30 minutes" holds no
+// matter which client wrote the entry or what `ttl_ms` it sent:
+//
+//   mode = "min"        entries carrying the tag never outlive `ttl_seconds`: a longer client TTL
+//                       (or none) is cut down to it, a shorter one is kept
+//   mode = "override"   entries carrying the tag always get exactly `ttl_seconds`
+//
+// When several tags of one entry have policies, the shortest override wins over the client's TTL and
+// every `min` policy then caps the result. Policies apply to writes that store an entry (PUT, ADD,
+// INCR / DECR, hash / list / set / JSON writes, rate limiters); lock leases keep their own TTL, and
+// imported or replicated entries keep the TTL they arrive with. Retagging a key does not touch its
+// TTL either.
+//
+// Policies come from `[[tag_ttl.policies]]` in the config and can be changed at runtime through
+// /admin/tag-ttl (not written back to the file). They name stored tags, so inside a namespace they
+// are set through the API with the namespace selected.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::cache::Tag;
+
+/// How a policy combines with the TTL a client asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TtlMode {
+    #[default]
+    Min,      // At most the policy's TTL
+    Override, // Exactly the policy's TTL
+}
+
+impl TtlMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "min" => Some(TtlMode::Min),
+            "override" => Some(TtlMode::Override),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TtlMode::Min => "min",
+            TtlMode::Override => "override",
+        }
+    }
+}
+
+/// One `[[tag_ttl.policies]]` entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagTtlPolicy {
+    pub tag: String,
+    pub ttl_seconds: u64,
+    #[serde(default)]
+    pub mode: TtlMode,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagTtlConfig {
+    pub policies: Vec<TagTtlPolicy>,
+}
+
+/// The live policy table consulted on every write.
+#[derive(Debug, Default)]
+pub struct TagTtl {
+    policies: RwLock<HashMap<Tag, (Duration, TtlMode)>>,
+}
+
+impl TagTtl {
+    pub fn new(config: &TagTtlConfig) -> Self {
+        let table = config.policies.iter()
+            .map(|p| (Tag::new(p.tag.clone()), (Duration::from_secs(p.ttl_seconds), p.mode)))
+            .collect();
+        Self { policies: RwLock::new(table) }
+    }
+
+    /// Add or replace the policy of `tag`.
+    pub fn set(&self, tag: Tag, ttl: Duration, mode: TtlMode) {
+        self.policies.write().insert(tag, (ttl, mode));
+    }
+
+    /// Drop the policy of `tag`; false when it had none.
+    pub fn remove(&self, tag: &Tag) -> bool {
+        self.policies.write().remove(tag).is_some()
+    }
+
+    pub fn get(&self, tag: &Tag) -> Option<(Duration, TtlMode)> {
+        self.policies.read().get(tag).copied()
+    }
+
+    /// Every policy, in tag order.
+    pub fn list(&self) -> Vec<(Tag, Duration, TtlMode)> {
+        let mut all: Vec<_> = self.policies.read().iter().map(|(t, (ttl, mode))| (t.clone(), *ttl, *mode)).collect();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        all
+    }
+
+    /// The TTL an entry carrying `tags` gets when the client asked for `ttl`.
+    pub fn apply(&self, tags: &[Tag], ttl: Option<Duration>) -> Option<Duration> {
+        let policies = self.policies.read();
+        if policies.is_empty() { return ttl; }
+        let matching: Vec<(Duration, TtlMode)> = tags.iter().filter_map(|t| policies.get(t).copied()).collect();
+        let overridden = matching.iter().filter(|(_, m)| *m == TtlMode::Override).map(|(d, _)| *d).min();
+        let capped = matching.iter().filter(|(_, m)| *m == TtlMode::Min).map(|(d, _)| *d).min();
+        match (overridden.or(ttl), capped) {
+            (Some(t), Some(cap)) => Some(t.min(cap)),
+            (t, cap) => t.or(cap),
+        }
+    }
+}
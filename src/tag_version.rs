@@ -0,0 +1,155 @@
+// =============================
+// VERSIONED TAG INVALIDATION
+// =============================
+// The default ("eager") tag invalidation removes every member of the tag before it returns, which for
+// a tag with millions of keys holds the caller for seconds. In "versioned" mode each tag has a
+// generation counter instead: writes stamp the entry with the current generation of each of its
+// versioned tags, and invalidating the tag only bumps the counter. An entry whose stamp no longer
+// matches is dead: every read path treats it like an expired entry (a miss), and the reaper removes it
+// in small batches afterwards (`Cache::reclaim_invalidated`).
+//
+//   [invalidation]
+//   mode = "eager"                  # or "versioned": the default for every tag
+//   versioned_tags = ["feed"]       # tags invalidated by generation whatever the default
+//   eager_tags = ["session"]        # tags invalidated eagerly whatever the default
+//
+// Tags are listed by their client-facing names and apply in every namespace. Versioned invalidation
+// covers single tags and the any / subtree modes; "all" invalidations (keys carrying every one of the
+// tags) stay eager, since no single counter describes them. The mode only decides how keys are
+// removed: events, webhooks and replication are the same in both.
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::cache::Tag;
+use crate::namespace;
+
+/// How invalidating a tag removes its keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidationMode {
+    #[default]
+    Eager,     // Remove every member before returning
+    Versioned, // Bump the tag's generation; members become misses and are reclaimed in the background
+}
+
+impl InvalidationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvalidationMode::Eager => "eager",
+            InvalidationMode::Versioned => "versioned",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InvalidationConfig {
+    pub mode: InvalidationMode,
+    pub versioned_tags: Vec<String>,
+    pub eager_tags: Vec<String>,
+}
+
+// The generation of one versioned tag seen by a write.
+#[derive(Debug, Clone)]
+struct Stamp(Arc<AtomicU64>, u64);
+
+/// Generations an entry was written under (empty when none of its tags is versioned).
+#[derive(Debug, Clone, Default)]
+pub struct Stamps(Box<[Stamp]>);
+
+impl Stamps {
+    /// Whether one of the entry's versioned tags was invalidated since the entry was written.
+    pub fn is_stale(&self) -> bool {
+        self.0.iter().any(|Stamp(generation, seen)| generation.load(Ordering::Acquire) != *seen)
+    }
+}
+
+/// Which tags are versioned, and their generation counters.
+#[derive(Debug, Default)]
+pub struct TagVersions {
+    mode: InvalidationMode,
+    versioned: HashSet<String>,
+    eager: HashSet<String>,
+    // Created on the first write of a tag; dropped by `prune` once no entry holds them.
+    generations: DashMap<Tag, Arc<AtomicU64>>,
+}
+
+impl TagVersions {
+    pub fn new(config: &InvalidationConfig) -> Self {
+        Self {
+            mode: config.mode,
+            versioned: config.versioned_tags.iter().cloned().collect(),
+            eager: config.eager_tags.iter().cloned().collect(),
+            generations: DashMap::new(),
+        }
+    }
+
+    /// True when nothing is versioned, so writes skip stamping altogether.
+    fn is_off(&self) -> bool {
+        self.mode == InvalidationMode::Eager && self.versioned.is_empty()
+    }
+
+    /// The mode `tag` is invalidated with.
+    pub fn mode_of(&self, tag: &Tag) -> InvalidationMode {
+        if self.is_off() { return InvalidationMode::Eager; }
+        let name = namespace::split(&tag.0).map_or(tag.as_str(), |(_, name)| name);
+        if self.versioned.contains(name) { InvalidationMode::Versioned }
+        else if self.eager.contains(name) { InvalidationMode::Eager }
+        else { self.mode }
+    }
+
+    /// Stamp a write carrying `tags` with the current generation of its versioned tags.
+    pub fn stamp(&self, tags: &[Tag]) -> Stamps {
+        if self.is_off() { return Stamps::default(); }
+        let stamps: Vec<Stamp> = tags.iter().filter(|t| self.mode_of(t) == InvalidationMode::Versioned).map(|t| self.current(t)).collect();
+        Stamps(stamps.into_boxed_slice())
+    }
+
+    /// Stamps for an entry whose tags change from `old_tags` (stamped `old`) to `tags`. Tags it
+    /// keeps keep the generation it was written under, so a bump racing the change still kills the
+    /// entry; only the added tags take the current generation.
+    pub fn restamp(&self, old: &Stamps, old_tags: &[Tag], tags: &[Tag]) -> Stamps {
+        if self.is_off() { return Stamps::default(); }
+        let stamps: Vec<Stamp> = tags.iter().filter(|t| self.mode_of(t) == InvalidationMode::Versioned).map(|t| {
+            let kept = if old_tags.contains(t) {
+                // The entry holds the counter, so `prune` cannot have replaced it.
+                self.generations.get(t).and_then(|g| old.0.iter().find(|s| Arc::ptr_eq(&s.0, g.value())).cloned())
+            } else { None };
+            kept.unwrap_or_else(|| self.current(t))
+        }).collect();
+        Stamps(stamps.into_boxed_slice())
+    }
+
+    fn current(&self, tag: &Tag) -> Stamp {
+        let generation = self.generations.entry(tag.clone()).or_default().clone();
+        let seen = generation.load(Ordering::Acquire);
+        Stamp(generation, seen)
+    }
+
+    /// Invalidate every entry stamped with `tag`'s current generation.
+    pub fn bump(&self, tag: &Tag) {
+        if let Some(generation) = self.generations.get(tag) { generation.fetch_add(1, Ordering::AcqRel); }
+    }
+
+    /// Current generation of `tag` (None before its first versioned write).
+    pub fn generation(&self, tag: &Tag) -> Option<u64> {
+        self.generations.get(tag).map(|g| g.load(Ordering::Acquire))
+    }
+
+    /// Drop the counters of tags no entry is stamped with any more; returns how many went.
+    pub fn prune(&self) -> usize {
+        let before = self.generations.len();
+        // The map's lock is held, so no write can be cloning the counter meanwhile.
+        self.generations.retain(|_, generation| Arc::strong_count(generation) > 1);
+        before - self.generations.len()
+    }
+
+    /// Number of tags with a generation counter.
+    pub fn tracked(&self) -> usize {
+        self.generations.len()
+    }
+}
@@ -0,0 +1,772 @@
+// =============================
+// TCP PROTOCOL IMPLEMENTATION
+// =============================
+// Custom lightweight line protocol for lower overhead than HTTP/JSON: one tab-separated command per
+// line, one reply line per command (except SUBSCRIBE / REPLICATE, which switch the connection into
+// a streaming mode).
+
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader}; // Async buffered IO extensions
+use tokio::net::{TcpListener, TcpStream}; // Async TCP server primitives
+use tracing::{info, warn};
+
+use crate::audit::AuditEvent;
+use crate::auth::{self, LoginError, Principal};
+use crate::cache::{Cache, Key, Tag, TagMatch};
+use crate::cluster::{self, TcpRoute};
+use crate::config::PerformanceConfig;
+use crate::counter::{CounterOptions, CounterValue};
+use crate::document::{JsonOp, JsonPath};
+use crate::events::EventFilter;
+use crate::http::{audit_login_failure, AppState};
+use crate::lock::{self, Lease, LockError};
+use crate::namespace::{self, Namespace, NamespaceError};
+use crate::ratelimit::{Algorithm, RateLimit};
+use crate::replication::{self, Role};
+use crate::transfer::TransferFilter;
+use crate::value::{Value, ValueError};
+
+// AUTH <user> <password> | AUTH <token-or-api-key> sets the connection's principal. Until then every
+// other command answers `ERR unauthorized`, unless `authentication.tcp_require_auth` is turned off
+// (then the connection may run read-only commands). Commands above the principal's role, or outside its scope,
+// answer `ERR forbidden`.
+//
+// SELECT <namespace|-> switches the connection to a namespace (see namespace.rs); keys and tags of
+// later commands are rewritten into it before they run, so the command handlers below never see
+// the difference. Accounts bound to a namespace start in it and cannot select another.
+
+// Minimum role per command.
+fn required_role(cmd: &str) -> auth::Role {
+    use auth::Role;
+    match cmd {
+        "PUT" | "ADD" | "INCR" | "DECR" | "HSET" | "HINCRBY" | "LPUSH" | "RPUSH" | "LPOP" | "RPOP" | "SADD" | "SREM" => Role::Writer,
+        "JSON_SET" | "JSON_MERGE" | "JSON_APPEND" | "JSON_INCR" | "RATELIMIT" => Role::Writer,
+        "LOCK" | "RENEW" | "UNLOCK" | "TAG_ADD" | "TAG_DEL" => Role::Writer,
+        "DEL" | "HDEL" | "INV_TAG" | "INV_TAGS_ANY" | "INV_TAGS_ALL" | "INV_TAGS_TREE" | "INV_KEYS" | "TAG_EXPIRE" => Role::Invalidator,
+        "FLUSH" | "PROMOTE" | "REPLICATE" | "TAG_RENAME" | "TAG_MERGE" | "RETAG" => Role::Admin,
+        _ => Role::ReadOnly,
+    }
+}
+
+// Check the keys / tags a command names against a scoped principal.
+fn check_scope(who: &Principal, cmd: &str, text: &str) -> Result<(), String> {
+    let fields: Vec<&str> = text.split('\t').collect();
+    let field = |i: usize| fields.get(i).copied().unwrap_or("");
+    let list = |i: usize| field(i).split(',').map(str::trim).filter(|s| !s.is_empty() && *s != "-");
+    match cmd {
+        "PUT" | "ADD" => { who.check_key(field(1))?; who.check_tags(list(3)) }
+        "INCR" | "DECR" => { who.check_key(field(1))?; who.check_tags(list(4)) }
+        "RATELIMIT" => { who.check_key(field(1))?; who.check_tags(list(5)) }
+        "LOCK" => { who.check_key(field(1))?; who.check_tags(list(4)) }
+        "RENEW" | "UNLOCK" => who.check_key(field(1)),
+        "TAG_ADD" | "TAG_DEL" => { who.check_key(field(1))?; who.check_tags(list(2)) }
+        "GET" | "DEL" => who.check_key(field(1)),
+        c if COLLECTION_STORE_COMMANDS.contains(&c) => { who.check_key(field(1))?; who.check_tags(list(3)) }
+        c if COLLECTION_COMMANDS.contains(&c) => who.check_key(field(1)),
+        "INV_TAG" | "KEYS_BY_TAG" | "KEYS" => who.check_tag_members(field(1)),
+        "INV_TAGS_ANY" | "INV_TAGS_ALL" => who.check_tags_members(list(1)),
+        "INV_TAGS_TREE" => list(1).try_for_each(|t| who.check_tag_tree(t)),
+        "TAG_INFO" => who.check_tag(field(1)),
+        "TAG_RENAME" => who.check_tags_members([field(1), field(2)]),
+        "TAG_EXPIRE" => who.check_tag_members(field(1)),
+        "TAG_MERGE" => { who.check_tag_members(field(1))?; who.check_tags_members(list(2)) }
+        "TAGS" | "RETAG" => who.check_unscoped(),
+        "INV_KEYS" => list(1).try_for_each(|k| who.check_key(k)),
+        "SUBSCRIBE" | "FLUSH" => who.check_unscoped(),
+        _ => Ok(()),
+    }
+}
+
+// Commands that act on the whole server; refused inside a namespace.
+const SERVER_WIDE_COMMANDS: &[&str] = &["SUBSCRIBE", "REPLICATE", "PROMOTE"];
+
+// Key and byte quotas of the namespace a (namespace-qualified) write command targets.
+fn check_write_quota(cache: &Cache, cmd: &str, text: &str) -> Result<(), NamespaceError> {
+    let fields: Vec<&str> = text.splitn(5, '\t').collect();
+    let key = fields.get(1).copied().unwrap_or("");
+    if namespace::split(key).is_none() { return Ok(()); }
+    let value_len = match cmd {
+        "PUT" | "ADD" => fields.get(4).map_or(0, |v| v.len()),
+        "INCR" | "DECR" => fields.get(2).map_or(1, |v| v.len()),
+        c if COLLECTION_STORE_COMMANDS.contains(&c) => {
+            return cache.admit_update(&Key(key.to_string()), fields.get(4).map_or(0, |v| v.len()));
+        }
+        "RATELIMIT" => return cache.admit_update(&Key(key.to_string()), 13), // At most one timestamp per request
+        "LOCK" => return cache.admit_update(&Key(key.to_string()), 48), // Owner and fencing tokens
+        _ => return Ok(()),
+    };
+    cache.admit_write(&Key(key.to_string()), value_len)
+}
+
+// Hash / list / set and JSON document commands. Those that store data take
+// `<key> <ttl_ms|-> <tag1,tag2|-> <args...>` like PUT (tags and TTL as for INCR); the others just
+// `<key> <args...>`. JSON values are sent and returned as compact JSON text.
+pub(crate) const COLLECTION_STORE_COMMANDS: &[&str] = &[
+    "HSET", "HINCRBY", "LPUSH", "RPUSH", "SADD", "JSON_SET", "JSON_MERGE", "JSON_APPEND", "JSON_INCR",
+];
+pub(crate) const COLLECTION_COMMANDS: &[&str] = &[
+    "HSET", "HINCRBY", "LPUSH", "RPUSH", "SADD", "JSON_SET", "JSON_MERGE", "JSON_APPEND", "JSON_INCR",
+    "HGET", "HGETALL", "HDEL", "LPOP", "RPOP", "LRANGE", "SREM", "SMEMBERS", "JSON_GET",
+];
+
+// Run a collection command; `args` is everything after the verb.
+fn collection_command(cache: &Cache, cmd: &str, args: &str) -> String {
+    let mut fields = args.split('\t');
+    let key = match fields.next() {
+        Some(k) if !k.is_empty() => Key(k.to_string()),
+        _ => return "ERR missing_key".to_string(),
+    };
+    let (ttl, tags) = if COLLECTION_STORE_COMMANDS.contains(&cmd) {
+        let ttl_part = fields.next().unwrap_or("-");
+        let tags_part = fields.next().unwrap_or("-");
+        let ttl = if ttl_part == "-" || ttl_part.is_empty() { None } else { ttl_part.parse::<u64>().ok().map(Duration::from_millis) };
+        let tags: Vec<Tag> = if tags_part == "-" || tags_part.is_empty() { Vec::new() } else { tags_part.split(',').filter(|s| !s.is_empty()).map(|s| Tag(s.to_string())).collect() };
+        (ttl, tags)
+    } else {
+        (None, Vec::new())
+    };
+    let args: Vec<String> = fields.map(str::to_string).collect();
+    let joined = |verb: &str, items: Vec<String>| std::iter::once(verb.to_string()).chain(items).collect::<Vec<_>>().join("\t");
+    let value_reply = |v: Option<String>| v.map_or_else(|| "NF".to_string(), |v| format!("VALUE\t{}", v));
+    let result: Result<String, ValueError> = match (cmd, args.as_slice()) {
+        ("HSET", [field, value]) => cache.hset(key, field.clone(), value.clone(), tags, ttl).map(|new| format!("HSET\t{}", new as u8)),
+        ("HGET", [field]) => cache.hget(&key, field).map(value_reply),
+        ("HGETALL", []) => cache.hgetall(&key).map(|h| joined("HASH", h.into_iter().flat_map(|(f, v)| [f, v]).collect())),
+        ("HDEL", [field]) => cache.hdel(key, field).map(|removed| format!("HDEL\t{}", removed as u8)),
+        ("HINCRBY", [field]) => cache.hincrby(key, field.clone(), 1, tags, ttl).map(|n| format!("VALUE\t{}", n)),
+        ("HINCRBY", [field, by]) => match by.parse::<i64>() {
+            Ok(by) => cache.hincrby(key, field.clone(), by, tags, ttl).map(|n| format!("VALUE\t{}", n)),
+            Err(_) => return "ERR invalid_increment".to_string(),
+        },
+        ("LPUSH", items) if !items.is_empty() => cache.lpush(key, items.to_vec(), tags, ttl).map(|n| format!("LPUSH\t{}", n)),
+        ("RPUSH", items) if !items.is_empty() => cache.rpush(key, items.to_vec(), tags, ttl).map(|n| format!("RPUSH\t{}", n)),
+        ("LPOP", []) => cache.lpop(key).map(value_reply),
+        ("RPOP", []) => cache.rpop(key).map(value_reply),
+        ("LRANGE", [start, stop]) => match (start.parse::<i64>(), stop.parse::<i64>()) {
+            (Ok(start), Ok(stop)) => cache.lrange(&key, start, stop).map(|items| joined("LIST", items)),
+            _ => return "ERR invalid_index".to_string(),
+        },
+        ("SADD", members) if !members.is_empty() => cache.sadd(key, members.to_vec(), tags, ttl).map(|n| format!("SADD\t{}", n)),
+        ("SREM", members) if !members.is_empty() => cache.srem(key, members).map(|n| format!("SREM\t{}", n)),
+        ("SMEMBERS", []) => cache.smembers(&key).map(|members| joined("SET", members)),
+        ("JSON_GET", []) => Ok(value_reply(cache.json_get(&key, &JsonPath::root()).map(|v| v.to_string()))),
+        ("JSON_GET", [path]) => JsonPath::parse(tcp_path(path)).map(|path| value_reply(cache.json_get(&key, &path).map(|v| v.to_string()))),
+        (c, [path, value @ ..]) if c.starts_with("JSON_") && c != "JSON_GET" && !value.is_empty() => {
+            let Ok(arg) = serde_json::from_str::<serde_json::Value>(&value.join("\t")) else { return "ERR invalid_json".to_string() };
+            JsonPath::parse(tcp_path(path))
+                .and_then(|path| Ok((path, JsonOp::parse(&c["JSON_".len()..], arg)?)))
+                .and_then(|(path, op)| cache.json_update(key, &path, op, tags, ttl))
+                .map(|v| format!("VALUE\t{}", v))
+        }
+        _ => return "ERR wrong_arguments".to_string(),
+    };
+    result.unwrap_or_else(|e| match e {
+        ValueError::WrongType(found) => format!("ERR {}\t{}", e.code(), found.as_str()),
+        ValueError::InvalidPath(_) => format!("ERR {}\t{}", e.code(), e),
+        _ => format!("ERR {}", e.code()),
+    })
+}
+
+// RATELIMIT <key> <algorithm> <limit> <window_ms> [tag1,tag2|-] -> ALLOWED|DENIED <remaining> <reset_after_ms>
+fn rate_limit_command(cache: &Cache, args: &str) -> String {
+    let fields: Vec<&str> = args.split('\t').collect();
+    let [key, algorithm, limit, window_ms, rest @ ..] = fields.as_slice() else { return "ERR wrong_arguments".to_string() };
+    if key.is_empty() { return "ERR missing_key".to_string(); }
+    let Some(algorithm) = Algorithm::parse(algorithm) else { return format!("ERR invalid_algorithm\t{}", algorithm) };
+    let limit = match (limit.parse::<u64>(), window_ms.parse::<u64>()) {
+        (Ok(limit), Ok(window_ms)) => RateLimit::new(algorithm, limit, Duration::from_millis(window_ms)),
+        _ => Err("limit and window_ms must be whole numbers".to_string()),
+    };
+    let limit = match limit {
+        Ok(limit) => limit,
+        Err(e) => return format!("ERR invalid_limit\t{}", e),
+    };
+    let tags: Vec<Tag> = match rest.first() {
+        None | Some(&"-") | Some(&"") => Vec::new(),
+        Some(list) => list.split(',').filter(|s| !s.is_empty()).map(|s| Tag(s.to_string())).collect(),
+    };
+    match cache.rate_limit(Key(key.to_string()), &limit, tags) {
+        Ok(d) => format!("{}\t{}\t{}", if d.allowed { "ALLOWED" } else { "DENIED" }, d.remaining, d.reset_after.as_millis()),
+        Err(ValueError::WrongType(found)) => format!("ERR wrong_type\t{}", found.as_str()),
+        Err(e) => format!("ERR {}", e.code()),
+    }
+}
+
+// TAGS [name|keys|bytes|-] [limit|-] [offset|-] -> TAGS <tag> <keys> <bytes> ... (keys / bytes sort
+// the biggest first); only the tags of `ns`.
+fn tag_list_command(cache: &Cache, ns: &Namespace, args: &str) -> String {
+    let mut fields = args.split('\t').map(|f| Some(f).filter(|f| !f.is_empty() && *f != "-"));
+    let (sort, limit, offset) = (fields.next().flatten(), fields.next().flatten(), fields.next().flatten());
+    let (Ok(limit), Ok(offset)) = (limit.map_or(Ok(100), str::parse::<usize>), offset.map_or(Ok(0), str::parse::<usize>)) else {
+        return "ERR invalid_number".to_string();
+    };
+    let mut tags: Vec<(String, usize, usize)> = cache.tag_stats().into_iter()
+        .filter_map(|s| Some((ns.strip(&s.tag.0)?.to_string(), s.keys, s.bytes)))
+        .collect();
+    match sort.unwrap_or("name") {
+        "name" => {}
+        "keys" => tags.sort_by_key(|t| std::cmp::Reverse(t.1)),
+        "bytes" => tags.sort_by_key(|t| std::cmp::Reverse(t.2)),
+        other => return format!("ERR invalid_sort\t{}", other),
+    }
+    let mut reply = "TAGS".to_string();
+    for (tag, keys, bytes) in tags.into_iter().skip(offset).take(limit) {
+        reply.push_str(&format!("\t{}\t{}\t{}", tag, keys, bytes));
+    }
+    reply
+}
+
+// RETAG <add|remove> <tag> [prefix|-] [tag_expr|-] -> RETAG <keys changed>; prefix and expression
+// name keys / tags as the client knows them (`ns` qualifies them).
+fn retag_command(cache: &Cache, ns: &Namespace, args: &str) -> String {
+    let fields: Vec<&str> = args.split('\t').collect();
+    let [action, tag, rest @ ..] = fields.as_slice() else { return "ERR wrong_arguments".to_string() };
+    if tag.is_empty() { return "ERR missing_tag".to_string(); }
+    let given = |i: usize| rest.get(i).copied().filter(|f| !f.is_empty() && *f != "-");
+    let filter = match TransferFilter::parse(given(0).map(str::to_string), given(1)) {
+        Ok(filter) if filter.is_empty() => return "ERR missing_filter".to_string(),
+        Ok(filter) => ns.qualify_filter(filter),
+        Err(e) => return format!("ERR invalid_expression\t{}", e),
+    };
+    let tag = [Tag(tag.to_string())];
+    let count = match *action {
+        "add" => cache.retag_matching(&filter, &tag, &[]),
+        "remove" => cache.retag_matching(&filter, &[], &tag),
+        other => return format!("ERR invalid_action\t{}", other),
+    };
+    format!("RETAG\t{}", count)
+}
+
+// Reply to LOCK / RENEW (and the errors of UNLOCK).
+fn lock_reply(lease: Result<Lease, LockError>) -> String {
+    match lease {
+        Ok(lease) => format!("LOCK\t{}\t{}", lease.owner, lease.fence),
+        Err(LockError::Held(left)) => format!("HELD\t{}", left.as_millis()),
+        Err(LockError::WrongType(found)) => format!("ERR wrong_type\t{}", found.as_str()),
+        Err(e) => format!("ERR {}", e.code()),
+    }
+}
+
+// `-` stands for the whole document, like an empty path or `$`.
+fn tcp_path(path: &str) -> &str {
+    if path == "-" { "" } else { path }
+}
+
+// Commands refused with `ERR read_only_replica` while this server is a read-only follower.
+const TCP_WRITE_COMMANDS: &[&str] = &[
+    "PUT", "ADD", "INCR", "DECR", "DEL", "INV_TAG", "INV_TAGS_ANY", "INV_TAGS_ALL", "INV_TAGS_TREE", "INV_KEYS",
+    "FLUSH", "HSET", "HINCRBY", "HDEL", "LPUSH", "RPUSH", "LPOP", "RPOP", "SADD", "SREM",
+    "JSON_SET", "JSON_MERGE", "JSON_APPEND", "JSON_INCR", "RATELIMIT", "LOCK", "RENEW", "UNLOCK",
+    "TAG_RENAME", "TAG_MERGE", "RETAG", "TAG_ADD", "TAG_DEL", "TAG_EXPIRE",
+];
+
+async fn handle_tcp_client(state: Arc<AppState>, mut stream: TcpStream) {
+    let cache = state.cache.clone();                    // Most commands only need the cache
+    let peer = stream.peer_addr().ok();                 // Capture peer address (optional)
+    let (r, mut w) = stream.split();                    // Split into read and write halves (independent borrowing)
+    let mut reader = BufReader::new(r);                 // Buffer reads line-by-line
+    let mut line = String::new();                       // Reusable line buffer
+    let mut principal = (!state.auth.tcp_require_auth()).then(|| Principal::anonymous("tcp")); // Who this connection acts as
+    while let Ok(n) = reader.read_line(&mut line).await { // Async read until newline (includes trailing \n)
+        if n == 0 { break; }                            // EOF => client disconnected
+        while line.ends_with(['\n','\r']) { line.pop(); } // Strip CR/LF
+        if line.is_empty() { continue; }                // Ignore empty lines
+        // LOCAL\t<command> (sent by cluster peers) runs the command on this node without cluster routing.
+        let local_only = line.starts_with(cluster::TCP_LOCAL_PREFIX);
+        let text = if local_only { &line[cluster::TCP_LOCAL_PREFIX.len()..] } else { line.as_str() };
+        let mut parts = text.splitn(5, '\t');          // Split into at most 5 segments by TAB
+        let cmd = parts.next().unwrap_or("").to_ascii_uppercase(); // Command verb (case-insensitive)
+        if cmd == "AUTH" {
+            let (a, b) = (parts.next().unwrap_or(""), parts.next());
+            let outcome = match b {
+                Some(password) => state.auth.login(a, password, peer.map(|p| p.ip())),
+                None => state.auth.authenticate_token(a).ok_or(LoginError::Invalid(None)),
+            };
+            let reply = match outcome {
+                Ok(who) => {
+                    state.audit.record(AuditEvent::Login, Some(&who.name), peer, Some("tcp".to_string()));
+                    principal = Some(who);
+                    "OK".to_string()
+                }
+                Err(LoginError::LockedOut(wait)) => format!("ERR locked_out\t{}", wait.as_secs().max(1)),
+                Err(LoginError::Invalid(locked)) => {
+                    if b.is_some() { audit_login_failure(&state.audit, a, peer, "tcp", locked); }
+                    "ERR unauthorized".to_string()
+                }
+            };
+            if w.write_all(format!("{}\n", reply).as_bytes()).await.is_err() { break; }
+            line.clear();
+            continue;
+        }
+        let denied = match &principal {
+            None => Some("ERR unauthorized"),
+            Some(who) if !who.has_role(required_role(&cmd)) => Some("ERR forbidden"),
+            Some(who) if !who.scope.is_unrestricted() && check_scope(who, &cmd, text).is_err() => Some("ERR forbidden"),
+            Some(_) => None,
+        };
+        if let Some(reply) = denied {
+            if w.write_all(format!("{}\n", reply).as_bytes()).await.is_err() { break; }
+            line.clear();
+            continue;
+        }
+        // Client keys and tags may not name another namespace's entries; cluster peers (LOCAL, with an
+        // admin account) send stored names.
+        let peer_call = local_only && principal.as_ref().is_some_and(|p| p.has_role(auth::Role::Admin));
+        let checked = if peer_call { Ok(()) } else { namespace::check_tcp(&cmd, text) };
+        if let Err(e) = checked {
+            if w.write_all(format!("ERR {}\t{}\n", e.code(), e.detail()).as_bytes()).await.is_err() { break; }
+            line.clear();
+            continue;
+        }
+        if cmd == "SELECT" {
+            let reply = match (Namespace::named(parts.next().unwrap_or("")), principal.as_mut()) {
+                (Ok(ns), Some(who)) => match cache.namespaces.admit(&ns) {
+                    Ok(()) if who.enter(Some(ns)).is_ok() => "OK".to_string(),
+                    Ok(()) => "ERR forbidden".to_string(),
+                    Err(e) => format!("ERR {}\t{}", e.code(), e.detail()),
+                },
+                (Err(e), _) => format!("ERR {}\t{}", e.code(), e.detail()),
+                (_, None) => "ERR unauthorized".to_string(),
+            };
+            if w.write_all(format!("{}\n", reply).as_bytes()).await.is_err() { break; }
+            line.clear();
+            continue;
+        }
+        // Inside a namespace: count the command against its ops/sec quota and qualify keys and tags.
+        let qualified;
+        let text = match principal.as_ref().map(|p| &p.namespace).filter(|ns| !ns.is_default()) {
+            Some(ns) => {
+                let refused = if SERVER_WIDE_COMMANDS.contains(&cmd.as_str()) { Some("ERR forbidden".to_string()) }
+                    else { cache.namespaces.admit(ns).err().map(|e| format!("ERR {}\t{}", e.code(), e.detail())) };
+                if let Some(reply) = refused {
+                    if w.write_all(format!("{}\n", reply).as_bytes()).await.is_err() { break; }
+                    line.clear();
+                    continue;
+                }
+                qualified = ns.qualify_tcp(&cmd, text);
+                qualified.as_str()
+            }
+            None => text,
+        };
+        let mut parts = text.splitn(5, '\t');
+        parts.next(); // The verb
+        if let Err(e) = check_write_quota(&cache, &cmd, text) {
+            if w.write_all(format!("ERR {}\t{}\n", e.code(), e.detail()).as_bytes()).await.is_err() { break; }
+            line.clear();
+            continue;
+        }
+        // SUBSCRIBE [types|-] [prefix|-] [tag|-] turns this connection into an event stream until it closes.
+        if cmd == "SUBSCRIBE" {
+            match EventFilter::parse(parts.next(), parts.next(), parts.next()) {
+                Ok(filter) => { stream_events_tcp(&cache, &mut reader, &mut w, filter).await; break; }
+                Err(e) => {
+                    if w.write_all(format!("ERR {}\n", e).as_bytes()).await.is_err() { break; }
+                    line.clear();
+                    continue;
+                }
+            }
+        }
+        // REPLICATE hands the connection to the replication stream (used by followers, not clients).
+        if cmd == "REPLICATE" {
+            replication::serve_replica(&cache, &state.replication, &mut reader, &mut w).await;
+            break;
+        }
+        if state.replication.rejects_writes() && TCP_WRITE_COMMANDS.contains(&cmd.as_str()) {
+            if w.write_all(b"ERR read_only_replica\n").await.is_err() { break; }
+            line.clear();
+            continue;
+        }
+        // Cluster mode: keys owned by another node get a MOVED reply; tag / multi-key commands fan out.
+        let route = match &state.cluster {
+            Some(c) if !local_only => c.route_tcp(&cmd, text.split('\t').nth(1)),
+            _ => TcpRoute::Local,
+        };
+        if let TcpRoute::Moved(reply) = &route {
+            if w.write_all(format!("{}\n", reply).as_bytes()).await.is_err() { break; }
+            line.clear();
+            continue;
+        }
+        // Match command and produce a response string.
+        let resp = match cmd.as_str() {
+            // PUT <key> <ttl_ms|- > <tag1,tag2|- > <value>
+            "PUT" => {
+                let maybe_key = parts.next();
+                match maybe_key {
+                    Some(k) if !k.is_empty() => {                   // Validate non-empty key
+                        let ttl_part = parts.next().unwrap_or("-"); // TTL field
+                        let tags_part = parts.next().unwrap_or("-"); // Tags list
+                        let value = parts.next().unwrap_or("");      // Remaining value (may contain spaces, not tabs)
+                        let ttl = if ttl_part == "-" || ttl_part.is_empty() { None } else { ttl_part.parse::<u64>().ok().map(Duration::from_millis) };
+                        let tags: Vec<Tag> = if tags_part == "-" || tags_part.is_empty() { Vec::new() } else { tags_part.split(',').filter(|s| !s.is_empty()).map(|s| Tag(s.to_string())).collect() };
+                        cache.put(Key(k.to_string()), value.to_string(), tags, ttl); // Store entry
+                        "OK".to_string()
+                    }
+                    _ => "ERR missing_key".to_string()
+                }
+            }
+            // ADD <key> <ttl_ms|- > <tag1,tag2|- > <value> - atomic add (fails if key exists)
+            "ADD" => {
+                let maybe_key = parts.next();
+                match maybe_key {
+                    Some(k) if !k.is_empty() => {                   // Validate non-empty key
+                        let ttl_part = parts.next().unwrap_or("-"); // TTL field
+                        let tags_part = parts.next().unwrap_or("-"); // Tags list
+                        let value = parts.next().unwrap_or("");      // Remaining value (may contain spaces, not tabs)
+                        let ttl = if ttl_part == "-" || ttl_part.is_empty() { None } else { ttl_part.parse::<u64>().ok().map(Duration::from_millis) };
+                        let tags: Vec<Tag> = if tags_part == "-" || tags_part.is_empty() { Vec::new() } else { tags_part.split(',').filter(|s| !s.is_empty()).map(|s| Tag(s.to_string())).collect() };
+                        if cache.add(Key(k.to_string()), value.to_string(), tags, ttl).is_some() {
+                            "ADDED".to_string()  // Successfully added
+                        } else {
+                            "EXISTS".to_string() // Key already exists
+                        }
+                    }
+                    _ => "ERR missing_key".to_string()
+                }
+            }
+            // INCR <key> [by] [ttl_ms|-] [tag1,tag2|-] [options|-] - atomic increment (by defaults to 1;
+            // options as in counter.rs: float,min=..,max=..,bound=clamp|reject,window=minute|hour|...)
+            "INCR" => {
+                let maybe_key = parts.next();
+                match maybe_key {
+                    Some(k) if !k.is_empty() => {
+                        let by_part = parts.next().unwrap_or("1");     // Increment amount (default 1)
+                        let ttl_part = parts.next().unwrap_or("-");    // TTL field
+                        let (tags_part, options_part) = parts.next().map_or(("-", "-"), |rest| rest.split_once('\t').unwrap_or((rest, "-"))); // Tags list, options
+                        
+                        let by = CounterValue::parse(by_part).unwrap_or(CounterValue::Int(1));
+                        let ttl = if ttl_part == "-" || ttl_part.is_empty() { None } else { ttl_part.parse::<u64>().ok().map(Duration::from_millis) };
+                        let tags: Vec<Tag> = if tags_part == "-" || tags_part.is_empty() { Vec::new() } else { tags_part.split(',').filter(|s| !s.is_empty()).map(|s| Tag(s.to_string())).collect() };
+                        
+                        match CounterOptions::parse(options_part) {
+                            Ok(options) => match cache.increment_with(Key(k.to_string()), by, &options, tags, ttl) {
+                                Ok((new_value, _)) => format!("VALUE\t{}", new_value),
+                                Err(error) => format!("ERR {}", error),
+                            },
+                            Err(error) => format!("ERR invalid_options\t{}", error),
+                        }
+                    }
+                    _ => "ERR missing_key".to_string()
+                }
+            }
+            // DECR <key> [by] [ttl_ms|-] [tag1,tag2|-] [options|-] - atomic decrement (by defaults to 1;
+            // options as in counter.rs: float,min=..,max=..,bound=clamp|reject,window=minute|hour|...)
+            "DECR" => {
+                let maybe_key = parts.next();
+                match maybe_key {
+                    Some(k) if !k.is_empty() => {
+                        let by_part = parts.next().unwrap_or("1");     // Decrement amount (default 1)
+                        let ttl_part = parts.next().unwrap_or("-");    // TTL field
+                        let (tags_part, options_part) = parts.next().map_or(("-", "-"), |rest| rest.split_once('\t').unwrap_or((rest, "-"))); // Tags list, options
+                        
+                        let by = CounterValue::parse(by_part).unwrap_or(CounterValue::Int(1));
+                        let ttl = if ttl_part == "-" || ttl_part.is_empty() { None } else { ttl_part.parse::<u64>().ok().map(Duration::from_millis) };
+                        let tags: Vec<Tag> = if tags_part == "-" || tags_part.is_empty() { Vec::new() } else { tags_part.split(',').filter(|s| !s.is_empty()).map(|s| Tag(s.to_string())).collect() };
+                        
+                        match CounterOptions::parse(options_part) {
+                            Ok(options) => match cache.decrement_with(Key(k.to_string()), by, &options, tags, ttl) {
+                                Ok((new_value, _)) => format!("VALUE\t{}", new_value),
+                                Err(error) => format!("ERR {}", error),
+                            },
+                            Err(error) => format!("ERR invalid_options\t{}", error),
+                        }
+                    }
+                    _ => "ERR missing_key".to_string()
+                }
+            }
+            // LOCK <key> <ttl_ms> [wait_ms|-] [tag1,tag2|-] -> LOCK <owner> <fence> | HELD <retry_after_ms>
+            // (see lock.rs; with wait_ms, keeps trying that long while the lock is held)
+            "LOCK" => {
+                let key = parts.next().filter(|k| !k.is_empty()).map(|k| Key(k.to_string()));
+                let ttl = parts.next().and_then(|t| t.parse::<u64>().ok()).filter(|t| *t > 0).map(Duration::from_millis);
+                let wait = parts.next().and_then(|w| w.parse::<u64>().ok()).filter(|w| *w > 0).map(Duration::from_millis);
+                let tags: Vec<Tag> = match parts.next() {
+                    None | Some("-") | Some("") => Vec::new(),
+                    Some(list) => list.split(',').filter(|s| !s.is_empty()).map(|s| Tag(s.to_string())).collect(),
+                };
+                match (key, ttl) {
+                    (None, _) => "ERR missing_key".to_string(),
+                    (_, None) => "ERR invalid_ttl".to_string(),
+                    (Some(key), Some(ttl)) => {
+                        let lease = match wait {
+                            Some(wait) => lock::acquire_wait(&cache, key, ttl, tags, wait).await,
+                            None => cache.lock_acquire(key, ttl, tags),
+                        };
+                        lock_reply(lease)
+                    }
+                }
+            }
+            // RENEW <key> <owner> <ttl_ms> -> LOCK <owner> <fence>
+            "RENEW" => {
+                let (key, owner) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+                match parts.next().and_then(|t| t.parse::<u64>().ok()).filter(|t| *t > 0) {
+                    Some(ttl) => lock_reply(cache.lock_renew(&Key(key.to_string()), owner, Duration::from_millis(ttl))),
+                    None => "ERR invalid_ttl".to_string(),
+                }
+            }
+            // UNLOCK <key> <owner> -> OK
+            "UNLOCK" => {
+                let (key, owner) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+                match cache.lock_release(&Key(key.to_string()), owner) {
+                    Ok(()) => "OK".to_string(),
+                    Err(e) => lock_reply(Err(e)),
+                }
+            }
+            // GET <key>
+            "GET" => {
+                let key = parts.next();
+                match key { Some(k) => {
+                    match cache.get_value(&Key(k.to_string())) {
+                        Some(Value::String(v)) => format!("VALUE\t{}", v),
+                        Some(other) => format!("ERR wrong_type\t{}", other.kind().as_str()),
+                        None => "NF".to_string(),
+                    }
+                }, None => "ERR missing_key".to_string() }
+            }
+            // DEL <key>
+            "DEL" => {
+                let key = parts.next();
+                match key { Some(k) => { if cache.invalidate_key(&Key(k.to_string())) { "DEL ok".to_string() } else { "DEL nf".to_string() } }, None => "ERR missing_key".to_string() }
+            }
+            // INV_TAG <tag>
+            "INV_TAG" => {
+                let tag = parts.next();
+                match tag { Some(t) => { let count = cache.invalidate_tag(&Tag(t.to_string())); format!("INV_TAG\t{}", count) }, None => "ERR missing_tag".to_string() }
+            }
+            // INV_TAGS_ANY <tag1,tag2,tag3> - invalidate keys with ANY of the tags (mode="any")
+            "INV_TAGS_ANY" => {
+                let tags_part = parts.next();
+                match tags_part {
+                    Some(tags_str) if !tags_str.is_empty() => {
+                        let tag_list: Vec<String> = tags_str.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+                        if tag_list.is_empty() {
+                            "ERR empty_tags".to_string()
+                        } else {
+                            let tags: Vec<Tag> = tag_list.into_iter().map(Tag).collect();
+                            let count = cache.invalidate_tags(&tags, TagMatch::Any); // Same logic as HTTP "any" mode
+                            format!("INV_TAGS_ANY\t{}", count)
+                        }
+                    }
+                    _ => "ERR missing_tags".to_string()
+                }
+            }
+            // INV_TAGS_ALL <tag1,tag2,tag3> - invalidate keys with ALL of the tags (mode="all")
+            "INV_TAGS_ALL" => {
+                let tags_part = parts.next();
+                match tags_part {
+                    Some(tags_str) if !tags_str.is_empty() => {
+                        let tag_list: Vec<String> = tags_str.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+                        if tag_list.is_empty() {
+                            "ERR empty_tags".to_string()
+                        } else {
+                            let tags: Vec<Tag> = tag_list.into_iter().map(Tag).collect();
+                            let count = cache.invalidate_tags(&tags, TagMatch::All); // Same logic as HTTP "all" mode
+                            format!("INV_TAGS_ALL\t{}", count)
+                        }
+                    }
+                    _ => "ERR missing_tags".to_string()
+                }
+            }
+            // INV_TAGS_TREE <tag1,tag2> - invalidate keys with any of the tags or a tag below them (mode="subtree")
+            "INV_TAGS_TREE" => {
+                let tags: Vec<Tag> = parts.next().unwrap_or("").split(',').map(str::trim).filter(|s| !s.is_empty()).map(|s| Tag(s.to_string())).collect();
+                if tags.is_empty() { "ERR missing_tags".to_string() } else { format!("INV_TAGS_TREE\t{}", cache.invalidate_tags(&tags, TagMatch::Subtree)) }
+            }
+            // INV_KEYS <key1,key2,key3> - invalidate multiple keys by name
+            "INV_KEYS" => {
+                let keys_part = parts.next();
+                match keys_part {
+                    Some(keys_str) if !keys_str.is_empty() => {
+                        let key_list: Vec<String> = keys_str.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+                        if key_list.is_empty() {
+                            "ERR empty_keys".to_string()
+                        } else {
+                            let mut count = 0usize;
+                            // Same logic as HTTP invalidate_keys_handler
+                            for k in key_list { 
+                                if cache.invalidate_key(&Key(k)) { 
+                                    count += 1; 
+                                } 
+                            }
+                            format!("INV_KEYS\t{}", count)
+                        }
+                    }
+                    _ => "ERR missing_keys".to_string()
+                }
+            }
+            // KEYS_BY_TAG <tag>  (alias KEYS <tag>)
+            "KEYS_BY_TAG" | "KEYS" => {
+                let tag = parts.next();
+                match tag { Some(t) => { let keys = cache.get_keys_by_tag(&Tag(t.to_string())); let list = keys.into_iter().map(|k| k.0).collect::<Vec<_>>().join(","); format!("KEYS\t{}", list) }, None => "ERR missing_tag".to_string() }
+            }
+            // TAG_ADD / TAG_DEL <key> <tag1,tag2> -> <VERB> 1 (tags changed) | <VERB> 0 | NF
+            "TAG_ADD" | "TAG_DEL" => {
+                let key = Key(parts.next().unwrap_or("").to_string());
+                let tags: Vec<Tag> = parts.next().unwrap_or("").split(',').map(str::trim).filter(|s| !s.is_empty() && *s != "-").map(|s| Tag(s.to_string())).collect();
+                if key.0.is_empty() { "ERR missing_key".to_string() }
+                else if tags.is_empty() { "ERR missing_tags".to_string() }
+                else {
+                    let edited = cache.edit_tags(&key, |current| {
+                        if cmd == "TAG_ADD" { current.extend(tags) } else { current.retain(|t| !tags.contains(t)) }
+                    });
+                    match edited {
+                        Some((_, changed)) => format!("{}\t{}", cmd, changed as u8),
+                        None => "NF".to_string(),
+                    }
+                }
+            }
+            // TAG_INFO <tag> -> TAG <keys> <bytes> | NF
+            "TAG_INFO" => match parts.next() {
+                Some(t) if !t.is_empty() => match cache.tag_info(&Tag(t.to_string())) {
+                    Some(s) => format!("TAG\t{}\t{}", s.keys, s.bytes),
+                    None => "NF".to_string(),
+                },
+                _ => "ERR missing_tag".to_string(),
+            },
+            // TAG_EXPIRE <tag> <unix_ms> -> TAG_EXPIRE <keys whose deadline moved>
+            "TAG_EXPIRE" => match (parts.next(), parts.next().map(|ms| ms.trim().parse::<u64>())) {
+                (Some(t), Some(Ok(at))) if !t.is_empty() => format!("TAG_EXPIRE\t{}", cache.expire_tag_at(&Tag(t.to_string()), UNIX_EPOCH + Duration::from_millis(at))),
+                (Some(t), None) if !t.is_empty() => "ERR missing_deadline".to_string(),
+                (Some(t), Some(Err(_))) if !t.is_empty() => "ERR invalid_deadline".to_string(),
+                _ => "ERR missing_tag".to_string(),
+            },
+            // TAG_RENAME <from> <to> ; TAG_MERGE <into> <from1,from2> -> <VERB> <keys changed>
+            "TAG_RENAME" => match (parts.next(), parts.next()) {
+                (Some(from), Some(to)) if !from.is_empty() && !to.is_empty() => format!("TAG_RENAME\t{}", cache.rename_tag(&Tag(from.to_string()), &Tag(to.to_string()))),
+                _ => "ERR missing_tag".to_string(),
+            },
+            "TAG_MERGE" => {
+                let into = parts.next().unwrap_or("");
+                let sources: Vec<Tag> = parts.next().unwrap_or("").split(',').map(str::trim).filter(|s| !s.is_empty()).map(|s| Tag(s.to_string())).collect();
+                if into.is_empty() || sources.is_empty() { "ERR missing_tag".to_string() } else { format!("TAG_MERGE\t{}", cache.merge_tags(&sources, &Tag(into.to_string()))) }
+            }
+            "TAGS" | "RETAG" => {
+                let ns = principal.as_ref().map(|p| p.namespace.clone()).unwrap_or_default();
+                let args = text.split_once('\t').map_or("", |(_, args)| args);
+                if cmd == "TAGS" { tag_list_command(&cache, &ns, args) } else { retag_command(&cache, &ns, args) }
+            }
+            // STATS => summary counters (the namespace's own inside a namespace)
+            "STATS" if principal.as_ref().is_some_and(|p| !p.namespace.is_default()) => {
+                let s = cache.namespaces.stats(&principal.as_ref().unwrap().namespace);
+                format!("STATS\t{}\t{}\t{}\t{}\t{:.6}", s.hits, s.misses, s.puts, s.invalidations, s.hit_ratio)
+            }
+            "STATS" => {
+                let s = cache.get_stats();
+                let hit_ratio = if s.hits + s.misses > 0 { s.hits as f64 / (s.hits + s.misses) as f64 } else { 0.0 };
+                format!("STATS\t{}\t{}\t{}\t{}\t{:.6}", s.hits, s.misses, s.puts, s.invalidations, hit_ratio)
+            }
+            "FLUSH" => { // FLUSH [namespace]: remove every entry (of one namespace)
+                match Namespace::named(parts.next().unwrap_or("")) {
+                    Ok(ns) => {
+                        let c = cache.flush_namespace(&ns);
+                        let who = principal.as_ref().map(|p| p.name.as_str());
+                        let scope = ns.name().map(|n| format!(" in namespace {}", n)).unwrap_or_default();
+                        state.audit.record(AuditEvent::Flush, who, peer, Some(format!("{} keys{} (tcp)", c, scope)));
+                        format!("FLUSH\t{}", c)
+                    }
+                    Err(e) => format!("ERR {}\t{}", e.code(), e.detail()),
+                }
+            }
+            // ROLE => leader | follower ; PROMOTE => stop following and accept writes
+            "ROLE" => format!("ROLE\t{}", state.replication.role().as_str()),
+            "PROMOTE" => {
+                state.replication.promote();
+                format!("ROLE\t{}", Role::Leader.as_str())
+            }
+            // HSET / HGET / HGETALL / HDEL / HINCRBY, LPUSH / RPUSH / LPOP / RPOP / LRANGE, SADD / SREM / SMEMBERS
+            c if COLLECTION_COMMANDS.contains(&c) => collection_command(&cache, c, text.split_once('\t').map_or("", |(_, args)| args)),
+            "RATELIMIT" => rate_limit_command(&cache, text.split_once('\t').map_or("", |(_, args)| args)),
+            _ => "ERR unknown_command".to_string(),            // Fallback for unrecognized commands
+        };
+        let resp = match (&route, &state.cluster) {
+            (TcpRoute::FanOut, Some(c)) => c.fan_out_tcp(text, resp).await,
+            _ => resp,
+        };
+        // Key lists name keys as the client knows them (peers asked with LOCAL get stored names).
+        let resp = match (resp.strip_prefix("KEYS\t"), principal.as_ref()) {
+            (Some(list), Some(who)) if !local_only => {
+                format!("KEYS\t{}", list.split(',').filter_map(|k| who.namespace.strip(k)).collect::<Vec<_>>().join(","))
+            }
+            _ => resp,
+        };
+        if w.write_all(resp.as_bytes()).await.is_err() { break; } // Send response body
+        let _ = w.write_all(b"\n").await;                          // Terminate line
+        line.clear();                                                       // Reuse buffer
+    }
+    let _ = w.shutdown().await;             // Try to close write half cleanly
+    let _ = peer;                                  // Silence unused variable (document purpose earlier)
+}
+
+// Stream keyspace events to a SUBSCRIBE'd TCP client: `SUBSCRIBED` once, then one
+// `EVENT\t<json>` line per matching event, or `LAGGED\t<n>` if the client fell behind and n events were dropped.
+async fn stream_events_tcp<R, W>(cache: &Cache, reader: &mut R, w: &mut W, filter: EventFilter)
+where R: tokio::io::AsyncBufRead + Unpin, W: tokio::io::AsyncWrite + Unpin {
+    let mut rx = cache.events.subscribe(); // Subscribe before acknowledging so nothing is missed
+    if w.write_all(b"SUBSCRIBED\n").await.is_err() { return; }
+    let mut buf = String::new();
+    loop {
+        tokio::select! {
+            msg = rx.recv() => {
+                let out = match msg {
+                    Ok(ev) if filter.matches(&ev) => match serde_json::to_string(&ev) { Ok(j) => format!("EVENT\t{}\n", j), Err(_) => continue },
+                    Ok(_) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => format!("LAGGED\t{}\n", n),
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
+                if w.write_all(out.as_bytes()).await.is_err() { break; }
+            }
+            // Input is ignored while subscribed; we only watch for the client going away.
+            n = reader.read_line(&mut buf) => {
+                match n { Ok(0) | Err(_) => break, Ok(_) => buf.clear() }
+            }
+        }
+    }
+}
+
+/// Bind the TCP protocol on `0.0.0.0:port` and serve it (see [`serve_tcp`]).
+pub async fn run_tcp_server(state: Arc<AppState>, port: u16, perf_config: PerformanceConfig) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?; // Bind to all interfaces
+    info!("TCP cache protocol listening on {} (nodelay: {}, keepalive: {}s)", port, perf_config.tcp_nodelay, perf_config.tcp_keepalive_seconds);
+    serve_tcp(listener, state, perf_config).await
+}
+
+/// Accept TCP protocol clients on an already bound listener, one task per connection.
+/// Keeps running forever unless accepting fails.
+pub async fn serve_tcp(listener: TcpListener, state: Arc<AppState>, perf_config: PerformanceConfig) -> anyhow::Result<()> {
+    loop {                                                      // Accept loop
+        let (sock, _) = listener.accept().await?;               // Wait for next connection
+        
+        // Apply TCP socket options
+        if perf_config.tcp_nodelay {
+            if let Err(e) = sock.set_nodelay(true) {
+                warn!("Failed to set TCP_NODELAY: {}", e);
+            }
+        }
+        
+        // Set TCP keepalive if configured
+        if perf_config.tcp_keepalive_seconds > 0 {
+            // Convert to socket2::Socket to set keepalive, then back to TcpStream
+            let std_sock = sock.into_std()?;
+            let socket2_sock = socket2::Socket::from(std_sock);
+            
+            let keepalive = socket2::TcpKeepalive::new()
+                .with_time(std::time::Duration::from_secs(perf_config.tcp_keepalive_seconds));
+            
+            if let Err(e) = socket2_sock.set_tcp_keepalive(&keepalive) {
+                warn!("Failed to set TCP keepalive: {}", e);
+            }
+            
+            // Convert back to tokio TcpStream
+            let sock = TcpStream::from_std(socket2_sock.into())?;
+            let c = state.clone();
+            tokio::spawn(async move {
+                handle_tcp_client(c, sock).await;
+            });
+        } else {
+            let c = state.clone();                                  // Clone Arc for task
+            tokio::spawn(async move {                               // Spawn independent task per client
+                handle_tcp_client(c, sock).await;                   // Handle lifecycle
+            });
+        }
+    }
+}
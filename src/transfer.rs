@@ -0,0 +1,285 @@
+// =============================
+// EXPORT / IMPORT
+// =============================
+// Dump the keyspace as JSON Lines and load it back (backups, migrations, seeding a new server).
+// One record per line:
+//
+//   {"key":"user:1","value":"alice","tags":["users"],"ttl_ms":59000,"created_ms":1730000000000}
+//   {"key":"cart:7","type":"hash","value":{"sku-1":"2"},"tags":["carts"]}
+//
+// Hashes, lists, sets and JSON documents carry a `type` and their value as JSON (object / array /
+// the document itself); strings have no `type`, so dumps of plain keys look as they always did.
+// `ttl_ms` is the REMAINING time to live at export time (absent = no TTL), so an imported key
+// expires when the original would have. `created_ms` is the wall clock creation time.
+//
+// Export copies one shard at a time (no map guard is held while the response is written) and
+// streams bounded chunks through a channel. Import parses the request body as it arrives, so
+// neither side needs the whole dump in memory.
+
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+use crate::cache::{Cache, Entry, Key, Tag};
+use crate::cluster::Cluster;
+use crate::tag_expr::TagExpr;
+use crate::value::{Value, ValueType};
+
+const CHUNK_BYTES: usize = 64 * 1024; // Target size of one streamed export chunk
+const MAX_REPORTED_ERRORS: usize = 100; // Per-line import errors listed in the report (the rest are only counted)
+
+/// Which entries an export / import covers. Empty = everything.
+#[derive(Debug, Clone, Default)]
+pub struct TransferFilter {
+    pub prefix: Option<String>,
+    pub tags: Option<TagExpr>,
+}
+
+impl TransferFilter {
+    /// Build from the `?prefix=&tags=` query parameters (empty strings count as absent).
+    pub fn parse(prefix: Option<String>, tags: Option<&str>) -> Result<Self, String> {
+        let tags = match tags.map(str::trim).filter(|t| !t.is_empty()) {
+            Some(expr) => Some(TagExpr::parse(expr)?),
+            None => None,
+        };
+        Ok(Self { prefix: prefix.filter(|p| !p.is_empty()), tags })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefix.is_none() && self.tags.is_none()
+    }
+
+    pub fn matches(&self, key: &str, tags: &[Tag]) -> bool {
+        self.prefix.as_deref().is_none_or(|p| key.starts_with(p)) && self.tags.as_ref().is_none_or(|e| e.matches(tags))
+    }
+}
+
+/// One exported entry (one JSONL line).
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportRecord<'a> {
+    pub key: &'a str,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ValueType>, // None for strings
+    #[serde(serialize_with = "plain_value")]
+    pub value: &'a Value,
+    pub tags: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_ms: Option<u64>,
+}
+
+impl<'a> ExportRecord<'a> {
+    /// None when the entry has already expired (or was invalidated through a versioned tag).
+    pub fn new(key: &'a Key, entry: &'a Entry, now: Instant) -> Option<Self> {
+        if entry.is_stale() { return None; }
+        let ttl_ms = match entry.deadline() {
+            Some(deadline) if deadline <= now => return None,
+            Some(deadline) => Some((deadline - now).as_millis().max(1) as u64),
+            None => None,
+        };
+        Some(Self {
+            key: &key.0,
+            kind: Some(entry.value.kind()).filter(|k| *k != ValueType::String),
+            value: &entry.value,
+            tags: entry.tags.iter().map(|t| t.0.as_str()).collect(),
+            ttl_ms,
+            created_ms: entry.created_system.duration_since(UNIX_EPOCH).ok().map(|d| d.as_millis() as u64),
+        })
+    }
+}
+
+// Strings as JSON strings, hashes as objects, lists and sets as arrays, documents as themselves
+// (the kind is in `type`).
+fn plain_value<S: Serializer>(value: &&Value, s: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Value::String(v) => s.serialize_str(v),
+        Value::Hash(h) => h.serialize(s),
+        Value::List(l) => l.serialize(s),
+        Value::Set(m) => m.serialize(s),
+        Value::Json(j, _) => j.serialize(s),
+    }
+}
+
+/// Stream every live entry matching `filter` as JSONL chunks. The receiver ends after the last
+/// shard; dropping it (client went away) stops the export.
+pub fn export(cache: Arc<Cache>, filter: TransferFilter) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel(8);
+    tokio::spawn(async move {
+        for shard in &cache.shards {
+            // Serialize one shard under its iterator, then send without holding any guard.
+            let now = Instant::now();
+            let mut chunks = Vec::new();
+            let mut buf = String::new();
+            for item in shard.entries.iter() {
+                if !filter.matches(&item.key().0, &item.value().tags) { continue; }
+                let Some(record) = ExportRecord::new(item.key(), item.value(), now) else { continue };
+                if let Ok(line) = serde_json::to_string(&record) {
+                    buf.push_str(&line);
+                    buf.push('\n');
+                }
+                if buf.len() >= CHUNK_BYTES { chunks.push(std::mem::take(&mut buf)); }
+            }
+            if !buf.is_empty() { chunks.push(buf); }
+            for chunk in chunks {
+                if tx.send(chunk).await.is_err() { return; }
+            }
+        }
+    });
+    rx
+}
+
+/// How an import treats keys already in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Upsert the imported keys; everything else stays.
+    #[default]
+    Merge,
+    /// Upsert the imported keys, then delete every other key matching the filter, so the filtered
+    /// keyspace ends up equal to the dump. Skipped if the upload breaks off midway.
+    Replace,
+}
+
+impl ImportMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "merge" => Some(ImportMode::Merge),
+            "replace" => Some(ImportMode::Replace),
+            _ => None,
+        }
+    }
+}
+
+/// One line of an import. `value` may be any JSON (non-strings are stored as their JSON text, like
+/// PUT /keys/:key), so hand-written files don't need to quote objects.
+#[derive(Debug, Deserialize)]
+struct ImportRecord {
+    key: String,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    value: serde_json::Value,
+    #[serde(default)]
+    tags: Vec<String>,
+    ttl_ms: Option<u64>,
+    created_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LineError {
+    pub line: usize,
+    pub error: String,
+}
+
+/// Outcome of POST /admin/import.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub imported: usize,
+    pub skipped: usize, // Filtered out or already expired
+    pub failed: usize,
+    pub removed: usize, // Replace mode: keys deleted because they were not in the dump
+    pub errors: Vec<LineError>,
+    pub errors_truncated: bool,
+}
+
+/// Incremental JSONL loader: feed body chunks as they arrive, then `finish`.
+pub struct Importer {
+    cache: Arc<Cache>,
+    cluster: Option<Arc<Cluster>>,
+    filter: TransferFilter,
+    pending: Vec<u8>,       // Bytes after the last newline seen so far
+    line: usize,
+    seen: HashSet<String>,  // Replace mode: keys present in the dump
+    report: ImportReport,
+}
+
+impl Importer {
+    pub fn new(cache: Arc<Cache>, cluster: Option<Arc<Cluster>>, mode: ImportMode, filter: TransferFilter) -> Self {
+        Self {
+            cache,
+            cluster,
+            filter,
+            pending: Vec::new(),
+            line: 0,
+            seen: HashSet::new(),
+            report: ImportReport { mode, imported: 0, skipped: 0, failed: 0, removed: 0, errors: Vec::new(), errors_truncated: false },
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.pending.extend_from_slice(chunk);
+        let Some(last_nl) = self.pending.iter().rposition(|&b| b == b'\n') else { return };
+        let rest = self.pending.split_off(last_nl + 1);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        for line in complete[..last_nl].split(|&b| b == b'\n') { self.apply_line(line); }
+    }
+
+    /// Apply the final unterminated line (if any) and, in replace mode, drop keys missing from the dump.
+    pub fn finish(mut self) -> ImportReport {
+        if !self.pending.is_empty() {
+            let last = std::mem::take(&mut self.pending);
+            self.apply_line(&last);
+        }
+        if self.report.mode == ImportMode::Replace {
+            let mut stale = Vec::new();
+            for shard in &self.cache.shards {
+                for item in shard.entries.iter() {
+                    if !self.seen.contains(&item.key().0) && self.filter.matches(&item.key().0, &item.value().tags) {
+                        stale.push(item.key().clone());
+                    }
+                }
+            }
+            for key in stale {
+                if self.cluster.as_ref().is_none_or(|c| c.is_local(&key.0)) && self.cache.invalidate_key(&key) {
+                    self.report.removed += 1;
+                }
+            }
+        }
+        self.report
+    }
+
+    /// Report without the replace sweep, for uploads that broke off.
+    pub fn abort(self) -> ImportReport {
+        self.report
+    }
+
+    fn apply_line(&mut self, raw: &[u8]) {
+        self.line += 1;
+        let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+        if raw.iter().all(u8::is_ascii_whitespace) { return; }
+        if let Err(e) = self.apply_record(raw) {
+            self.report.failed += 1;
+            if self.report.errors.len() < MAX_REPORTED_ERRORS {
+                self.report.errors.push(LineError { line: self.line, error: e });
+            } else {
+                self.report.errors_truncated = true;
+            }
+        }
+    }
+
+    fn apply_record(&mut self, raw: &[u8]) -> Result<(), String> {
+        let rec: ImportRecord = serde_json::from_slice(raw).map_err(|e| format!("invalid record: {}", e))?;
+        if rec.key.is_empty() { return Err("empty key".to_string()); }
+        let tags: Vec<Tag> = rec.tags.into_iter().map(Tag).collect();
+        if !self.filter.matches(&rec.key, &tags) || rec.ttl_ms == Some(0) {
+            self.report.skipped += 1;
+            return Ok(());
+        }
+        if let Some(cluster) = &self.cluster {
+            if !cluster.is_local(&rec.key) { return Err(format!("key belongs to node {}", cluster.owner(&rec.key).id)); }
+        }
+        let kind = match rec.kind.as_deref() {
+            Some(k) => ValueType::parse(k).ok_or_else(|| format!("unknown type '{}'", k))?,
+            None => ValueType::String,
+        };
+        let value = Value::from_json(kind, rec.value)?;
+        let created = rec.created_ms.map(|ms| UNIX_EPOCH + Duration::from_millis(ms)).filter(|t| *t <= SystemTime::now());
+        if self.report.mode == ImportMode::Replace { self.seen.insert(rec.key.clone()); }
+        self.cache.restore(Key(rec.key), value, tags, rec.ttl_ms.map(Duration::from_millis), created);
+        self.report.imported += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,280 @@
+// =============================
+// VALUE TYPES
+// =============================
+// What an entry holds: a plain string (the original and still the common case), a hash, list or
+// set that is edited an element at a time (HSET, LPUSH, SADD, ...) instead of being rewritten whole,
+// or a parsed JSON document that is read and updated at paths (see `document`).
+// Every kind keeps the entry's tags and TTL. An operation meant for one kind fails with
+// `wrong_type` on a key holding another; a collection whose last element is removed is deleted.
+//
+// On the wire (replication, export) a string stays a JSON string; collections are wrapped as
+// {"hash":{...}}, {"list":[...]}, {"set":[...]} or {"json":...}.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::document::{self, JsonOp, JsonPath};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "WireValue", into = "WireValue")]
+pub enum Value {
+    String(String),
+    Hash(BTreeMap<String, String>), // Field -> value (sorted, so HGETALL is stable)
+    List(VecDeque<String>),
+    Set(BTreeSet<String>),
+    Json(serde_json::Value, usize), // Document and the length of its JSON text (see `Value::json`)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    String,
+    Hash,
+    List,
+    Set,
+    Json,
+}
+
+impl ValueType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::String => "string",
+            ValueType::Hash => "hash",
+            ValueType::List => "list",
+            ValueType::Set => "set",
+            ValueType::Json => "json",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "string" => Some(ValueType::String),
+            "hash" => Some(ValueType::Hash),
+            "list" => Some(ValueType::List),
+            "set" => Some(ValueType::Set),
+            "json" => Some(ValueType::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueError {
+    WrongType(ValueType), // The key holds this kind of value
+    NotInteger,           // HINCRBY on a field that is not an integer
+    Overflow,
+    InvalidPath(String),  // JSON path that cannot be parsed or does not fit the document
+    NotNumber,            // JSON increment of a non-number
+    NotArray,             // JSON append to a non-array
+    UnknownOperation(String),
+    OutOfRange,           // Counter result outside its bounds with `bound=reject`
+}
+
+impl ValueError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValueError::WrongType(_) => "wrong_type",
+            ValueError::NotInteger => "not_an_integer",
+            ValueError::Overflow => "overflow",
+            ValueError::InvalidPath(_) => "invalid_path",
+            ValueError::NotNumber => "not_a_number",
+            ValueError::NotArray => "not_an_array",
+            ValueError::UnknownOperation(_) => "unknown_operation",
+            ValueError::OutOfRange => "out_of_range",
+        }
+    }
+}
+
+impl std::fmt::Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueError::WrongType(found) => write!(f, "wrong_type: key holds a {}", found.as_str()),
+            ValueError::NotInteger => f.write_str("value is not an integer"),
+            ValueError::Overflow => f.write_str("integer overflow"),
+            ValueError::InvalidPath(why) => write!(f, "invalid path {}", why),
+            ValueError::NotNumber => f.write_str("value is not a number"),
+            ValueError::NotArray => f.write_str("value is not an array"),
+            ValueError::UnknownOperation(op) => write!(f, "unknown operation '{}'", op),
+            ValueError::OutOfRange => f.write_str("value out of range"),
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl Value {
+    /// A JSON document value (measures its text once).
+    pub fn json(doc: serde_json::Value) -> Self {
+        let size = document::text_len(&doc);
+        Value::Json(doc, size)
+    }
+
+    /// An empty value of the given kind.
+    pub fn empty(kind: ValueType) -> Self {
+        match kind {
+            ValueType::String => Value::String(String::new()),
+            ValueType::Hash => Value::Hash(BTreeMap::new()),
+            ValueType::List => Value::List(VecDeque::new()),
+            ValueType::Set => Value::Set(BTreeSet::new()),
+            ValueType::Json => Value::json(serde_json::Value::Null),
+        }
+    }
+
+    pub fn kind(&self) -> ValueType {
+        match self {
+            Value::String(_) => ValueType::String,
+            Value::Hash(_) => ValueType::Hash,
+            Value::List(_) => ValueType::List,
+            Value::Set(_) => ValueType::Set,
+            Value::Json(..) => ValueType::Json,
+        }
+    }
+
+    /// The text of a string value (None for collections).
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    // Typed access for the collection operations; `wrong_type` for any other kind.
+    pub fn hash(&self) -> Result<&BTreeMap<String, String>, ValueError> {
+        match self { Value::Hash(h) => Ok(h), other => Err(ValueError::WrongType(other.kind())) }
+    }
+
+    pub fn hash_mut(&mut self) -> Result<&mut BTreeMap<String, String>, ValueError> {
+        match self { Value::Hash(h) => Ok(h), other => Err(ValueError::WrongType(other.kind())) }
+    }
+
+    pub fn list(&self) -> Result<&VecDeque<String>, ValueError> {
+        match self { Value::List(l) => Ok(l), other => Err(ValueError::WrongType(other.kind())) }
+    }
+
+    pub fn list_mut(&mut self) -> Result<&mut VecDeque<String>, ValueError> {
+        match self { Value::List(l) => Ok(l), other => Err(ValueError::WrongType(other.kind())) }
+    }
+
+    pub fn set(&self) -> Result<&BTreeSet<String>, ValueError> {
+        match self { Value::Set(s) => Ok(s), other => Err(ValueError::WrongType(other.kind())) }
+    }
+
+    pub fn set_mut(&mut self) -> Result<&mut BTreeSet<String>, ValueError> {
+        match self { Value::Set(s) => Ok(s), other => Err(ValueError::WrongType(other.kind())) }
+    }
+
+    /// Apply `op` at `path` of a JSON document, keeping its size current; returns the value now at `path`.
+    pub fn json_apply(&mut self, path: &JsonPath, op: JsonOp) -> Result<serde_json::Value, ValueError> {
+        let Value::Json(doc, size) = self else { return Err(ValueError::WrongType(self.kind())) };
+        let (result, grown) = path.apply(doc, op)?;
+        *size = size.saturating_add_signed(grown);
+        Ok(result)
+    }
+
+    /// The part of the value at `path`, reading any kind as its `to_json` form (so paths also work
+    /// on strings holding JSON and on hashes / lists / sets).
+    pub fn json_at(&self, path: &JsonPath) -> Option<serde_json::Value> {
+        match self {
+            Value::Json(doc, _) => path.get(doc).cloned(),
+            other => path.get(&other.to_json()).cloned(),
+        }
+    }
+
+    /// Bytes held (fields and values, list items or members for collections, JSON text for documents).
+    pub fn size(&self) -> usize {
+        match self {
+            Value::String(s) => s.len(),
+            Value::Hash(h) => h.iter().map(|(f, v)| f.len() + v.len()).sum(),
+            Value::List(l) => l.iter().map(String::len).sum(),
+            Value::Set(s) => s.iter().map(String::len).sum(),
+            Value::Json(_, size) => *size,
+        }
+    }
+
+    /// True for a collection without elements (the key is then removed).
+    pub fn is_empty_collection(&self) -> bool {
+        match self {
+            Value::String(_) | Value::Json(..) => false,
+            Value::Hash(h) => h.is_empty(),
+            Value::List(l) => l.is_empty(),
+            Value::Set(s) => s.is_empty(),
+        }
+    }
+
+    /// As JSON for the HTTP API: strings that hold JSON are parsed (as GET /keys/:key always did),
+    /// hashes become objects and lists / sets arrays.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::String(s) => serde_json::from_str(s).unwrap_or_else(|_| serde_json::Value::String(s.clone())),
+            Value::Hash(h) => serde_json::json!(h),
+            Value::List(l) => serde_json::json!(l),
+            Value::Set(s) => serde_json::json!(s),
+            Value::Json(j, _) => j.clone(),
+        }
+    }
+
+    /// Build a value of `kind` from JSON (import, PUT /keys/:key with a `type`). Non-string
+    /// scalars are kept as their JSON text.
+    pub fn from_json(kind: ValueType, json: serde_json::Value) -> Result<Self, String> {
+        let text = |v: serde_json::Value| match v { serde_json::Value::String(s) => s, other => other.to_string() };
+        match (kind, json) {
+            (ValueType::String, v) => Ok(Value::String(text(v))),
+            (ValueType::Json, v) => Ok(Value::json(v)),
+            (ValueType::Hash, serde_json::Value::Object(m)) => Ok(Value::Hash(m.into_iter().map(|(f, v)| (f, text(v))).collect())),
+            (ValueType::List, serde_json::Value::Array(a)) => Ok(Value::List(a.into_iter().map(text).collect())),
+            (ValueType::Set, serde_json::Value::Array(a)) => Ok(Value::Set(a.into_iter().map(text).collect())),
+            (kind, _) => Err(format!("a {} value must be a JSON {}", kind.as_str(), if kind == ValueType::Hash { "object" } else { "array" })),
+        }
+    }
+}
+
+// Serialized form: a bare string, or a collection wrapped in a one-field object naming its kind.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum WireValue {
+    String(String),
+    Collection(WireCollection),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum WireCollection {
+    Hash(BTreeMap<String, String>),
+    List(VecDeque<String>),
+    Set(BTreeSet<String>),
+    Json(serde_json::Value),
+}
+
+impl From<WireValue> for Value {
+    fn from(w: WireValue) -> Self {
+        match w {
+            WireValue::String(s) => Value::String(s),
+            WireValue::Collection(WireCollection::Hash(h)) => Value::Hash(h),
+            WireValue::Collection(WireCollection::List(l)) => Value::List(l),
+            WireValue::Collection(WireCollection::Set(s)) => Value::Set(s),
+            WireValue::Collection(WireCollection::Json(j)) => Value::json(j),
+        }
+    }
+}
+
+impl From<Value> for WireValue {
+    fn from(v: Value) -> Self {
+        match v {
+            Value::String(s) => WireValue::String(s),
+            Value::Hash(h) => WireValue::Collection(WireCollection::Hash(h)),
+            Value::List(l) => WireValue::Collection(WireCollection::List(l)),
+            Value::Set(s) => WireValue::Collection(WireCollection::Set(s)),
+            Value::Json(j, _) => WireValue::Collection(WireCollection::Json(j)),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+// =============================
+// TAG INVALIDATION WEBHOOKS
+// =============================
+// Outbound HTTP notifications for tag invalidations and flushes, so CDNs / search indexers can purge
+// their own copies. The cache hands a notice to `WebhookDispatcher::notify` (non-blocking, unbounded
+// queue); a background task fans it out to every configured endpoint.
+//
+// Delivery: POST of a JSON body, retried with exponential backoff on non-2xx or transport errors.
+// When an endpoint has a `secret`, the body is signed with HMAC-SHA256 and sent as
+// `X-TagCache-Signature: sha256=<hex>`. Deliveries that exhaust their retries are appended to the
+// dead-letter file (JSONL) if one is configured.
+
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    pub secret: Option<String>,    // HMAC-SHA256 signing key (unsigned if absent)
+    #[serde(default)]
+    pub events: Vec<String>,       // Subset of events to send; empty = all
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub timeout_ms: u64,
+    pub dead_letter_file: Option<String>,
+    pub endpoints: Vec<WebhookEndpoint>,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            timeout_ms: 5_000,
+            dead_letter_file: None,
+            endpoints: Vec::new(),
+        }
+    }
+}
+
+// What happened; serialized as the `event` field and the X-TagCache-Event header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    InvalidateTag,
+    InvalidateTagsAny,
+    InvalidateTagsAll,
+    InvalidateTagsSubtree,
+    FlushAll,
+    FlushNamespace,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::InvalidateTag => "invalidate_tag",
+            WebhookEvent::InvalidateTagsAny => "invalidate_tags_any",
+            WebhookEvent::InvalidateTagsAll => "invalidate_tags_all",
+            WebhookEvent::InvalidateTagsSubtree => "invalidate_tags_subtree",
+            WebhookEvent::FlushAll => "flush_all",
+            WebhookEvent::FlushNamespace => "flush_namespace",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub id: String,
+    pub event: &'static str,
+    pub namespace: Option<String>, // None for the default namespace
+    pub tags: Vec<String>,         // Client-facing names, without the namespace
+    pub count: usize,
+    pub ts: u64,
+}
+
+#[derive(Debug)]
+pub struct WebhookDispatcher {
+    tx: mpsc::UnboundedSender<WebhookPayload>,
+}
+
+impl WebhookDispatcher {
+    // Spawn the delivery task. Returns None when no endpoints are configured so callers can skip
+    // building payloads entirely. Must be called from within a tokio runtime.
+    pub fn start(config: WebhooksConfig) -> Option<Arc<Self>> {
+        if config.endpoints.is_empty() { return None; }
+        let (tx, mut rx) = mpsc::unbounded_channel::<WebhookPayload>();
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms.max(1)))
+            .build()
+            .ok()?;
+        let config = Arc::new(config);
+        let dead_letter = Arc::new(Mutex::new(()));
+        tokio::spawn(async move {
+            while let Some(payload) = rx.recv().await {
+                let body = match serde_json::to_string(&payload) { Ok(b) => Arc::new(b), Err(_) => continue };
+                for endpoint in &config.endpoints {
+                    if !endpoint.events.is_empty() && !endpoint.events.iter().any(|e| e == payload.event) { continue; }
+                    // Each endpoint retries independently so one slow receiver doesn't delay the others.
+                    tokio::spawn(deliver(client.clone(), endpoint.clone(), payload.clone(), body.clone(), config.clone(), dead_letter.clone()));
+                }
+            }
+        });
+        Some(Arc::new(Self { tx }))
+    }
+
+    pub fn notify(&self, event: WebhookEvent, namespace: Option<String>, tags: Vec<String>, count: usize) {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        let _ = self.tx.send(WebhookPayload { id: uuid::Uuid::new_v4().to_string(), event: event.as_str(), namespace, tags, count, ts });
+    }
+}
+
+// Hex-encoded HMAC-SHA256 of `body` keyed with `secret`.
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn deliver(client: reqwest::Client, endpoint: WebhookEndpoint, payload: WebhookPayload, body: Arc<String>, config: Arc<WebhooksConfig>, dead_letter: Arc<Mutex<()>>) {
+    let signature = endpoint.secret.as_deref().map(|s| sign(s, &body));
+    let mut delay = Duration::from_millis(config.initial_backoff_ms);
+    let max_delay = Duration::from_millis(config.max_backoff_ms.max(config.initial_backoff_ms));
+    let mut last_error = String::new();
+    for attempt in 0..=config.max_retries {
+        let mut req = client.post(&endpoint.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-TagCache-Event", payload.event)
+            .header("X-TagCache-Delivery", payload.id.as_str())
+            .header("X-TagCache-Attempt", (attempt + 1).to_string());
+        if let Some(sig) = &signature { req = req.header("X-TagCache-Signature", format!("sha256={}", sig)); }
+        match req.body(body.as_ref().clone()).send().await {
+            Ok(resp) if resp.status().is_success() => return,
+            Ok(resp) => last_error = format!("HTTP {}", resp.status()),
+            Err(e) => last_error = e.to_string(),
+        }
+        if attempt < config.max_retries {
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(max_delay);
+        }
+    }
+    warn!("Webhook delivery {} to {} failed after {} attempts: {}", payload.id, endpoint.url, config.max_retries + 1, last_error);
+    if let Some(path) = &config.dead_letter_file {
+        let record = serde_json::json!({
+            "url": endpoint.url,
+            "attempts": config.max_retries + 1,
+            "error": last_error,
+            "failed_at": SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+            "payload": payload,
+        });
+        let _guard = dead_letter.lock(); // Serialize appends so lines never interleave
+        let written = std::fs::OpenOptions::new().create(true).append(true).open(path)
+            .and_then(|mut f| writeln!(f, "{}", record));
+        if let Err(e) = written { warn!("Failed to write webhook dead-letter record to {}: {}", path, e); }
+    }
+}
@@ -0,0 +1,185 @@
+//! Brute-force lockout (per IP and per user, exponential), the JSONL audit log and /admin/audit,
+//! and reloading accounts from the configuration file.
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::audit::{AuditConfig, AuditEvent, AuditLog, AuditQuery};
+use tagcache::auth::{LoginError, Role, Scope, UserConfig};
+use tagcache::config::TagCacheConfig;
+use tagcache::lockout::{Locked, Lockout, LockoutConfig};
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials};
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("tagcache-audit-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+// HTTP (with client addresses) and TCP on one state; accounts come from `config`, saved to `conf_path`.
+async fn start_server(config: &TagCacheConfig, conf_path: PathBuf) -> (Arc<AppState>, SocketAddr, SocketAddr) {
+    config.save_to_file(&conf_path).unwrap();
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let auth = AuthState::new(creds, conf_path).with_accounts(&config.authentication);
+    let state = Arc::new(AppState::new(Arc::new(Cache::new(4)), Arc::new(auth)));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None).into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state.clone(), config.performance.clone()));
+    (state, http_addr, tcp_addr)
+}
+
+fn reader() -> UserConfig {
+    UserConfig { username: "reader".into(), password: "reader-pw".into(), role: Role::ReadOnly, scope: Scope::default() }
+}
+
+async fn status(req: reqwest::RequestBuilder) -> StatusCode {
+    req.send().await.unwrap().status()
+}
+
+#[test]
+fn lockout_doubles_after_the_threshold() {
+    let lockout = Lockout::new(LockoutConfig { enabled: true, max_failures: 2, base_seconds: 1, max_seconds: 3 });
+    let ip: Option<IpAddr> = Some("10.0.0.1".parse().unwrap());
+    assert_eq!(lockout.record_failure(ip, Some("a")), None);
+    assert_eq!(lockout.record_failure(ip, Some("b")), Some(Locked::Ip(Duration::from_secs(1))));
+    assert!(lockout.check(ip, Some("c")).is_err()); // The IP is locked for every user
+    assert!(lockout.check(None, Some("a")).is_ok()); // Each user only failed once
+    assert_eq!(lockout.record_failure(None, Some("a")), Some(Locked::User(Duration::from_secs(1))));
+    assert_eq!(lockout.record_failure(None, Some("a")), Some(Locked::User(Duration::from_secs(2))));
+    assert_eq!(lockout.record_failure(None, Some("a")), Some(Locked::User(Duration::from_secs(3)))); // Capped
+    lockout.record_success("a");
+    assert!(lockout.check(None, Some("a")).is_ok());
+
+    let disabled = Lockout::new(LockoutConfig { enabled: false, ..LockoutConfig::default() });
+    for _ in 0..10 { assert_eq!(disabled.record_failure(ip, Some("a")), None); }
+    assert!(disabled.check(ip, Some("a")).is_ok());
+}
+
+#[test]
+fn lockout_tracks_known_accounts_and_bounded_addresses() {
+    // Unknown usernames only count against the address; real accounts still lock.
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let auth = AuthState::new(creds, temp_path("known.conf"));
+    for _ in 0..10 { assert!(matches!(auth.login("nobody", "guess", None), Err(LoginError::Invalid(None)))); }
+    for _ in 0..5 { assert!(matches!(auth.login("admin", "guess", None), Err(LoginError::Invalid(_)))); }
+    assert!(matches!(auth.login("admin", "password", None), Err(LoginError::LockedOut(_))));
+
+    // Past the cap new addresses go uncounted; the ones already tracked stay locked.
+    let lockout = Lockout::new(LockoutConfig { enabled: true, max_failures: 1, base_seconds: 60, max_seconds: 60 });
+    let addr = |i: u32| -> Option<IpAddr> { Some(IpAddr::from(i.to_be_bytes())) };
+    for i in 0..100_000 { assert!(lockout.record_failure(addr(i), None).is_some()); }
+    assert_eq!(lockout.record_failure(addr(100_000), None), None);
+    assert!(lockout.check(addr(100_000), None).is_ok());
+    assert!(lockout.check(addr(7), None).is_err());
+}
+
+#[tokio::test]
+async fn failed_logins_lock_out_and_are_audited() {
+    let mut config = TagCacheConfig::default();
+    config.authentication.users = vec![reader()];
+    config.authentication.lockout = LockoutConfig { enabled: true, max_failures: 3, base_seconds: 1, max_seconds: 60 };
+    let (_, http, tcp) = start_server(&config, temp_path("lockout.conf")).await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+
+    // Per user: three wrong passwords lock the account, even for the right one.
+    for _ in 0..3 {
+        assert_eq!(status(client.get(url("/stats")).basic_auth("reader", Some("nope"))).await, StatusCode::UNAUTHORIZED);
+    }
+    let resp = client.post(url("/auth/login")).json(&json!({"username":"reader","password":"reader-pw"})).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!((body["error"].clone(), body["retry_after"].clone()), (json!("locked_out"), json!(1)));
+
+    // The IP (127.0.0.1) has also failed three times, so TCP AUTH from it is refused too.
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+    sock.get_mut().write_all(b"AUTH\tadmin\tpassword\n").await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    assert!(reply.starts_with("ERR locked_out"), "{reply}");
+
+    // After the lockout the right password works and clears the user's counter.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(status(client.post(url("/auth/login")).json(&json!({"username":"reader","password":"reader-pw"}))).await, StatusCode::OK);
+    assert_eq!(status(client.post(url("/flush")).basic_auth("admin", Some("password"))).await, StatusCode::OK);
+
+    let audit: Value = client.get(url("/admin/audit")).basic_auth("admin", Some("password")).send().await.unwrap().json().await.unwrap();
+    let events: Vec<&str> = audit["entries"].as_array().unwrap().iter().map(|e| e["event"].as_str().unwrap()).collect();
+    assert_eq!(events, ["flush", "login", "lockout", "login_failed", "login_failed", "login_failed"]); // Newest first
+    let failed = &audit["entries"][3];
+    assert_eq!((failed["principal"].clone(), failed["detail"].clone()), (json!("reader"), json!("basic")));
+    assert!(failed["peer"].as_str().unwrap().starts_with("127.0.0.1:"));
+
+    let filtered: Value = client.get(url("/admin/audit?event=login&limit=5")).basic_auth("admin", Some("password")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(filtered["entries"].as_array().unwrap().len(), 1);
+    assert_eq!(status(client.get(url("/admin/audit?event=bogus")).basic_auth("admin", Some("password"))).await, StatusCode::BAD_REQUEST);
+    assert_eq!(status(client.get(url("/admin/audit")).basic_auth("reader", Some("reader-pw"))).await, StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn audit_log_appends_jsonl_and_reloads_recent_records() {
+    assert_eq!(AuditConfig::default().file, None); // Only a configured file is written
+    let path = temp_path("log.jsonl");
+    let config = AuditConfig { file: Some(path.to_string_lossy().into_owned()), recent: 3 };
+    let peer: SocketAddr = "10.1.2.3:4567".parse().unwrap();
+    {
+        let log = AuditLog::open(&config).unwrap();
+        log.record(AuditEvent::Login, Some("admin"), Some(peer), None);
+        log.record(AuditEvent::PasswordChange, Some("admin"), Some(peer), None);
+        log.record(AuditEvent::Flush, Some("ops"), None, Some("3 keys".into()));
+        log.record(AuditEvent::ConfigReload, Some("admin"), None, None);
+    }
+    let lines: Vec<Value> = std::fs::read_to_string(&path).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!((lines[0]["event"].clone(), lines[0]["peer"].clone()), (json!("login"), json!("10.1.2.3:4567")));
+    assert!(lines[0]["ts_ms"].as_u64().unwrap() > 0);
+
+    // Reopening appends and keeps the last `recent` records queryable.
+    let log = AuditLog::open(&config).unwrap();
+    log.record(AuditEvent::Rotate, Some("admin"), None, None);
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 5);
+    let recent = log.recent(&AuditQuery::default()).unwrap();
+    let events: Vec<AuditEvent> = recent.iter().map(|r| r.event).collect();
+    assert_eq!(events, [AuditEvent::Rotate, AuditEvent::ConfigReload, AuditEvent::Flush]);
+    let by_ops = log.recent(&AuditQuery { principal: Some("ops".into()), ..AuditQuery::default() }).unwrap();
+    assert_eq!(by_ops[0].detail.as_deref(), Some("3 keys"));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn reload_picks_up_account_changes() {
+    let conf_path = temp_path("reload.conf");
+    let mut config = TagCacheConfig::default();
+    config.authentication.users = vec![reader()];
+    let (state, http, _) = start_server(&config, conf_path.clone()).await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+    let login: Value = client.post(url("/auth/login")).json(&json!({"username":"reader","password":"reader-pw"})).send().await.unwrap().json().await.unwrap();
+    let token = login["token"].as_str().unwrap().to_string();
+
+    // Edit the file: reader is promoted, a new account appears.
+    config.authentication.users[0].role = Role::Writer;
+    config.authentication.users.push(UserConfig { username: "ops".into(), password: "ops-pw".into(), role: Role::Invalidator, scope: Scope::default() });
+    config.save_to_file(&conf_path).unwrap();
+    assert_eq!(status(client.get(url("/get/k")).basic_auth("ops", Some("ops-pw"))).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(client.post(url("/admin/reload")).basic_auth("admin", Some("password"))).await, StatusCode::OK);
+
+    assert_eq!(status(client.get(url("/get/k")).basic_auth("ops", Some("ops-pw"))).await, StatusCode::OK);
+    assert_eq!(status(client.get(url("/stats")).bearer_auth(&token)).await, StatusCode::UNAUTHORIZED); // Role changed
+    let put = json!({"key":"k","value":"v","tags":[]});
+    assert_eq!(status(client.post(url("/put")).basic_auth("reader", Some("reader-pw")).json(&put)).await, StatusCode::OK);
+    let reloads = state.audit.recent(&AuditQuery { event: Some("config_reload".into()), ..AuditQuery::default() }).unwrap();
+    assert_eq!(reloads[0].principal.as_deref(), Some("admin"));
+    let _ = std::fs::remove_file(&conf_path);
+}
@@ -0,0 +1,279 @@
+//! Multi-user accounts and RBAC: role checks and scopes over HTTP, accounts and API keys managed
+//! through /auth/*, TCP `AUTH` (required by default, read-only access without it when turned off), password hashing and the session
+//! token lifecycle.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::auth::{self, ApiKeyConfig, Role, Scope, UserConfig};
+use tagcache::config::{AuthConfig, TagCacheConfig};
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Key, Tag};
+
+// Account changes are persisted, so every server gets its own config file.
+fn config_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("tagcache-auth-{}-{}.conf", std::process::id(), name))
+}
+
+fn user(name: &str, role: Role, key_prefixes: &[&str], tags: &[&str]) -> UserConfig {
+    UserConfig {
+        username: name.into(),
+        password: format!("{name}-pw"),
+        role,
+        scope: Scope { key_prefixes: key_prefixes.iter().map(|s| s.to_string()).collect(), tags: tags.iter().map(|s| s.to_string()).collect(), namespace: None },
+    }
+}
+
+async fn start_server(name: &str, accounts: AuthConfig) -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let _ = std::fs::remove_file(config_path(name));
+    let auth = AuthState::new(creds, config_path(name)).with_accounts(&accounts);
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(auth)));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn call(req: reqwest::RequestBuilder) -> (StatusCode, Value) {
+    let resp = req.send().await.unwrap();
+    let status = resp.status();
+    (status, resp.json().await.unwrap_or(Value::Null))
+}
+
+async fn tcp_exchange(addr: SocketAddr, lines: &[&str]) -> Vec<String> {
+    let mut sock = BufReader::new(TcpStream::connect(addr).await.unwrap());
+    let mut replies = Vec::new();
+    for line in lines {
+        sock.get_mut().write_all(format!("{line}\n").as_bytes()).await.unwrap();
+        let mut reply = String::new();
+        sock.read_line(&mut reply).await.unwrap();
+        replies.push(reply.trim_end().to_string());
+    }
+    replies
+}
+
+#[tokio::test]
+async fn roles_gate_endpoints_and_scopes_narrow_keys_and_tags() {
+    let accounts = AuthConfig {
+        users: vec![
+            user("reader", Role::ReadOnly, &[], &[]),
+            user("purger", Role::Invalidator, &[], &["tenant:1"]),
+            user("tenant", Role::Writer, &["t1:"], &["tenant:1"]),
+        ],
+        ..TagCacheConfig::default().authentication
+    };
+    let (cache, http, _) = start_server("roles", accounts).await;
+    cache.put(Key::new("t1:a"), "1".into(), vec![Tag::new("tenant:1")], None);
+    cache.put(Key::new("t2:a"), "2".into(), vec![Tag::new("tenant:2")], None);
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+
+    // read_only: reads yes, writes / invalidations / admin no.
+    let (status, body) = call(client.get(url("/get/t2:a")).basic_auth("reader", Some("reader-pw"))).await;
+    assert_eq!((status, body["value"].clone()), (StatusCode::OK, json!("2")));
+    let (status, body) = call(client.post(url("/put")).basic_auth("reader", Some("reader-pw")).json(&json!({"key":"x","value":"v","tags":[]}))).await;
+    assert_eq!((status, body["error"].clone()), (StatusCode::FORBIDDEN, json!("forbidden")));
+    assert_eq!(call(client.post(url("/invalidate-tag")).basic_auth("reader", Some("reader-pw")).json(&json!({"tag":"tenant:2"}))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(client.get(url("/auth/users")).basic_auth("reader", Some("reader-pw"))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(client.get(url("/get/t2:a")).basic_auth("reader", Some("wrong"))).await.0, StatusCode::UNAUTHORIZED);
+
+    // invalidator limited to one tag.
+    assert_eq!(call(client.post(url("/invalidate-tag")).basic_auth("purger", Some("purger-pw")).json(&json!({"tag":"tenant:2"}))).await.0, StatusCode::FORBIDDEN);
+    let (status, body) = call(client.post(url("/invalidate-tag")).basic_auth("purger", Some("purger-pw")).json(&json!({"tag":"tenant:1"}))).await;
+    assert_eq!((status, body["count"].clone()), (StatusCode::OK, json!(1)));
+    assert_eq!(cache.get(&Key::new("t2:a")).as_deref(), Some("2"));
+
+    // writer scoped to a key prefix: own keys only, no keyspace-wide operations.
+    let tenant = |req: reqwest::RequestBuilder| req.basic_auth("tenant", Some("tenant-pw"));
+    assert_eq!(call(tenant(client.post(url("/put")).json(&json!({"key":"t1:b","value":"v","tags":["tenant:1"]})))).await.0, StatusCode::OK);
+    assert_eq!(call(tenant(client.post(url("/put")).json(&json!({"key":"t2:b","value":"v","tags":[]})))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(tenant(client.post(url("/put")).json(&json!({"key":"t1:c","value":"v","tags":["tenant:2"]})))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(tenant(client.get(url("/get/t2:a")))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(tenant(client.get(url("/keys")))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(cache.get(&Key::new("t1:b")).as_deref(), Some("v"));
+
+    let (_, me) = call(tenant(client.get(url("/auth/whoami")))).await;
+    assert_eq!((me["role"].clone(), me["key_prefixes"].clone()), (json!("writer"), json!(["t1:"])));
+}
+
+#[tokio::test]
+async fn key_prefix_scopes_cannot_reach_other_keys_through_tags() {
+    let accounts = AuthConfig { users: vec![user("checkout", Role::Writer, &["checkout:"], &[])], ..TagCacheConfig::default().authentication };
+    let (cache, http, tcp) = start_server("prefix-tags", accounts).await;
+    cache.put(Key::new("checkout:1"), "c".into(), vec![Tag::new("cart")], None);
+    cache.put(Key::new("cart:1"), "other".into(), vec![Tag::new("cart")], None);
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+    let checkout = |req: reqwest::RequestBuilder| req.basic_auth("checkout", Some("checkout-pw"));
+
+    // Every operation on all members of a tag is refused: it would reach cart:1.
+    assert_eq!(call(checkout(client.post(url("/invalidate-tag")).json(&json!({"tag":"cart"})))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(checkout(client.post(url("/invalidate/tags")).json(&json!({"tags":["cart"],"mode":"any"})))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(checkout(client.get(url("/keys-by-tag?tag=cart")))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(checkout(client.post(url("/tags/cart/rename")).json(&json!({"to":"basket"})))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(checkout(client.post(url("/tags/cart/expire")).json(&json!({"in_ms":0})))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(
+        tcp_exchange(tcp, &["AUTH\tcheckout\tcheckout-pw", "INV_TAG\tcart", "KEYS_BY_TAG\tcart", "TAG_EXPIRE\tcart\t0", "DEL\tcheckout:1"]).await,
+        ["OK", "ERR forbidden", "ERR forbidden", "ERR forbidden", "DEL ok"]
+    );
+    assert_eq!(cache.get(&Key::new("cart:1")).as_deref(), Some("other"));
+
+    assert_eq!(call(checkout(client.post(url("/search")).json(&json!({"tag_any":["cart"]})))).await.0, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn accounts_and_api_keys_are_managed_over_http() {
+    let (_, http, _) = start_server("manage", TagCacheConfig::default().authentication).await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+    let admin = |req: reqwest::RequestBuilder| req.basic_auth("admin", Some("password"));
+
+    let new_user = json!({"username":"ops","password":"s3cret","role":"invalidator"});
+    assert_eq!(call(admin(client.post(url("/auth/users")).json(&new_user))).await.0, StatusCode::OK);
+    let bad_role = json!({"username":"x","password":"y","role":"root"});
+    assert_eq!(call(admin(client.post(url("/auth/users")).json(&bad_role))).await.0, StatusCode::BAD_REQUEST);
+    let (_, list) = call(admin(client.get(url("/auth/users")))).await;
+    assert_eq!(list["users"][0]["username"], "ops");
+    assert!(list["users"][0].get("password").is_none());
+    let saved = TagCacheConfig::load_from_file(config_path("manage").to_str().unwrap()).unwrap();
+    assert_eq!(saved.authentication.users.iter().map(|u| (u.username.as_str(), u.role)).collect::<Vec<_>>(), [("ops", Role::Invalidator)]);
+    assert!(auth::verify_password("s3cret", &saved.authentication.users[0].password));
+    assert!(auth::is_password_hash(&saved.authentication.users[0].password));
+
+    // Login tokens carry the account's role.
+    let (status, login) = call(client.post(url("/auth/login")).json(&json!({"username":"ops","password":"s3cret"}))).await;
+    assert_eq!((status, login["role"].clone()), (StatusCode::OK, json!("invalidator")));
+    let token = login["token"].as_str().unwrap().to_string();
+    assert_eq!(call(client.post(url("/invalidate-key")).bearer_auth(&token).json(&json!({"key":"k"}))).await.0, StatusCode::OK);
+    assert_eq!(call(client.post(url("/put")).bearer_auth(&token).json(&json!({"key":"k","value":"v","tags":[]}))).await.0, StatusCode::FORBIDDEN);
+
+    // Deleting the account revokes its tokens.
+    assert_eq!(call(admin(client.delete(url("/auth/users/ops")))).await.1["ok"], true);
+    assert_eq!(call(client.get(url("/get/k")).bearer_auth(&token)).await.0, StatusCode::UNAUTHORIZED);
+
+    // API keys: shown once on creation, listed by prefix only.
+    let (status, created) = call(admin(client.post(url("/auth/api-keys")).json(&json!({"name":"ci","role":"writer","key_prefixes":["ci:"]})))).await;
+    assert_eq!(status, StatusCode::OK);
+    let key = created["key"].as_str().unwrap().to_string();
+    assert!(key.starts_with("tc_"));
+    let (_, keys) = call(admin(client.get(url("/auth/api-keys")))).await;
+    assert_eq!(keys["api_keys"][0]["key_prefix"], json!(&key[..8]));
+    assert!(!keys.to_string().contains(&key));
+    assert_eq!(call(client.post(url("/put")).bearer_auth(&key).json(&json!({"key":"ci:1","value":"v","tags":[]}))).await.0, StatusCode::OK);
+    assert_eq!(call(client.post(url("/put")).bearer_auth(&key).json(&json!({"key":"prod:1","value":"v","tags":[]}))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(call(admin(client.delete(url("/auth/api-keys/ci")))).await.1["ok"], true);
+    assert_eq!(call(client.get(url("/get/ci:1")).bearer_auth(&key)).await.0, StatusCode::UNAUTHORIZED);
+
+}
+
+#[tokio::test]
+async fn tcp_requires_auth_by_default() {
+    let accounts = AuthConfig {
+        users: vec![user("reader", Role::ReadOnly, &[], &[])],
+        api_keys: vec![ApiKeyConfig { name: "app".into(), key: "tc_app_key".into(), role: Role::Writer, scope: Scope { key_prefixes: vec!["app:".into()], tags: vec![], namespace: None } }],
+        ..TagCacheConfig::default().authentication
+    };
+    let (_, _, tcp) = start_server("tcp", accounts).await;
+
+    assert_eq!(tcp_exchange(tcp, &["GET\tk"]).await, ["ERR unauthorized"]);
+    assert_eq!(tcp_exchange(tcp, &["AUTH\treader\twrong", "GET\tk"]).await, ["ERR unauthorized", "ERR unauthorized"]);
+    assert_eq!(tcp_exchange(tcp, &["AUTH\treader\treader-pw", "GET\tk", "PUT\tk\t-\t-\tv"]).await, ["OK", "NF", "ERR forbidden"]);
+    assert_eq!(
+        tcp_exchange(tcp, &["AUTH\ttc_app_key", "PUT\tapp:1\t-\t-\tv", "PUT\tother\t-\t-\tv", "GET\tapp:1"]).await,
+        ["OK", "OK", "ERR forbidden", "VALUE\tv"]
+    );
+    assert_eq!(tcp_exchange(tcp, &["AUTH\tadmin\tpassword", "FLUSH"]).await[0], "OK");
+
+    // Open mode: unauthenticated connections only read.
+    let open = AuthConfig { tcp_require_auth: false, ..TagCacheConfig::default().authentication };
+    let (_, _, tcp) = start_server("tcp-open", open).await;
+    assert_eq!(
+        tcp_exchange(tcp, &["GET\tk", "PUT\tk\t-\t-\tv", "INV_TAG\tt", "FLUSH", "REPLICATE\t0", "TAG_RENAME\ta\tb"]).await,
+        ["NF", "ERR forbidden", "ERR forbidden", "ERR forbidden", "ERR forbidden", "ERR forbidden"]
+    );
+    assert_eq!(tcp_exchange(tcp, &["AUTH\tadmin\tpassword", "PUT\tk\t-\t-\tv"]).await, ["OK", "OK"]);
+}
+
+#[test]
+fn passwords_are_hashed_and_plaintext_configs_migrate() {
+    let hash = auth::hash_password("hunter2");
+    assert!(hash.starts_with("$argon2id$"));
+    assert_ne!(hash, auth::hash_password("hunter2")); // Salted
+    assert!(auth::verify_password("hunter2", &hash));
+    assert!(!auth::verify_password("hunter3", &hash));
+    assert!(auth::verify_password("legacy", "legacy")); // Unmigrated plain text still works
+
+    let path = config_path("migrate");
+    let mut config = TagCacheConfig::default();
+    config.authentication.users = vec![user("ops", Role::Writer, &[], &[])];
+    config.save_to_file(&path).unwrap();
+    assert_eq!(TagCacheConfig::hash_stored_passwords(&path).unwrap(), 2);
+    assert_eq!(TagCacheConfig::hash_stored_passwords(&path).unwrap(), 0);
+    let migrated = std::fs::read_to_string(&path).unwrap();
+    assert!(!migrated.contains("\"password\"") && !migrated.contains("ops-pw"));
+
+    // Hashed and plain-text configurations authenticate the same way.
+    let saved = TagCacheConfig::load_from_file(&path).unwrap().authentication;
+    let state = AuthState::new(Credentials { username: saved.username.clone(), password: saved.password.clone() }, path.clone()).with_accounts(&saved);
+    assert!(state.authenticate_basic("admin", "password").is_some());
+    assert!(state.authenticate_basic("ops", "ops-pw").is_some_and(|p| p.role == Role::Writer));
+    assert!(state.authenticate_basic("ops", "ops-pw").is_some()); // Remembered check
+    assert!(state.authenticate_basic("ops", "nope").is_none());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn tokens_expire_refresh_and_revoke() {
+    let accounts = AuthConfig { token_lifetime_seconds: 1, users: vec![user("reader", Role::ReadOnly, &[], &[])], ..TagCacheConfig::default().authentication };
+    let (_, http, _) = start_server("tokens", accounts).await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+    let login = || async { call(client.post(url("/auth/login")).json(&json!({"username":"reader","password":"reader-pw"}))).await.1 };
+
+    let first = login().await;
+    assert_eq!(first["expires_in"], 1);
+    let token = first["token"].as_str().unwrap().to_string();
+    assert_eq!(call(client.get(url("/stats")).bearer_auth(&token)).await.0, StatusCode::OK);
+
+    // Refresh swaps the token; Basic auth and API keys have nothing to refresh.
+    let (status, refreshed) = call(client.post(url("/auth/refresh")).bearer_auth(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    let token2 = refreshed["token"].as_str().unwrap().to_string();
+    assert_eq!(call(client.get(url("/stats")).bearer_auth(&token)).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(call(client.get(url("/stats")).bearer_auth(&token2)).await.0, StatusCode::OK);
+    assert_eq!(call(client.post(url("/auth/refresh")).basic_auth("reader", Some("reader-pw"))).await.0, StatusCode::BAD_REQUEST);
+
+    // Admins list sessions (without the tokens) and revoke them by id.
+    assert_eq!(call(client.get(url("/auth/tokens")).bearer_auth(&token2)).await.0, StatusCode::FORBIDDEN);
+    let (_, list) = call(client.get(url("/auth/tokens")).basic_auth("admin", Some("password"))).await;
+    let sessions = list["tokens"].as_array().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!((sessions[0]["name"].clone(), sessions[0]["id"].clone()), (json!("reader"), refreshed["id"].clone()));
+    assert!(!list.to_string().contains(&token2));
+    let id = refreshed["id"].as_str().unwrap();
+    assert_eq!(call(client.delete(url(&format!("/auth/tokens/{id}"))).basic_auth("admin", Some("password"))).await.1["ok"], true);
+    assert_eq!(call(client.get(url("/stats")).bearer_auth(&token2)).await.0, StatusCode::UNAUTHORIZED);
+
+    // Logout revokes the presented token.
+    let token3 = login().await["token"].as_str().unwrap().to_string();
+    assert_eq!(call(client.post(url("/auth/logout")).bearer_auth(&token3)).await.1["ok"], true);
+    assert_eq!(call(client.get(url("/stats")).bearer_auth(&token3)).await.0, StatusCode::UNAUTHORIZED);
+
+    // Tokens stop working after token_lifetime_seconds, and can no longer be refreshed.
+    let token4 = login().await["token"].as_str().unwrap().to_string();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(call(client.get(url("/stats")).bearer_auth(&token4)).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(call(client.post(url("/auth/refresh")).bearer_auth(&token4)).await.0, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,142 @@
+//! The `tagcache-client` crate against an in-process server: typed verbs over both transports,
+//! pipelining, the Auto-mode HTTP fallback and the blocking facade.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+
+use tagcache::config::TagCacheConfig;
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials};
+use tagcache_client::{blocking, Client, Command, Config, Error, Mode, Reply, TagMode};
+
+// Serve one shared cache over HTTP and TCP on ephemeral ports.
+async fn start_server() -> (SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let state = Arc::new(AppState::new(Arc::new(Cache::new(4)), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (http_addr, tcp_addr)
+}
+
+fn config(mode: Mode, http: SocketAddr, tcp: SocketAddr) -> Config {
+    Config { mode, http_url: format!("http://{http}"), tcp_addr: tcp.to_string(), ..Config::default() }
+        .with_basic_auth("admin", "password")
+}
+
+#[tokio::test]
+async fn typed_verbs_behave_the_same_over_tcp_and_http() {
+    let (http, tcp) = start_server().await;
+    for mode in [Mode::Tcp, Mode::Http] {
+        let client = Client::new(config(mode, http, tcp)).unwrap();
+        let p = format!("{:?}", mode);
+        client.put(&format!("{p}:a"), "1", &["t1"], None).await.unwrap();
+        assert!(client.add(&format!("{p}:b"), "2", &["t1", "t2"], Some(Duration::from_secs(60))).await.unwrap());
+        assert!(!client.add(&format!("{p}:b"), "3", &[], None).await.unwrap());
+        assert_eq!(client.get(&format!("{p}:a")).await.unwrap().as_deref(), Some("1"));
+        assert_eq!(client.get(&format!("{p}:missing")).await.unwrap(), None);
+        assert_eq!(client.incr(&format!("{p}:n"), 5).await.unwrap(), 5);
+        assert_eq!(client.decr(&format!("{p}:n"), 2).await.unwrap(), 3);
+        // Non-numeric values are rejected by the server, not mangled by the client.
+        client.put(&format!("{p}:word"), "abc", &[], None).await.unwrap();
+        assert!(matches!(client.incr(&format!("{p}:word"), 1).await, Err(Error::Server(_))));
+        let mut keys = client.keys_by_tag("t1").await.unwrap();
+        keys.sort();
+        assert_eq!(keys, vec![format!("{p}:a"), format!("{p}:b")]);
+        assert_eq!(client.invalidate_tags(&["t1", "t2"], TagMode::All).await.unwrap(), 1);
+        assert_eq!(client.invalidate_tag("t1").await.unwrap(), 1);
+        assert!(client.del(&format!("{p}:n")).await.unwrap());
+        assert!(!client.del(&format!("{p}:n")).await.unwrap());
+        assert!(client.stats().await.unwrap().puts >= 2);
+    }
+}
+
+#[tokio::test]
+async fn pipeline_returns_one_result_per_command_in_order() {
+    let (http, tcp) = start_server().await;
+    let client = Client::new(config(Mode::Tcp, http, tcp)).unwrap();
+    let mut batch = client.pipeline();
+    for i in 0..500 {
+        batch.push(Command::Put { key: format!("k{i}"), value: i.to_string(), tags: vec!["bulk".into()], ttl: None });
+    }
+    batch.push(Command::Get { key: "k42".into() });
+    batch.push(Command::Incr { key: "k1".into(), by: 1, tags: Vec::new(), ttl: None });
+    batch.push(Command::InvalidateTag { tag: "bulk".into() });
+    let replies = batch.execute().await.unwrap();
+    assert_eq!(replies.len(), 503);
+    assert!(replies[..500].iter().all(|r| matches!(r, Ok(Reply::Ok))));
+    assert_eq!(replies[500].as_ref().unwrap(), &Reply::Value(Some("42".into())));
+    assert_eq!(replies[501].as_ref().unwrap(), &Reply::Int(2));
+    assert_eq!(replies[502].as_ref().unwrap(), &Reply::Count(500));
+}
+
+#[tokio::test]
+async fn auto_mode_falls_back_to_http() {
+    let (http, tcp) = start_server().await;
+    // Values with newlines cannot be framed on the line protocol; Auto sends them over HTTP.
+    let client = Client::new(config(Mode::Auto, http, tcp)).unwrap();
+    client.put("multi", "line 1\nline 2", &[], None).await.unwrap();
+    let over_http = Client::new(config(Mode::Http, http, tcp)).unwrap();
+    assert_eq!(over_http.get("multi").await.unwrap().as_deref(), Some("line 1\nline 2"));
+    let tcp_only = Client::new(config(Mode::Tcp, http, tcp)).unwrap();
+    assert!(matches!(tcp_only.put("multi", "a\nb", &[], None).await, Err(Error::InvalidInput(_))));
+
+    // Dead TCP port: everything, including pipelines, goes over HTTP.
+    let dead = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let client = Client::new(Config { max_retries: 0, ..config(Mode::Auto, http, dead) }).unwrap();
+    client.put("fallback", "ok", &[], None).await.unwrap();
+    let mut batch = client.pipeline();
+    batch.push(Command::Get { key: "fallback".into() });
+    batch.push(Command::Del { key: "fallback".into() });
+    let replies = batch.execute().await.unwrap();
+    assert_eq!(replies[0].as_ref().unwrap(), &Reply::Value(Some("ok".into())));
+    assert_eq!(replies[1].as_ref().unwrap(), &Reply::Deleted(true));
+
+    // Without credentials the HTTP fallback is refused.
+    let anon = Client::new(Config { mode: Mode::Http, http_url: format!("http://{http}"), ..Config::default() }).unwrap();
+    assert!(matches!(anon.get("fallback").await, Err(Error::Unauthorized)));
+}
+
+#[tokio::test]
+async fn blocking_client_works_outside_the_runtime() {
+    let (http, tcp) = start_server().await;
+    let cfg = config(Mode::Tcp, http, tcp);
+    tokio::task::spawn_blocking(move || {
+        let client = blocking::Client::new(cfg).unwrap();
+        client.put("sync", "v", &["s"], None).unwrap();
+        assert_eq!(client.get("sync").unwrap().as_deref(), Some("v"));
+        let replies = client.pipeline(vec![Command::Get { key: "sync".into() }, Command::Flush]).unwrap();
+        assert_eq!(replies[1].as_ref().unwrap(), &Reply::Count(1));
+        assert_eq!(client.http_get("/health").unwrap()["status"], "ok");
+    })
+    .await
+    .unwrap();
+}
+
+#[test]
+fn tcp_lines_parse_back_into_commands() {
+    let commands = [
+        Command::Put { key: "k".into(), value: "a\tb".into(), tags: vec!["t1".into(), "t2".into()], ttl: Some(Duration::from_millis(500)) },
+        Command::Add { key: "k".into(), value: String::new(), tags: Vec::new(), ttl: None },
+        Command::Decr { key: "n".into(), by: -3, tags: vec!["c".into()], ttl: None },
+        Command::InvalidateTags { tags: vec!["a".into(), "b".into()], mode: TagMode::All },
+        Command::InvalidateKeys { keys: vec!["x".into(), "y".into()] },
+        Command::KeysByTag { tag: "t".into() },
+        Command::Flush,
+    ];
+    for cmd in commands {
+        assert_eq!(Command::from_tcp_line(&cmd.to_tcp_line().unwrap()).unwrap(), cmd);
+    }
+    // Same leniency as the server: lower-case verbs and a default INCR amount.
+    assert_eq!(Command::from_tcp_line("incr\tn").unwrap(), Command::Incr { key: "n".into(), by: 1, tags: Vec::new(), ttl: None });
+    assert!(matches!(Command::from_tcp_line("GET"), Err(Error::InvalidInput(_))));
+    assert!(matches!(Command::from_tcp_line("NOPE\tk"), Err(Error::InvalidInput(_))));
+    assert!(matches!(Command::from_tcp_line("PUT\tk\tsoon\t-\tv"), Err(Error::InvalidInput(_))));
+}
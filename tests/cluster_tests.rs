@@ -0,0 +1,150 @@
+//! Cluster mode: deterministic hash ring, owner forwarding / redirects and tag fan-out across
+//! several HTTP nodes on localhost.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+use tagcache::audit::AuditLog;
+use tagcache::cluster::{Cluster, ClusterConfig, ClusterNode, HashRing, RoutingMode};
+use tagcache::replication::{Replication, ReplicationConfig};
+use tagcache::schedule::Scheduler;
+use tagcache::{build_app, AppState, AuthState, Cache, Credentials, Key};
+
+const AUTH: &str = "Basic YWRtaW46cGFzc3dvcmQ="; // admin:password
+
+// Start `n` nodes sharing one member list; returns each node's base URL and cache.
+async fn start_cluster(n: usize, mode: RoutingMode) -> Vec<(String, Arc<Cache>)> {
+    let mut listeners = Vec::new();
+    let mut nodes = Vec::new();
+    for i in 0..n {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let http = listener.local_addr().unwrap().to_string();
+        nodes.push(ClusterNode { id: format!("node{i}"), http, tcp: "127.0.0.1:1".into() });
+        listeners.push(listener);
+    }
+    let mut out = Vec::new();
+    for (i, listener) in listeners.into_iter().enumerate() {
+        let config = ClusterConfig { enabled: true, node_id: format!("node{i}"), mode, nodes: nodes.clone(), ..ClusterConfig::default() };
+        let cache = Arc::new(Cache::new(4));
+        let creds = Credentials { username: "admin".into(), password: "password".into() };
+        let audit = Arc::new(AuditLog::memory(100));
+        let state = Arc::new(AppState {
+            cache: cache.clone(),
+            auth: Arc::new(AuthState::new(creds, PathBuf::from("unused.conf"))),
+            system: Arc::new(parking_lot::Mutex::new(sysinfo::System::new())),
+            replication: Replication::new(&ReplicationConfig::default()),
+            cluster: Some(Cluster::new(&config).unwrap()),
+            schedules: Arc::new(Scheduler::memory(cache.clone(), audit.clone())),
+            audit,
+        });
+        tokio::spawn(async move { axum::serve(listener, build_app(state, None)).await.unwrap() });
+        out.push((format!("http://{}", nodes[i].http), cache));
+    }
+    out
+}
+
+async fn post(client: &reqwest::Client, url: String, body: Value) -> (u16, Value) {
+    let resp = client.post(url).header("Authorization", AUTH).json(&body).send().await.unwrap();
+    (resp.status().as_u16(), resp.json().await.unwrap_or(Value::Null))
+}
+
+async fn get(client: &reqwest::Client, url: String) -> Value {
+    client.get(url).header("Authorization", AUTH).send().await.unwrap().json().await.unwrap()
+}
+
+#[test]
+fn ring_is_deterministic_balanced_and_stable() {
+    let three = HashRing::new(&["a", "b", "c"], 128);
+    let again = HashRing::new(&["a", "b", "c"], 128);
+    let four = HashRing::new(&["a", "b", "c", "d"], 128);
+    let mut per_node: HashMap<usize, usize> = HashMap::new();
+    let mut moved = 0;
+    for i in 0..10_000 {
+        let key = format!("user:{i}");
+        let owner = three.owner(&key).unwrap();
+        assert_eq!(again.owner(&key), Some(owner));
+        *per_node.entry(owner).or_default() += 1;
+        let new_owner = four.owner(&key).unwrap();
+        if new_owner != owner {
+            assert_eq!(new_owner, 3, "keys only move to the added node");
+            moved += 1;
+        }
+    }
+    for count in per_node.values() { assert!((2_300..=4_400).contains(count), "unbalanced: {per_node:?}"); }
+    assert!((1_500..=3_500).contains(&moved), "moved {moved}");
+}
+
+#[tokio::test]
+async fn forward_mode_routes_keys_and_fans_out_tags() {
+    let nodes = start_cluster(3, RoutingMode::Forward).await;
+    let client = reqwest::Client::new();
+    let entry = &nodes[0].0;
+    for i in 0..30 {
+        let (status, body) = post(&client, format!("{entry}/put"), json!({"key": format!("k{i}"), "value": i.to_string(), "tags": ["all"]})).await;
+        assert_eq!((status, body["ok"].clone()), (200, json!(true)));
+    }
+    // Each key lives only on its owner, and the keyspace is spread over every node.
+    let held: Vec<usize> = nodes.iter().map(|(_, c)| c.shards.iter().map(|s| s.entries.len()).sum()).collect();
+    assert_eq!(held.iter().sum::<usize>(), 30);
+    assert!(held.iter().all(|&n| n > 0), "{held:?}");
+
+    // Any node can read any key.
+    for i in 0..30 {
+        assert_eq!(get(&client, format!("{}/get/k{i}", nodes[2].0)).await["value"], json!(i.to_string()));
+    }
+    let keys = get(&client, format!("{}/keys-by-tag?tag=all", nodes[1].0)).await;
+    assert_eq!(keys["keys"].as_array().unwrap().len(), 30);
+    let limited = get(&client, format!("{}/keys-by-tag?tag=all&limit=5", nodes[1].0)).await;
+    assert_eq!(limited["keys"].as_array().unwrap().len(), 5);
+
+    let (_, deleted) = post(&client, format!("{}/invalidate/keys", nodes[2].0), json!({"keys": ["k0", "k1", "missing"]})).await;
+    assert_eq!(deleted["count"], 2);
+    let (status, body) = post(&client, format!("{}/invalidate-tag", nodes[1].0), json!({"tag": "all"})).await;
+    assert_eq!((status, body["count"].clone()), (200, json!(28)));
+    assert!(nodes.iter().all(|(_, c)| c.get(&Key::new("k5")).is_none()));
+}
+
+#[tokio::test]
+async fn redirect_mode_points_clients_at_the_owner() {
+    let nodes = start_cluster(2, RoutingMode::Redirect).await;
+    let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+    let info = get(&client, format!("{}/cluster?key=k1", nodes[0].0)).await;
+    let owner = info["owner"].as_str().unwrap();
+    let (owner_url, other_url) = if owner == "node0" { (&nodes[0].0, &nodes[1].0) } else { (&nodes[1].0, &nodes[0].0) };
+
+    let (status, body) = post(&client, format!("{other_url}/put"), json!({"key": "k1", "value": "v", "tags": []})).await;
+    assert_eq!(status, 307);
+    assert_eq!(body["location"], json!(format!("{owner_url}/put")));
+    assert_eq!(body["node"], json!(owner));
+
+    // Retrying at the advertised location succeeds (clients must re-send credentials across origins).
+    let (status, _) = post(&client, body["location"].as_str().unwrap().to_string(), json!({"key": "k1", "value": "v", "tags": []})).await;
+    assert_eq!(status, 200);
+    let resp = client.get(format!("{other_url}/get/k1")).header("Authorization", AUTH).send().await.unwrap();
+    assert_eq!(resp.status().as_u16(), 307);
+    assert_eq!(get(&client, format!("{owner_url}/get/k1")).await["value"], json!("v"));
+}
+
+#[tokio::test]
+async fn schedules_share_one_id_across_nodes() {
+    let nodes = start_cluster(3, RoutingMode::Forward).await;
+    let client = reqwest::Client::new();
+    let (status, created) = post(&client, format!("{}/schedules", nodes[0].0), json!({"target": "tags", "tags": ["all"], "in_ms": 600_000})).await;
+    assert_eq!(status, 200);
+    let id = created["schedule"]["id"].as_str().unwrap().to_string();
+    let at_ms = created["schedule"]["at_ms"].clone();
+    for (url, _) in &nodes {
+        assert_eq!(get(&client, format!("{url}/schedules/{id}")).await["schedule"]["at_ms"], at_ms); // Same copy everywhere
+    }
+
+    // Cancelling through any node cancels every copy.
+    let resp = client.delete(format!("{}/schedules/{id}", nodes[2].0)).header("Authorization", AUTH).send().await.unwrap();
+    assert_eq!(resp.json::<Value>().await.unwrap()["ok"], json!(true));
+    for (url, _) in &nodes {
+        assert_eq!(get(&client, format!("{url}/schedules/{id}")).await["error"], json!("not_found"));
+    }
+}
@@ -0,0 +1,136 @@
+//! Counters: float amounts, bounds (clamp / reject) and reset windows, over the cache, HTTP, TCP
+//! and the client.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::counter::{Bound, CounterOptions, CounterValue, Window};
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Key};
+use tagcache_client::{Client, Command, Config, CounterOptions as ClientCounterOptions, Error, Mode};
+
+async fn start_server() -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+#[test]
+fn float_and_bounded_counters() {
+    let cache = Cache::new(2);
+    let incr = |key: &str, by: CounterValue, options: &CounterOptions| cache.increment_with(Key::new(key), by, options, vec![], None).map(|(value, _)| value);
+    let plain = CounterOptions::default();
+
+    assert_eq!(incr("f", CounterValue::Float(1.5), &plain), Ok(CounterValue::Float(1.5)));
+    assert_eq!(incr("f", CounterValue::Int(2), &plain), Err("value is not an integer".into())); // Like before
+    assert_eq!(incr("f", CounterValue::Float(-0.25), &plain), Ok(CounterValue::Float(1.25)));
+    let float = CounterOptions { float: true, ..CounterOptions::default() };
+    assert_eq!(incr("f", CounterValue::Int(2), &float), Ok(CounterValue::Float(3.25)));
+    assert_eq!(cache.get(&Key::new("f")).as_deref(), Some("3.25"));
+    assert_eq!(incr("i", CounterValue::Int(3), &float), Ok(CounterValue::Float(3.0)));
+    assert_eq!(cache.get(&Key::new("i")).as_deref(), Some("3")); // Whole floats read back as integers
+    assert_eq!(cache.increment(Key::new("i"), 1, vec![], None), Ok(4));
+
+    let clamp = CounterOptions::parse("min=0,max=10").unwrap();
+    assert_eq!(incr("c", CounterValue::Int(7), &clamp), Ok(CounterValue::Int(7)));
+    assert_eq!(incr("c", CounterValue::Int(7), &clamp), Ok(CounterValue::Int(10)));
+    assert_eq!(cache.decrement_with(Key::new("c"), CounterValue::Int(25), &clamp, vec![], None).map(|(value, _)| value), Ok(CounterValue::Int(0)));
+    let reject = CounterOptions::parse("max=2.5,bound=reject").unwrap();
+    assert_eq!(reject.bound, Bound::Reject);
+    assert_eq!(incr("r", CounterValue::Int(2), &reject), Ok(CounterValue::Int(2)));
+    assert_eq!(incr("r", CounterValue::Int(1), &reject), Err("value out of range".into()));
+    assert_eq!(cache.get(&Key::new("r")).as_deref(), Some("2"));
+
+    for bad in ["min=x", "bound=maybe", "window=0s", "window=3w", "nope", "min=5,max=1", "window=367d", "window=2305843009213693952s", "window=3074457345618258603h"] {
+        assert!(CounterOptions::parse(bad).is_err(), "{bad}");
+    }
+    assert!(CounterOptions::parse("window=366d").is_ok());
+}
+
+#[test]
+fn windows_reset_at_the_boundary() {
+    let minute = Window::parse("minute").unwrap();
+    assert_eq!(minute, Window::parse("60s").unwrap());
+    assert_eq!(Window::parse("2h").unwrap(), Window::parse("120m").unwrap());
+    let at = |ms: u64| UNIX_EPOCH + Duration::from_millis(ms);
+    assert_eq!(minute.remaining(at(120_000)), Duration::from_secs(60));
+    assert_eq!(minute.remaining(at(179_250)), Duration::from_millis(750));
+
+    // The counter expires at the end of the window, so the next INCR starts over.
+    let cache = Cache::new(1);
+    let options = CounterOptions::parse("window=1s").unwrap();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    if now % 1000 > 800 { std::thread::sleep(Duration::from_millis(250)); } // Stay inside one window
+    let incr = || cache.increment_with(Key::new("rps"), CounterValue::Int(1), &options, vec![], Some(Duration::from_secs(3600))).map(|(value, _)| value);
+    assert_eq!(incr(), Ok(CounterValue::Int(1)));
+    assert_eq!(incr(), Ok(CounterValue::Int(2)));
+    std::thread::sleep(Duration::from_millis(1050));
+    assert_eq!(cache.get(&Key::new("rps")), None);
+    assert_eq!(incr(), Ok(CounterValue::Int(1)));
+}
+
+#[tokio::test]
+async fn http_tcp_and_client_counter_options() {
+    let (_, http, tcp) = start_server().await;
+    let client = reqwest::Client::new();
+    let incr = |path: &'static str, body: Value| {
+        let req = client.post(format!("http://{http}{path}")).basic_auth("admin", Some("password")).json(&body);
+        async move { req.send().await.unwrap().json::<Value>().await.unwrap() }
+    };
+    assert_eq!(incr("/incr", json!({"key":"price","by":0.5})).await["value"], 0.5);
+    assert_eq!(incr("/incr", json!({"key":"price","by":1,"float":true})).await["value"], 1.5);
+    assert_eq!(incr("/incr", json!({"key":"stock","by":5,"max":3})).await["value"], 3);
+    let body = incr("/decr", json!({"key":"stock","by":5,"min":0,"bound":"reject"})).await;
+    assert_eq!(body, json!({"ok":false,"error":"value out of range"}));
+    let body = incr("/incr", json!({"key":"hits","window":"hour"})).await;
+    assert_eq!(body["value"], 1);
+    assert!(body["ttl_ms"].as_u64().unwrap() <= 3_600_000);
+
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "INCR\tt\t2.5").await, "VALUE\t2.5");
+    assert_eq!(send(&mut sock, "INCR\tq\t8\t-\tquotas\tmax=5").await, "VALUE\t5");
+    assert_eq!(send(&mut sock, "DECR\tq\t1\t-\t-\tmin=0,max=5,bound=reject").await, "VALUE\t4");
+    assert_eq!(send(&mut sock, "KEYS_BY_TAG\tquotas").await, "KEYS\tq");
+    assert_eq!(send(&mut sock, "INCR\tq\t9\t-\t-\tmax=5,bound=reject").await, "ERR value out of range");
+    assert_eq!(send(&mut sock, "INCR\tq\t1\t-\t-\tfloat").await, "VALUE\t5");
+    assert!(send(&mut sock, "INCR\tq\t1\t-\t-\tspeed=9").await.starts_with("ERR invalid_options\t"));
+
+    let options = ClientCounterOptions { min: Some(0.0), max: Some(10.0), window: Some("minute".into()), ..ClientCounterOptions::default() };
+    let cmd = Command::Counter { key: "k".into(), by: -1.5, options: options.clone(), tags: vec!["t".into()], ttl: None };
+    let line = cmd.to_tcp_line().unwrap();
+    assert_eq!(line, "INCR\tk\t-1.5\t-\tt\tmin=0,max=10,window=minute");
+    assert_eq!(Command::from_tcp_line(&line).unwrap(), cmd);
+    assert_eq!(Command::from_tcp_line("DECR\tk\t0.5").unwrap(), Command::Counter { key: "k".into(), by: -0.5, options: ClientCounterOptions::default(), tags: vec![], ttl: None });
+    for mode in [Mode::Tcp, Mode::Http] {
+        let config = Config { mode, http_url: format!("http://{http}"), tcp_addr: tcp.to_string(), ..Config::default() }.with_basic_auth("admin", "password");
+        let client = Client::new(config).unwrap();
+        let key = format!("{mode:?}:temp");
+        assert_eq!(client.incr_with(&key, 20.5, options.clone()).await.unwrap(), 10.0);
+        assert_eq!(client.incr_with(&key, -2.5, options.clone()).await.unwrap(), 7.5);
+        let reject = ClientCounterOptions { max: Some(8.0), reject: true, ..ClientCounterOptions::default() };
+        assert!(matches!(client.incr_with(&key, 1.0, reject).await, Err(Error::Server(_))));
+    }
+}
@@ -10,239 +10,8 @@ use std::time::{Duration, Instant};
 use tokio::time::timeout;
 use tokio::task::JoinSet;
 
-// Since the types are in main.rs, we need to include them directly
-// For now, let's define the basic types we need for testing
-use dashmap::{DashMap, DashSet};
-use smallvec::SmallVec;
-use ahash::RandomState;
-use std::hash::{Hash, Hasher, BuildHasher};
-use parking_lot::Mutex;
-
-/// Key wrapper for type safety
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Key(String);
-
-impl Key {
-    pub fn new<S: Into<String>>(s: S) -> Self { Key(s.into()) }
-    pub fn as_str(&self) -> &str { &self.0 }
-}
-
-/// Tag wrapper for type safety  
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Tag(String);
-
-impl Tag {
-    pub fn new<S: Into<String>>(s: S) -> Self { Tag(s.into()) }
-    pub fn as_str(&self) -> &str { &self.0 }
-}
-
-/// Cache entry with TTL support
-#[derive(Debug, Clone)]
-pub struct Entry {
-    pub value: String,
-    pub tags: SmallVec<[Tag; 4]>,
-    pub created_at: std::time::Instant,
-    pub ttl: Option<Duration>,
-}
-
-impl Entry {
-    pub fn is_expired(&self) -> bool {
-        if let Some(ttl) = self.ttl {
-            self.created_at.elapsed() > ttl
-        } else {
-            false
-        }
-    }
-}
-
-/// Cache shard
-#[derive(Debug)]
-pub struct Shard {
-    pub entries: DashMap<Key, Entry>,
-    pub tag_to_keys: DashMap<Tag, DashSet<Key>>,
-}
-
-impl Shard {
-    pub fn new() -> Self {
-        Self {
-            entries: DashMap::new(),
-            tag_to_keys: DashMap::new(),
-        }
-    }
-}
-
-/// Cache statistics
-#[derive(Debug, Default, Clone)]
-pub struct CacheStats {
-    pub hits: u64,
-    pub misses: u64,
-    pub puts: u64,
-    pub invalidations: u64,
-}
-
-/// Main cache structure
-#[derive(Debug)]
-pub struct Cache {
-    pub shards: Vec<Shard>,
-    pub stats: Arc<Mutex<CacheStats>>,
-    hasher: RandomState,
-}
-
-impl Cache {
-    pub fn new(num_shards: usize) -> Self {
-        assert!(num_shards > 0, "num_shards must be > 0");
-        let mut shards = Vec::with_capacity(num_shards);
-        for _ in 0..num_shards {
-            shards.push(Shard::new());
-        }
-        Self {
-            shards,
-            stats: Arc::new(Mutex::new(CacheStats::default())),
-            hasher: RandomState::new(),
-        }
-    }
-
-    fn hash_key(&self, key: &Key) -> usize {
-        let mut hasher = self.hasher.build_hasher();
-        key.hash(&mut hasher);
-        (hasher.finish() as usize) % self.shards.len()
-    }
-
-    pub fn put(&self, key: Key, value: String, tags: Vec<Tag>, ttl: Option<Duration>) {
-        let shard_idx = self.hash_key(&key);
-        let shard = &self.shards[shard_idx];
-
-        let entry = Entry {
-            value,
-            tags: SmallVec::from_vec(tags.clone()),
-            created_at: std::time::Instant::now(),
-            ttl,
-        };
-
-        // Get old tags first to avoid lock conflicts
-        let old_tags = if let Some(old_entry) = shard.entries.get(&key) {
-            old_entry.tags.clone()
-        } else {
-            SmallVec::new()
-        };
-
-        // Clean up old tag associations
-        for tag in &old_tags {
-            if let Some(keys) = shard.tag_to_keys.get(tag) {
-                keys.remove(&key);
-                if keys.is_empty() {
-                    drop(keys);
-                    shard.tag_to_keys.remove(tag);
-                }
-            }
-        }
-
-        // Add new tag associations
-        for tag in &tags {
-            shard
-                .tag_to_keys
-                .entry(tag.clone())
-                .or_insert_with(DashSet::new)
-                .insert(key.clone());
-        }
-
-        shard.entries.insert(key, entry);
-        self.stats.lock().puts += 1;
-    }
-
-    pub fn get(&self, key: &Key) -> Option<String> {
-        let shard_idx = self.hash_key(key);
-        let shard = &self.shards[shard_idx];
-        
-        // First, check if entry exists and get its expiration status
-        let (value, is_expired) = if let Some(entry) = shard.entries.get(key) {
-            if entry.is_expired() {
-                (None, true)
-            } else {
-                (Some(entry.value.clone()), false)
-            }
-        } else {
-            (None, false)
-        };
-        
-        // Handle expired entry removal without holding read lock
-        if is_expired {
-            if let Some((_, old_entry)) = shard.entries.remove(key) {
-                // Clean up tag associations
-                for tag in &old_entry.tags {
-                    if let Some(tag_keys) = shard.tag_to_keys.get_mut(tag) {
-                        tag_keys.remove(key);
-                        if tag_keys.is_empty() {
-                            drop(tag_keys);
-                            shard.tag_to_keys.remove(tag);
-                        }
-                    }
-                }
-            }
-            self.stats.lock().misses += 1;
-            return None;
-        }
-        
-        if let Some(val) = value {
-            self.stats.lock().hits += 1;
-            Some(val)
-        } else {
-            self.stats.lock().misses += 1;
-            None
-        }
-    }
-
-    pub fn invalidate_key(&self, key: &Key) -> bool {
-        let shard_idx = self.hash_key(key);
-        let shard = &self.shards[shard_idx];
-        if let Some((_, entry)) = shard.entries.remove(key) {
-            for tag in &entry.tags {
-                if let Some(keys) = shard.tag_to_keys.get(tag) {
-                    keys.remove(key);
-                    if keys.is_empty() {
-                        drop(keys);
-                        shard.tag_to_keys.remove(tag);
-                    }
-                }
-            }
-            self.stats.lock().invalidations += 1;
-            true
-        } else {
-            false
-        }
-    }
-
-    pub fn cleanup_expired(&self) -> usize {
-        let mut count = 0;
-        for shard in &self.shards {
-            let mut to_remove = Vec::new();
-            for entry in shard.entries.iter() {
-                if entry.value().is_expired() {
-                    to_remove.push(entry.key().clone());
-                }
-            }
-            for key in to_remove {
-                if let Some((_, entry)) = shard.entries.remove(&key) {
-                    for tag in &entry.tags {
-                        if let Some(keys) = shard.tag_to_keys.get(tag) {
-                            keys.remove(&key);
-                            if keys.is_empty() {
-                                drop(keys);
-                                shard.tag_to_keys.remove(tag);
-                            }
-                        }
-                    }
-                    count += 1;
-                }
-            }
-        }
-        count
-    }
-
-    pub fn get_stats(&self) -> CacheStats {
-        self.stats.lock().clone()
-    }
-}
+// Exercises the real engine from the library crate.
+use tagcache::{Cache, Key, Tag};
 
 /// Test for the specific deadlock issue in Cache::get() method
 /// This test reproduces the scenario where expired entries cause deadlocks
@@ -0,0 +1,177 @@
+//! JSON documents: path parsing, atomic path updates in the cache, and the HTTP / TCP commands.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use reqwest::StatusCode;
+use serde_json::{json, Value as Json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::document::{JsonOp, JsonPath};
+use tagcache::value::ValueError;
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Key, Tag, ValueType};
+
+async fn start_server() -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+fn path(p: &str) -> JsonPath {
+    JsonPath::parse(p).unwrap()
+}
+
+#[test]
+fn pointer_and_jsonpath_name_the_same_locations() {
+    let doc = json!({"items":[{"price":5},{"price":7}],"a/b":1,"odd key":2});
+    for (p, expected) in [
+        ("$.items[1].price", json!(7)), ("/items/1/price", json!(7)), ("$.items[-1].price", json!(7)),
+        ("$['odd key']", json!(2)), ("/a~1b", json!(1)), ("$", doc.clone()), ("", doc.clone()),
+    ] {
+        assert_eq!(path(p).get(&doc), Some(&expected), "{p}");
+    }
+    assert_eq!(path("$.items[5]").get(&doc), None);
+    assert_eq!(path("$.items.price").get(&doc), None);
+    for bad in ["items", "$.", "$[abc]", "$['x", "$.a[1"] {
+        assert!(matches!(JsonPath::parse(bad), Err(ValueError::InvalidPath(_))), "{bad}");
+    }
+}
+
+#[test]
+fn path_updates_are_atomic() {
+    let cache = Cache::new(2);
+    let key = || Key::new("product:1");
+    let update = |p: &str, op: JsonOp| cache.json_update(key(), &path(p), op, vec![], None);
+    assert_eq!(update("$.price", JsonOp::Set(json!(10))), Ok(json!(10))); // Created from nothing
+    cache.json_update(key(), &path("$.meta.color"), JsonOp::Set(json!("red")), vec![Tag::new("products")], None).unwrap();
+    assert_eq!(update("$.price", JsonOp::Incr(2.into())), Ok(json!(12)));
+    assert_eq!(update("$.price", JsonOp::Incr(serde_json::Number::from_f64(0.5).unwrap())), Ok(json!(12.5)));
+    assert_eq!(update("$.stock", JsonOp::Incr((-1).into())), Ok(json!(-1)));
+    assert_eq!(update("$.sizes", JsonOp::Append(vec![json!("S"), json!("M")])), Ok(json!(["S", "M"])));
+    assert_eq!(update("$.meta", JsonOp::Merge(json!({"color": null, "weight": 3}))), Ok(json!({"weight": 3})));
+
+    // Failing updates leave the document untouched.
+    update("$.big", JsonOp::Set(json!(i64::MAX))).unwrap();
+    let before = cache.get_value(&key());
+    assert_eq!(update("$.meta.weight.grams", JsonOp::Set(json!(1))).map_err(|e| e.code()), Err("invalid_path"));
+    assert_eq!(update("$.sizes[9]", JsonOp::Set(json!("L"))).map_err(|e| e.code()), Err("invalid_path"));
+    assert_eq!(update("$.sizes", JsonOp::Incr(1.into())), Err(ValueError::NotNumber));
+    assert_eq!(update("$.price", JsonOp::Append(vec![json!(1)])), Err(ValueError::NotArray));
+    assert_eq!(update("$.big", JsonOp::Incr(1.into())), Err(ValueError::Overflow));
+    assert_eq!(cache.get_value(&key()), before);
+
+    // Documents keep tags, refuse string operations and are readable at any path.
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("products")), vec![key()]);
+    assert_eq!(cache.get(&key()), None);
+    cache.put(Key::new("plain"), r#"{"a":[1,2]}"#.into(), vec![], None);
+    assert_eq!(cache.json_get(&Key::new("plain"), &path("$.a[0]")), Some(json!(1)));
+    assert_eq!(cache.json_update(Key::new("plain"), &path("$.a"), JsonOp::Set(json!(0)), vec![], None), Err(ValueError::WrongType(ValueType::String)));
+}
+
+#[test]
+fn document_size_follows_path_updates() {
+    // The size kept with a document always equals the length of its JSON text.
+    let cache = Cache::new(1);
+    let key = || Key::new("doc");
+    let steps = [
+        ("$.a.b", JsonOp::Set(json!(1))),
+        ("$['q\"uote'].x", JsonOp::Set(json!("é\n"))),
+        ("$.list", JsonOp::Append(vec![])),
+        ("$.list", JsonOp::Append(vec![json!(1), json!({"k": [true]})])),
+        ("$.list[-1].k", JsonOp::Append(vec![json!(null)])),
+        ("$.a", JsonOp::Merge(json!({"b": null, "c": {"d": 2.5}}))),
+        ("$.a.c.d", JsonOp::Incr(serde_json::Number::from_f64(-10.25).unwrap())),
+        ("$.n", JsonOp::Incr(7.into())),
+        ("$.list[0]", JsonOp::Set(json!("replaced"))),
+        ("$", JsonOp::Merge(json!({"list": null}))),
+        ("$", JsonOp::Set(json!([]))),
+        ("$", JsonOp::Append(vec![json!("x")])),
+    ];
+    for (p, op) in steps {
+        cache.json_update(key(), &path(p), op, vec![], None).unwrap();
+        let value = cache.get_value(&key()).unwrap();
+        assert_eq!(value.size(), value.to_json().to_string().len(), "after {p}");
+    }
+}
+
+#[tokio::test]
+async fn http_reads_and_patches_documents() {
+    let (cache, http, _) = start_server().await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+    let call = |req: reqwest::RequestBuilder| async move {
+        let resp = req.basic_auth("admin", Some("password")).send().await.unwrap();
+        (resp.status(), resp.json::<Json>().await.unwrap())
+    };
+
+    let doc = json!({"name":"lamp","price":20,"tags":["home"]});
+    let (status, _) = call(client.put(url("/keys/product:1")).json(&json!({"type":"json","value":doc,"tags":["products"]}))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = call(client.get(url("/keys/product:1"))).await;
+    assert_eq!((body["type"].clone(), body["value"].clone()), (json!("json"), doc));
+    let (_, body) = call(client.get(url("/keys/product:1?path=$.price"))).await;
+    assert_eq!((body["value"].clone(), body["path"].clone()), (json!(20), json!("$.price")));
+    let (_, body) = call(client.get(url("/keys/product:1?path=/tags/0"))).await;
+    assert_eq!(body["value"], "home");
+    let (_, body) = call(client.get(url("/keys/product:1?path=$.missing"))).await;
+    assert_eq!(body["error"], "not_found");
+    let (status, body) = call(client.get(url("/keys/product:1?path=price"))).await;
+    assert_eq!((status, body["error"].clone()), (StatusCode::BAD_REQUEST, json!("invalid_path")));
+
+    let patch = |body: Json| call(client.patch(url("/keys/product:1")).json(&body));
+    let (_, body) = patch(json!({"op":"incr","path":"$.price","value":5})).await;
+    assert_eq!(body, json!({"ok":true,"path":"$.price","value":25}));
+    let (_, body) = patch(json!({"op":"append","path":"/tags","value":"sale"})).await;
+    assert_eq!(body["value"], json!(["home","sale"]));
+    let (_, body) = patch(json!({"op":"merge","value":{"name":null,"stock":{"eu":3}}})).await;
+    assert_eq!(body["value"], json!({"price":25,"tags":["home","sale"],"stock":{"eu":3}}));
+    let (_, body) = patch(json!({"op":"set","path":"$.stock.us","value":1})).await;
+    assert_eq!(body["value"], 1);
+    let (status, body) = patch(json!({"op":"incr","path":"$.tags","value":1})).await;
+    assert_eq!((status, body["error"].clone()), (StatusCode::BAD_REQUEST, json!("not_a_number")));
+    let (status, body) = patch(json!({"op":"rename","value":1})).await;
+    assert_eq!((status, body["error"].clone()), (StatusCode::BAD_REQUEST, json!("unknown_operation")));
+
+    cache.put(Key::new("name"), "plain".into(), vec![], None);
+    let (status, body) = call(client.patch(url("/keys/name")).json(&json!({"op":"set","path":"$.x","value":1}))).await;
+    assert_eq!((status, body["type"].clone()), (StatusCode::CONFLICT, json!("string")));
+    let (_, body) = call(client.get(url("/get/product:1"))).await;
+    assert_eq!(body, json!({"error":"wrong_type","type":"json"}));
+}
+
+#[tokio::test]
+async fn tcp_json_commands() {
+    let (_, _, tcp) = start_server().await;
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "JSON_SET\tcfg\t-\tconfigs\t$\t{\"limits\":{\"rps\":10}}").await, "VALUE\t{\"limits\":{\"rps\":10}}");
+    assert_eq!(send(&mut sock, "JSON_INCR\tcfg\t-\t-\t$.limits.rps\t5").await, "VALUE\t15");
+    assert_eq!(send(&mut sock, "JSON_APPEND\tcfg\t-\t-\t/hosts\t[\"a\",\"b\"]").await, "VALUE\t[\"a\",\"b\"]");
+    assert_eq!(send(&mut sock, "JSON_MERGE\tcfg\t-\t-\t-\t{\"limits\":null}").await, "VALUE\t{\"hosts\":[\"a\",\"b\"]}");
+    assert_eq!(send(&mut sock, "JSON_GET\tcfg\t$.hosts[1]").await, "VALUE\t\"b\"");
+    assert_eq!(send(&mut sock, "JSON_GET\tcfg").await, "VALUE\t{\"hosts\":[\"a\",\"b\"]}");
+    assert_eq!(send(&mut sock, "JSON_GET\tcfg\t$.nope").await, "NF");
+    assert_eq!(send(&mut sock, "KEYS_BY_TAG\tconfigs").await, "KEYS\tcfg");
+    assert_eq!(send(&mut sock, "JSON_SET\tcfg\t-\t-\t$.x\tnot json").await, "ERR invalid_json");
+    assert_eq!(send(&mut sock, "JSON_INCR\tcfg\t-\t-\t$.hosts\t1").await, "ERR not_a_number");
+    assert!(send(&mut sock, "JSON_SET\tcfg\t-\t-\thosts\t1").await.starts_with("ERR invalid_path\t"));
+    assert_eq!(send(&mut sock, "GET\tcfg").await, "ERR wrong_type\tjson");
+}
@@ -0,0 +1,58 @@
+//! Embedding TagCache in-process through the library API: one shared cache served over HTTP and the
+//! TCP protocol at the same time, and driven directly.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::{build_app, server, tcp, AppState, AuthState, Cache, Credentials, Key, Tag};
+
+#[tokio::test]
+async fn http_tcp_and_direct_access_share_one_cache() {
+    let cache = Arc::new(Cache::new(4));
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+
+    // Written in-process, read over HTTP.
+    cache.put(Key::new("direct"), "1".into(), vec![Tag::new("t")], None);
+    let client = reqwest::Client::new();
+    let body: Value = client.get(format!("http://{http_addr}/get/direct")).basic_auth("admin", Some("password"))
+        .send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["value"], json!("1"));
+
+    // Written over TCP, read in-process.
+    let mut sock = BufReader::new(TcpStream::connect(tcp_addr).await.unwrap());
+    sock.get_mut().write_all(b"AUTH\tadmin\tpassword\nPUT\tremote\t-\tt\t2\n").await.unwrap();
+    let mut reply = String::new();
+    for _ in 0..2 {
+        reply.clear();
+        sock.read_line(&mut reply).await.unwrap();
+        assert_eq!(reply.trim(), "OK");
+    }
+    assert_eq!(cache.get(&Key::new("remote")).as_deref(), Some("2"));
+    assert_eq!(cache.invalidate_tag(&Tag::new("t")), 2);
+}
+
+#[tokio::test]
+async fn expiry_reaper_reclaims_embedded_entries() {
+    let cache = Arc::new(Cache::new(2));
+    cache.put(Key::new("short"), "v".into(), vec![Tag::new("t")], Some(Duration::from_millis(20)));
+    let reaper = server::spawn_expiry_reaper(cache.clone(), Duration::from_millis(10), 100);
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    reaper.abort();
+    assert_eq!(cache.shards.iter().map(|s| s.entries.len()).sum::<usize>(), 0);
+    assert_eq!(cache.get_stats().expired, 1);
+}
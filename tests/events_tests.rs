@@ -0,0 +1,54 @@
+//! Keyspace event bus tests: mutations publish typed events, filters select them and slow
+//! subscribers lag instead of blocking writers.
+
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+use tagcache::{Cache, Key, Tag};
+use tagcache::events::{EventFilter, EventKind};
+
+#[test]
+fn mutations_publish_typed_events() {
+    let cache = Cache::new(4);
+    let mut rx = cache.events.subscribe();
+
+    cache.put(Key::new("a"), "1".into(), vec![Tag::new("t")], None);
+    assert!(cache.add(Key::new("a"), "2".into(), vec![], None).is_none()); // existing key: no event
+    cache.increment(Key::new("n"), 5, vec![], None).unwrap();
+    cache.invalidate_key(&Key::new("n"));
+    cache.invalidate_tag(&Tag::new("t"));
+    cache.flush_all();
+
+    let kinds: Vec<&str> = std::iter::from_fn(|| rx.try_recv().ok()).map(|e| e.kind.as_str()).collect();
+    assert_eq!(kinds, vec!["put", "incr", "delete", "invalidate_tag", "flush"]);
+}
+
+#[test]
+fn filter_by_type_prefix_and_tag() {
+    let f = EventFilter::parse(Some("put,delete"), Some("user:"), Some("-")).unwrap();
+    assert_eq!(f.kinds, vec![EventKind::Put, EventKind::Delete]);
+    assert!(f.prefix.as_deref() == Some("user:") && f.tag.is_none());
+    assert!(EventFilter::parse(Some("bogus"), None, None).is_err());
+
+    let cache = Cache::new(1);
+    let mut rx = cache.events.subscribe();
+    cache.put(Key::new("user:1"), "x".into(), vec![Tag::new("vip")], None);
+    cache.put(Key::new("order:1"), "x".into(), vec![], None);
+    let by_tag = EventFilter::parse(None, None, Some("vip")).unwrap();
+    let evs: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+    assert_eq!(evs.iter().filter(|e| by_tag.matches(e)).count(), 1);
+}
+
+#[test]
+fn slow_subscriber_lags_without_blocking_writers() {
+    let cache = Cache::new(1).with_event_buffer(4);
+    let mut rx = cache.events.subscribe();
+    for i in 0..10 {
+        cache.put(Key::new(format!("k{i}")), "v".into(), vec![], None);
+    }
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Lagged(6))));
+    assert_eq!(rx.try_recv().unwrap().key.as_deref(), Some("k6"));
+    drop(rx);
+    let mut closed = cache.events.subscribe();
+    drop(cache);
+    assert!(matches!(closed.blocking_recv(), Err(RecvError::Closed)));
+}
@@ -0,0 +1,63 @@
+//! Expiration index tests: entries with a TTL are reaped incrementally from the per-shard
+//! deadline heaps instead of by full scans.
+
+use std::thread;
+use std::time::Duration;
+
+use tagcache::{Cache, Key, Tag};
+
+#[test]
+fn expire_due_removes_entries_and_tag_links() {
+    let cache = Cache::new(4);
+    cache.put(Key::new("short"), "a".into(), vec![Tag::new("t")], Some(Duration::from_millis(10)));
+    cache.put(Key::new("long"), "b".into(), vec![Tag::new("t")], Some(Duration::from_secs(60)));
+    cache.put(Key::new("forever"), "c".into(), vec![], None);
+    assert_eq!(cache.pending_expirations(), 2);
+
+    thread::sleep(Duration::from_millis(30));
+    assert_eq!(cache.expire_due(100), 1);
+
+    let keys: Vec<String> = cache.get_keys_by_tag(&Tag::new("t")).into_iter().map(|k| k.as_str().to_string()).collect();
+    assert_eq!(keys, vec!["long".to_string()]);
+    assert_eq!(cache.get(&Key::new("forever")), Some("c".to_string()));
+    assert_eq!(cache.get_stats().expired, 1);
+    assert_eq!(cache.pending_expirations(), 1);
+}
+
+#[test]
+fn expire_due_skips_stale_records_for_rewritten_keys() {
+    let cache = Cache::new(1);
+    cache.put(Key::new("k"), "old".into(), vec![], Some(Duration::from_millis(10)));
+    cache.put(Key::new("k"), "new".into(), vec![], Some(Duration::from_secs(60)));
+
+    thread::sleep(Duration::from_millis(30));
+    assert_eq!(cache.expire_due(100), 0);
+    assert_eq!(cache.get(&Key::new("k")), Some("new".to_string()));
+}
+
+#[test]
+fn expire_due_is_bounded_per_shard() {
+    let cache = Cache::new(1);
+    for i in 0..50 {
+        cache.put(Key::new(format!("k{i}")), "v".into(), vec![], Some(Duration::from_millis(1)));
+    }
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(cache.expire_due(10), 10);
+    assert_eq!(cache.expire_due(100), 40);
+    assert_eq!(cache.get_stats().expired, 50);
+}
+
+#[test]
+fn rewrites_do_not_grow_the_expiry_index() {
+    let cache = Cache::new(1);
+    for i in 0..5000 {
+        cache.put(Key::new("hot"), format!("v{i}"), vec![], Some(Duration::from_secs(60)));
+    }
+    cache.put(Key::new("short"), "a".into(), vec![], Some(Duration::from_millis(10)));
+    assert_eq!(cache.pending_expirations(), 5001); // One record per write until the reaper runs
+
+    thread::sleep(Duration::from_millis(30));
+    assert_eq!(cache.expire_due(100), 1); // Compacts, then reaps what is due
+    assert_eq!(cache.pending_expirations(), 1);
+    assert_eq!(cache.get(&Key::new("hot")), Some("v4999".to_string()));
+}
@@ -0,0 +1,146 @@
+//! Adding / removing / replacing the tags of one key in place: the tag index follows, value, TTL and
+//! creation time do not move, over the cache, HTTP, TCP and the client.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value as Json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Entry, Key, Tag, TagMatch};
+use tagcache_client::{Client, Command, Config, Mode};
+
+async fn start_server() -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+fn tags(names: &[&str]) -> Vec<Tag> {
+    names.iter().map(|t| Tag::new(*t)).collect()
+}
+
+fn entry(cache: &Cache, key: &str) -> Entry {
+    cache.shards.iter().find_map(|s| s.entries.get(&Key::new(key)).map(|e| e.clone())).unwrap()
+}
+
+#[test]
+fn edit_keeps_value_ttl_and_age() {
+    let cache = Cache::new(4);
+    cache.put(Key::new("k"), "v".into(), tags(&["a", "b"]), Some(Duration::from_secs(60)));
+    let before = entry(&cache, "k");
+    std::thread::sleep(Duration::from_millis(5));
+
+    let edited = cache.edit_tags(&Key::new("k"), |t| { t.retain(|t| t.as_str() != "a"); t.push(Tag::new("c")); t.push(Tag::new("c")); });
+    assert_eq!(edited, Some((tags(&["b", "c"]), true)));
+    let after = entry(&cache, "k");
+    assert_eq!((after.ttl, after.created_at, after.created_system), (before.ttl, before.created_at, before.created_system));
+    assert_eq!(cache.get(&Key::new("k")).as_deref(), Some("v"));
+
+    assert!(cache.get_keys_by_tag(&Tag::new("a")).is_empty());
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("c")), vec![Key::new("k")]);
+    assert_eq!(cache.edit_tags(&Key::new("k"), |t| t.push(Tag::new("b"))), Some((tags(&["b", "c"]), false)));
+    assert_eq!(cache.edit_tags(&Key::new("missing"), |t| t.push(Tag::new("b"))), None);
+
+    // The new tag invalidates it, the dropped one no longer does.
+    assert_eq!(cache.invalidate_tag(&Tag::new("a")), 0);
+    assert_eq!(cache.invalidate_tags(&tags(&["c"]), TagMatch::Any), 1);
+    assert!(cache.get_keys_by_tag(&Tag::new("b")).is_empty());
+}
+
+#[test]
+fn concurrent_edits_keep_the_index_in_sync() {
+    let cache = Arc::new(Cache::new(4));
+    for i in 0..50 { cache.put(Key::new(format!("k{i}")), "v".into(), tags(&["base"]), None); }
+    // Editors lock an entry and then the tag index; readers listing a tag run alongside them, and
+    // taking the two the other way round would deadlock.
+    let (done, finished) = std::sync::mpsc::channel();
+    for w in 0..6 {
+        let (cache, done) = (cache.clone(), done.clone());
+        std::thread::spawn(move || {
+            for round in 0..2000 {
+                let tag = Tag::new(format!("t{}", round % 5));
+                if w >= 4 { cache.get_keys_by_tag(&tag); continue; }
+                let key = Key::new(format!("k{}", (round * 7 + w) % 50));
+                cache.edit_tags(&key, |t| if round % 2 == 0 { t.push(tag) } else { t.retain(|x| *x != tag) });
+            }
+            done.send(()).unwrap();
+        });
+    }
+    for _ in 0..6 { finished.recv_timeout(Duration::from_secs(20)).expect("workers deadlocked"); }
+
+    // Every key listed under a tag carries it, and every tag a key carries lists the key.
+    for i in 0..50 {
+        let key = Key::new(format!("k{i}"));
+        for tag in entry(&cache, key.as_str()).tags.iter() {
+            assert!(cache.get_keys_by_tag(tag).contains(&key), "{} missing from {}", key.as_str(), tag.as_str());
+        }
+    }
+    for t in 0..5 {
+        let tag = Tag::new(format!("t{t}"));
+        for key in cache.get_keys_by_tag(&tag) { assert!(entry(&cache, key.as_str()).tags.contains(&tag)); }
+    }
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("base")).len(), 50);
+}
+
+#[tokio::test]
+async fn http_tcp_and_client() {
+    let (cache, http, tcp) = start_server().await;
+    cache.put(Key::new("user:1"), "alice".into(), tags(&["users", "trial"]), Some(Duration::from_secs(600)));
+    let client = reqwest::Client::new();
+    let post = |body: Json| client.post(format!("http://{http}/keys/user:1/tags")).basic_auth("admin", Some("password")).json(&body).send();
+
+    let body: Json = post(json!({"add":["vip"],"remove":["trial"]})).await.unwrap().json().await.unwrap();
+    assert_eq!(body, json!({"ok":true,"key":"user:1","tags":["users","vip"],"changed":true}));
+    let body: Json = post(json!({"replace":["users"]})).await.unwrap().json().await.unwrap();
+    assert_eq!(body["tags"], json!(["users"]));
+    assert_eq!(post(json!({"replace":["a"],"add":["b"]})).await.unwrap().status(), 400);
+    assert_eq!(post(json!({})).await.unwrap().status(), 400);
+    let missing: Json = client.post(format!("http://{http}/keys/nobody/tags")).basic_auth("admin", Some("password"))
+        .json(&json!({"add":["x"]})).send().await.unwrap().json().await.unwrap();
+    assert_eq!(missing["error"], "not_found");
+
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "TAG_ADD\tuser:1\tbeta,gamma").await, "TAG_ADD\t1");
+    assert_eq!(send(&mut sock, "TAG_DEL\tuser:1\tgamma").await, "TAG_DEL\t1");
+    assert_eq!(send(&mut sock, "TAG_DEL\tuser:1\tgamma").await, "TAG_DEL\t0");
+    assert_eq!(send(&mut sock, "TAG_ADD\tnobody\tx").await, "NF");
+    assert_eq!(send(&mut sock, "TAG_ADD\tuser:1").await, "ERR missing_tags");
+
+    let cmd = Command::AddTags { key: "user:1".into(), tags: vec!["x".into(), "y".into()] };
+    assert_eq!(cmd.to_tcp_line().unwrap(), "TAG_ADD\tuser:1\tx,y");
+    assert_eq!(Command::from_tcp_line("TAG_ADD\tuser:1\tx,y").unwrap(), cmd);
+    for (mode, tag) in [(Mode::Tcp, "from-tcp"), (Mode::Http, "from-http")] {
+        let config = Config { mode, http_url: format!("http://{http}"), tcp_addr: tcp.to_string(), ..Config::default() }.with_basic_auth("admin", "password");
+        let client = Client::new(config).unwrap();
+        assert_eq!(client.add_tags("user:1", &[tag]).await.unwrap(), Some(true));
+        assert_eq!(client.remove_tags("user:1", &["beta"]).await.unwrap(), Some(mode == Mode::Tcp));
+        assert_eq!(client.add_tags("nobody", &[tag]).await.unwrap(), None);
+    }
+    let mut tags: Vec<String> = entry(&cache, "user:1").tags.iter().map(|t| t.as_str().to_string()).collect();
+    tags.sort();
+    assert_eq!(tags, vec!["from-http", "from-tcp", "users"]);
+    assert_eq!(cache.get(&Key::new("user:1")).as_deref(), Some("alice"));
+    assert!(entry(&cache, "user:1").ttl.is_some());
+}
@@ -0,0 +1,146 @@
+//! Locks: leases, owner-only renew / release, fencing tokens and blocking acquire, over the cache,
+//! HTTP and TCP.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::StatusCode;
+use serde_json::{json, Value as Json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::lock::{self, LockError};
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Key, Tag, ValueType};
+
+async fn start_server() -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+#[test]
+fn leases_owners_and_fencing_tokens() {
+    let cache = Cache::new(2);
+    let key = || Key::new("lock:cron:daily");
+    let lease = cache.lock_acquire(key(), Duration::from_secs(30), vec![Tag::new("cron")]).unwrap();
+    assert_eq!(lease.owner.len(), 24);
+    assert!(matches!(cache.lock_acquire(key(), Duration::from_secs(30), vec![]), Err(LockError::Held(left)) if left <= Duration::from_secs(30)));
+
+    // Only the owner renews or releases.
+    assert_eq!(cache.lock_renew(&key(), "intruder", Duration::from_secs(60)), Err(LockError::NotOwner));
+    assert_eq!(cache.lock_release(&key(), "intruder"), Err(LockError::NotOwner));
+    let renewed = cache.lock_renew(&key(), &lease.owner, Duration::from_secs(60)).unwrap();
+    assert_eq!((renewed.fence, renewed.ttl), (lease.fence, Duration::from_secs(60)));
+    assert_eq!(cache.lock_release(&key(), &lease.owner), Ok(()));
+    assert_eq!(cache.lock_release(&key(), &lease.owner), Err(LockError::NotHeld));
+    assert_eq!(cache.lock_renew(&key(), &lease.owner, Duration::from_secs(1)), Err(LockError::NotHeld));
+
+    // A holder that dies lets its lease run out; every grant gets a higher fencing token.
+    let short = cache.lock_acquire(key(), Duration::from_millis(30), vec![]).unwrap();
+    assert!(short.fence > lease.fence);
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(cache.lock_renew(&key(), &short.owner, Duration::from_secs(1)), Err(LockError::NotHeld));
+    let next = cache.lock_acquire(key(), Duration::from_secs(30), vec![]).unwrap();
+    assert!(next.fence > short.fence && next.owner != short.owner);
+    let other = cache.lock_acquire(Key::new("lock:other"), Duration::from_secs(30), vec![]).unwrap();
+    assert!(other.fence > next.fence);
+
+    cache.put(Key::new("plain"), "x".into(), vec![], None);
+    assert_eq!(cache.lock_acquire(Key::new("plain"), Duration::from_secs(1), vec![]), Err(LockError::WrongType(ValueType::String)));
+    assert_eq!(cache.lock_release(&Key::new("plain"), "x"), Err(LockError::WrongType(ValueType::String)));
+}
+
+#[tokio::test]
+async fn blocking_acquire_waits_for_release_or_expiry() {
+    let cache = Arc::new(Cache::new(2));
+    let key = || Key::new("lock:job");
+    let held = cache.lock_acquire(key(), Duration::from_secs(30), vec![]).unwrap();
+    let releaser = {
+        let cache = cache.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cache.lock_release(&key(), &held.owner).unwrap();
+        })
+    };
+    let started = Instant::now();
+    let lease = lock::acquire_wait(&cache, key(), Duration::from_millis(200), vec![], Duration::from_secs(5)).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2), "woken by the release");
+    releaser.await.unwrap();
+
+    // Nobody releases the 200ms lease: a waiter gets the lock once it runs out, or gives up first.
+    assert!(matches!(lock::acquire_wait(&cache, key(), Duration::from_secs(1), vec![], Duration::from_millis(20)).await, Err(LockError::Held(_))));
+    let next = lock::acquire_wait(&cache, key(), Duration::from_secs(1), vec![], Duration::from_secs(5)).await.unwrap();
+    assert!(next.fence > lease.fence);
+}
+
+#[tokio::test]
+async fn http_and_tcp_locks() {
+    let (cache, http, tcp) = start_server().await;
+    let client = reqwest::Client::new();
+    let call = |path: &str, body: Json| {
+        let req = client.post(format!("http://{http}/keys/lock:reports/lock/{path}")).basic_auth("admin", Some("password")).json(&body);
+        async move {
+            let resp = req.send().await.unwrap();
+            (resp.status(), resp.json::<Json>().await.unwrap())
+        }
+    };
+    let (status, lease) = call("acquire", json!({"ttl_ms": 10_000, "tags": ["workers"]})).await;
+    assert_eq!(status, StatusCode::OK);
+    let (owner, fence) = (lease["owner"].as_str().unwrap().to_string(), lease["fence"].as_u64().unwrap());
+    let (status, body) = call("acquire", json!({"ttl_ms": 10_000, "wait_ms": 50})).await;
+    assert_eq!((status, body["error"].clone()), (StatusCode::CONFLICT, json!("locked")));
+    assert!(body["retry_after_ms"].as_u64().unwrap() <= 10_000);
+    let (status, body) = call("renew", json!({"owner": owner, "ttl_ms": 20_000})).await;
+    assert_eq!((status, body["fence"].as_u64(), body["ttl_ms"].clone()), (StatusCode::OK, Some(fence), json!(20_000)));
+    let (status, body) = call("release", json!({"owner": "someone-else"})).await;
+    assert_eq!((status, body["error"].clone()), (StatusCode::CONFLICT, json!("not_owner")));
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("workers")), vec![Key::new("lock:reports")]);
+    assert_eq!(call("release", json!({"owner": owner})).await.0, StatusCode::OK);
+    let (status, body) = call("release", json!({"owner": owner})).await;
+    assert_eq!((status, body["error"].clone()), (StatusCode::NOT_FOUND, json!("not_held")));
+
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    let reply = send(&mut sock, "LOCK\tlock:nightly\t5000\t-\tworkers").await;
+    let fields: Vec<&str> = reply.split('\t').collect();
+    let [verb, owner, fence] = fields.as_slice() else { panic!("{reply}") };
+    assert_eq!(*verb, "LOCK");
+    assert!(fence.parse::<u64>().unwrap() > 0);
+    assert!(send(&mut sock, "LOCK\tlock:nightly\t5000").await.starts_with("HELD\t"));
+    assert_eq!(send(&mut sock, &format!("RENEW\tlock:nightly\t{owner}\t8000")).await, format!("LOCK\t{owner}\t{fence}"));
+    assert_eq!(send(&mut sock, "UNLOCK\tlock:nightly\tnobody").await, "ERR not_owner");
+    assert_eq!(send(&mut sock, &format!("UNLOCK\tlock:nightly\t{owner}")).await, "OK");
+    assert_eq!(send(&mut sock, &format!("UNLOCK\tlock:nightly\t{owner}")).await, "ERR not_held");
+    assert_eq!(send(&mut sock, "LOCK\tlock:nightly\t0").await, "ERR invalid_ttl");
+
+    // A blocking LOCK on one connection is granted as soon as another connection unlocks.
+    let held = send(&mut sock, "LOCK\tlock:nightly\t5000").await;
+    let holder = held.split('\t').nth(1).unwrap().to_string();
+    let waiter = tokio::spawn(async move {
+        let mut other = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+        assert_eq!(send(&mut other, "AUTH\tadmin\tpassword").await, "OK");
+        send(&mut other, "LOCK\tlock:nightly\t5000\t3000").await
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(send(&mut sock, &format!("UNLOCK\tlock:nightly\t{holder}")).await, "OK");
+    assert!(waiter.await.unwrap().starts_with("LOCK\t"));
+}
@@ -0,0 +1,199 @@
+//! Namespaces: isolation over HTTP (header and /ns/ prefix) and TCP (SELECT), scoped flush and
+//! stats, quotas, and accounts bound to a namespace.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::auth::{Role, Scope, UserConfig};
+use tagcache::config::TagCacheConfig;
+use tagcache::namespace::{Namespace, NamespaceConfig, Quota};
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Key, Tag};
+
+async fn start_server(config: TagCacheConfig) -> (SocketAddr, SocketAddr) {
+    let path = std::env::temp_dir().join(format!("tagcache-ns-{}-{}.conf", std::process::id(), rand_suffix()));
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let auth = AuthState::new(creds, path).with_accounts(&config.authentication);
+    let cache = Cache::new(4).with_namespaces(config.namespaces.clone());
+    let state = Arc::new(AppState::new(Arc::new(cache), Arc::new(auth)));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None).into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, config.performance.clone()));
+    (http_addr, tcp_addr)
+}
+
+fn rand_suffix() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+#[test]
+fn namespaced_keys_and_tags_are_isolated_in_the_cache() {
+    let cache = Cache::new(2);
+    let (a, b) = (Namespace::named("team-a").unwrap(), Namespace::named("team-b").unwrap());
+    assert!(Namespace::named("default").unwrap().is_default());
+    assert!(Namespace::named("bad name").is_err());
+    for ns in [&a, &b, &Namespace::default()] {
+        cache.put(ns.key("k"), "v".into(), vec![ns.tag("t")], None);
+    }
+    assert_eq!(cache.invalidate_tag(&a.tag("t")), 1);
+    assert_eq!(cache.get(&a.key("k")), None);
+    assert_eq!(cache.get(&b.key("k")).as_deref(), Some("v"));
+    assert_eq!(cache.get(&Key::new("k")).as_deref(), Some("v"));
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("t")), vec![Key::new("k")]);
+
+    assert_eq!(cache.flush_namespace(&b), 1);
+    assert_eq!(cache.get(&Key::new("k")).as_deref(), Some("v"));
+    let stats = cache.namespaces.stats(&b);
+    assert_eq!((stats.keys, stats.puts, stats.hits, stats.invalidations), (0, 1, 1, 1));
+    assert_eq!(cache.namespaces.stats(&Namespace::default()).keys, 1);
+}
+
+#[tokio::test]
+async fn http_namespaces_by_header_and_prefix() {
+    let (http, _) = start_server(TagCacheConfig::default()).await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+    let put = |ns: Option<&str>, value: &str| {
+        let req = client.post(url("/put")).basic_auth("admin", Some("password")).json(&json!({"key":"user:1","value":value,"tags":["users"]}));
+        match ns { Some(ns) => req.header("x-tagcache-namespace", ns), None => req }
+    };
+    assert!(put(Some("team-a"), "a").send().await.unwrap().status().is_success());
+    assert!(put(Some("team-b"), "b").send().await.unwrap().status().is_success());
+    assert!(put(None, "plain").send().await.unwrap().status().is_success());
+
+    let get = |path: &str| client.get(url(path)).basic_auth("admin", Some("password")).send();
+    let body: Value = get("/ns/team-a/get/user:1").await.unwrap().json().await.unwrap();
+    assert_eq!(body["value"], "a");
+    let body: Value = get("/get/user:1").await.unwrap().json().await.unwrap();
+    assert_eq!(body["value"], "plain");
+    let body: Value = get("/ns/team-b/keys-by-tag?tag=users").await.unwrap().json().await.unwrap();
+    assert_eq!(body["keys"], json!(["user:1"]));
+
+    // Invalidation, flush and stats stay inside the namespace.
+    let resp = client.post(url("/ns/team-a/invalidate-tag")).basic_auth("admin", Some("password")).json(&json!({"tag":"users"})).send().await.unwrap();
+    assert_eq!(resp.json::<Value>().await.unwrap()["count"], 1);
+    let resp = client.post(url("/flush")).header("x-tagcache-namespace", "team-b").basic_auth("admin", Some("password")).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = get("/get/user:1").await.unwrap().json().await.unwrap();
+    assert_eq!(body["value"], "plain");
+    let stats: Value = get("/ns/team-a/stats").await.unwrap().json().await.unwrap();
+    assert_eq!((stats["namespace"].clone(), stats["puts"].clone(), stats["invalidations"].clone()), (json!("team-a"), json!(1), json!(1)));
+
+    let list: Value = get("/admin/namespaces").await.unwrap().json().await.unwrap();
+    let names: Vec<&str> = list["namespaces"].as_array().unwrap().iter().map(|n| n["namespace"].as_str().unwrap()).collect();
+    assert_eq!(names, ["default", "team-a", "team-b"]);
+    assert_eq!(get("/ns/bad%20name/get/k").await.unwrap().status(), StatusCode::BAD_REQUEST);
+    assert_eq!(get("/ns/team-a/admin/namespaces").await.unwrap().status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn quotas_and_bound_accounts() {
+    let mut config = TagCacheConfig::default();
+    config.namespaces.allow_undeclared = false;
+    config.namespaces.declared = vec![
+        NamespaceConfig { name: "small".into(), quota: Quota { max_keys: 2, max_bytes: 64, max_ops_per_sec: 0 } },
+        NamespaceConfig { name: "slow".into(), quota: Quota { max_ops_per_sec: 3, ..Quota::default() } },
+    ];
+    let scope = Scope { namespace: Some("small".into()), ..Scope::default() };
+    config.authentication.users = vec![UserConfig { username: "team".into(), password: "team-pw".into(), role: Role::Writer, scope }];
+    let (http, _) = start_server(config).await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+    let put = |key: &str, value: &str| client.post(url("/put")).basic_auth("team", Some("team-pw")).json(&json!({"key":key,"value":value,"tags":[]})).send();
+
+    // The bound account works in its namespace without naming it.
+    assert_eq!(put("a", "1").await.unwrap().status(), StatusCode::OK);
+    assert_eq!(put("b", "2").await.unwrap().status(), StatusCode::OK);
+    assert_eq!(put("a", "3").await.unwrap().status(), StatusCode::OK); // Replacing does not add a key
+    let resp = put("c", "4").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+    assert_eq!(resp.json::<Value>().await.unwrap(), json!({"error":"quota_exceeded","quota":"max_keys"}));
+    let resp = put("b", &"x".repeat(100)).await.unwrap();
+    assert_eq!(resp.json::<Value>().await.unwrap()["quota"], "max_bytes");
+    let body: Value = client.get(url("/ns/small/get/a")).basic_auth("admin", Some("password")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["value"], "3");
+
+    // ...and cannot leave it or reach server-wide endpoints.
+    let other = client.get(url("/ns/slow/get/a")).basic_auth("team", Some("team-pw")).send().await.unwrap();
+    assert_eq!(other.status(), StatusCode::FORBIDDEN);
+    let whoami: Value = client.get(url("/auth/whoami")).basic_auth("team", Some("team-pw")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(whoami["namespace"], "small");
+    assert_eq!(client.get(url("/ns/nope/get/a")).basic_auth("admin", Some("password")).send().await.unwrap().status(), StatusCode::NOT_FOUND);
+
+    // Operations past max_ops_per_sec are refused until the window turns over.
+    let mut codes = Vec::new();
+    for _ in 0..4 { codes.push(client.get(url("/ns/slow/get/a")).basic_auth("admin", Some("password")).send().await.unwrap().status()); }
+    assert_eq!(codes, [StatusCode::OK, StatusCode::OK, StatusCode::OK, StatusCode::TOO_MANY_REQUESTS]);
+}
+
+#[tokio::test]
+async fn tcp_select_scopes_the_connection() {
+    let mut config = TagCacheConfig::default();
+    config.namespaces.declared = vec![NamespaceConfig { name: "tiny".into(), quota: Quota { max_keys: 1, ..Quota::default() } }];
+    let (_, tcp) = start_server(config).await;
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "PUT\tk\t-\tt\tplain").await, "OK");
+    assert_eq!(send(&mut sock, "SELECT\tteam-a").await, "OK");
+    assert_eq!(send(&mut sock, "GET\tk").await, "NF");
+    assert_eq!(send(&mut sock, "PUT\tk\t-\tt\tscoped").await, "OK");
+    assert_eq!(send(&mut sock, "KEYS_BY_TAG\tt").await, "KEYS\tk");
+    assert_eq!(send(&mut sock, "INV_TAG\tt").await, "INV_TAG\t1");
+    assert_eq!(send(&mut sock, "SUBSCRIBE").await, "ERR forbidden");
+    assert_eq!(send(&mut sock, "SELECT\t-").await, "OK");
+    assert_eq!(send(&mut sock, "GET\tk").await, "VALUE\tplain");
+
+    assert_eq!(send(&mut sock, "SELECT\ttiny").await, "OK");
+    assert_eq!(send(&mut sock, "PUT\ta\t-\t\t1").await, "OK");
+    assert_eq!(send(&mut sock, "PUT\tb\t-\t\t2").await, "ERR quota_exceeded\tmax_keys");
+    assert_eq!(send(&mut sock, "SELECT\tno way").await, "ERR invalid_namespace\tno way");
+}
+
+#[tokio::test]
+async fn separator_in_names_is_refused() {
+    let (http, tcp) = start_server(TagCacheConfig::default()).await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+    let admin = |req: reqwest::RequestBuilder| req.basic_auth("admin", Some("password"));
+    let put = admin(client.post(url("/put")).header("X-TagCache-Namespace", "team-a")).json(&json!({"key": "secret", "value": "v", "tags": ["t"]}));
+    assert_eq!(put.send().await.unwrap().status(), StatusCode::OK);
+
+    // From the default namespace, `team-a\x1fsecret` must not reach team-a's entry.
+    let sneaky = "team-a\u{1f}secret";
+    let resp = admin(client.post(url("/put"))).json(&json!({"key": sneaky, "value": "stolen", "tags": []})).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(resp.json::<Value>().await.unwrap(), json!({"error": "invalid_name", "name": sneaky}));
+    assert_eq!(admin(client.get(url("/get/team-a%1Fsecret"))).send().await.unwrap().status(), StatusCode::BAD_REQUEST);
+    assert_eq!(admin(client.get(url("/keys-by-tag?tag=team-a%1Ft"))).send().await.unwrap().status(), StatusCode::BAD_REQUEST);
+    let tagged = admin(client.post(url("/put"))).json(&json!({"key": "k", "value": "v", "tags": ["team-a\u{1f}t"]}));
+    assert_eq!(tagged.send().await.unwrap().status(), StatusCode::BAD_REQUEST);
+    let inv = admin(client.post(url("/invalidate/tags"))).json(&json!({"tags": ["team-a\u{1f}t"]}));
+    assert_eq!(inv.send().await.unwrap().status(), StatusCode::BAD_REQUEST);
+    let read: Value = admin(client.get(url("/get/secret")).header("X-TagCache-Namespace", "team-a")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(read["value"], "v");
+
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "GET\tteam-a\u{1f}secret").await, "ERR invalid_name\tteam-a\u{1f}secret");
+    assert_eq!(send(&mut sock, "PUT\tteam-a\u{1f}secret\t-\t-\tstolen").await, "ERR invalid_name\tteam-a\u{1f}secret");
+    assert_eq!(send(&mut sock, "PUT\tk\t-\tok,team-a\u{1f}t\tv").await, "ERR invalid_name\tteam-a\u{1f}t");
+    assert_eq!(send(&mut sock, "INV_TAG\tteam-a\u{1f}t").await, "ERR invalid_name\tteam-a\u{1f}t");
+    assert_eq!(send(&mut sock, "PUT\tk\t-\t-\tvalues may contain \u{1f}").await, "OK");
+    assert_eq!(send(&mut sock, "SELECT\tteam-a").await, "OK");
+    assert_eq!(send(&mut sock, "GET\tsecret").await, "VALUE\tv");
+}
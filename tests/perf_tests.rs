@@ -6,18 +6,23 @@
 use std::time::{Duration, Instant};
 use std::sync::Arc;
 use rand::{Rng, distributions::Alphanumeric};
-// The crate currently exposes types only in main.rs (no lib.rs). To reuse them in integration tests
-// we include the main.rs file as a module. This avoids refactoring now; later you should extract
-// core cache types into src/lib.rs and have main.rs depend on that.
-#[path = "../src/main.rs"]
-mod main_rs;
-use main_rs::{Cache, Key, Tag};
+use tagcache::{Cache, Key, Tag};
 use sysinfo::System;
 use hdrhistogram::Histogram;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
 use std::io::Write;
 
+// Connect to a live server's TCP port and AUTH as the default admin (TCP requires AUTH by default).
+fn connect_tcp(addr: &str) -> std::io::Result<std::net::TcpStream> {
+    use std::io::Read;
+    let mut stream = std::net::TcpStream::connect(addr)?;
+    stream.write_all(b"AUTH\tadmin\tpassword\n")?;
+    let mut byte = [0u8; 1];
+    while byte[0] != b'\n' { stream.read_exact(&mut byte)?; } // Skip the reply
+    Ok(stream)
+}
+
 fn random_string(len: usize) -> String {
     rand::thread_rng().sample_iter(&Alphanumeric).take(len).map(char::from).collect()
 }
@@ -39,7 +44,6 @@ fn population_test() {
     let threshold_mb = target_mb * 0.70;
     let cache = Arc::new(Cache::new(std::env::var("NUM_SHARDS").ok().and_then(|v| v.parse().ok()).unwrap_or(16)));
 
-    let mut last_report = Instant::now();
     let start = Instant::now();
     let mut inserted: u64 = 0;
     loop {
@@ -48,7 +52,7 @@ fn population_test() {
     let tag = Tag::new(format!("t{}", inserted % 10));
         cache.put(key, value, vec![tag], None);
         inserted += 1;
-        if inserted % 10_000 == 0 {
+        if inserted.is_multiple_of(10_000) {
             let mem = approx_process_memory_mb();
             if mem >= threshold_mb { break; }
         }
@@ -140,6 +144,45 @@ fn large_tag_invalidation() {
     println!("removed={} time_ms={:.2} per_key_us={:.2}", removed, dur.as_secs_f64()*1000.0, dur.as_secs_f64()*1_000_000.0 / removed as f64);
 }
 
+/// Eager vs versioned invalidation of one big tag: time to invalidate, read latency right after, and
+/// (versioned) the background sweep. Also times puts / gets with and without stamping.
+/// Run: `BIG_TAG_KEYS=1000000 cargo test --release --test perf_tests -- versioned_vs_eager_invalidation --ignored --nocapture`
+#[test]
+#[ignore]
+fn versioned_vs_eager_invalidation() {
+    use tagcache::tag_version::{InvalidationConfig, InvalidationMode};
+    let big_n = std::env::var("BIG_TAG_KEYS").ok().and_then(|v| v.parse().ok()).unwrap_or(500_000usize);
+    let batch = std::env::var("RECLAIM_BATCH").ok().and_then(|v| v.parse().ok()).unwrap_or(1000usize);
+    for mode in [InvalidationMode::Eager, InvalidationMode::Versioned] {
+        let config = InvalidationConfig { mode, ..InvalidationConfig::default() };
+        let cache = Cache::new(32).with_invalidation(&config);
+        let big_tag = Tag::new("huge");
+        let t0 = Instant::now();
+        for i in 0..big_n { cache.put(Key::new(format!("bk{i}")), random_string(16), vec![big_tag.clone(), Tag::new(format!("t{}", i % 10))], None); }
+        let put_us = t0.elapsed().as_secs_f64() * 1_000_000.0 / big_n as f64;
+        let t0 = Instant::now();
+        for i in 0..big_n { let _ = cache.get(&Key::new(format!("bk{i}"))); }
+        let get_us = t0.elapsed().as_secs_f64() * 1_000_000.0 / big_n as f64;
+
+        let t0 = Instant::now();
+        let count = cache.invalidate_tag(&big_tag);
+        let invalidate_ms = t0.elapsed().as_secs_f64() * 1000.0;
+        let t0 = Instant::now();
+        for i in 0..10_000 { assert!(cache.get(&Key::new(format!("bk{}", i * (big_n / 10_000).max(1)))).is_none()); }
+        let miss_us = t0.elapsed().as_secs_f64() * 1_000_000.0 / 10_000.0;
+
+        let (t0, mut passes, mut worst) = (Instant::now(), 0, Duration::ZERO);
+        while cache.pending_reclaims() > 0 {
+            let pass = Instant::now();
+            cache.reclaim_invalidated(batch);
+            worst = worst.max(pass.elapsed());
+            passes += 1;
+        }
+        println!("mode={} keys={} invalidate_ms={:.2} put_us={:.2} get_us={:.2} miss_after_us={:.2} reclaim_ms={:.2} reclaim_passes={} worst_pass_ms={:.2}",
+            mode.as_str(), count, invalidate_ms, put_us, get_us, miss_us, t0.elapsed().as_secs_f64() * 1000.0, passes, worst.as_secs_f64() * 1000.0);
+    }
+}
+
 /// Continuous mixed read/write/invalidate workload for a target duration (default 180s).
 /// Env overrides:
 ///   DURATION_SECS (u64) - shorten for quick runs
@@ -184,7 +227,7 @@ fn continuous_rw_invalidation() {
     let mut handles = Vec::new();
     for t in 0..threads {
         let cache_cl = cache.clone();
-        let stop = stop_at.clone();
+        let stop = stop_at;
         let og = ops_get.clone();
         let op = ops_put.clone();
         let oi = ops_inv.clone();
@@ -256,7 +299,7 @@ fn large_memory_huge_values_tag_invalidation() {
         if current_mem - baseline >= threshold_mb { break; }
         cache.put(Key::new(format!("big{inserted}")), value.clone(), vec![big_tag.clone()], None);
         inserted += 1;
-        if inserted % 1000 == 0 && inserted > 0 { println!("inserted={} mem_delta_mb={:.1}", inserted, current_mem - baseline); }
+        if inserted.is_multiple_of(1000) && inserted > 0 { println!("inserted={} mem_delta_mb={:.1}", inserted, current_mem - baseline); }
     }
     let load_time = start.elapsed();
     println!("Loaded keys={} total_time_s={:.2} avg_insert_us={:.2}", inserted, load_time.as_secs_f64(), load_time.as_micros() as f64 / inserted as f64);
@@ -297,7 +340,7 @@ fn tcp_workload_live() {
 
     // Helper: open a connection
     let open_conn = || -> Option<(TcpStream, BufReader<TcpStream>)> {
-        match TcpStream::connect(&server_addr) { Ok(s) => { s.set_nodelay(true).ok(); let r = s.try_clone().unwrap(); Some((s, BufReader::new(r))) }, Err(e) => { println!("SKIP: cannot connect to {} ({})", server_addr, e); None } }
+        match connect_tcp(&server_addr) { Ok(s) => { s.set_nodelay(true).ok(); let r = s.try_clone().unwrap(); Some((s, BufReader::new(r))) }, Err(e) => { println!("SKIP: cannot connect to {} ({})", server_addr, e); None } }
     };
     let (mut ctrl_w, mut ctrl_r) = match open_conn() { Some(v) => v, None => return };
 
@@ -334,14 +377,14 @@ fn tcp_workload_live() {
     let mut handles = Vec::new();
     for _ in 0..conns {
         let server = server_addr.clone();
-        let stop_clone = stop_at.clone();
+        let stop_clone = stop_at;
         let og = ops_get.clone(); let opu = ops_put.clone(); let oi = ops_inv.clone(); let ot = ops_total.clone();
         let h = hits.clone(); let m = misses.clone();
         let pd = pipeline_depth;
         handles.push(thread::spawn(move || {
             let mut rng = rand::thread_rng();
             // open connection
-            let mut conn = match TcpStream::connect(&server) { Ok(s) => s, Err(_) => return }; conn.set_nodelay(true).ok();
+            let mut conn = match connect_tcp(&server) { Ok(s) => s, Err(_) => return }; conn.set_nodelay(true).ok();
             let reader_stream = conn.try_clone().unwrap();
             let mut reader = BufReader::new(reader_stream);
             let mut buf = String::new();
@@ -393,7 +436,7 @@ fn tcp_workload_live() {
 
     // Progress logging
     let progress_total = ops_total.clone();
-    let progress_stop = stop_at.clone();
+    let progress_stop = stop_at;
     thread::spawn(move || {
         let mut last = 0u64; let mut last_t = Instant::now();
         while Instant::now() < progress_stop {
@@ -441,7 +484,6 @@ fn tcp_massive_dataset_hotset() {
     use rand::distributions::Alphanumeric;
     use rand::Rng;
     use std::io::{BufRead, Write};
-    use std::net::TcpStream;
     use std::sync::atomic::{AtomicU64, Ordering};
     use std::sync::Arc;
     use std::thread;
@@ -450,8 +492,7 @@ fn tcp_massive_dataset_hotset() {
     let server_addr = std::env::var("SERVER_ADDR").unwrap_or_else(|_| "127.0.0.1:1984".to_string());
     let total_keys: u64 = std::env::var("TOTAL_KEYS").ok().and_then(|v| v.parse().ok()).unwrap_or(2_000_000);
     let value_size: usize = std::env::var("VALUE_SIZE").ok().and_then(|v| v.parse().ok()).unwrap_or(10_000);
-    let mut hot_ratio: f64 = std::env::var("HOT_RATIO").ok().and_then(|v| v.parse().ok()).unwrap_or(0.05);
-    if hot_ratio < 0.0001 { hot_ratio = 0.0001; } else if hot_ratio > 0.99 { hot_ratio = 0.99; }
+    let hot_ratio: f64 = std::env::var("HOT_RATIO").ok().and_then(|v| v.parse().ok()).unwrap_or(0.05f64).clamp(0.0001, 0.99);
     let tag_mod: u64 = std::env::var("TAG_MOD").ok().and_then(|v| v.parse().ok()).unwrap_or(1000);
     let conns: usize = std::env::var("CONNS").ok().and_then(|v| v.parse().ok()).unwrap_or(8).max(1);
     let pipeline_depth: usize = std::env::var("PIPELINE_DEPTH").ok().and_then(|v| v.parse().ok()).unwrap_or(32).max(1);
@@ -496,7 +537,7 @@ fn tcp_massive_dataset_hotset() {
         let td = puts_done.clone();
         let pd = pipeline_depth;
         threads.push(thread::spawn(move || {
-            let mut stream = match TcpStream::connect(&server) { Ok(s) => s, Err(_) => { return; } };
+            let mut stream = match connect_tcp(&server) { Ok(s) => s, Err(_) => { return; } };
             stream.set_nodelay(true).ok();
             let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
             let mut line = String::new();
@@ -549,10 +590,10 @@ fn tcp_massive_dataset_hotset() {
         let repair_mode = repair_mode.clone();
         let og = ops_get.clone(); let opu = ops_put.clone(); let oi = ops_inv.clone(); let ot = ops_total.clone();
         let h = hits.clone(); let m = misses.clone();
-        let stop = stop_at.clone();
+        let stop = stop_at;
         workers.push(thread::spawn(move || {
             let mut rng = rand::thread_rng();
-            let mut conn = match TcpStream::connect(&server) { Ok(s) => s, Err(_) => { return; } };
+            let mut conn = match connect_tcp(&server) { Ok(s) => s, Err(_) => { return; } };
             conn.set_nodelay(true).ok();
             let reader_stream = conn.try_clone().unwrap();
             let mut reader = std::io::BufReader::new(reader_stream);
@@ -0,0 +1,151 @@
+//! Rate limiters: the three algorithms, resetting by tag, and the HTTP / TCP / client commands.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde_json::{json, Value as Json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::ratelimit::{Algorithm, Decision, RateLimit};
+use tagcache::value::ValueError;
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Key, Tag, Value, ValueType};
+use tagcache_client::{Client, Command, Config, Mode, RateLimitAlgorithm};
+
+async fn start_server() -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+// (allowed, remaining, reset_after_ms) of a request at `now` against `state`.
+fn hit(limit: &RateLimit, state: &mut Value, now: u64) -> (bool, u64, u64) {
+    let Decision { allowed, remaining, reset_after, .. } = limit.hit(state, now).unwrap();
+    (allowed, remaining, reset_after.as_millis() as u64)
+}
+
+#[test]
+fn algorithms_count_requests_per_window() {
+    // Fixed windows are aligned to the epoch: 3 per second, whatever the offset inside the second.
+    let fixed = RateLimit::new(Algorithm::FixedWindow, 3, Duration::from_secs(1)).unwrap();
+    let mut state = Value::empty(ValueType::String);
+    assert_eq!(hit(&fixed, &mut state, 10_200), (true, 2, 800));
+    assert_eq!(hit(&fixed, &mut state, 10_500), (true, 1, 500));
+    assert_eq!(hit(&fixed, &mut state, 10_900), (true, 0, 100));
+    assert_eq!(hit(&fixed, &mut state, 10_950), (false, 0, 50));
+    assert_eq!(state, Value::String("3".into()));
+    assert_eq!(fixed.ttl(10_950), Duration::from_millis(50)); // The counter is gone at 11_000
+
+    // The sliding log never allows more than 2 in any second, even across a window edge.
+    let sliding = RateLimit::new(Algorithm::SlidingWindow, 2, Duration::from_secs(1)).unwrap();
+    let mut state = Value::empty(ValueType::List);
+    assert_eq!(hit(&sliding, &mut state, 10_900), (true, 1, 1000));
+    assert_eq!(hit(&sliding, &mut state, 10_950), (true, 0, 1000));
+    assert_eq!(hit(&sliding, &mut state, 11_100), (false, 0, 800));
+    assert_eq!(hit(&sliding, &mut state, 11_900), (true, 0, 1000));
+    assert_eq!(hit(&sliding, &mut state, 11_920), (false, 0, 30));
+
+    // A bucket of 4 refills at 4 per second (one token every 250 ms).
+    let bucket = RateLimit::new(Algorithm::TokenBucket, 4, Duration::from_secs(1)).unwrap();
+    let mut state = Value::empty(ValueType::Hash);
+    for remaining in [3, 2, 1, 0] {
+        assert_eq!(hit(&bucket, &mut state, 5_000).1, remaining);
+    }
+    assert_eq!(hit(&bucket, &mut state, 5_100), (false, 0, 150));
+    assert_eq!(hit(&bucket, &mut state, 5_250), (true, 0, 1000));
+    assert_eq!(hit(&bucket, &mut state, 7_000), (true, 3, 250)); // Refilled to the brim, not beyond
+
+    assert!(RateLimit::new(Algorithm::TokenBucket, 0, Duration::from_secs(1)).is_err());
+    assert!(RateLimit::new(Algorithm::FixedWindow, 1, Duration::ZERO).is_err());
+    assert_eq!(Algorithm::parse("sliding-window"), Some(Algorithm::SlidingWindow));
+    assert_eq!(Algorithm::parse("leaky"), None);
+}
+
+#[test]
+fn limiters_are_tagged_entries() {
+    let cache = Cache::new(2);
+    let limit = RateLimit::new(Algorithm::SlidingWindow, 1, Duration::from_secs(60)).unwrap();
+    let customer = || vec![Tag::new("customer:9")];
+    assert!(cache.rate_limit(Key::new("rl:9:search"), &limit, customer()).unwrap().allowed);
+    assert!(cache.rate_limit(Key::new("rl:9:upload"), &limit, customer()).unwrap().allowed);
+    assert!(!cache.rate_limit(Key::new("rl:9:search"), &limit, vec![]).unwrap().allowed);
+
+    // Resetting the customer's limits lets requests through again.
+    assert_eq!(cache.invalidate_tag(&Tag::new("customer:9")), 2);
+    assert!(cache.rate_limit(Key::new("rl:9:search"), &limit, customer()).unwrap().allowed);
+
+    cache.put(Key::new("plain"), "text".into(), vec![], None);
+    assert_eq!(cache.rate_limit(Key::new("plain"), &limit, vec![]), Err(ValueError::WrongType(ValueType::String)));
+    let fixed = RateLimit::new(Algorithm::FixedWindow, 1, Duration::from_secs(60)).unwrap();
+    assert_eq!(cache.rate_limit(Key::new("plain"), &fixed, vec![]), Err(ValueError::NotInteger));
+    assert_eq!(cache.get(&Key::new("plain")).as_deref(), Some("text"));
+}
+
+#[tokio::test]
+async fn http_tcp_and_client_rate_limits() {
+    let (cache, http, tcp) = start_server().await;
+    let client = reqwest::Client::new();
+    let limit = |key: &str, body: Json| {
+        let req = client.post(format!("http://{http}/keys/{key}/ratelimit")).basic_auth("admin", Some("password")).json(&body);
+        async move {
+            let resp = req.send().await.unwrap();
+            (resp.status(), resp.json::<Json>().await.unwrap())
+        }
+    };
+    let body = json!({"algorithm":"fixed_window","limit":2,"window_ms":60_000,"tags":["customer:1"]});
+    let (status, first) = limit("api:1", body.clone()).await;
+    assert_eq!((status, first["allowed"].clone(), first["remaining"].clone(), first["limit"].clone()), (StatusCode::OK, json!(true), json!(1), json!(2)));
+    assert!(first["reset_after_ms"].as_u64().unwrap() <= 60_000);
+    limit("api:1", body.clone()).await;
+    assert_eq!(limit("api:1", body.clone()).await.1["allowed"], false);
+    let (status, err) = limit("api:1", json!({"algorithm":"leaky","limit":1,"window_ms":1000})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(err["error"].as_str().unwrap().contains("leaky"));
+
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "RATELIMIT\tapi:1\tfixed_window\t2\t60000").await.split('\t').next(), Some("DENIED"));
+    assert_eq!(send(&mut sock, "RATELIMIT\tapi:2\ttoken_bucket\t10\t1000\tcustomer:1").await, "ALLOWED\t9\t100");
+    assert_eq!(send(&mut sock, "KEYS_BY_TAG\tcustomer:1").await.len(), "KEYS\tapi:1,api:2".len());
+    assert_eq!(send(&mut sock, "INV_TAG\tcustomer:1").await, "INV_TAG\t2");
+    assert!(send(&mut sock, "RATELIMIT\tapi:1\tfixed_window\t2\t60000").await.starts_with("ALLOWED\t1\t"));
+    assert!(send(&mut sock, "RATELIMIT\tapi:1\tleaky\t2\t60000").await.starts_with("ERR invalid_algorithm\t"));
+    assert!(send(&mut sock, "RATELIMIT\tapi:1\tfixed_window\t0\t60000").await.starts_with("ERR invalid_limit\t"));
+    assert_eq!(send(&mut sock, "RATELIMIT\tapi:1\tsliding_window\t2\t60000").await, "ERR wrong_type\tstring");
+
+    let cmd = Command::RateLimit { key: "k".into(), algorithm: RateLimitAlgorithm::SlidingWindow, limit: 5, window: Duration::from_secs(1), tags: vec!["t".into()] };
+    let line = cmd.to_tcp_line().unwrap();
+    assert_eq!(line, "RATELIMIT\tk\tsliding_window\t5\t1000\tt");
+    assert_eq!(Command::from_tcp_line(&line).unwrap(), cmd);
+    for mode in [Mode::Tcp, Mode::Http] {
+        let config = Config { mode, http_url: format!("http://{http}"), tcp_addr: tcp.to_string(), ..Config::default() }.with_basic_auth("admin", "password");
+        let client = Client::new(config).unwrap();
+        let key = format!("{mode:?}:login");
+        let check = || client.rate_limit(&key, RateLimitAlgorithm::SlidingWindow, 2, Duration::from_secs(60), &["logins"]);
+        assert_eq!(check().await.unwrap().remaining, 1);
+        assert!(check().await.unwrap().allowed);
+        let denied = check().await.unwrap();
+        assert!(!denied.allowed && denied.reset_after <= Duration::from_secs(60));
+    }
+    assert_eq!(cache.invalidate_tag(&Tag::new("logins")), 2);
+}
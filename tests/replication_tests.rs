@@ -0,0 +1,133 @@
+//! Leader/follower replication over localhost TCP: initial snapshot, streamed mutations,
+//! read-only followers and promotion.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpListener;
+
+use tagcache::replication::{self, Replication, ReplicationConfig, Role};
+use tagcache::{Cache, Key, Tag, TagMatch};
+
+// Minimal leader: accept connections and hand each REPLICATE'd one to the replication stream.
+async fn start_leader(cache: Arc<Cache>) -> (String, Arc<Replication>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let repl = Replication::new(&ReplicationConfig { heartbeat_ms: 50, ..ReplicationConfig::default() });
+    let leader_repl = repl.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let (cache, repl) = (cache.clone(), leader_repl.clone());
+            tokio::spawn(async move {
+                let (r, mut w) = sock.split();
+                let mut reader = BufReader::new(r);
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                assert_eq!(line.trim(), "REPLICATE");
+                replication::serve_replica(&cache, &repl, &mut reader, &mut w).await;
+            });
+        }
+    });
+    (addr, repl)
+}
+
+fn start_follower(leader: String) -> (Arc<Cache>, Arc<Replication>) {
+    let cache = Arc::new(Cache::new(4));
+    let repl = Replication::new(&ReplicationConfig {
+        role: Role::Follower,
+        leader: Some(leader),
+        reconnect_delay_ms: 20,
+        ..ReplicationConfig::default()
+    });
+    tokio::spawn(replication::run_follower(cache.clone(), repl.clone()));
+    (cache, repl)
+}
+
+async fn eventually<F: Fn() -> bool>(what: &str, check: F) {
+    for _ in 0..200 {
+        if check() { return; }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
+
+#[tokio::test]
+async fn follower_gets_snapshot_then_streamed_mutations() {
+    let leader = Arc::new(Cache::new(4));
+    leader.put(Key::new("a"), "line1\nline2\twith tab".into(), vec![Tag::new("t1")], None);
+    leader.put(Key::new("b"), "2".into(), vec![Tag::new("t1"), Tag::new("t2")], Some(Duration::from_secs(60)));
+    let (addr, leader_repl) = start_leader(leader.clone()).await;
+    let (follower, repl) = start_follower(addr);
+
+    eventually("snapshot", || follower.get(&Key::new("b")).is_some()).await;
+    assert_eq!(follower.get(&Key::new("a")).as_deref(), Some("line1\nline2\twith tab"));
+    assert_eq!(leader_repl.status().followers, 1);
+
+    leader.put(Key::new("c"), "3".into(), vec![Tag::new("t2")], None);
+    leader.increment(Key::new("n"), 5, vec![], None).unwrap();
+    leader.invalidate_key(&Key::new("a"));
+    eventually("streamed writes", || follower.get(&Key::new("n")).as_deref() == Some("5") && follower.get(&Key::new("a")).is_none()).await;
+    assert_eq!(follower.get(&Key::new("c")).as_deref(), Some("3"));
+
+    assert_eq!(leader.invalidate_tags(&[Tag::new("t1"), Tag::new("t2")], TagMatch::All), 1);
+    eventually("all-mode invalidation", || follower.get(&Key::new("b")).is_none()).await;
+    assert_eq!(follower.get(&Key::new("c")).as_deref(), Some("3")); // only had t2
+
+    leader.invalidate_tag(&Tag::new("t2"));
+    eventually("tag invalidation", || follower.get(&Key::new("c")).is_none()).await;
+
+    let status = repl.status();
+    assert!(status.connected && status.read_only);
+    assert_eq!(status.full_syncs, 1);
+    assert!(status.lag_ms.is_some());
+}
+
+#[tokio::test]
+async fn follower_reconnects_and_resyncs() {
+    // Point the follower at a port nobody listens on yet, then start the leader there.
+    let probe = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = probe.local_addr().unwrap().to_string();
+    drop(probe);
+    let (follower, repl) = start_follower(addr.clone());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!repl.status().connected);
+
+    let leader = Arc::new(Cache::new(2));
+    leader.put(Key::new("k"), "v".into(), vec![], None);
+    let listener = TcpListener::bind(&addr).await.unwrap();
+    let leader_repl = Replication::new(&ReplicationConfig::default());
+    tokio::spawn(async move {
+        let (mut sock, _) = listener.accept().await.unwrap();
+        let (r, mut w) = sock.split();
+        let mut reader = BufReader::new(r);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        replication::serve_replica(&leader, &leader_repl, &mut reader, &mut w).await;
+    });
+    eventually("resync after reconnect", || follower.get(&Key::new("k")).as_deref() == Some("v")).await;
+}
+
+#[tokio::test]
+async fn promote_stops_replication_and_allows_writes() {
+    let leader = Arc::new(Cache::new(2));
+    let (addr, _) = start_leader(leader.clone()).await;
+    let (follower, repl) = start_follower(addr);
+    leader.put(Key::new("before"), "1".into(), vec![], None);
+    eventually("replicated write", || follower.get(&Key::new("before")).is_some()).await;
+    assert!(repl.rejects_writes());
+
+    assert_eq!(repl.promote(), Role::Follower);
+    assert_eq!(repl.role(), Role::Leader);
+    assert!(!repl.rejects_writes());
+    assert_eq!(repl.promote(), Role::Leader); // idempotent
+
+    leader.put(Key::new("after"), "2".into(), vec![], None);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(follower.get(&Key::new("after")).is_none());
+    assert_eq!(follower.get(&Key::new("before")).as_deref(), Some("1"));
+
+    let writable = Replication::new(&ReplicationConfig { role: Role::Follower, allow_writes: true, ..ReplicationConfig::default() });
+    assert!(!writable.rejects_writes());
+}
@@ -0,0 +1,189 @@
+//! Scheduled invalidations: tag, key and query targets, persistence across restarts, and the HTTP API
+//! with its events and audit records.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value as Json};
+use tokio::net::TcpListener;
+
+use tagcache::audit::{AuditLog, AuditQuery};
+use tagcache::config::TagCacheConfig;
+use tagcache::events::EventKind;
+use tagcache::namespace::Namespace;
+use tagcache::schedule::{Schedule, Scheduler, SchedulesConfig, Target};
+use tagcache::{build_app, AppState, AuthState, Cache, Credentials, Key, Tag};
+
+const LATER: u64 = 4_000_000_000_000; // Far enough ahead that the runner leaves it alone
+
+fn schedule(id: &str, target: Target, namespace: Option<&str>) -> Schedule {
+    Schedule { id: id.into(), at_ms: LATER, target, namespace: namespace.map(str::to_string), created_by: Some("admin".into()), created_ms: 0 }
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+fn fill(cache: &Cache) {
+    let tags = |names: &[&str]| names.iter().map(|t| Tag::new(*t)).collect::<Vec<_>>();
+    cache.put(Key::new("banner:1"), "v".into(), tags(&["campaign:summer"]), None);
+    cache.put(Key::new("banner:2"), "v".into(), tags(&["campaign:summer", "evergreen"]), None);
+    cache.put(Key::new("promo:1"), "v".into(), tags(&["summer"]), None);
+    cache.put(Key::new("promo:2"), "v".into(), tags(&["summer", "evergreen"]), None);
+    cache.put(Key::new("page:1"), "v".into(), vec![], None);
+    let ns = Namespace::named("shop").unwrap();
+    cache.put(ns.key("banner:1"), "v".into(), vec![ns.tag("campaign:summer")], None);
+}
+
+fn alive(cache: &Cache, keys: &[&str]) -> Vec<String> {
+    keys.iter().filter(|k| cache.get(&Key::new(**k)).is_some()).map(|k| k.to_string()).collect()
+}
+
+#[tokio::test]
+async fn targets_run_once_when_due() {
+    let cache = Arc::new(Cache::new(4));
+    fill(&cache);
+    let scheduler = Arc::new(Scheduler::memory(cache.clone(), Arc::new(AuditLog::memory(100))));
+    scheduler.add(schedule("tags", Target::Tags { tags: strings(&["campaign:summer"]), mode: "any".into() }, None), None);
+    scheduler.add(schedule("query", Target::Query { prefix: Some("promo:".into()), tags: Some("summer&!evergreen".into()) }, None), None);
+    scheduler.add(schedule("keys", Target::Keys { keys: strings(&["page:1", "missing"]) }, None), None);
+    assert_eq!(scheduler.list(&Namespace::default()).len(), 3);
+    assert!(scheduler.list(&Namespace::named("shop").unwrap()).is_empty());
+
+    assert_eq!(scheduler.run_due(LATER - 1), 0);
+    let all = ["banner:1", "banner:2", "promo:1", "promo:2", "page:1"];
+    assert_eq!(alive(&cache, &all).len(), 5);
+    assert_eq!(scheduler.run_due(LATER), 3);
+    assert_eq!(alive(&cache, &all), vec!["promo:2"]);
+    assert!(scheduler.list(&Namespace::default()).is_empty());
+    assert_eq!(scheduler.run_due(LATER), 0); // Each schedule runs once
+
+    // Namespaced schedules only reach their own keys; cancelled ones never run.
+    fill(&cache);
+    scheduler.add(schedule("shop", Target::Tags { tags: strings(&["campaign:summer"]), mode: "any".into() }, Some("shop")), None);
+    scheduler.add(schedule("gone", Target::Keys { keys: strings(&["page:1"]) }, None), None);
+    assert!(scheduler.cancel("gone", Some("admin"), None).is_some());
+    assert!(scheduler.cancel("gone", Some("admin"), None).is_none());
+    assert_eq!(scheduler.run_due(LATER), 1);
+    assert_eq!(cache.get(&Namespace::named("shop").unwrap().key("banner:1")), None);
+    assert_eq!(alive(&cache, &all).len(), 5);
+
+    // Bad targets are rejected before they are stored.
+    assert!(Target::Tags { tags: vec![], mode: "any".into() }.validate().is_err());
+    assert!(Target::Tags { tags: strings(&["a"]), mode: "some".into() }.validate().is_err());
+    assert!(Target::Query { prefix: None, tags: None }.validate().is_err());
+    assert!(Target::Query { prefix: None, tags: Some("a&".into()) }.validate().is_err());
+}
+
+#[tokio::test]
+async fn pending_schedules_survive_a_restart() {
+    let path = std::env::temp_dir().join(format!("tagcache-schedules-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let config = SchedulesConfig { file: Some(path.to_string_lossy().into_owned()) };
+    let cache = Arc::new(Cache::new(4));
+    fill(&cache);
+    let audit = Arc::new(AuditLog::memory(100));
+
+    let first = Arc::new(Scheduler::open(&config, cache.clone(), audit.clone()).unwrap());
+    first.add(schedule("a", Target::Tags { tags: strings(&["campaign:summer"]), mode: "any".into() }, None), None);
+    first.add(schedule("b", Target::Keys { keys: strings(&["page:1"]) }, None), None);
+    first.cancel("b", None, None);
+    drop(first);
+
+    let second = Arc::new(Scheduler::open(&config, cache.clone(), audit).unwrap());
+    let restored = second.list(&Namespace::default());
+    assert_eq!(restored, vec![schedule("a", Target::Tags { tags: strings(&["campaign:summer"]), mode: "any".into() }, None)]);
+    assert_eq!(second.run_due(LATER), 1);
+    assert_eq!(alive(&cache, &["banner:1", "banner:2", "page:1"]), vec!["page:1"]);
+
+    let third = Scheduler::open(&config, cache, Arc::new(AuditLog::memory(10))).unwrap();
+    assert!(third.list(&Namespace::default()).is_empty()); // Ran schedules are dropped from the file
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_saves_and_unreadable_files() {
+    let path = std::env::temp_dir().join(format!("tagcache-schedules-race-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let config = SchedulesConfig { file: Some(path.to_string_lossy().into_owned()) };
+    let cache = Arc::new(Cache::new(4));
+    let scheduler = Arc::new(Scheduler::open(&config, cache.clone(), Arc::new(AuditLog::memory(10))).unwrap());
+
+    // Saves from many writers at once: the file ends up with the last state, not an older snapshot.
+    let writers: Vec<_> = (0..8).map(|w| {
+        let scheduler = scheduler.clone();
+        tokio::task::spawn_blocking(move || {
+            for i in 0..25 {
+                scheduler.add(schedule(&format!("{w}-{i}"), Target::Keys { keys: strings(&["page:1"]) }, None), None);
+            }
+        })
+    }).collect();
+    for writer in writers { writer.await.unwrap(); }
+    let reopened = Scheduler::open(&config, cache.clone(), Arc::new(AuditLog::memory(10))).unwrap();
+    assert_eq!(reopened.list(&Namespace::default()).len(), 200);
+
+    // A file that does not parse is an error (and left alone), at startup too.
+    std::fs::write(&path, "{not json").unwrap();
+    assert!(Scheduler::open(&config, cache, Arc::new(AuditLog::memory(10))).is_err());
+    let server = TagCacheConfig { schedules: config, ..TagCacheConfig::default() };
+    let conf_path = std::env::temp_dir().join(format!("tagcache-schedules-race-{}.conf", std::process::id()));
+    let started = tokio::time::timeout(Duration::from_secs(10), tagcache::server::serve(server, conf_path)).await;
+    let err = started.expect("server started with an unreadable schedules file").unwrap_err();
+    assert!(err.to_string().contains("could not load the schedules"), "{err}");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{not json");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn http_api_events_and_audit() {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    fill(&cache);
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let mut events = cache.events.subscribe();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{addr}{path}");
+    let create = |body: Json| client.post(url("/schedules")).basic_auth("admin", Some("password")).json(&body).send();
+
+    assert_eq!(create(json!({"target": "tags", "tags": ["x"]})).await.unwrap().status(), 400); // No time
+    assert_eq!(create(json!({"target": "keys", "keys": [], "in_ms": 10})).await.unwrap().status(), 400);
+    let later: Json = create(json!({"target": "keys", "keys": ["page:1"], "at_ms": LATER, "id": "page-drop"})).await.unwrap().json().await.unwrap();
+    assert_eq!(later["schedule"]["id"], "page-drop");
+    assert_eq!(later["schedule"]["created_by"], "admin");
+    let soon: Json = create(json!({"target": "tags", "tags": ["campaign:summer"], "in_ms": 50})).await.unwrap().json().await.unwrap();
+    let soon_id = soon["schedule"]["id"].as_str().unwrap().to_string();
+
+    let list: Json = client.get(url("/schedules")).basic_auth("admin", Some("password")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(list["count"], 2);
+    assert_eq!(list["schedules"][0]["id"], soon_id.as_str()); // Soonest first
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(alive(&cache, &["banner:1", "banner:2", "page:1"]), vec!["page:1"]);
+    let one: Json = client.get(url(&format!("/schedules/{soon_id}"))).basic_auth("admin", Some("password")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(one, json!({"error": "not_found"}));
+
+    let cancel: Json = client.delete(url("/schedules/page-drop")).basic_auth("admin", Some("password")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(cancel["ok"], true);
+    assert!(state.schedules.list(&Namespace::default()).is_empty());
+
+    let mut actions = Vec::new();
+    while let Ok(ev) = events.try_recv() {
+        if ev.kind == EventKind::Schedule { actions.push((ev.schedule.unwrap(), ev.action.unwrap(), ev.count)); }
+    }
+    assert_eq!(actions, vec![
+        ("page-drop".to_string(), "created", None),
+        (soon_id.clone(), "created", None),
+        (soon_id.clone(), "ran", Some(2)),
+        ("page-drop".to_string(), "cancelled", None),
+    ]);
+
+    let records = state.audit.recent(&AuditQuery { event: Some("schedule_run".into()), ..Default::default() }).unwrap();
+    assert_eq!(records.len(), 1);
+    assert!(records[0].detail.as_deref().unwrap().contains("2 keys removed"));
+    assert_eq!(state.audit.recent(&AuditQuery { event: Some("schedule".into()), ..Default::default() }).unwrap().len(), 3);
+}
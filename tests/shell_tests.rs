@@ -0,0 +1,87 @@
+//! `tagcache shell`: word splitting (quotes, multi-line JSON) and scripted sessions against an
+//! in-process server, including the `/auth/login` token session.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::net::TcpListener;
+
+use tagcache::shell::{run_script, split_words, Parsed};
+use tagcache::{build_app, AppState, AuthState, Cache, Credentials};
+use tagcache_client::{Config, Mode};
+
+async fn start_server() -> (SocketAddr, Arc<AppState>) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let state = Arc::new(AppState::new(Arc::new(Cache::new(4)), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    (addr, state)
+}
+
+async fn script(addr: SocketAddr, user: &str, pass: &str, input: &'static str) -> anyhow::Result<String> {
+    let config = Config { mode: Mode::Http, http_url: format!("http://{addr}"), ..Config::default() }.with_basic_auth(user, pass);
+    tokio::task::spawn_blocking(move || {
+        let mut out = Vec::new();
+        run_script(config, input.as_bytes(), &mut out).map(|_| String::from_utf8(out).unwrap())
+    })
+    .await
+    .unwrap()
+}
+
+#[test]
+fn split_words_handles_quotes_and_json() {
+    let words = |s: &str| match split_words(s).unwrap() { Parsed::Words(w) => w, Parsed::NeedMore => panic!("incomplete: {s}") };
+    assert_eq!(words("put  k 'a b' --tags x,y"), vec!["put", "k", "a b", "--tags", "x,y"]);
+    assert_eq!(words(r#"put k "say \"hi\"\n""#), vec!["put", "k", "say \"hi\"\n"]);
+    assert_eq!(words("put k {\"a\": [1, {\"b\": \"}\"}]}"), vec!["put", "k", r#"{"a":[1,{"b":"}"}]}"#]);
+    assert_eq!(words("put k [1,\n 2]"), vec!["put", "k", "[1,2]"]);
+    assert_eq!(split_words("put k {\"a\": 1,").unwrap(), Parsed::NeedMore);
+    assert_eq!(split_words("put k \"open").unwrap(), Parsed::NeedMore);
+    assert!(split_words("put k {\"a\" 1}").is_err());
+}
+
+#[tokio::test]
+async fn scripted_session_runs_commands() {
+    let (addr, state) = start_server().await;
+    let out = script(addr, "admin", "password", concat!(
+        "# comment\n",
+        "put user:1 {\"name\": \"alice\",\n",
+        "  \"age\": 30} --tags users,team:a --ttl 60000\n",
+        "put user:2 plain --tags users\n",
+        "get user:1\n",
+        "info user:1\n",
+        "keys users\n",
+        "search user --all users,team:a\n",
+        "incr n 5\n",
+        "bogus\n",
+        "inv-tags users\n",
+        "get user:2\n",
+        "exit\n",
+        "put never x\n",
+    )).await.unwrap();
+    let expected = [
+        "OK", "OK",
+        "{", "  \"age\": 30,", "  \"name\": \"alice\"", "}",
+        "key:     user:1", "tags:    users,team:a",
+    ];
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(&lines[..expected.len()], &expected, "{out}");
+    assert!(lines.iter().any(|l| l.starts_with("ttl:     ") && l.ends_with("ms remaining")), "{out}");
+    assert!(out.contains("user:1\nuser:2\n(2 keys)"), "{out}");
+    assert!(out.contains("user:1") && out.contains("tags=users,team:a\n(1 keys)"), "{out}");
+    assert!(out.contains("\n5\n"), "{out}");
+    assert!(out.contains("ERR unknown command 'bogus'"), "{out}");
+    assert!(out.contains("2 invalidated\n(not found)\n"), "{out}");
+    assert!(state.cache.get(&tagcache::Key::new("never")).is_none());
+}
+
+#[tokio::test]
+async fn login_uses_auth_endpoint() {
+    let (addr, _state) = start_server().await;
+    assert_eq!(script(addr, "admin", "password", "put a 1\nget a\nlogin admin password\nget a\n").await.unwrap(), "OK\n1\nLogged in as admin\n1\n");
+    let err = script(addr, "admin", "wrong", "get a\n").await.unwrap_err();
+    assert!(err.to_string().contains("invalid credentials"), "{err}");
+}
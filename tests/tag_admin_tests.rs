@@ -0,0 +1,139 @@
+//! Tag administration: per-tag counts and sizes, rename / merge, bulk tagging by query, over the
+//! cache, HTTP and TCP.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value as Json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::transfer::TransferFilter;
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Entry, Key, Tag};
+
+async fn start_server() -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+fn tags(names: &[&str]) -> Vec<Tag> {
+    names.iter().map(|t| Tag::new(*t)).collect()
+}
+
+fn entry(cache: &Cache, key: &str) -> Entry {
+    cache.shards.iter().find_map(|s| s.entries.get(&Key::new(key)).map(|e| e.clone())).unwrap()
+}
+
+fn tags_of(cache: &Cache, key: &str) -> Vec<String> {
+    let mut tags: Vec<String> = entry(cache, key).tags.iter().map(|t| t.as_str().to_string()).collect();
+    tags.sort();
+    tags
+}
+
+#[test]
+fn stats_rename_and_merge() {
+    let cache = Cache::new(4);
+    cache.put(Key::new("a"), "xxxx".into(), tags(&["users", "eu"]), None);
+    cache.put(Key::new("b"), "xx".into(), tags(&["usres"]), Some(Duration::from_secs(60)));
+    cache.put(Key::new("c"), "x".into(), tags(&["user", "users"]), None);
+
+    let stats = cache.tag_stats();
+    assert_eq!(stats.iter().map(|s| (s.tag.as_str(), s.keys)).collect::<Vec<_>>(), vec![("eu", 1), ("user", 1), ("users", 2), ("usres", 1)]);
+    let users = cache.tag_info(&Tag::new("users")).unwrap();
+    assert!(users.bytes > cache.tag_info(&Tag::new("eu")).unwrap().bytes);
+    assert!(cache.tag_info(&Tag::new("nobody")).is_none());
+
+    // Value, TTL and creation time survive a retag.
+    let before = entry(&cache, "b");
+    assert_eq!(cache.merge_tags(&tags(&["user", "usres"]), &Tag::new("users")), 2);
+    let after = entry(&cache, "b");
+    assert_eq!((after.ttl, after.created_at, after.created_system), (before.ttl, before.created_at, before.created_system));
+    assert_eq!(cache.get(&Key::new("b")).as_deref(), Some("xx"));
+    assert_eq!(tags_of(&cache, "c"), vec!["users"]);
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("users")).len(), 3);
+    assert!(cache.get_keys_by_tag(&Tag::new("usres")).is_empty());
+
+    assert_eq!(cache.rename_tag(&Tag::new("eu"), &Tag::new("region:eu")), 1);
+    assert_eq!(tags_of(&cache, "a"), vec!["region:eu", "users"]);
+    assert_eq!(cache.rename_tag(&Tag::new("eu"), &Tag::new("region:eu")), 0);
+}
+
+#[test]
+fn retag_by_query() {
+    let cache = Cache::new(4);
+    cache.put(Key::new("user:1"), "x".into(), tags(&["tenant:1"]), None);
+    cache.put(Key::new("user:2"), "x".into(), tags(&["tenant:1", "banned"]), None);
+    cache.put(Key::new("user:3"), "x".into(), tags(&["tenant:2"]), None);
+    cache.put(Key::new("order:1"), "x".into(), tags(&["tenant:1"]), None);
+
+    let filter = TransferFilter::parse(Some("user:".into()), Some("tenant:1&!banned")).unwrap();
+    assert_eq!(cache.retag_matching(&filter, &tags(&["active"]), &[]), 1);
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("active")), vec![Key::new("user:1")]);
+    // Already tagged: nothing changes.
+    assert_eq!(cache.retag_matching(&filter, &tags(&["active"]), &[]), 0);
+
+    let tenant1 = TransferFilter::parse(None, Some("tenant:1")).unwrap();
+    assert_eq!(cache.retag_matching(&tenant1, &[], &tags(&["tenant:1"])), 3);
+    assert!(cache.get_keys_by_tag(&Tag::new("tenant:1")).is_empty());
+    assert_eq!(tags_of(&cache, "user:2"), vec!["banned"]);
+    assert_eq!(cache.get(&Key::new("order:1")).as_deref(), Some("x"));
+}
+
+#[tokio::test]
+async fn http_and_tcp_tag_admin() {
+    let (cache, http, tcp) = start_server().await;
+    cache.put(Key::new("a"), "x".repeat(100), tags(&["big", "all"]), None);
+    cache.put(Key::new("b"), "x".into(), tags(&["small", "all"]), None);
+    cache.put(Key::new("c"), "x".into(), tags(&["small"]), None);
+    let client = reqwest::Client::new();
+    let get = |path: &str| client.get(format!("http://{http}{path}")).basic_auth("admin", Some("password")).send();
+
+    let listed: Json = get("/tags?sort=keys").await.unwrap().json().await.unwrap();
+    let order: Vec<&str> = listed["tags"].as_array().unwrap().iter().map(|t| t["tag"].as_str().unwrap()).collect();
+    assert_eq!(order, vec!["all", "small", "big"]);
+    assert_eq!(listed["total"], 3);
+    let listed: Json = get("/tags?sort=bytes&limit=1").await.unwrap().json().await.unwrap();
+    assert_eq!(listed["tags"][0]["tag"], "all");
+    assert_eq!(get("/tags?sort=size").await.unwrap().status(), 400);
+    let info: Json = get("/tags/small").await.unwrap().json().await.unwrap();
+    assert_eq!(info["keys"], 2);
+
+    let renamed: Json = client.post(format!("http://{http}/tags/small/rename")).basic_auth("admin", Some("password"))
+        .json(&json!({"to":"tiny"})).send().await.unwrap().json().await.unwrap();
+    assert_eq!(renamed["count"], 2);
+    let added: Json = client.post(format!("http://{http}/tags/checked/add")).basic_auth("admin", Some("password"))
+        .json(&json!({"tags":"tiny&!all"})).send().await.unwrap().json().await.unwrap();
+    assert_eq!(added["count"], 1);
+    assert_eq!(tags_of(&cache, "c"), vec!["checked", "tiny"]);
+
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "TAG_INFO\ttiny").await, "TAG\t2\t4");
+    assert_eq!(send(&mut sock, "TAG_INFO\tsmall").await, "NF");
+    assert_eq!(send(&mut sock, "TAG_MERGE\tall\ttiny,checked").await, "TAG_MERGE\t2");
+    assert_eq!(send(&mut sock, "TAGS\tkeys\t2").await, "TAGS\tall\t3\t105\tbig\t1\t101");
+    assert_eq!(send(&mut sock, "RETAG\tremove\tall\t-\tbig").await, "RETAG\t1");
+    assert_eq!(send(&mut sock, "RETAG\tremove\tall").await, "ERR missing_filter");
+    assert_eq!(send(&mut sock, "TAG_RENAME\tbig\thuge").await, "TAG_RENAME\t1");
+    assert_eq!(tags_of(&cache, "a"), vec!["huge"]);
+    assert_eq!(cache.get(&Key::new("a")).map(|v| v.len()), Some(100));
+}
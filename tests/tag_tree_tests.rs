@@ -0,0 +1,128 @@
+//! Hierarchical tags: subtree lookups and invalidation, child listings with counts, a custom
+//! separator, over the cache, HTTP, TCP and the client.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value as Json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Key, Tag, TagMatch};
+use tagcache_client::{Client, Command, Config, Mode, TagMode};
+
+async fn start_server() -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+fn tags(names: &[&str]) -> Vec<Tag> {
+    names.iter().map(|t| Tag::new(*t)).collect()
+}
+
+#[test]
+fn subtrees_and_children() {
+    let cache = Cache::new(4);
+    cache.put(Key::new("p1"), "x".into(), tags(&["tenant:42:product:1", "catalog"]), None);
+    cache.put(Key::new("p2"), "x".into(), tags(&["tenant:42:product:2"]), None);
+    cache.put(Key::new("both"), "x".into(), tags(&["tenant:42:product:1", "tenant:42:product:2"]), None);
+    cache.put(Key::new("o1"), "x".into(), tags(&["tenant:42:order:9"]), None);
+    cache.put(Key::new("t42"), "x".into(), tags(&["tenant:42"]), None);
+    cache.put(Key::new("t420"), "x".into(), tags(&["tenant:420:product:1"]), None);
+
+    assert_eq!(cache.tag_subtree(&Tag::new("tenant:42:product")), tags(&["tenant:42:product:1", "tenant:42:product:2"]));
+    assert_eq!(cache.tag_subtree(&Tag::new("tenant:42:product:1")), tags(&["tenant:42:product:1"]));
+    let children = |parent: &str| cache.tag_children(&Tag::new(parent)).into_iter().map(|(t, n)| (t.as_str().to_string(), n)).collect::<Vec<_>>();
+    assert_eq!(children("tenant:42"), vec![("tenant:42:order".into(), 1), ("tenant:42:product".into(), 3)]);
+    assert_eq!(children("tenant"), vec![("tenant:42".into(), 5), ("tenant:420".into(), 1)]);
+    assert_eq!(children(""), vec![("catalog".into(), 1), ("tenant".into(), 6)]);
+
+    // The subtree of tenant:42 is the tag itself and everything below it, but not tenant:420.
+    assert_eq!(cache.invalidate_tags(&tags(&["tenant:42", "tenant:42:order"]), TagMatch::Subtree), 5);
+    assert_eq!(cache.get(&Key::new("t420")).as_deref(), Some("x"));
+    assert_eq!(children(""), vec![("tenant".into(), 1)]);
+    assert!(cache.get_keys_by_tag(&Tag::new("catalog")).is_empty());
+
+    // Another separator splits other levels.
+    let slashes = Cache::new(2).with_tag_separator("/");
+    slashes.put(Key::new("a"), "x".into(), tags(&["site/eu/page:1"]), None);
+    slashes.put(Key::new("b"), "x".into(), tags(&["site/us"]), None);
+    assert_eq!(slashes.tag_children(&Tag::new("site")).len(), 2);
+    assert_eq!(slashes.invalidate_tags(&tags(&["site/eu"]), TagMatch::Subtree), 1);
+}
+
+#[test]
+fn index_follows_removals() {
+    let cache = Cache::new(2);
+    let tree_len = |cache: &Cache| cache.shards.iter().map(|s| s.tag_tree.lock().len()).sum::<usize>();
+    cache.put(Key::new("a"), "x".into(), tags(&["org:1:team:1", "org:1:team:2"]), None);
+    cache.put(Key::new("b"), "x".into(), tags(&["org:1:team:2"]), Some(Duration::from_millis(20)));
+    cache.put(Key::new("a"), "x".into(), tags(&["org:1:team:3"]), None); // Retagged: teams 1 and 2 lose it
+    assert_eq!(cache.tag_subtree(&Tag::new("org:1")), tags(&["org:1:team:2", "org:1:team:3"]));
+
+    std::thread::sleep(Duration::from_millis(40));
+    assert_eq!(cache.expire_due(100), 1);
+    assert_eq!(cache.tag_subtree(&Tag::new("org")), tags(&["org:1:team:3"]));
+    assert!(cache.invalidate_key(&Key::new("a")));
+    assert!(cache.tag_subtree(&Tag::new("org")).is_empty());
+    assert_eq!(tree_len(&cache), 0);
+
+    // A plain invalidation also drops the other tags of the keys it removes.
+    cache.put(Key::new("c"), "x".into(), tags(&["org:2", "org:2:team:1"]), None);
+    assert_eq!(cache.invalidate_tag(&Tag::new("org:2")), 1);
+    assert!(cache.tag_children(&Tag::new("org")).is_empty());
+    assert_eq!(tree_len(&cache), 0);
+}
+
+#[tokio::test]
+async fn http_tcp_and_client_subtrees() {
+    let (cache, http, tcp) = start_server().await;
+    for (key, tag) in [("a", "tenant:7:product:1"), ("b", "tenant:7:product:2"), ("c", "tenant:7:user:1"), ("d", "tenant:70")] {
+        cache.put(Key::new(key), "x".into(), tags(&[tag]), None);
+    }
+    let client = reqwest::Client::new();
+    let children: Json = client.get(format!("http://{http}/tags/children?tag=tenant:7")).basic_auth("admin", Some("password"))
+        .send().await.unwrap().json().await.unwrap();
+    assert_eq!(children["children"], json!([{"tag":"tenant:7:product","count":2},{"tag":"tenant:7:user","count":1}]));
+    assert_eq!(children["separator"], ":");
+    let body: Json = client.post(format!("http://{http}/invalidate/tags")).basic_auth("admin", Some("password"))
+        .json(&json!({"tags":["tenant:7:product"],"mode":"subtree"})).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["count"], 2);
+
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "INV_TAGS_TREE\ttenant:7").await, "INV_TAGS_TREE\t1");
+    assert_eq!(send(&mut sock, "INV_TAGS_TREE").await, "ERR missing_tags");
+    assert_eq!(cache.get(&Key::new("d")).as_deref(), Some("x"));
+
+    let cmd = Command::InvalidateTags { tags: vec!["tenant:70".into()], mode: TagMode::Subtree };
+    assert_eq!(cmd.to_tcp_line().unwrap(), "INV_TAGS_TREE\ttenant:70");
+    assert_eq!(Command::from_tcp_line("INV_TAGS_TREE\ttenant:70").unwrap(), cmd);
+    for mode in [Mode::Tcp, Mode::Http] {
+        cache.put(Key::new("e"), "x".into(), tags(&["tenant:70:user:3"]), None);
+        let config = Config { mode, http_url: format!("http://{http}"), tcp_addr: tcp.to_string(), ..Config::default() }.with_basic_auth("admin", "password");
+        let client = Client::new(config).unwrap();
+        assert_eq!(client.invalidate_tags(&["tenant:70:user"], TagMode::Subtree).await.unwrap(), 1);
+    }
+    assert_eq!(cache.get(&Key::new("d")).as_deref(), Some("x"));
+}
@@ -0,0 +1,170 @@
+//! Tag TTL policies (min / override) on every write path, and deadlines set on a whole tag, over the
+//! cache, HTTP and TCP.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value as Json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::counter::CounterOptions;
+use tagcache::tag_ttl::{TagTtlConfig, TagTtlPolicy, TtlMode};
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Key, Tag, Value};
+
+fn tags(names: &[&str]) -> Vec<Tag> {
+    names.iter().map(|t| Tag::new(*t)).collect()
+}
+
+fn ttl_of(cache: &Cache, key: &str) -> Option<Duration> {
+    cache.shards.iter().find_map(|s| s.entries.get(&Key::new(key)).map(|e| e.ttl)).unwrap()
+}
+
+fn policy_cache() -> Cache {
+    let config = TagTtlConfig { policies: vec![
+        TagTtlPolicy { tag: "session".into(), ttl_seconds: 1800, mode: TtlMode::Override },
+        TagTtlPolicy { tag: "catalog".into(), ttl_seconds: 86400, mode: TtlMode::Min },
+    ] };
+    Cache::new(4).with_tag_ttl(&config)
+}
+
+#[test]
+fn policies_cap_or_override_the_ttl() {
+    let cache = policy_cache();
+    let (min, day, half_hour) = (Duration::from_secs(60), Duration::from_secs(86400), Duration::from_secs(1800));
+
+    cache.put(Key::new("s1"), "v".into(), tags(&["session"]), Some(min));
+    assert_eq!(ttl_of(&cache, "s1"), Some(half_hour)); // Override ignores the client TTL
+    cache.put(Key::new("c1"), "v".into(), tags(&["catalog"]), None);
+    assert_eq!(ttl_of(&cache, "c1"), Some(day));
+    cache.put(Key::new("c2"), "v".into(), tags(&["catalog"]), Some(min));
+    assert_eq!(ttl_of(&cache, "c2"), Some(min)); // Shorter than the cap: kept
+    cache.put(Key::new("both"), "v".into(), tags(&["catalog", "session"]), None);
+    assert_eq!(ttl_of(&cache, "both"), Some(half_hour));
+    cache.put(Key::new("plain"), "v".into(), tags(&["other"]), None);
+    assert_eq!(ttl_of(&cache, "plain"), None);
+
+    assert_eq!(cache.add(Key::new("a1"), "v".into(), tags(&["catalog"]), Some(day * 2)), Some(Some(day))); // The TTL stored
+    assert_eq!(ttl_of(&cache, "a1"), Some(day));
+    assert_eq!(cache.add(Key::new("a1"), "v".into(), tags(&["session"]), None), None); // Not added
+    assert_eq!(ttl_of(&cache, "a1"), Some(day));
+    let (_, stored) = cache.increment_with(Key::new("n1"), 1.into(), &CounterOptions::default(), tags(&["session"]), None).unwrap();
+    assert_eq!((stored, ttl_of(&cache, "n1")), (Some(half_hour), Some(half_hour)));
+    cache.hset(Key::new("h1"), "f".into(), "v".into(), tags(&["catalog"]), None).unwrap();
+    assert_eq!(ttl_of(&cache, "h1"), Some(day));
+
+    // A later write without tags keeps the entry's tags, and so its policy.
+    let (_, stored) = cache.increment_with(Key::new("n1"), 1.into(), &CounterOptions::default(), vec![], Some(day)).unwrap();
+    assert_eq!((stored, ttl_of(&cache, "n1")), (Some(half_hour), Some(half_hour)));
+
+    // Imported / replicated entries keep the TTL they come with.
+    cache.restore(Key::new("r1"), Value::String("v".into()), tags(&["session"]), Some(min), None);
+    assert_eq!(ttl_of(&cache, "r1"), Some(min));
+
+    // Runtime changes apply to the next write.
+    cache.tag_ttl.set(Tag::new("other"), min, TtlMode::Min);
+    assert!(cache.tag_ttl.remove(&Tag::new("session")));
+    cache.put(Key::new("plain"), "v".into(), tags(&["other"]), None);
+    assert_eq!(ttl_of(&cache, "plain"), Some(min));
+    cache.put(Key::new("s1"), "v".into(), tags(&["session"]), None);
+    assert_eq!(ttl_of(&cache, "s1"), None);
+}
+
+#[test]
+fn tag_deadline_expires_current_members() {
+    let cache = Cache::new(4);
+    cache.put(Key::new("forever"), "v".into(), tags(&["campaign:summer"]), None);
+    cache.put(Key::new("long"), "v".into(), tags(&["campaign:summer"]), Some(Duration::from_secs(3600)));
+    cache.put(Key::new("short"), "v".into(), tags(&["campaign:summer", "x"]), Some(Duration::from_millis(20)));
+    cache.put(Key::new("elsewhere"), "v".into(), tags(&["x"]), None);
+
+    let count = cache.expire_tag_at(&Tag::new("campaign:summer"), SystemTime::now() + Duration::from_millis(80));
+    assert_eq!(count, 2); // `short` is due before the deadline anyway
+    assert!(ttl_of(&cache, "forever").is_some());
+    assert_eq!(cache.get(&Key::new("long")).as_deref(), Some("v"));
+    std::thread::sleep(Duration::from_millis(120));
+    for key in ["forever", "long", "short"] { assert_eq!(cache.get(&Key::new(key)), None); }
+    assert_eq!(cache.get(&Key::new("elsewhere")).as_deref(), Some("v"));
+
+    // A deadline in the past expires the members right away; keys tagged later are not affected.
+    cache.put(Key::new("now"), "v".into(), tags(&["t"]), None);
+    assert_eq!(cache.expire_tag_at(&Tag::new("t"), UNIX_EPOCH), 1);
+    assert_eq!(cache.get(&Key::new("now")), None);
+    cache.put(Key::new("later"), "v".into(), tags(&["t"]), None);
+    assert_eq!(ttl_of(&cache, "later"), None);
+}
+
+async fn start_server() -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+#[tokio::test]
+async fn http_and_tcp() {
+    let (cache, http, tcp) = start_server().await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+
+    let set: Json = client.put(url("/admin/tag-ttl/session")).basic_auth("admin", Some("password"))
+        .json(&json!({"ttl_seconds": 1800, "mode": "override"})).send().await.unwrap().json().await.unwrap();
+    assert_eq!(set, json!({"ok": true, "tag": "session", "ttl_ms": 1_800_000, "mode": "override"}));
+    let bad = client.put(url("/admin/tag-ttl/x")).basic_auth("admin", Some("password")).json(&json!({"ttl_ms": 5, "mode": "max"})).send().await.unwrap();
+    assert_eq!(bad.status(), 400);
+    let list: Json = client.get(url("/admin/tag-ttl")).basic_auth("admin", Some("password")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(list["policies"], json!([{"tag": "session", "ttl_ms": 1_800_000, "mode": "override"}]));
+
+    // The reply reports the TTL the entry really got.
+    let put: Json = client.post(url("/put")).basic_auth("admin", Some("password"))
+        .json(&json!({"key": "u1", "value": "v", "tags": ["session"], "ttl_ms": 10})).send().await.unwrap().json().await.unwrap();
+    assert_eq!(put["ttl_ms"], 1_800_000);
+    assert_eq!(ttl_of(&cache, "u1"), Some(Duration::from_secs(1800)));
+    let incr: Json = client.post(url("/incr")).basic_auth("admin", Some("password"))
+        .json(&json!({"key": "n1", "tags": ["session"]})).send().await.unwrap().json().await.unwrap();
+    assert_eq!(incr["ttl_ms"], 1_800_000);
+    let add = |key: &'static str| client.post(url("/add")).basic_auth("admin", Some("password"))
+        .json(&json!({"key": key, "value": "v", "tags": ["session"]})).send();
+    let added: Json = add("a1").await.unwrap().json().await.unwrap();
+    assert_eq!((added["added"].clone(), added["ttl_ms"].clone()), (json!(true), json!(1_800_000)));
+    let refused: Json = add("a1").await.unwrap().json().await.unwrap();
+    assert_eq!((refused["added"].clone(), refused["ttl_ms"].clone()), (json!(false), Json::Null)); // Nothing stored
+
+    let del: Json = client.delete(url("/admin/tag-ttl/session")).basic_auth("admin", Some("password")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(del["removed"], true);
+    assert!(cache.tag_ttl.list().is_empty());
+
+    cache.put(Key::new("p1"), "v".into(), tags(&["promo"]), None);
+    cache.put(Key::new("p2"), "v".into(), tags(&["promo"]), None);
+    let expire = |body: Json| client.post(url("/tags/promo/expire")).basic_auth("admin", Some("password")).json(&body).send();
+    assert_eq!(expire(json!({})).await.unwrap().status(), 400);
+    let body: Json = expire(json!({"in_ms": 60_000})).await.unwrap().json().await.unwrap();
+    assert_eq!(body, json!({"success": true, "count": 2}));
+    let deadline = cache.shards.iter().find_map(|s| s.entries.get(&Key::new("p1")).and_then(|e| e.deadline())).unwrap();
+    assert!(deadline <= Instant::now() + Duration::from_secs(60));
+
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "TAG_EXPIRE\tpromo\t0").await, "TAG_EXPIRE\t2");
+    assert_eq!(send(&mut sock, "TAG_EXPIRE\tpromo").await, "ERR missing_deadline");
+    assert_eq!(send(&mut sock, "TAG_EXPIRE\tpromo\tsoon").await, "ERR invalid_deadline");
+    assert_eq!(send(&mut sock, "GET\tp1").await, "NF");
+}
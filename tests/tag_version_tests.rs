@@ -0,0 +1,176 @@
+//! Versioned tag invalidation: generation bumps instead of eager removal, lazy misses, background
+//! reclamation, and the per-tag / global configuration.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tagcache::events::EventKind;
+use tagcache::namespace::Namespace;
+use tagcache::server;
+use tagcache::tag_version::{InvalidationConfig, InvalidationMode};
+use tagcache::transfer::ExportRecord;
+use tagcache::{Cache, Key, Tag, TagMatch};
+
+fn tags(names: &[&str]) -> Vec<Tag> {
+    names.iter().map(|t| Tag::new(*t)).collect()
+}
+
+fn stored(cache: &Cache) -> usize {
+    cache.shards.iter().map(|s| s.entries.len()).sum()
+}
+
+fn config(mode: InvalidationMode, versioned: &[&str], eager: &[&str]) -> InvalidationConfig {
+    let list = |names: &[&str]| names.iter().map(|t| t.to_string()).collect();
+    InvalidationConfig { mode, versioned_tags: list(versioned), eager_tags: list(eager) }
+}
+
+#[test]
+fn bump_hides_members_until_reclaimed() {
+    let cache = Cache::new(4).with_invalidation(&config(InvalidationMode::Eager, &["feed"], &[]));
+    for i in 0..100 { cache.put(Key::new(format!("f{i}")), "v".into(), tags(&["feed", "other"]), None); }
+    cache.put(Key::new("plain"), "v".into(), tags(&["other"]), None);
+    let mut events = cache.events.subscribe();
+
+    assert_eq!(cache.invalidate_tag(&Tag::new("feed")), 100);
+    assert_eq!(stored(&cache), 101); // Nothing removed yet...
+    assert_eq!(cache.get(&Key::new("f1")), None); // ...but every member reads as a miss
+    assert!(cache.get_keys_by_tag(&Tag::new("feed")).is_empty());
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("other")), vec![Key::new("plain")]);
+    assert_eq!(cache.tag_info(&Tag::new("other")).unwrap().keys, 1);
+    let f3 = Key::new("f3");
+    let entry = cache.shards.iter().find_map(|s| s.entries.get(&f3).map(|e| e.clone())).unwrap();
+    assert!(entry.is_stale() && ExportRecord::new(&f3, &entry, Instant::now()).is_none()); // Not exported either
+    drop(entry); // Holds the generation counter
+
+    // Writes after the bump carry the new generation and survive reclamation.
+    cache.put(Key::new("f2"), "new".into(), tags(&["feed"]), None);
+    assert_eq!(cache.get(&Key::new("f2")).as_deref(), Some("new"));
+    assert!(cache.pending_reclaims() > 0);
+    let mut reclaimed = 0;
+    while cache.pending_reclaims() > 0 { reclaimed += cache.reclaim_invalidated(10); }
+    assert_eq!(reclaimed, 98); // f1 went on read, f2 was rewritten
+    assert_eq!(stored(&cache), 2);
+    assert_eq!(cache.get(&Key::new("f2")).as_deref(), Some("new"));
+    assert_eq!(cache.get_stats().reclaimed, 99);
+
+    // One invalidate event for the tag; the lazy miss and the sweep publish nothing.
+    let kinds: Vec<EventKind> = std::iter::from_fn(|| events.try_recv().ok()).map(|e| e.kind).collect();
+    assert_eq!(kinds, vec![EventKind::InvalidateTag, EventKind::Put]);
+
+    // Counters of tags without entries are dropped once everything is swept.
+    cache.invalidate_tag(&Tag::new("feed"));
+    assert_eq!(cache.tag_versions.generation(&Tag::new("feed")), Some(2));
+    while cache.pending_reclaims() > 0 { cache.reclaim_invalidated(10); }
+    assert_eq!(cache.tag_versions.generation(&Tag::new("feed")), None);
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("other")), vec![Key::new("plain")]);
+}
+
+#[test]
+fn global_mode_with_eager_overrides() {
+    let cache = Cache::new(4).with_invalidation(&config(InvalidationMode::Versioned, &[], &["session"]));
+    let ns = Namespace::named("shop").unwrap();
+    cache.put(Key::new("s1"), "v".into(), tags(&["session"]), None);
+    cache.put(Key::new("p1"), "v".into(), tags(&["tenant:1:product:7"]), None);
+    cache.put(Key::new("p2"), "v".into(), tags(&["tenant:1", "x"]), None);
+    cache.put(Key::new("p3"), "v".into(), tags(&["tenant:10"]), None);
+    cache.put(ns.key("s1"), "v".into(), vec![ns.tag("session")], None);
+    cache.hset(Key::new("h1"), "f".into(), "v".into(), tags(&["x", "y"]), None).unwrap();
+
+    // Overrides follow the client-facing name in every namespace.
+    assert_eq!(cache.tag_versions.mode_of(&ns.tag("session")), InvalidationMode::Eager);
+    assert_eq!(cache.tag_versions.mode_of(&ns.tag("feed")), InvalidationMode::Versioned);
+    let before = stored(&cache);
+    assert_eq!(cache.invalidate_tag(&Tag::new("session")), 1);
+    assert_eq!(stored(&cache), before - 1); // Eager: gone at once
+    assert_eq!(cache.invalidate_tag(&ns.tag("session")), 1);
+    assert_eq!(stored(&cache), before - 2);
+
+    assert_eq!(cache.invalidate_tags(&tags(&["tenant:1"]), TagMatch::Subtree), 2);
+    assert_eq!(stored(&cache), before - 2);
+    for key in ["p1", "p2"] { assert_eq!(cache.get(&Key::new(key)), None); }
+    assert_eq!(cache.get(&Key::new("p3")).as_deref(), Some("v"));
+
+    // Retagging restamps: a key moved onto `y` survives an invalidation of `x`.
+    cache.put(Key::new("k1"), "v".into(), tags(&["x"]), None);
+    cache.edit_tags(&Key::new("k1"), |t| { t.clear(); t.push(Tag::new("z")); }).unwrap();
+    assert_eq!(cache.invalidate_tags(&tags(&["x"]), TagMatch::Any), 1); // h1 (p2 went on read)
+    assert_eq!(cache.get(&Key::new("k1")).as_deref(), Some("v"));
+    assert_eq!(cache.hget(&Key::new("h1"), "f").unwrap(), None);
+
+    // "all" stays eager.
+    cache.put(Key::new("a1"), "v".into(), tags(&["m", "n"]), None);
+    let before = stored(&cache);
+    assert_eq!(cache.invalidate_tags(&tags(&["m", "n"]), TagMatch::All), 1);
+    assert_eq!(stored(&cache), before - 1);
+}
+
+#[tokio::test]
+async fn reaper_reclaims_in_the_background() {
+    let parsed: InvalidationConfig = toml::from_str("mode = \"versioned\"\neager_tags = [\"session\"]\n").unwrap();
+    assert_eq!(parsed.mode, InvalidationMode::Versioned);
+    assert_eq!(parsed.eager_tags, vec!["session".to_string()]);
+
+    let cache = Arc::new(Cache::new(4).with_invalidation(&parsed));
+    for i in 0..1000 { cache.put(Key::new(format!("k{i}")), "v".into(), tags(&["big"]), None); }
+    let reaper = server::spawn_expiry_reaper(cache.clone(), Duration::from_millis(5), 100);
+    assert_eq!(cache.invalidate_tag(&Tag::new("big")), 1000);
+    for _ in 0..200 {
+        if cache.tag_versions.tracked() == 0 { break; } // Pruned after the last sweep
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    reaper.abort();
+    assert_eq!(stored(&cache), 0);
+    assert!(cache.get_keys_by_tag(&Tag::new("big")).is_empty());
+    assert_eq!(cache.get_stats().reclaimed, 1000);
+    assert_eq!(cache.tag_versions.tracked(), 0);
+}
+
+#[test]
+fn writes_racing_a_bump_are_never_stranded() {
+    // A write stamped with the generation a concurrent bump retires must still land in a set the
+    // bump detached, or reclamation would never see it.
+    let cache = Arc::new(Cache::new(2).with_invalidation(&config(InvalidationMode::Versioned, &[], &[])));
+    let feed = Tag::new("feed");
+    for round in 0..2000 {
+        let barrier = Arc::new(std::sync::Barrier::new(3));
+        let writers: Vec<_> = (0..2).map(|w| {
+            let (cache, barrier) = (cache.clone(), barrier.clone());
+            std::thread::spawn(move || {
+                barrier.wait();
+                for i in 0..8 {
+                    let key = Key::new(format!("k{w}-{i}"));
+                    if i % 2 == 0 { cache.put(key, format!("{round}"), tags(&["feed"]), None); }
+                    else { cache.add(key, format!("{round}"), tags(&["feed"]), None); }
+                }
+            })
+        }).collect();
+        barrier.wait();
+        cache.invalidate_tag(&feed);
+        for writer in writers { writer.join().unwrap(); }
+        while cache.pending_reclaims() > 0 { cache.reclaim_invalidated(64); }
+
+        // Whatever survived is live and listed under the tag; nothing dead is left behind.
+        let stale: Vec<Key> = cache.shards.iter()
+            .flat_map(|s| s.entries.iter().filter(|e| e.is_stale()).map(|e| e.key().clone()).collect::<Vec<_>>())
+            .collect();
+        assert!(stale.is_empty(), "round {round}: stranded {stale:?}");
+        assert_eq!(cache.get_keys_by_tag(&feed).len(), stored(&cache), "round {round}");
+    }
+}
+
+#[test]
+fn retagging_keeps_the_generation_of_kept_tags() {
+    // A bump landing between a retag's liveness check and its restamp must still kill the entry.
+    let versions = tagcache::tag_version::TagVersions::new(&config(InvalidationMode::Versioned, &[], &[]));
+    let written = versions.stamp(&tags(&["feed", "old"]));
+    versions.bump(&Tag::new("feed"));
+    let retagged = versions.restamp(&written, &tags(&["feed", "old"]), &tags(&["feed", "new"]));
+    assert!(retagged.is_stale());
+
+    // Added tags take the current generation, and a later bump of one of them still counts.
+    let written = versions.stamp(&tags(&["feed"]));
+    let retagged = versions.restamp(&written, &tags(&["feed"]), &tags(&["feed", "new"]));
+    assert!(!retagged.is_stale());
+    versions.bump(&Tag::new("new"));
+    assert!(retagged.is_stale());
+}
@@ -0,0 +1,132 @@
+//! JSONL export / import: tag expression filters, a round trip between two servers through the
+//! client crate, per-line error reporting and replace mode.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use tokio::net::TcpListener;
+
+use tagcache::tag_expr::TagExpr;
+use tagcache::{build_app, AppState, AuthState, Cache, Credentials, Entry, Key, Tag};
+use tagcache_client::{Client, Config, ImportMode, Mode, TransferFilter};
+
+async fn start_server() -> (Arc<Cache>, Client) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let app = build_app(state, None);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let config = Config { mode: Mode::Http, http_url: format!("http://{addr}"), ..Config::default() }.with_basic_auth("admin", "password");
+    (cache, Client::new(config).unwrap())
+}
+
+fn entry(cache: &Cache, key: &str) -> Entry {
+    cache.shards.iter().find_map(|s| s.entries.get(&Key::new(key)).map(|e| e.clone())).unwrap()
+}
+
+fn tags(list: &[&str]) -> Vec<Tag> {
+    list.iter().map(|t| Tag::new(*t)).collect()
+}
+
+#[test]
+fn tag_expressions_parse_with_precedence() {
+    let e = TagExpr::parse("a | b & !c").unwrap();
+    assert_eq!(e.to_string(), "a|b&!c");
+    assert!(e.matches(&tags(&["a", "c"])));
+    assert!(e.matches(&tags(&["b"])));
+    assert!(!e.matches(&tags(&["b", "c"])));
+
+    let grouped = TagExpr::parse("(a,b)&!c").unwrap();
+    assert_eq!(grouped.to_string(), "(a|b)&!c");
+    assert!(!grouped.matches(&tags(&["a", "c"])));
+    assert!(grouped.matches(&tags(&["tenant:1", "b"])));
+    assert!(!TagExpr::parse("!(a&b)").unwrap().matches(&tags(&["a", "b"])));
+
+    for bad in ["", "a&", "(a|b", "a)b", "a&&b"] {
+        assert!(TagExpr::parse(bad).is_err(), "{bad:?} should not parse");
+    }
+}
+
+#[tokio::test]
+async fn export_and_import_round_trip_with_filters() {
+    let (src, src_client) = start_server().await;
+    src.put(Key::new("user:1"), "alice".into(), tags(&["users"]), Some(Duration::from_secs(60)));
+    src.put(Key::new("user:2"), "line one\nline two".into(), tags(&["users", "banned"]), None);
+    src.put(Key::new("user:3"), r#"{"name":"carol"}"#.into(), tags(&["users", "admins"]), None);
+    src.put(Key::new("session:1"), "s".into(), tags(&["sessions"]), None);
+
+    let mut all = Vec::new();
+    assert_eq!(src_client.export(&TransferFilter::default(), &mut all).await.unwrap(), 4);
+    let mut users = Vec::new();
+    let filter = TransferFilter::default().prefix("user:").tags("users&!banned");
+    assert_eq!(src_client.export(&filter, &mut users).await.unwrap(), 2);
+    let lines: Vec<serde_json::Value> = String::from_utf8(users).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    let user1 = lines.iter().find(|r| r["key"] == "user:1").unwrap();
+    assert_eq!(user1["value"], "alice");
+    assert_eq!(user1["tags"], serde_json::json!(["users"]));
+    let ttl = user1["ttl_ms"].as_u64().unwrap();
+    assert!(ttl > 50_000 && ttl <= 60_000, "remaining ttl {ttl}");
+    assert!(user1["created_ms"].as_u64().is_some());
+    assert!(lines.iter().all(|r| r.get("ttl_ms").is_none() || r["key"] == "user:1"));
+
+    // Load the full dump into a second server and compare.
+    let (dst, dst_client) = start_server().await;
+    let report = dst_client.import(std::io::Cursor::new(all), ImportMode::Merge, &TransferFilter::default()).await.unwrap();
+    assert_eq!((report.imported, report.skipped, report.failed), (4, 0, 0));
+    assert_eq!(dst.get(&Key::new("user:2")).as_deref(), Some("line one\nline two"));
+    assert_eq!(dst.get(&Key::new("user:3")).as_deref(), Some(r#"{"name":"carol"}"#));
+    assert_eq!(dst.get_keys_by_tag(&Tag::new("users")).len(), 3);
+    let created_ms = |cache: &Cache| entry(cache, "user:3").created_system.duration_since(UNIX_EPOCH).unwrap().as_millis();
+    assert_eq!(created_ms(&src), created_ms(&dst));
+    assert!(entry(&dst, "user:1").ttl.unwrap() <= Duration::from_secs(60));
+}
+
+#[tokio::test]
+async fn import_reports_bad_lines_and_replace_only_touches_the_filter() {
+    let (cache, client) = start_server().await;
+    cache.put(Key::new("user:old"), "stale".into(), tags(&["users"]), None);
+    cache.put(Key::new("user:keep"), "old".into(), tags(&["users"]), None);
+    cache.put(Key::new("other:1"), "untouched".into(), vec![], None);
+
+    let dump = [
+        r#"{"key":"user:keep","value":"new","tags":["users"]}"#,
+        "",
+        "not json",
+        r#"{"key":"user:obj","value":{"a":1},"tags":["users"]}"#,
+        r#"{"key":"other:2","value":"outside the prefix"}"#,
+        r#"{"key":"user:gone","value":"x","ttl_ms":0}"#,
+        r#"{"value":"no key"}"#,
+        r#"{"key":"user:last","value":"no trailing newline"}"#,
+    ]
+    .join("\n");
+    let filter = TransferFilter::default().prefix("user:");
+    let report = client.import(std::io::Cursor::new(dump.into_bytes()), ImportMode::Replace, &filter).await.unwrap();
+    assert_eq!((report.imported, report.skipped, report.failed, report.removed), (3, 2, 2, 1));
+    let bad_lines: Vec<usize> = report.errors.iter().map(|e| e.line).collect();
+    assert_eq!(bad_lines, vec![3, 7]);
+
+    assert_eq!(cache.get(&Key::new("user:keep")).as_deref(), Some("new"));
+    assert_eq!(cache.get(&Key::new("user:obj")).as_deref(), Some(r#"{"a":1}"#));
+    assert_eq!(cache.get(&Key::new("user:last")).as_deref(), Some("no trailing newline"));
+    assert_eq!(cache.get(&Key::new("user:old")), None); // Matched the filter, missing from the dump
+    assert_eq!(cache.get(&Key::new("other:1")).as_deref(), Some("untouched"));
+    assert_eq!(cache.get(&Key::new("other:2")), None);
+}
+
+#[tokio::test]
+async fn large_imports_stream_past_the_json_body_limit() {
+    let (cache, client) = start_server().await;
+    let value = "x".repeat(1024);
+    let dump: String = (0..4000).map(|i| format!("{{\"key\":\"k{i}\",\"value\":\"{value}\"}}\n")).collect();
+    assert!(dump.len() > 4 * 1024 * 1024);
+    let report = client.import(std::io::Cursor::new(dump.into_bytes()), ImportMode::Merge, &TransferFilter::default()).await.unwrap();
+    assert_eq!((report.imported, report.failed), (4000, 0));
+    assert_eq!(cache.shards.iter().map(|s| s.entries.len()).sum::<usize>(), 4000);
+
+    let mut out = Vec::new();
+    assert_eq!(client.export(&TransferFilter::default(), &mut out).await.unwrap(), 4000);
+}
@@ -0,0 +1,196 @@
+//! Hash, list and set values: the cache operations, type errors, tags / TTL, and the HTTP and TCP
+//! commands built on them.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde_json::{json, Value as Json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use tagcache::config::TagCacheConfig;
+use tagcache::replication::ReplOp;
+use tagcache::value::ValueError;
+use tagcache::{build_app, tcp, AppState, AuthState, Cache, Credentials, Key, Tag, Value, ValueType};
+
+async fn start_server() -> (Arc<Cache>, SocketAddr, SocketAddr) {
+    let creds = Credentials { username: "admin".into(), password: "password".into() };
+    let cache = Arc::new(Cache::new(4));
+    let state = Arc::new(AppState::new(cache.clone(), Arc::new(AuthState::new(creds, PathBuf::from("unused.conf")))));
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let app = build_app(state.clone(), None);
+    tokio::spawn(async move { axum::serve(http, app).await.unwrap() });
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp_listener.local_addr().unwrap();
+    tokio::spawn(tcp::serve_tcp(tcp_listener, state, TagCacheConfig::default().performance));
+    (cache, http_addr, tcp_addr)
+}
+
+async fn send(sock: &mut BufReader<TcpStream>, line: &str) -> String {
+    sock.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    let mut reply = String::new();
+    sock.read_line(&mut reply).await.unwrap();
+    reply.trim_end().to_string()
+}
+
+#[test]
+fn collections_keep_tags_and_reject_the_wrong_type() {
+    let cache = Cache::new(2);
+    let key = || Key::new("cart:1");
+    assert_eq!(cache.hset(key(), "sku-1".into(), "2".into(), vec![Tag::new("carts")], None), Ok(true));
+    assert_eq!(cache.hset(key(), "sku-1".into(), "3".into(), vec![], None), Ok(false)); // Tags stay
+    assert_eq!(cache.hincrby(key(), "sku-1".into(), 4, vec![], None), Ok(7));
+    assert_eq!(cache.hincrby(key(), "sku-2".into(), -1, vec![], None), Ok(-1));
+    assert_eq!(cache.hget(&key(), "sku-1"), Ok(Some("7".into())));
+    assert_eq!(cache.hgetall(&key()).unwrap().len(), 2);
+    assert_eq!(cache.get_keys_by_tag(&Tag::new("carts")), vec![key()]);
+
+    // String operations and other collection operations on a hash are type errors.
+    assert_eq!(cache.get(&key()), None);
+    assert_eq!(cache.get_value(&key()).map(|v| v.kind()), Some(ValueType::Hash));
+    assert_eq!(cache.lpush(key(), vec!["x".into()], vec![], None), Err(ValueError::WrongType(ValueType::Hash)));
+    assert!(cache.increment(key(), 1, vec![], None).unwrap_err().starts_with("wrong_type"));
+    cache.put(Key::new("name"), "alice".into(), vec![], None);
+    assert_eq!(cache.sadd(Key::new("name"), vec!["a".into()], vec![], None), Err(ValueError::WrongType(ValueType::String)));
+    cache.hset(key(), "note".into(), "n/a".into(), vec![], None).unwrap();
+    assert_eq!(cache.hincrby(key(), "note".into(), 1, vec![], None), Err(ValueError::NotInteger));
+
+    // Removing the last element deletes the key.
+    for field in ["sku-1", "sku-2", "note"] { assert_eq!(cache.hdel(key(), field), Ok(true)); }
+    assert!(cache.get_value(&key()).is_none());
+    assert!(cache.get_keys_by_tag(&Tag::new("carts")).is_empty());
+    assert_eq!(cache.hdel(key(), "sku-1"), Ok(false));
+}
+
+#[test]
+fn lists_and_sets() {
+    let cache = Cache::new(2);
+    let q = || Key::new("queue");
+    assert_eq!(cache.rpush(q(), vec!["b".into(), "c".into()], vec![], None), Ok(2));
+    assert_eq!(cache.lpush(q(), vec!["a".into(), "z".into()], vec![], None), Ok(4)); // z, a, b, c
+    assert_eq!(cache.lrange(&q(), 0, -1).unwrap(), ["z", "a", "b", "c"]);
+    assert_eq!(cache.lrange(&q(), 1, 2).unwrap(), ["a", "b"]);
+    assert_eq!(cache.lrange(&q(), -2, 100).unwrap(), ["b", "c"]);
+    assert!(cache.lrange(&q(), 3, 1).unwrap().is_empty());
+    assert_eq!(cache.lpop(q()), Ok(Some("z".into())));
+    assert_eq!(cache.rpop(q()), Ok(Some("c".into())));
+    assert_eq!(cache.lpop(Key::new("missing")), Ok(None));
+
+    let s = || Key::new("members");
+    assert_eq!(cache.sadd(s(), vec!["b".into(), "a".into(), "b".into()], vec![], None), Ok(2));
+    assert_eq!(cache.srem(s(), &["a".into(), "x".into()]), Ok(1));
+    assert_eq!(cache.smembers(&s()).unwrap(), ["b"]);
+    assert!(cache.smembers(&Key::new("missing")).unwrap().is_empty());
+}
+
+#[test]
+fn collection_writes_restart_the_ttl_like_incr() {
+    let cache = Cache::new(1);
+    let key = || Key::new("tmp");
+    cache.sadd(key(), vec!["a".into()], vec![], Some(Duration::from_millis(80))).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    cache.sadd(key(), vec!["b".into()], vec![], None).unwrap(); // Restarts the 80ms
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(cache.smembers(&key()).unwrap(), ["a", "b"]);
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(cache.smembers(&key()).unwrap().is_empty());
+    // An expired key starts over empty.
+    assert_eq!(cache.sadd(key(), vec!["c".into()], vec![], None), Ok(1));
+}
+
+#[test]
+fn collections_serialize_for_replication() {
+    let mut hash = std::collections::BTreeMap::new();
+    hash.insert("f".to_string(), "v".to_string());
+    let op = ReplOp::Set { key: "h".into(), value: Value::Hash(hash), tags: vec![], ttl_ms: None, ts: 1 };
+    let line = serde_json::to_string(&op).unwrap();
+    assert!(line.contains(r#""value":{"hash":{"f":"v"}}"#), "{line}");
+    assert_eq!(serde_json::from_str::<ReplOp>(&line).unwrap(), op);
+    let plain: ReplOp = serde_json::from_str(r#"{"op":"set","key":"k","value":"v","tags":[],"ttl_ms":null,"ts":1}"#).unwrap();
+    assert!(matches!(plain, ReplOp::Set { value: Value::String(ref v), .. } if v == "v"));
+}
+
+#[tokio::test]
+async fn http_collection_endpoints() {
+    let (cache, http, _) = start_server().await;
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{http}{path}");
+    let call = |req: reqwest::RequestBuilder| async move {
+        let resp = req.basic_auth("admin", Some("password")).send().await.unwrap();
+        (resp.status(), resp.json::<Json>().await.unwrap())
+    };
+
+    let (status, body) = call(client.put(url("/keys/cart:1/hash/sku-1")).json(&json!({"value":"2","tags":["carts"],"ttl_ms":60000}))).await;
+    assert_eq!((status, body["created"].clone()), (StatusCode::OK, json!(true)));
+    let (_, body) = call(client.post(url("/keys/cart:1/hash/sku-1/incr")).json(&json!({"by":3}))).await;
+    assert_eq!(body["value"], 5);
+    let (_, body) = call(client.get(url("/keys/cart:1/hash"))).await;
+    assert_eq!(body["fields"], json!({"sku-1":"5"}));
+    let (_, body) = call(client.get(url("/keys/cart:1"))).await;
+    assert_eq!((body["type"].clone(), body["value"].clone(), body["tags"].clone()), (json!("hash"), json!({"sku-1":"5"}), json!(["carts"])));
+    let (_, body) = call(client.get(url("/get/cart:1"))).await;
+    assert_eq!(body, json!({"error":"wrong_type","type":"hash"}));
+
+    let (status, body) = call(client.post(url("/keys/cart:1/list/push")).json(&json!({"items":["x"]}))).await;
+    assert_eq!((status, body["type"].clone()), (StatusCode::CONFLICT, json!("hash")));
+
+    let (_, body) = call(client.post(url("/keys/jobs/list/push")).json(&json!({"items":["a","b"]}))).await;
+    assert_eq!(body["len"], 2);
+    call(client.post(url("/keys/jobs/list/push")).json(&json!({"items":["first"],"side":"left"}))).await;
+    let (_, body) = call(client.get(url("/keys/jobs/list?start=0&stop=-1"))).await;
+    assert_eq!(body["items"], json!(["first","a","b"]));
+    let (_, body) = call(client.post(url("/keys/jobs/list/pop"))).await;
+    assert_eq!(body["value"], "first");
+    let (_, body) = call(client.post(url("/keys/jobs/list/pop?side=right"))).await;
+    assert_eq!(body["value"], "b");
+
+    let (_, body) = call(client.post(url("/keys/online/set/add")).json(&json!({"members":["u2","u1","u2"]}))).await;
+    assert_eq!(body["added"], 2);
+    let (_, body) = call(client.post(url("/keys/online/set/remove")).json(&json!({"members":["u2"]}))).await;
+    assert_eq!(body["removed"], 1);
+    let (_, body) = call(client.get(url("/keys/online/set"))).await;
+    assert_eq!(body["members"], json!(["u1"]));
+
+    // A whole collection can be written with PUT /keys/:key and a `type`.
+    let (status, _) = call(client.put(url("/keys/colors")).json(&json!({"type":"set","value":["red","blue"]}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cache.smembers(&Key::new("colors")).unwrap(), ["blue", "red"]);
+    let (status, _) = call(client.put(url("/keys/colors")).json(&json!({"type":"hash","value":["red"]}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Invalidating the tag drops the hash like any other entry.
+    call(client.post(url("/invalidate-tag")).json(&json!({"tag":"carts"}))).await;
+    assert!(cache.get_value(&Key::new("cart:1")).is_none());
+}
+
+#[tokio::test]
+async fn tcp_collection_commands() {
+    let (_, _, tcp) = start_server().await;
+    let mut sock = BufReader::new(TcpStream::connect(tcp).await.unwrap());
+    assert_eq!(send(&mut sock, "AUTH\tadmin\tpassword").await, "OK");
+    assert_eq!(send(&mut sock, "HSET\tuser:1\t-\tusers\tname\talice").await, "HSET\t1");
+    assert_eq!(send(&mut sock, "HINCRBY\tuser:1\t-\t-\tvisits\t2").await, "VALUE\t2");
+    assert_eq!(send(&mut sock, "HGET\tuser:1\tname").await, "VALUE\talice");
+    assert_eq!(send(&mut sock, "HGET\tuser:1\tnope").await, "NF");
+    assert_eq!(send(&mut sock, "HGETALL\tuser:1").await, "HASH\tname\talice\tvisits\t2");
+    assert_eq!(send(&mut sock, "KEYS_BY_TAG\tusers").await, "KEYS\tuser:1");
+    assert_eq!(send(&mut sock, "GET\tuser:1").await, "ERR wrong_type\thash");
+    assert_eq!(send(&mut sock, "SADD\tuser:1\t-\t-\tx").await, "ERR wrong_type\thash");
+    assert_eq!(send(&mut sock, "HDEL\tuser:1\tname").await, "HDEL\t1");
+
+    assert_eq!(send(&mut sock, "RPUSH\tq\t-\t-\ta\tb").await, "RPUSH\t2");
+    assert_eq!(send(&mut sock, "LPUSH\tq\t-\t-\tz").await, "LPUSH\t3");
+    assert_eq!(send(&mut sock, "LRANGE\tq\t0\t-1").await, "LIST\tz\ta\tb");
+    assert_eq!(send(&mut sock, "RPOP\tq").await, "VALUE\tb");
+    assert_eq!(send(&mut sock, "LRANGE\tq\tx\t1").await, "ERR invalid_index");
+
+    assert_eq!(send(&mut sock, "SADD\ts\t-\t-\tb\ta").await, "SADD\t2");
+    assert_eq!(send(&mut sock, "SREM\ts\ta\tc").await, "SREM\t1");
+    assert_eq!(send(&mut sock, "SMEMBERS\ts").await, "SET\tb");
+    assert_eq!(send(&mut sock, "SMEMBERS\tnone").await, "SET");
+    assert_eq!(send(&mut sock, "HSET\tuser:1\t-\t-\tonly-field").await, "ERR wrong_arguments");
+}
@@ -0,0 +1,135 @@
+//! Webhook delivery tests against a local HTTP stub: payload + signature, retries with backoff,
+//! and the dead-letter log.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{extract::State, http::{HeaderMap, StatusCode}, routing::post, Router};
+use parking_lot::Mutex;
+
+use tagcache::webhooks::{sign, WebhookDispatcher, WebhookEndpoint, WebhooksConfig};
+use tagcache::namespace::Namespace;
+use tagcache::{Cache, Key, Tag, TagMatch};
+
+#[derive(Clone, Default)]
+struct Stub {
+    fail_first: usize,
+    hits: Arc<AtomicUsize>,
+    received: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+}
+
+async fn hook(State(stub): State<Stub>, headers: HeaderMap, body: String) -> StatusCode {
+    let n = stub.hits.fetch_add(1, Ordering::SeqCst);
+    stub.received.lock().push((headers, body));
+    if n < stub.fail_first { StatusCode::INTERNAL_SERVER_ERROR } else { StatusCode::OK }
+}
+
+async fn start_stub(stub: Stub) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/hook", post(hook)).with_state(stub);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/hook", addr)
+}
+
+fn config(url: String, secret: Option<&str>, max_retries: u32) -> WebhooksConfig {
+    WebhooksConfig {
+        max_retries,
+        initial_backoff_ms: 10,
+        max_backoff_ms: 40,
+        endpoints: vec![WebhookEndpoint { url, secret: secret.map(str::to_string), events: vec![] }],
+        ..WebhooksConfig::default()
+    }
+}
+
+async fn wait_for(hits: &AtomicUsize, n: usize) {
+    for _ in 0..200 {
+        if hits.load(Ordering::SeqCst) >= n { return; }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("expected {} webhook requests, got {}", n, hits.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn multi_tag_invalidation_sends_one_signed_payload() {
+    let stub = Stub::default();
+    let url = start_stub(stub.clone()).await;
+    let cache = Cache::new(4).with_webhooks(WebhookDispatcher::start(config(url, Some("s3cret"), 0)));
+    cache.put(Key::new("a"), "1".into(), vec![Tag::new("x")], None);
+    cache.put(Key::new("b"), "2".into(), vec![Tag::new("y")], None);
+
+    assert_eq!(cache.invalidate_tags(&[Tag::new("x"), Tag::new("y")], TagMatch::Any), 2);
+    wait_for(&stub.hits, 1).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(stub.hits.load(Ordering::SeqCst), 1);
+
+    let (headers, body) = stub.received.lock()[0].clone();
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["event"], "invalidate_tags_any");
+    assert_eq!(json["tags"], serde_json::json!(["x", "y"]));
+    assert_eq!(json["namespace"], serde_json::Value::Null);
+    assert_eq!(json["count"], 2);
+    let expected = format!("sha256={}", sign("s3cret", &body));
+    assert_eq!(headers["x-tagcache-signature"].to_str().unwrap(), expected);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried() {
+    let stub = Stub { fail_first: 2, ..Stub::default() };
+    let url = start_stub(stub.clone()).await;
+    let cache = Cache::new(1).with_webhooks(WebhookDispatcher::start(config(url, None, 3)));
+    cache.flush_all();
+
+    wait_for(&stub.hits, 3).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(stub.hits.load(Ordering::SeqCst), 3);
+    let attempts: Vec<String> = stub.received.lock().iter().map(|(h, _)| h["x-tagcache-attempt"].to_str().unwrap().to_string()).collect();
+    assert_eq!(attempts, vec!["1", "2", "3"]);
+}
+
+#[tokio::test]
+async fn exhausted_deliveries_go_to_dead_letter_file() {
+    let stub = Stub { fail_first: usize::MAX, ..Stub::default() };
+    let url = start_stub(stub.clone()).await;
+    let dead_letter = std::env::temp_dir().join(format!("tagcache-dlq-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&dead_letter);
+    let mut cfg = config(url, None, 1);
+    cfg.dead_letter_file = Some(dead_letter.to_string_lossy().into_owned());
+    let cache = Cache::new(1).with_webhooks(WebhookDispatcher::start(cfg));
+    cache.invalidate_tag(&Tag::new("gone"));
+
+    for _ in 0..200 {
+        if dead_letter.exists() { break; }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let line = std::fs::read_to_string(&dead_letter).unwrap();
+    let record: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+    assert_eq!(record["attempts"], 2);
+    assert_eq!(record["payload"]["event"], "invalidate_tag");
+    assert_eq!(record["payload"]["tags"], serde_json::json!(["gone"]));
+    let _ = std::fs::remove_file(&dead_letter);
+}
+
+#[tokio::test]
+async fn namespaced_payloads_carry_the_namespace_apart_from_tags() {
+    let stub = Stub::default();
+    let url = start_stub(stub.clone()).await;
+    let cache = Cache::new(2).with_webhooks(WebhookDispatcher::start(config(url, None, 0)));
+    let team = Namespace::named("team").unwrap();
+    cache.put(team.key("a"), "1".into(), vec![team.tag("campaign:summer")], None);
+    cache.put(team.key("b"), "2".into(), vec![], None);
+
+    cache.invalidate_tag(&team.tag("campaign:summer"));
+    wait_for(&stub.hits, 1).await;
+    assert_eq!(cache.flush_namespace(&team), 1);
+    wait_for(&stub.hits, 2).await;
+
+    let payloads: Vec<serde_json::Value> = stub.received.lock().iter().map(|(_, b)| serde_json::from_str(b).unwrap()).collect();
+    assert_eq!(payloads[0]["event"], "invalidate_tag");
+    assert_eq!(payloads[0]["namespace"], "team");
+    assert_eq!(payloads[0]["tags"], serde_json::json!(["campaign:summer"]));
+    assert_eq!(payloads[1]["event"], "flush_namespace");
+    assert_eq!(payloads[1]["namespace"], "team");
+    assert_eq!(payloads[1]["count"], 1);
+}